@@ -16,7 +16,8 @@
 //
 
 use ripple_sdk::{
-    async_trait::async_trait, framework::bootstrap::Bootstep, utils::error::RippleError,
+    async_trait::async_trait, framework::bootstrap::Bootstep, log::error, tokio,
+    utils::error::RippleError,
 };
 
 use crate::broker::endpoint_broker::BrokerOutputForwarder;
@@ -33,6 +34,17 @@ impl Bootstep<BootstrapState> for StartCommunicationBroker {
 
     async fn setup(&self, state: BootstrapState) -> Result<(), RippleError> {
         let ps = state.platform_state.clone();
+        // rules pointing at undefined endpoints would silently go unhandled at request time
+        if ps.endpoint_state.check_rule_endpoints().is_err() {
+            return Err(RippleError::BootstrapError);
+        }
+        let rule_errors = ps.endpoint_state.validate_rules();
+        for (alias, err) in rule_errors.iter() {
+            error!("invalid rule {}: {}", alias, err);
+        }
+        if !rule_errors.is_empty() && ps.get_manifest().strict_rules {
+            return Err(RippleError::BootstrapError);
+        }
         // When endpoint broker starts up enable RPC processor there might be internal services which might need
         // brokering data
         state
@@ -67,6 +79,15 @@ impl Bootstep<BootstrapState> for StartOtherBrokers {
         }
         // Setup the endpoints from the manifests
         let mut endpoint_state = ps.clone().endpoint_state;
+        let mut build_failures = endpoint_state.subscribe_build_failures();
+        tokio::spawn(async move {
+            while let Ok(failure) = build_failures.recv().await {
+                error!(
+                    "endpoint {} failed to build: {}",
+                    failure.endpoint, failure.error
+                );
+            }
+        });
         endpoint_state.build_other_endpoints(ps.clone(), ps.session_state.get_account_session());
         Ok(())
     }
@@ -24,11 +24,11 @@ use crate::{
             accessory_rpc::AccessoryRippleProvider, account_rpc::AccountRPCProvider,
             advertising_rpc::AdvertisingRPCProvider,
             audio_description_rpc::AudioDescriptionRPCProvider,
-            authentication_rpc::AuthRPCProvider, capabilities_rpc::CapRPCProvider,
-            closed_captions_rpc::ClosedcaptionsRPCProvider, device_rpc::DeviceRPCProvider,
-            discovery_rpc::DiscoveryRPCProvider, keyboard_rpc::KeyboardRPCProvider,
-            lcm_rpc::LifecycleManagementProvider, lifecycle_rpc::LifecycleRippleProvider,
-            localization_rpc::LocalizationRPCProvider,
+            authentication_rpc::AuthRPCProvider, broker_rpc::BrokerRPCProvider,
+            capabilities_rpc::CapRPCProvider, closed_captions_rpc::ClosedcaptionsRPCProvider,
+            device_rpc::DeviceRPCProvider, discovery_rpc::DiscoveryRPCProvider,
+            keyboard_rpc::KeyboardRPCProvider, lcm_rpc::LifecycleManagementProvider,
+            lifecycle_rpc::LifecycleRippleProvider, localization_rpc::LocalizationRPCProvider,
             metrics_management_rpc::MetricsManagementProvider, metrics_rpc::MetricsRPCProvider,
             parameters_rpc::ParametersRPCProvider, privacy_rpc::PrivacyProvider,
             profile_rpc::ProfileRPCProvider, provider_registrar::ProviderRegistrar,
@@ -75,6 +75,7 @@ impl FireboltGatewayStep {
         let _ = methods.merge(AuthRPCProvider::provide_with_alias(state.clone()));
         let _ = methods.merge(AccountRPCProvider::provide_with_alias(state.clone()));
         let _ = methods.merge(MetricsManagementProvider::provide_with_alias(state.clone()));
+        let _ = methods.merge(BrokerRPCProvider::provide_with_alias(state.clone()));
         let _ = methods.merge(AudioDescriptionRPCProvider::provide_with_alias(
             state.clone(),
         ));
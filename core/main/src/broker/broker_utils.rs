@@ -15,26 +15,43 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use crate::state::platform_state::PlatformState;
+use crate::{broker::endpoint_broker::BrokerConnectRequest, state::platform_state::PlatformState};
 use futures::stream::{SplitSink, SplitStream};
 use futures_util::StreamExt;
 use jsonrpsee::core::RpcResult;
+use ripple_sdk::tokio::io::AsyncBufReadExt;
 use ripple_sdk::{
     api::gateway::rpc_gateway_api::{CallContext, JsonRpcApiError, RpcRequest},
     log::{error, info},
-    tokio::{self, net::TcpStream},
+    tokio::{
+        self,
+        io::{BufReader, Lines},
+        net::{
+            unix::{OwnedReadHalf, OwnedWriteHalf},
+            TcpStream, UnixStream,
+        },
+    },
     utils::rpc_utils::extract_tcp_port,
 };
 use serde_json::Value;
 use std::time::Duration;
 use tokio_tungstenite::{client_async, tungstenite::Message, WebSocketStream};
 
+/// Internal requests nested deeper than this are rejected so event handlers cannot recurse
+pub const MAX_INTERNAL_REQUEST_DEPTH: usize = 8;
+
+/// Call context tag recording the depth of a request in a chain of nested internal requests
+const INTERNAL_DEPTH_TAG: &str = "internal_depth:";
+
 pub struct BrokerUtils;
 
 impl BrokerUtils {
+    /// Connects to a websocket endpoint, retrying until it is available. Failed attempts and
+    /// the eventual connection are reported to the connect request of the endpoint if given.
     pub async fn get_ws_broker(
         endpoint: &str,
         alias: Option<String>,
+        connect_request: Option<&BrokerConnectRequest>,
     ) -> (
         SplitSink<WebSocketStream<TcpStream>, Message>,
         SplitStream<WebSocketStream<TcpStream>>,
@@ -58,9 +75,15 @@ impl BrokerUtils {
                 // Setup handshake for websocket with the tcp port
                 // Some WS servers lock on to the Port but not setup handshake till they are fully setup
                 if let Ok((stream, _)) = client_async(url_path.clone(), v).await {
+                    if let Some(request) = connect_request {
+                        request.report_build_success();
+                    }
                     break stream.split();
                 }
             }
+            if let Some(request) = connect_request {
+                request.report_build_failure(&format!("unable to connect to {}", url_path));
+            }
             if (index % 10).eq(&0) {
                 error!(
                     "Broker with {} failed with retry for last {} secs in {}",
@@ -72,6 +95,43 @@ impl BrokerUtils {
         }
     }
 
+    /// Connects to a unix domain socket endpoint given either as a path or a `unix://` url,
+    /// retrying until the socket is available. Connection failures are reported like
+    /// [BrokerUtils::get_ws_broker] does.
+    pub async fn get_unix_broker(
+        endpoint: &str,
+        connect_request: Option<&BrokerConnectRequest>,
+    ) -> (Lines<BufReader<OwnedReadHalf>>, OwnedWriteHalf) {
+        info!("Broker Endpoint socket {}", endpoint);
+        let path = endpoint.strip_prefix("unix://").unwrap_or(endpoint);
+        let mut index = 0;
+
+        loop {
+            match UnixStream::connect(path).await {
+                Ok(stream) => {
+                    if let Some(request) = connect_request {
+                        request.report_build_success();
+                    }
+                    let (read, write) = stream.into_split();
+                    break (BufReader::new(read).lines(), write);
+                }
+                Err(e) => {
+                    if let Some(request) = connect_request {
+                        request.report_build_failure(&format!("{}: {}", path, e));
+                    }
+                }
+            }
+            if (index % 10).eq(&0) {
+                error!(
+                    "Broker with {} failed with retry for last {} secs",
+                    path, index
+                );
+            }
+            index += 1;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     pub async fn process_internal_main_request<'a>(
         state: &mut PlatformState,
         method: &'a str,
@@ -87,9 +147,66 @@ impl BrokerUtils {
         params: Option<Value>,
     ) -> RpcResult<Value> {
         let rpc_request = RpcRequest::internal(method, on_behalf_of).with_params(params);
+        Self::send_internal_request(state, rpc_request).await
+    }
+
+    /// Makes an internal request while handling `parent`, one level deeper in its chain of
+    /// internal requests. The request is aborted once the chain exceeds
+    /// [MAX_INTERNAL_REQUEST_DEPTH].
+    pub async fn process_nested_internal_main_request(
+        state: &mut PlatformState,
+        parent: &CallContext,
+        method: &str,
+        params: Option<Value>,
+    ) -> RpcResult<Value> {
+        let rpc_request = Self::nested_internal_request(parent, method, params)?;
+        Self::send_internal_request(state, rpc_request).await
+    }
+
+    /// Depth of `ctx` in a chain of nested internal requests, 0 when it is not part of one
+    pub fn internal_request_depth(ctx: &CallContext) -> usize {
+        ctx.context
+            .iter()
+            .find_map(|c| c.strip_prefix(INTERNAL_DEPTH_TAG))
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(0)
+    }
+
+    fn nested_internal_request(
+        parent: &CallContext,
+        method: &str,
+        params: Option<Value>,
+    ) -> RpcResult<RpcRequest> {
+        let depth = Self::internal_request_depth(parent) + 1;
+        if depth > MAX_INTERNAL_REQUEST_DEPTH {
+            error!(
+                "aborting internal request {} made for {}, nested {} deep",
+                method, parent.method, depth
+            );
+            return Err(JsonRpcApiError::default()
+                .with_code(-32100)
+                .with_message(format!(
+                    "{} aborted, internal requests nested deeper than {}",
+                    method, MAX_INTERNAL_REQUEST_DEPTH
+                ))
+                .into());
+        }
+        let mut rpc_request = RpcRequest::internal(method, None);
+        rpc_request
+            .ctx
+            .context
+            .push(format!("{}{}", INTERNAL_DEPTH_TAG, depth));
+        Ok(rpc_request.with_params(params))
+    }
+
+    async fn send_internal_request(
+        state: &mut PlatformState,
+        rpc_request: RpcRequest,
+    ) -> RpcResult<Value> {
+        let method = rpc_request.method.clone();
         state
             .metrics
-            .add_api_stats(&rpc_request.ctx.request_id, method);
+            .add_api_stats(&rpc_request.ctx.request_id, &method);
 
         match state.internal_rpc_request(&rpc_request).await {
             Ok(res) => match res.as_value() {
@@ -106,3 +223,24 @@ impl BrokerUtils {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::Mockable;
+
+    #[test]
+    fn test_nested_internal_request_depth() {
+        let mut parent = CallContext::mock();
+        assert_eq!(BrokerUtils::internal_request_depth(&parent), 0);
+
+        // an event handler whose internal request keeps re-entering the handler
+        for depth in 1..=MAX_INTERNAL_REQUEST_DEPTH {
+            let request =
+                BrokerUtils::nested_internal_request(&parent, "module.onEvent", None).unwrap();
+            assert_eq!(BrokerUtils::internal_request_depth(&request.ctx), depth);
+            parent = request.ctx;
+        }
+        assert!(BrokerUtils::nested_internal_request(&parent, "module.onEvent", None).is_err());
+    }
+}
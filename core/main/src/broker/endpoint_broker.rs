@@ -17,38 +17,50 @@
 
 use ripple_sdk::{
     api::{
-        firebolt::fb_capabilities::{
-            FireboltPermission, CAPABILITY_NOT_AVAILABLE, JSON_RPC_STANDARD_ERROR_INVALID_PARAMS,
+        firebolt::{
+            fb_capabilities::{
+                FireboltPermission, CAPABILITY_NOT_AVAILABLE, CAPABILITY_NOT_PERMITTED,
+            },
+            fb_metrics::{Counter, Tag, Timer, TimerType},
+            fb_telemetry::OperationalMetricRequest,
         },
         gateway::rpc_gateway_api::{
             ApiMessage, ApiProtocol, CallContext, JsonRpcApiRequest, JsonRpcApiResponse,
             RpcRequest, RPC_V2,
         },
+        manifest::device_manifest::TelemetryMethodPolicy,
         observability::log_signal::LogSignal,
         session::AccountSession,
     },
+    chrono::Utc,
     extn::extn_client_message::{ExtnEvent, ExtnMessage},
     framework::RippleResponse,
-    log::{debug, error, trace},
+    log::{debug, error, info, trace, warn},
     tokio::{
         self,
-        sync::mpsc::{self, Receiver, Sender},
+        sync::{
+            broadcast,
+            mpsc::{self, error::TrySendError, Receiver, Sender},
+            OwnedSemaphorePermit, Semaphore,
+        },
     },
     utils::error::RippleError,
 };
-use serde_json::{json, Value};
+use serde::Serialize;
+use serde_json::{json, value::RawValue, Value};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc, RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
     },
+    time::{Duration, Instant},
 };
 
 use crate::{
     broker::broker_utils::BrokerUtils,
     firebolt::firebolt_gateway::{FireboltGatewayCommand, JsonRpcError},
-    service::extn::ripple_client::RippleClient,
+    service::{extn::ripple_client::RippleClient, observability::ObservabilityClient},
     state::{metrics_state::MetricsState, platform_state::PlatformState, session_state::Session},
     utils::router_utils::{
         add_telemetry_status_code, capture_stage, get_rpc_header, return_extn_response,
@@ -59,9 +71,15 @@ use super::{
     event_management_utility::EventManagementUtility,
     extn_broker::ExtnBroker,
     http_broker::HttpBroker,
+    mqtt_broker::MqttBroker,
     provider_broker_state::{ProvideBrokerState, ProviderResult},
-    rules_engine::{jq_compile, Rule, RuleEndpoint, RuleEndpointProtocol, RuleEngine},
+    rules_engine::{
+        composite_key_source, jq_compile, normalize_method, report_jq_failure, RateLimit, Rule,
+        RuleEndpoint, RuleEndpointProtocol, RuleEngine, RuleExplanation, RuleSummary,
+        RuleTransform, RuleTransformType,
+    },
     thunder_broker::ThunderBroker,
+    unix_socket_broker::UnixSocketBroker,
     websocket_broker::WebsocketBroker,
     workflow_broker::WorkflowBroker,
 };
@@ -93,6 +111,12 @@ pub struct BrokerRequest {
     pub subscription_processed: Option<bool>,
     pub workflow_callback: Option<BrokerCallback>,
     pub telemetry_response_listeners: Vec<Sender<BrokerOutput>>,
+    /// Further requests for the same thunder endpoint which are sent upstream together with
+    /// this one, see [EndpointBrokerState::bulk_subscribe]
+    pub batch: Vec<BrokerRequest>,
+    /// Outcome of the request transform when it already ran before dispatch, e.g. to trace
+    /// the request, so the broker does not run it again
+    pub transformed_params: Option<Result<Value, RippleError>>,
 }
 impl ripple_sdk::api::observability::log_signal::ContextAsJson for BrokerRequest {
     fn as_json(&self) -> serde_json::Value {
@@ -144,6 +168,80 @@ impl std::fmt::Display for BrokerRequest {
 
 pub type BrokerSubMap = HashMap<String, Vec<BrokerRequest>>;
 
+const BROKER_STREAM_CAPACITY: usize = 32;
+
+/// Number of broker outputs retained for diagnostics
+const RECENT_OUTPUTS_CAPACITY: usize = 50;
+const REDACTED: &str = "[redacted]";
+
+/// Attempts made to hand a dropped connection to the reconnect thread
+const RECONNECT_SEND_ATTEMPTS: u64 = 3;
+const RECONNECT_SEND_RETRY_DELAY_MS: u64 = 100;
+
+/// Keys of endpoints which lost their connection and can no longer reconnect
+pub type FatalEndpoints = Arc<RwLock<HashSet<String>>>;
+
+/// Sent to a BrokerCleaner in place of an app id to clean up the subscriptions of every app
+pub const CLEANUP_ALL_SENTINEL: &str = "*";
+
+/// Service unavailable error code returned for brokered requests while maintenance mode is on
+pub const BROKER_MAINTENANCE_ERROR_CODE: i32 = -32001;
+
+/// Error code returned when a transformed result does not match its rule's response_schema
+pub const RESPONSE_SCHEMA_VIOLATION_ERROR_CODE: i32 = -32006;
+
+/// Error code returned for requests over the rate limit of their rule
+pub const RATE_LIMIT_ERROR_CODE: i32 = -32005;
+
+/// Error code returned when a provider does not respond within its provider timeout
+pub const PROVIDER_TIMEOUT_ERROR_CODE: i32 = -32007;
+
+/// Raised when the broker of an endpoint fails to establish its connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointBuildFailure {
+    pub endpoint: String,
+    pub error: String,
+}
+
+/// Endpoints whose broker could not connect, an endpoint is removed once its broker connects
+#[derive(Debug, Clone)]
+pub struct BuildFailures {
+    failed: Arc<RwLock<HashMap<String, String>>>,
+    notifier: broadcast::Sender<EndpointBuildFailure>,
+}
+
+impl Default for BuildFailures {
+    fn default() -> Self {
+        Self {
+            failed: Arc::new(RwLock::new(HashMap::new())),
+            notifier: broadcast::channel(16).0,
+        }
+    }
+}
+
+impl BuildFailures {
+    fn report(&self, endpoint: &str, error: &str) {
+        let reported = self
+            .failed
+            .write()
+            .unwrap()
+            .insert(endpoint.to_owned(), error.to_owned())
+            .is_some();
+        // notify only once per failure, connection helpers keep retrying
+        if !reported {
+            error!("Endpoint {} failed to connect: {}", endpoint, error);
+            let _ = self.notifier.send(EndpointBuildFailure {
+                endpoint: endpoint.to_owned(),
+                error: error.to_owned(),
+            });
+        }
+    }
+
+    fn clear(&self, endpoint: &str) {
+        self.failed.write().unwrap().remove(endpoint);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BrokerConnectRequest {
     pub key: String,
@@ -151,6 +249,8 @@ pub struct BrokerConnectRequest {
     pub sub_map: BrokerSubMap,
     pub session: Option<AccountSession>,
     pub reconnector: Sender<BrokerConnectRequest>,
+    pub fatal_endpoints: FatalEndpoints,
+    pub build_failures: BuildFailures,
 }
 impl Default for BrokerConnectRequest {
     fn default() -> Self {
@@ -160,6 +260,8 @@ impl Default for BrokerConnectRequest {
             sub_map: HashMap::new(),
             session: None,
             reconnector: mpsc::channel(2).0,
+            fatal_endpoints: FatalEndpoints::default(),
+            build_failures: BuildFailures::default(),
         }
     }
 }
@@ -198,6 +300,8 @@ impl BrokerConnectRequest {
             sub_map: HashMap::new(),
             session: None,
             reconnector,
+            fatal_endpoints: FatalEndpoints::default(),
+            build_failures: BuildFailures::default(),
         }
     }
 
@@ -213,7 +317,53 @@ impl BrokerConnectRequest {
             sub_map: HashMap::new(),
             session,
             reconnector,
+            fatal_endpoints: FatalEndpoints::default(),
+            build_failures: BuildFailures::default(),
+        }
+    }
+
+    /// Hands the request to the reconnect thread, retrying a bounded number of times. When
+    /// every attempt fails the endpoint is marked fatal so it shows up in the health report.
+    pub async fn send_reconnect(
+        &self,
+        reconnect_request: BrokerConnectRequest,
+    ) -> Result<(), RippleError> {
+        for attempt in 1..=RECONNECT_SEND_ATTEMPTS {
+            if self
+                .reconnector
+                .send(reconnect_request.clone())
+                .await
+                .is_ok()
+            {
+                return Ok(());
+            }
+            warn!(
+                "Reconnect attempt {} of {} failed for {}",
+                attempt, RECONNECT_SEND_ATTEMPTS, self.key
+            );
+            if attempt < RECONNECT_SEND_ATTEMPTS {
+                tokio::time::sleep(tokio::time::Duration::from_millis(
+                    RECONNECT_SEND_RETRY_DELAY_MS * attempt,
+                ))
+                .await;
+            }
         }
+        error!("Endpoint {} can no longer reconnect", self.key);
+        self.fatal_endpoints
+            .write()
+            .unwrap()
+            .insert(self.key.clone());
+        Err(RippleError::SendFailure)
+    }
+
+    /// Marks the endpoint as failed to connect, see
+    /// [EndpointBrokerState::subscribe_build_failures]
+    pub fn report_build_failure(&self, error: &str) {
+        self.build_failures.report(&self.key, error);
+    }
+
+    pub fn report_build_success(&self) {
+        self.build_failures.clear(&self.key);
     }
 }
 
@@ -236,6 +386,8 @@ impl BrokerRequest {
             subscription_processed: None,
             workflow_callback,
             telemetry_response_listeners,
+            batch: Vec::new(),
+            transformed_params: None,
         }
     }
 
@@ -260,6 +412,29 @@ impl Default for BrokerCallback {
 
 static ATOMIC_ID: AtomicU64 = AtomicU64::new(0);
 
+/// Requests sent by [BrokerSender::send_and_wait] keyed by the call id of their response
+fn awaited_requests() -> &'static RwLock<HashMap<u64, BrokerRequest>> {
+    static AWAITED_REQUESTS: OnceLock<RwLock<HashMap<u64, BrokerRequest>>> = OnceLock::new();
+    AWAITED_REQUESTS.get_or_init(Default::default)
+}
+
+/// Takes the request awaiting the response with the given id
+fn take_awaited_request(id: u64) -> Option<BrokerRequest> {
+    awaited_requests().write().unwrap().remove(&id)
+}
+
+/// Counter of requests for methods without a broker rule, tagged by method
+pub const RULE_NOT_FOUND_COUNTER: &str = "broker_rule_not_found";
+/// Counter of requests which found their broker channel full, tagged by endpoint
+pub const BROKER_BLOCKED_COUNTER: &str = "broker_channel_blocked";
+/// Counter of requests which could not be sent to their broker, tagged by endpoint
+pub const BROKER_SEND_FAILURE_COUNTER: &str = "broker_send_failure";
+/// Number of requests queued in a broker channel, tagged by endpoint
+pub const BROKER_QUEUE_DEPTH_GAUGE: &str = "broker_queue_depth";
+/// Time from dispatching a request to its endpoint until the response arrives, tagged by
+/// endpoint, protocol and for thunder the callsign
+pub const BROKER_LATENCY_TIMER: &str = "broker_latency";
+
 impl BrokerCallback {
     pub async fn send_json_rpc_api_response(&self, response: JsonRpcApiResponse) {
         let output = BrokerOutput::new(response);
@@ -267,10 +442,11 @@ impl BrokerCallback {
             error!("couldnt send response for {:?}", e);
         }
     }
-    /// Default method used for sending errors via the BrokerCallback
+    /// Default method used for sending errors via the BrokerCallback, the error code
+    /// reflects the kind of RippleError
     pub async fn send_error(&self, request: BrokerRequest, error: RippleError) {
         let value = serde_json::to_value(JsonRpcError {
-            code: JSON_RPC_STANDARD_ERROR_INVALID_PARAMS,
+            code: error.to_jsonrpc_code(),
             message: format!("Error with {:?}", error),
             data: None,
         })
@@ -309,6 +485,9 @@ impl BrokerOutput {
         self.data.result.is_some()
     }
 
+    /// Subscription id prefixed to the method of an event notification. This only looks
+    /// at the method name, use [EndpointBrokerState::get_event_id] to confirm the id
+    /// belongs to a subscription.
     pub fn get_event(&self) -> Option<u64> {
         if let Some(e) = &self.data.method {
             let event: Vec<&str> = e.split('.').collect();
@@ -360,8 +539,342 @@ impl BrokerSender {
             Ok(())
         }
     }
+
+    /// Sends the request and waits for its response, for internal callers which would
+    /// otherwise correlate the response themselves. The request gets a fresh call id and
+    /// a one-shot workflow callback, the broker output forwarder applies the rule's
+    /// response transform and hands the result to that callback. Fails with
+    /// [RippleError::TimeoutError] when no response arrives in time.
+    #[allow(dead_code)]
+    pub async fn send_and_wait(
+        &self,
+        mut request: BrokerRequest,
+        timeout: Duration,
+    ) -> Result<BrokerOutput, RippleError> {
+        let id = EndpointBrokerState::get_next_id();
+        request.rpc.ctx.call_id = id;
+        let (tx, mut rx) = mpsc::channel(1);
+        request.workflow_callback = Some(BrokerCallback { sender: tx });
+        awaited_requests()
+            .write()
+            .unwrap()
+            .insert(id, request.clone());
+        if let Err(e) = self.send(request).await {
+            take_awaited_request(id);
+            return Err(e);
+        }
+        match tokio::time::timeout(timeout, rx.recv()).await {
+            Ok(Some(output)) => Ok(output),
+            Ok(None) => Err(RippleError::SendFailure),
+            Err(_) => {
+                take_awaited_request(id);
+                warn!("no response for request {} within {:?}", id, timeout);
+                Err(RippleError::TimeoutError)
+            }
+        }
+    }
+
+    /// Same as send, but reports the queue depth of the broker channel and counts
+    /// the requests which had to wait for a full channel or could not be sent at all.
+    /// The metrics carry the given endpoint tags, see [EndpointBrokerState::broker_metric_tags].
+    pub async fn send_with_backpressure(
+        &self,
+        metrics_state: &MetricsState,
+        tags: &HashMap<String, String>,
+        request: BrokerRequest,
+    ) -> RippleResponse {
+        let tags = tags.clone();
+        let endpoint = tags.get("endpoint").cloned().unwrap_or_default();
+        let mut depth = Counter::new(BROKER_QUEUE_DEPTH_GAUGE.to_string(), 0, Some(tags.clone()));
+        depth.set_value((self.sender.max_capacity() - self.sender.capacity()) as u64);
+        ObservabilityClient::report(OperationalMetricRequest::Counter(depth));
+
+        if self.sender.capacity() == 0 && !self.sender.is_closed() {
+            warn!(
+                "broker channel for endpoint {} is full, request will wait",
+                endpoint
+            );
+            let counter = metrics_state.increment_counter(BROKER_BLOCKED_COUNTER, tags.clone());
+            ObservabilityClient::report(OperationalMetricRequest::Counter(counter));
+        }
+        let result = self.send(request).await;
+        if result.is_err() {
+            let counter = metrics_state.increment_counter(BROKER_SEND_FAILURE_COUNTER, tags);
+            ObservabilityClient::report(OperationalMetricRequest::Counter(counter));
+        }
+        result
+    }
+}
+
+/// Point in time view of a single endpoint, see [EndpointBrokerState::health_snapshot]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointHealth {
+    pub connected: bool,
+    /// Last failure to hand a request over to the endpoint
+    pub last_error: Option<String>,
+    /// Requests routed to the endpoint which have not been answered yet
+    pub pending_requests: usize,
+}
+
+/// Number of request trace entries retained, older entries are dropped first
+const REQUEST_TRACE_CAPACITY: usize = 500;
+
+/// A single stage of a traced request, see [EndpointBrokerState::enable_request_trace]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestTraceEntry {
+    /// Id assigned to the request by the broker
+    pub call_id: u64,
+    pub request_id: String,
+    pub session_id: String,
+    pub method: String,
+    pub stage: String,
+    /// Milliseconds since the epoch
+    pub timestamp: i64,
+    pub data: Value,
+}
+
+#[derive(Debug, Default)]
+struct RequestTracer {
+    /// Trace requests of every session
+    all_sessions: bool,
+    sessions: HashSet<String>,
+    entries: VecDeque<RequestTraceEntry>,
+}
+
+impl RequestTracer {
+    fn is_enabled(&self, session_id: &str) -> bool {
+        self.all_sessions || self.sessions.contains(session_id)
+    }
+}
+
+/// Time [EndpointBrokerState::bulk_subscribe] waits for the subscriptions to be acknowledged
+pub const BULK_SUBSCRIBE_TIMEOUT_MS: u64 = 10000;
+
+/// Outcome of a single subscription of [EndpointBrokerState::bulk_subscribe]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkSubscribeResult {
+    pub event: String,
+    /// Id of the listen request, events of the subscription are delivered with this id
+    pub id: u64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+/// Brokered request on its way to the endpoint
+struct DispatchRequest {
+    id: u64,
+    endpoint: String,
+    sender: BrokerSender,
+    metric_tags: HashMap<String, String>,
+    /// Delay before the request is sent, see [Rule::inject_latency_ms]
+    inject_latency: Option<u64>,
+    limiter: Option<EndpointLimiter>,
+    request: BrokerRequest,
+}
+
+/// Parts of the [EndpointBrokerState] a request needs once it is dispatched on its own task
+#[derive(Debug, Clone)]
+struct Dispatcher {
+    callback: BrokerCallback,
+    metrics_state: MetricsState,
+    request_map: Arc<RwLock<HashMap<u64, BrokerRequest>>>,
+    in_flight_permits: Arc<RwLock<HashMap<u64, OwnedSemaphorePermit>>>,
+    last_endpoint_errors: Arc<RwLock<HashMap<String, String>>>,
+    request_tracer: Arc<RwLock<RequestTracer>>,
+}
+
+impl Dispatcher {
+    /// Takes a concurrency slot of the endpoint for the request. Returns false when the
+    /// request must not be sent, because the endpoint rejected it or the request was
+    /// dropped while it was queued.
+    async fn acquire_permit(&self, dispatch: &DispatchRequest) -> bool {
+        let Some(limiter) = &dispatch.limiter else {
+            return true;
+        };
+        match limiter.acquire(&dispatch.endpoint).await {
+            Ok(_) if !self.request_map.read().unwrap().contains_key(&dispatch.id) => false,
+            Ok(permit) => {
+                self.in_flight_permits
+                    .write()
+                    .unwrap()
+                    .insert(dispatch.id, permit);
+                true
+            }
+            Err(e) => {
+                self.callback.send_error(dispatch.request.clone(), e).await;
+                false
+            }
+        }
+    }
+
+    /// Sends requests for the same endpoint upstream, the first request carries the others
+    /// in its [BrokerRequest::batch]
+    async fn send_upstream(&self, mut dispatches: Vec<DispatchRequest>) {
+        if dispatches.is_empty() {
+            return;
+        }
+        let batched = dispatches.split_off(1);
+        let mut leader = dispatches.remove(0);
+        leader.request.batch = batched.iter().map(|d| d.request.clone()).collect();
+        let sent = leader
+            .sender
+            .send_with_backpressure(
+                &self.metrics_state,
+                &leader.metric_tags,
+                leader.request.clone(),
+            )
+            .await;
+        leader.request.batch.clear();
+        for dispatch in std::iter::once(&leader).chain(batched.iter()) {
+            EndpointBrokerState::record_trace_stage(
+                &self.request_tracer,
+                &dispatch.request.rpc,
+                "upstream",
+                json!({
+                    "endpoint": dispatch.endpoint,
+                    "error": sent.as_ref().err().map(|e| format!("{:?}", e)),
+                }),
+            );
+        }
+        if let Err(e) = sent {
+            LogSignal::new(
+                "handle_brokerage".to_string(),
+                "broker send error".to_string(),
+                leader.request.rpc.ctx.clone(),
+            )
+            .emit_error();
+            self.last_endpoint_errors
+                .write()
+                .unwrap()
+                .insert(leader.endpoint.clone(), format!("{:?}", e));
+            for dispatch in std::iter::once(leader).chain(batched) {
+                self.in_flight_permits.write().unwrap().remove(&dispatch.id);
+                self.callback.send_error(dispatch.request, e.clone()).await;
+            }
+        } else {
+            self.last_endpoint_errors
+                .write()
+                .unwrap()
+                .remove(&leader.endpoint);
+        }
+    }
+}
+
+/// Normalized form of a [TelemetryMethodPolicy]
+#[derive(Debug, Clone, Default)]
+struct TelemetryFilter {
+    allow: Option<HashSet<String>>,
+    deny: HashSet<String>,
+}
+
+impl TelemetryFilter {
+    fn new(policy: &TelemetryMethodPolicy) -> Self {
+        Self {
+            allow: policy.allow.as_ref().map(|methods| {
+                methods
+                    .iter()
+                    .map(|method| normalize_method(method))
+                    .collect()
+            }),
+            deny: policy
+                .deny
+                .iter()
+                .map(|method| normalize_method(method))
+                .collect(),
+        }
+    }
+
+    fn is_telemetered(&self, method: &str) -> bool {
+        let method = normalize_method(method);
+        !self.deny.contains(&method)
+            && self
+                .allow
+                .as_ref()
+                .map_or(true, |allow| allow.contains(&method))
+    }
+}
+
+/// Requests an app made to a rule in the current window of its [RateLimit]
+#[derive(Debug, Clone, Copy)]
+struct RateWindow {
+    started: Instant,
+    length: Duration,
+    count: u32,
+}
+
+impl RateWindow {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            started: now,
+            length: Duration::from_millis(limit.window_ms),
+            count: 0,
+        }
+    }
+
+    fn has_ended(&self, now: Instant) -> bool {
+        now.duration_since(self.started) >= self.length
+    }
+
+    /// Counts a request, returning the milliseconds until the window ends when the limit
+    /// is already reached
+    fn admit(&mut self, limit: &RateLimit, now: Instant) -> Result<(), u64> {
+        if self.has_ended(now) {
+            *self = Self::new(limit, now);
+        } else if self.count >= limit.requests {
+            let remaining = self.length - now.duration_since(self.started);
+            return Err(remaining.as_millis() as u64);
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Bounds the requests an endpoint works on at once, see [RuleEndpoint::max_concurrency]
+#[derive(Debug, Clone)]
+struct EndpointLimiter {
+    permits: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+    max_queued: usize,
+}
+
+impl EndpointLimiter {
+    fn new(max_concurrency: usize, max_queued: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+            queued: Arc::new(AtomicUsize::new(0)),
+            max_queued,
+        }
+    }
+
+    /// Waits for a free slot, failing fast with [RippleError::ServiceNotReady] when the
+    /// queue is already full
+    async fn acquire(&self, endpoint: &str) -> Result<OwnedSemaphorePermit, RippleError> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queued {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            warn!("endpoint {} is busy, rejecting request", endpoint);
+            return Err(RippleError::ServiceNotReady);
+        }
+        let permit = self.permits.clone().acquire_owned().await;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        permit.map_err(|_| RippleError::ServiceNotReady)
+    }
+
+    /// Whether a request would have to wait for a slot
+    fn is_busy(&self) -> bool {
+        self.permits.available_permits() == 0
+    }
 }
 
+/// In flight request id mapped to its coalescing key and the ids of identical requests waiting on it
+type CoalescedRequestMap = Arc<RwLock<HashMap<u64, (String, Vec<u64>)>>>;
+
 #[derive(Debug, Clone)]
 pub struct EndpointBrokerState {
     endpoint_map: Arc<RwLock<HashMap<String, BrokerSender>>>,
@@ -373,6 +886,29 @@ pub struct EndpointBrokerState {
     reconnect_tx: Sender<BrokerConnectRequest>,
     provider_broker_state: ProvideBrokerState,
     metrics_state: MetricsState,
+    in_flight_map: Arc<RwLock<HashMap<String, u64>>>,
+    coalesced_map: CoalescedRequestMap,
+    recent_outputs: Arc<RwLock<VecDeque<BrokerOutput>>>,
+    fatal_endpoints: FatalEndpoints,
+    build_failures: BuildFailures,
+    last_endpoint_errors: Arc<RwLock<HashMap<String, String>>>,
+    endpoint_limiters: Arc<RwLock<HashMap<String, EndpointLimiter>>>,
+    /// Concurrency slots held by dispatched requests until their response arrives
+    in_flight_permits: Arc<RwLock<HashMap<u64, OwnedSemaphorePermit>>>,
+    /// Methods still brokered during maintenance, maintenance mode is off when unset
+    maintenance_allowlist: Arc<RwLock<Option<HashSet<String>>>>,
+    request_tracer: Arc<RwLock<RequestTracer>>,
+    telemetry_filter: TelemetryFilter,
+    /// Latency timers of dispatched requests, reported once their response arrives
+    broker_timers: Arc<RwLock<HashMap<u64, Timer>>>,
+    /// Subscription maps of the thunder brokers keyed by endpoint
+    subscription_maps: Arc<RwLock<HashMap<String, Arc<RwLock<BrokerSubMap>>>>>,
+    /// Adds the misspelled `messsage` key to capability not available errors
+    legacy_messsage_key: bool,
+    /// Rate limit windows keyed by app id and rule alias, see [Rule::rate_limit]
+    rate_windows: Arc<RwLock<HashMap<(String, String), RateWindow>>>,
+    /// Decorator functions rules select with their event_decorator_method
+    event_utility: Arc<EventManagementUtility>,
 }
 impl Default for EndpointBrokerState {
     fn default() -> Self {
@@ -386,6 +922,22 @@ impl Default for EndpointBrokerState {
             reconnect_tx: mpsc::channel(2).0,
             provider_broker_state: ProvideBrokerState::default(),
             metrics_state: MetricsState::default(),
+            in_flight_map: Arc::new(RwLock::new(HashMap::new())),
+            coalesced_map: Arc::new(RwLock::new(HashMap::new())),
+            recent_outputs: Arc::new(RwLock::new(VecDeque::new())),
+            fatal_endpoints: FatalEndpoints::default(),
+            build_failures: BuildFailures::default(),
+            last_endpoint_errors: Arc::new(RwLock::new(HashMap::new())),
+            endpoint_limiters: Arc::new(RwLock::new(HashMap::new())),
+            in_flight_permits: Arc::new(RwLock::new(HashMap::new())),
+            maintenance_allowlist: Arc::new(RwLock::new(None)),
+            request_tracer: Arc::new(RwLock::new(RequestTracer::default())),
+            telemetry_filter: TelemetryFilter::default(),
+            broker_timers: Arc::new(RwLock::new(HashMap::new())),
+            subscription_maps: Arc::new(RwLock::new(HashMap::new())),
+            legacy_messsage_key: false,
+            rate_windows: Arc::new(RwLock::new(HashMap::new())),
+            event_utility: Arc::new(EventManagementUtility::with_custom_functions()),
         }
     }
 }
@@ -408,6 +960,22 @@ impl EndpointBrokerState {
             reconnect_tx,
             provider_broker_state: ProvideBrokerState::default(),
             metrics_state,
+            in_flight_map: Arc::new(RwLock::new(HashMap::new())),
+            coalesced_map: Arc::new(RwLock::new(HashMap::new())),
+            recent_outputs: Arc::new(RwLock::new(VecDeque::new())),
+            fatal_endpoints: FatalEndpoints::default(),
+            build_failures: BuildFailures::default(),
+            last_endpoint_errors: Arc::new(RwLock::new(HashMap::new())),
+            endpoint_limiters: Arc::new(RwLock::new(HashMap::new())),
+            in_flight_permits: Arc::new(RwLock::new(HashMap::new())),
+            maintenance_allowlist: Arc::new(RwLock::new(None)),
+            request_tracer: Arc::new(RwLock::new(RequestTracer::default())),
+            telemetry_filter: TelemetryFilter::default(),
+            broker_timers: Arc::new(RwLock::new(HashMap::new())),
+            subscription_maps: Arc::new(RwLock::new(HashMap::new())),
+            legacy_messsage_key: false,
+            rate_windows: Arc::new(RwLock::new(HashMap::new())),
+            event_utility: Arc::new(EventManagementUtility::with_custom_functions()),
         };
         state.reconnect_thread(rec_tr, ripple_client);
         state
@@ -417,6 +985,36 @@ impl EndpointBrokerState {
         self
     }
 
+    pub fn with_provider_timeouts(mut self, provider_timeouts: HashMap<String, u64>) -> Self {
+        self.provider_broker_state = ProvideBrokerState::new(provider_timeouts);
+        self
+    }
+
+    /// Traces the brokered requests of every session when enabled
+    pub fn with_request_trace(self, all_sessions: bool) -> Self {
+        self.request_tracer.write().unwrap().all_sessions = all_sessions;
+        self
+    }
+
+    /// Restricts the telemetry listeners of brokered requests to the methods of the policy
+    pub fn with_telemetry_policy(mut self, policy: &TelemetryMethodPolicy) -> Self {
+        self.telemetry_filter = TelemetryFilter::new(policy);
+        self
+    }
+
+    /// Event decorator functions of the broker. Extensions register their own decorators
+    /// here, which rules then name in their event_decorator_method.
+    pub fn get_event_utility(&self) -> Arc<EventManagementUtility> {
+        self.event_utility.clone()
+    }
+
+    /// Keeps the misspelled `messsage` key in capability not available errors, see
+    /// [ripple_sdk::api::manifest::device_manifest::RippleFeatures::legacy_messsage_key]
+    pub fn with_legacy_messsage_key(mut self, legacy_messsage_key: bool) -> Self {
+        self.legacy_messsage_key = legacy_messsage_key;
+        self
+    }
+
     fn reconnect_thread(&self, mut rx: Receiver<BrokerConnectRequest>, client: RippleClient) {
         let mut state = self.clone();
         tokio::spawn(async move {
@@ -436,6 +1034,36 @@ impl EndpointBrokerState {
         });
     }
 
+    /// Id of the subscription the output is an event for. Methods whose first segment
+    /// merely looks numeric, such as `4.enabled`, are not events unless a subscription
+    /// is registered under that id.
+    fn get_event_id(&self, output: &BrokerOutput) -> Option<u64> {
+        let id = output.get_event()?;
+        self.request_map
+            .read()
+            .unwrap()
+            .get(&id)
+            .filter(|request| request.rpc.is_subscription())
+            .map(|_| id)
+    }
+
+    /// Ids of the listening subscriptions which receive id-less upstream notifications
+    /// with the given method, see [Rule::notification_passthrough]
+    fn get_notification_subscribers(&self, method: &str) -> Vec<u64> {
+        self.request_map
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, request)| {
+                request.rpc.is_subscription()
+                    && request.rpc.is_listening()
+                    && request.rule.routes_notifications()
+                    && request.rule.alias.eq_ignore_ascii_case(method)
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
     fn get_request(&self, id: u64) -> Result<BrokerRequest, RippleError> {
         let result = { self.request_map.read().unwrap().get(&id).cloned() };
         if result.is_none() {
@@ -475,8 +1103,7 @@ impl EndpointBrokerState {
     }
 
     pub fn get_next_id() -> u64 {
-        ATOMIC_ID.fetch_add(1, Ordering::Relaxed);
-        ATOMIC_ID.load(Ordering::Relaxed)
+        ATOMIC_ID.fetch_add(1, Ordering::Relaxed) + 1
     }
 
     fn update_request(
@@ -499,6 +1126,8 @@ impl EndpointBrokerState {
                     subscription_processed: None,
                     workflow_callback: workflow_callback.clone(),
                     telemetry_response_listeners: telemetry_response_listeners.clone(),
+                    batch: Vec::new(),
+                    transformed_params: None,
                 },
             );
         }
@@ -546,13 +1175,255 @@ impl EndpointBrokerState {
         }
     }
 
-    fn add_endpoint(&mut self, key: String, endpoint: BrokerSender) {
+    /// Fails every brokered request other than the allowlisted methods with a
+    /// service unavailable error until [EndpointBrokerState::disable_maintenance_mode] is called
+    pub fn enable_maintenance_mode(&self, allowed_methods: Vec<String>) {
+        info!(
+            "broker maintenance mode on, allowed methods {:?}",
+            allowed_methods
+        );
+        let allowlist = allowed_methods
+            .iter()
+            .map(|method| normalize_method(method))
+            .collect();
+        *self.maintenance_allowlist.write().unwrap() = Some(allowlist);
+    }
+
+    pub fn disable_maintenance_mode(&self) {
+        info!("broker maintenance mode off");
+        *self.maintenance_allowlist.write().unwrap() = None;
+    }
+
+    pub fn is_maintenance_mode(&self) -> bool {
+        self.maintenance_allowlist.read().unwrap().is_some()
+    }
+
+    fn is_blocked_by_maintenance(&self, method: &str) -> bool {
+        self.maintenance_allowlist
+            .read()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |allowlist| {
+                !allowlist.contains(&normalize_method(method))
+            })
+    }
+
+    /// Records the request, transform, upstream and response stages of the brokered requests
+    /// of the session, see [EndpointBrokerState::request_trace]
+    pub fn enable_request_trace(&self, session_id: &str) {
+        self.request_tracer
+            .write()
+            .unwrap()
+            .sessions
+            .insert(session_id.to_owned());
+    }
+
+    pub fn disable_request_trace(&self, session_id: &str) {
+        self.request_tracer
+            .write()
+            .unwrap()
+            .sessions
+            .remove(session_id);
+    }
+
+    pub fn is_request_traced(&self, rpc_request: &RpcRequest) -> bool {
+        self.request_tracer
+            .read()
+            .unwrap()
+            .is_enabled(&rpc_request.ctx.session_id)
+    }
+
+    fn trace_request_stage(&self, rpc_request: &RpcRequest, stage: &str, data: Value) {
+        Self::record_trace_stage(&self.request_tracer, rpc_request, stage, data)
+    }
+
+    fn record_trace_stage(
+        request_tracer: &RwLock<RequestTracer>,
+        rpc_request: &RpcRequest,
+        stage: &str,
+        data: Value,
+    ) {
+        let mut tracer = request_tracer.write().unwrap();
+        if !tracer.is_enabled(&rpc_request.ctx.session_id) {
+            return;
+        }
+        if tracer.entries.len() == REQUEST_TRACE_CAPACITY {
+            tracer.entries.pop_front();
+        }
+        tracer.entries.push_back(RequestTraceEntry {
+            call_id: rpc_request.ctx.call_id,
+            request_id: rpc_request.ctx.request_id.clone(),
+            session_id: rpc_request.ctx.session_id.clone(),
+            method: rpc_request.ctx.method.clone(),
+            stage: stage.to_owned(),
+            timestamp: Utc::now().timestamp_millis(),
+            data,
+        });
+    }
+
+    /// Recorded trace entries in the order they were captured, optionally only those of the
+    /// given request id
+    pub fn request_trace(&self, request_id: Option<&str>) -> Vec<RequestTraceEntry> {
+        self.request_tracer
+            .read()
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|entry| request_id.map_or(true, |id| entry.request_id == id))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn set_endpoint_concurrency(
+        &self,
+        key: &str,
+        max_concurrency: usize,
+        max_queued: usize,
+    ) {
+        self.endpoint_limiters.write().unwrap().insert(
+            key.to_owned(),
+            EndpointLimiter::new(max_concurrency, max_queued),
+        );
+    }
+
+    fn dispatcher(&self) -> Dispatcher {
+        Dispatcher {
+            callback: self.callback.clone(),
+            metrics_state: self.metrics_state.clone(),
+            request_map: self.request_map.clone(),
+            in_flight_permits: self.in_flight_permits.clone(),
+            last_endpoint_errors: self.last_endpoint_errors.clone(),
+            request_tracer: self.request_tracer.clone(),
+        }
+    }
+
+    /// Frees the concurrency slot held by the request, if any
+    fn release_permit(&self, id: u64) {
+        self.in_flight_permits.write().unwrap().remove(&id);
+    }
+
+    fn endpoint_protocol(&self, endpoint: &str) -> Option<RuleEndpointProtocol> {
+        match self.rule_engine.rules.endpoints.get(endpoint) {
+            Some(config) => Some(config.protocol.clone()),
+            None if endpoint == "thunder" => Some(RuleEndpointProtocol::Thunder),
+            None => None,
+        }
+    }
+
+    /// Tags of the broker metrics of requests served by the endpoint. Requests to thunder
+    /// are also tagged with the callsign the rule alias addresses.
+    fn broker_metric_tags(&self, endpoint: &str, rule: &Rule) -> HashMap<String, String> {
+        let mut tags = HashMap::from([("endpoint".to_owned(), endpoint.to_owned())]);
+        if let Some(protocol) = self.endpoint_protocol(endpoint) {
+            if matches!(protocol, RuleEndpointProtocol::Thunder) {
+                let (callsign, _) = ThunderBroker::get_callsign_and_method_from_alias(&rule.alias);
+                tags.insert("callsign".to_owned(), callsign);
+            }
+            tags.insert("protocol".to_owned(), protocol.as_str().to_owned());
+        }
+        tags
+    }
+
+    /// Stops the latency timer of the request, if any, and reports it with the error code
+    /// of the response as status
+    fn report_broker_latency(&self, id: u64, response: &JsonRpcApiResponse) -> Option<Timer> {
+        let mut timer = self.broker_timers.write().unwrap().remove(&id)?;
+        timer.stop();
+        // same status codes as the api stats of the forwarder, 1 for success
+        let status = response
+            .error
+            .as_ref()
+            .and_then(RippleError::jsonrpc_error_code)
+            .unwrap_or(1);
+        timer.insert_tag(Tag::Status.key(), status.to_string());
+        ObservabilityClient::report(OperationalMetricRequest::Timer(timer.clone()));
+        Some(timer)
+    }
+
+    pub(crate) fn add_endpoint(&mut self, key: String, endpoint: BrokerSender) {
         let mut endpoint_map = self.endpoint_map.write().unwrap();
         endpoint_map.insert(key, endpoint);
     }
     pub fn get_endpoints(&self) -> HashMap<String, BrokerSender> {
         self.endpoint_map.read().unwrap().clone()
     }
+    /// Endpoints which lost their connection and failed to reconnect
+    pub fn get_fatal_endpoints(&self) -> Vec<String> {
+        let mut fatal_endpoints: Vec<String> = self
+            .fatal_endpoints
+            .read()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        fatal_endpoints.sort();
+        fatal_endpoints
+    }
+    /// Endpoints whose broker has not been able to connect yet, with the last error
+    pub fn get_build_failures(&self) -> HashMap<String, String> {
+        self.build_failures.failed.read().unwrap().clone()
+    }
+    /// Notifies each endpoint whose broker fails to connect once, subscribe before the
+    /// endpoints are built to observe every failure
+    pub fn subscribe_build_failures(&self) -> broadcast::Receiver<EndpointBuildFailure> {
+        self.build_failures.notifier.subscribe()
+    }
+    /// Connection status of every configured and built endpoint, an endpoint is
+    /// connected while its broker is still receiving requests and has not failed fatally
+    pub fn get_endpoint_health(&self) -> HashMap<String, bool> {
+        let fatal_endpoints = self.fatal_endpoints.read().unwrap();
+        let endpoint_map = self.endpoint_map.read().unwrap();
+        self.rule_engine
+            .rules
+            .endpoints
+            .keys()
+            .chain(endpoint_map.keys())
+            .map(|key| {
+                let connected = !fatal_endpoints.contains(key)
+                    && endpoint_map
+                        .get(key)
+                        .map(|broker| !broker.sender.is_closed())
+                        .unwrap_or(false);
+                (key.clone(), connected)
+            })
+            .collect()
+    }
+    /// Connection status, last send error and number of unanswered requests of every
+    /// endpoint, keyed by endpoint name. Requests are attributed to the endpoint of their
+    /// rule, falling back to thunder like [EndpointBrokerState::handle_brokerage] does.
+    pub fn health_snapshot(&self) -> BTreeMap<String, EndpointHealth> {
+        let mut pending: HashMap<String, usize> = HashMap::new();
+        for request in self.request_map.read().unwrap().values() {
+            // subscriptions stay in the map for as long as they are active
+            if request.rpc.is_subscription() {
+                continue;
+            }
+            let endpoint = request
+                .rule
+                .endpoint
+                .clone()
+                .unwrap_or_else(|| "thunder".to_owned());
+            *pending.entry(endpoint).or_default() += 1;
+        }
+        let last_errors = self.last_endpoint_errors.read().unwrap();
+        self.get_endpoint_health()
+            .into_iter()
+            .map(|(key, connected)| {
+                let health = EndpointHealth {
+                    connected,
+                    last_error: last_errors.get(&key).cloned(),
+                    pending_requests: pending.get(&key).cloned().unwrap_or(0),
+                };
+                (key, health)
+            })
+            .collect()
+    }
+    pub fn explain_rule(&self, rpc_request: &RpcRequest) -> RuleExplanation {
+        self.rule_engine.explain(rpc_request)
+    }
+    pub fn resolve_rule(&self, method: &str, app_id: &str) -> Option<RuleSummary> {
+        self.rule_engine.resolve(method, app_id)
+    }
     pub fn get_other_endpoints(&self, me: &str) -> HashMap<String, BrokerSender> {
         let f = self.endpoint_map.read().unwrap().clone();
         let mut result = HashMap::new();
@@ -564,9 +1435,14 @@ impl EndpointBrokerState {
         result
     }
 
-    fn build_endpoint(&mut self, ps: Option<PlatformState>, request: BrokerConnectRequest) {
+    fn build_endpoint(&mut self, ps: Option<PlatformState>, mut request: BrokerConnectRequest) {
+        request.fatal_endpoints = self.fatal_endpoints.clone();
+        request.build_failures = self.build_failures.clone();
         let endpoint = request.endpoint.clone();
         let key = request.key.clone();
+        if let Some(max_concurrency) = endpoint.max_concurrency {
+            self.set_endpoint_concurrency(&key, max_concurrency, endpoint.get_max_queued());
+        }
         let (broker, cleaner) = match endpoint.protocol {
             RuleEndpointProtocol::Http => (
                 HttpBroker::get_broker(None, request, self.callback.clone(), self).get_sender(),
@@ -580,6 +1456,10 @@ impl EndpointBrokerState {
             RuleEndpointProtocol::Thunder => {
                 let thunder_broker =
                     ThunderBroker::get_broker(None, request, self.callback.clone(), self);
+                self.subscription_maps
+                    .write()
+                    .unwrap()
+                    .insert(key.clone(), thunder_broker.get_subscription_map());
                 (
                     thunder_broker.get_sender(),
                     Some(thunder_broker.get_cleaner()),
@@ -593,11 +1473,23 @@ impl EndpointBrokerState {
                 ExtnBroker::get_broker(ps, request, self.callback.clone(), self).get_sender(),
                 None,
             ),
+            RuleEndpointProtocol::UnixSocket => {
+                let unix_broker =
+                    UnixSocketBroker::get_broker(None, request, self.callback.clone(), self);
+                (unix_broker.get_sender(), Some(unix_broker.get_cleaner()))
+            }
+            RuleEndpointProtocol::Mqtt => {
+                let mqtt_broker =
+                    MqttBroker::get_broker(None, request, self.callback.clone(), self);
+                (mqtt_broker.get_sender(), Some(mqtt_broker.get_cleaner()))
+            }
         };
         self.add_endpoint(key, broker);
 
         if let Some(cleaner) = cleaner {
             let mut cleaner_list = self.cleaner_list.write().unwrap();
+            // cleaners of brokers which stopped, e.g. before a reconnect, are dropped
+            cleaner_list.retain(|c| c.cleaner.as_ref().map_or(true, |tx| !tx.is_closed()));
             cleaner_list.push(cleaner);
         }
     }
@@ -638,13 +1530,42 @@ impl EndpointBrokerState {
         session: Option<Session>,
         telemetry_response_listeners: Vec<Sender<BrokerOutput>>,
     ) {
-        let (id, request) =
-            self.update_request(rpc_request, rule, None, None, telemetry_response_listeners);
-        match self.provider_broker_state.check_provider_request(
+        let provider_result = self.provider_broker_state.check_provider_request(
             rpc_request,
             &permission,
             session.clone(),
-        ) {
+        );
+        if let Some(fallback) = rule.provider_fallback.clone() {
+            let not_available = matches!(
+                provider_result,
+                Some(ProviderResult::NotAvailable(_)) | None
+            );
+            if not_available && !rpc_request.method.contains(".provide") {
+                // the fallback is the result as configured, the response transform is dropped
+                let rule = Rule {
+                    transform: RuleTransform::default(),
+                    ..rule
+                };
+                let (id, _) = self.update_request(
+                    rpc_request,
+                    rule,
+                    None,
+                    None,
+                    telemetry_response_listeners,
+                );
+                let data = JsonRpcApiResponse {
+                    jsonrpc: "2.0".to_owned(),
+                    id: Some(id),
+                    result: Some(fallback),
+                    ..Default::default()
+                };
+                tokio::spawn(async move { callback.send_json_rpc_api_response(data).await });
+                return;
+            }
+        }
+        let (id, request) =
+            self.update_request(rpc_request, rule, None, None, telemetry_response_listeners);
+        match provider_result {
             Some(ProviderResult::Registered) => {
                 // return empty result and handle the rest with jq rule
                 let data = JsonRpcApiResponse {
@@ -660,16 +1581,18 @@ impl EndpointBrokerState {
                 tokio::spawn(async move { callback.sender.send(output).await });
             }
             Some(ProviderResult::Session(s)) => {
+                let provider_app_id = s.get_app_id();
                 ProvideBrokerState::send_to_provider(request, id, s);
+                if let Some(timeout) = self.provider_broker_state.get_provider_timeout(&permission)
+                {
+                    self.start_provider_timer(id, timeout, provider_app_id, callback);
+                }
             }
             Some(ProviderResult::NotAvailable(p)) => {
                 // Not Available
                 let data = JsonRpcApiResponse::new(
                     Some(id),
-                    Some(json!({
-                        "error": CAPABILITY_NOT_AVAILABLE,
-                        "messsage": format!("{} not available", p)
-                    })),
+                    Some(self.capability_not_available_error(format!("{} not available", p))),
                 );
 
                 let output = BrokerOutput { data };
@@ -679,10 +1602,9 @@ impl EndpointBrokerState {
                 // Not Available
                 let data = JsonRpcApiResponse::new(
                     Some(id),
-                    Some(json!({
-                        "error": CAPABILITY_NOT_AVAILABLE,
-                        "messsage": "capability not available".to_string()
-                    })),
+                    Some(
+                        self.capability_not_available_error("capability not available".to_string()),
+                    ),
                 );
 
                 let output = BrokerOutput { data };
@@ -691,24 +1613,313 @@ impl EndpointBrokerState {
         }
     }
 
-    fn get_sender(&self, hash: &str) -> Option<BrokerSender> {
-        self.endpoint_map.read().unwrap().get(hash).cloned()
+    /// Counts the request against the rate limit of its rule, returning the milliseconds
+    /// until the app may call again when it is over the limit
+    fn check_rate_limit(&self, app_id: &str, rule: &Rule) -> Result<(), u64> {
+        let limit = match &rule.rate_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let now = Instant::now();
+        let key = (app_id.to_owned(), rule.alias.clone());
+        let mut rate_windows = self.rate_windows.write().unwrap();
+        if !rate_windows.contains_key(&key) {
+            // ended windows limit nothing, they are dropped whenever a new one starts
+            rate_windows.retain(|_, window| !window.has_ended(now));
+        }
+        rate_windows
+            .entry(key)
+            .or_insert_with(|| RateWindow::new(limit, now))
+            .admit(limit, now)
     }
 
-    /// Main handler method whcih checks for brokerage and then sends the request for
-    /// asynchronous processing
-    pub fn handle_brokerage(
+    fn capability_not_available_error(&self, message: String) -> Value {
+        let mut error = json!({
+            "error": CAPABILITY_NOT_AVAILABLE,
+            "message": message
+        });
+        if self.legacy_messsage_key {
+            error["messsage"] = Value::String(message);
+        }
+        error
+    }
+
+    /// Fails a request with the given error without dispatching it, e.g. during maintenance,
+    /// for apps outside of the rule's allowed apps or over its rate limit. The response
+    /// transform is dropped so the error reaches the app unchanged.
+    fn handle_rejected_request(
         &self,
-        rpc_request: RpcRequest,
-        extn_message: Option<ExtnMessage>,
-        requestor_callback: Option<BrokerCallback>,
-        permissions: Vec<FireboltPermission>,
-        session: Option<Session>,
+        rpc_request: &RpcRequest,
+        rule: Rule,
+        error: JsonRpcError,
+        callback: BrokerCallback,
+        telemetry_response_listeners: Vec<Sender<BrokerOutput>>,
+    ) {
+        let rule = Rule {
+            transform: RuleTransform::default(),
+            ..rule
+        };
+        let (id, _) =
+            self.update_request(rpc_request, rule, None, None, telemetry_response_listeners);
+        let error = serde_json::to_value(error).unwrap();
+        let data = JsonRpcApiResponse {
+            jsonrpc: "2.0".to_owned(),
+            id: Some(id),
+            error: Some(error),
+            ..Default::default()
+        };
+        tokio::spawn(async move { callback.send_json_rpc_api_response(data).await });
+    }
+
+    /// Fails the provided request with a timeout error if the provider app has not
+    /// responded within the configured time, so the caller does not wait forever.
+    fn start_provider_timer(
+        &self,
+        id: u64,
+        timeout: u64,
+        provider_app_id: String,
+        callback: BrokerCallback,
+    ) {
+        let request_map = self.request_map.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(tokio::time::Duration::from_millis(timeout)).await;
+            // taking the request out of the map drops a provider response arriving later
+            let request = request_map.write().unwrap().remove(&id);
+            if let Some(request) = request {
+                warn!(
+                    "Provider {} did not respond to {} within {}ms",
+                    provider_app_id, request.rpc.method, timeout
+                );
+                // the error is routed under a new id, the id sent to the provider is retired
+                let error_id = Self::get_next_id();
+                let method = request.rpc.method.clone();
+                request_map.write().unwrap().insert(error_id, request);
+                callback
+                    .send_json_rpc_api_response(JsonRpcApiResponse {
+                        jsonrpc: "2.0".to_owned(),
+                        id: Some(error_id),
+                        error: Some(json!(JsonRpcError {
+                            code: PROVIDER_TIMEOUT_ERROR_CODE,
+                            message: format!(
+                                "Provider did not respond to {} within {}ms",
+                                method, timeout
+                            ),
+                            data: None,
+                        })),
+                        ..Default::default()
+                    })
+                    .await;
+            }
+        });
+    }
+
+    /// Returns the id of an identical request which is still waiting for its response,
+    /// otherwise tracks the given request as in flight.
+    fn coalesce_request(&self, rpc_request: &RpcRequest, rule: &Rule, id: u64) -> Option<u64> {
+        if rpc_request.is_subscription() {
+            return None;
+        }
+        let key = format!(
+            "{}:{}:{}",
+            rule.alias,
+            rule.transform.request.clone().unwrap_or_default(),
+            rpc_request.get_params().unwrap_or(Value::Null)
+        );
+        let mut in_flight_map = self.in_flight_map.write().unwrap();
+        if let Some(pending_id) = in_flight_map.get(&key).cloned() {
+            if let Some((_, waiting)) = self.coalesced_map.write().unwrap().get_mut(&pending_id) {
+                waiting.push(id);
+                return Some(pending_id);
+            }
+        }
+        in_flight_map.insert(key.clone(), id);
+        self.coalesced_map
+            .write()
+            .unwrap()
+            .insert(id, (key, Vec::new()));
+        None
+    }
+
+    /// Forwards a copy of the response to every request coalesced into the given one.
+    fn fan_out_response(&self, id: u64, data: &JsonRpcApiResponse) {
+        let coalesced = { self.coalesced_map.write().unwrap().remove(&id) };
+        if let Some((key, waiting)) = coalesced {
+            self.in_flight_map.write().unwrap().remove(&key);
+            for waiting_id in waiting {
+                let mut response = data.clone();
+                response.id = Some(waiting_id);
+                self.handle_broker_response(response);
+            }
+        }
+    }
+
+    /// Drops the state kept for a request until its response arrives. A request still
+    /// coalesced into it takes its place so the pending response reaches that request,
+    /// otherwise the coalesced requests are answered with an error.
+    fn forget_request(&self, id: u64) {
+        if self.promote_waiting_request(id) {
+            return;
+        }
+        self.release_permit(id);
+        self.broker_timers.write().unwrap().remove(&id);
+        let coalesced = { self.coalesced_map.write().unwrap().remove(&id) };
+        match coalesced {
+            Some((key, waiting)) => {
+                self.in_flight_map.write().unwrap().remove(&key);
+                for waiting_id in waiting {
+                    let error = RippleError::NoResponse;
+                    self.handle_broker_response(JsonRpcApiResponse {
+                        jsonrpc: "2.0".to_owned(),
+                        id: Some(waiting_id),
+                        error: Some(json!(JsonRpcError {
+                            code: error.to_jsonrpc_code(),
+                            message: format!("Error with {:?}", error),
+                            data: None,
+                        })),
+                        result: None,
+                        method: None,
+                        params: None,
+                    });
+                }
+            }
+            None => {
+                for (_, waiting) in self.coalesced_map.write().unwrap().values_mut() {
+                    waiting.retain(|waiting_id| *waiting_id != id);
+                }
+            }
+        }
+    }
+
+    /// Moves the first request coalesced into the given in flight request, which is still
+    /// pending, under its id. Returns false if no coalesced request is left to promote.
+    fn promote_waiting_request(&self, id: u64) -> bool {
+        let mut coalesced_map = self.coalesced_map.write().unwrap();
+        let Some((_, waiting)) = coalesced_map.get_mut(&id) else {
+            return false;
+        };
+        let mut request_map = self.request_map.write().unwrap();
+        waiting.retain(|waiting_id| request_map.contains_key(waiting_id));
+        if waiting.is_empty() {
+            return false;
+        }
+        let promoted_id = waiting.remove(0);
+        if let Some(request) = request_map.remove(&promoted_id) {
+            debug!("promoting request {} to in flight id {}", promoted_id, id);
+            request_map.insert(id, request);
+        }
+        let mut extn_map = self.extension_request_map.write().unwrap();
+        match extn_map.remove(&promoted_id) {
+            Some(extn_message) => extn_map.insert(id, extn_message),
+            None => extn_map.remove(&id),
+        };
+        true
+    }
+
+    /// Removes the requests matching the filter and forgets their pending state
+    fn remove_requests(&self, filter: impl Fn(&BrokerRequest) -> bool) {
+        let ids: Vec<u64> = {
+            let mut request_map = self.request_map.write().unwrap();
+            let ids: Vec<u64> = request_map
+                .iter()
+                .filter(|(_, request)| filter(request))
+                .map(|(id, _)| *id)
+                .collect();
+            for id in &ids {
+                request_map.remove(id);
+            }
+            ids
+        };
+        for id in ids {
+            self.forget_request(id);
+        }
+    }
+
+    /// Drops the pending requests which answer to the given callback, e.g. the requests of
+    /// workflow steps which timed out
+    pub fn cancel_requests(&self, callback: &BrokerCallback) {
+        self.remove_requests(|request| {
+            request
+                .workflow_callback
+                .as_ref()
+                .map_or(false, |c| c.sender.same_channel(&callback.sender))
+        });
+    }
+
+    fn get_sender(&self, hash: &str) -> Option<BrokerSender> {
+        self.endpoint_map.read().unwrap().get(hash).cloned()
+    }
+
+    /// Main handler method whcih checks for brokerage and then sends the request for
+    /// asynchronous processing
+    pub fn handle_brokerage(
+        &self,
+        rpc_request: RpcRequest,
+        extn_message: Option<ExtnMessage>,
+        requestor_callback: Option<BrokerCallback>,
+        permissions: Vec<FireboltPermission>,
+        session: Option<Session>,
+        telemetry_response_listeners: Vec<Sender<BrokerOutput>>,
+    ) -> bool {
+        self.handle_brokerage_with_endpoint(
+            rpc_request,
+            None,
+            extn_message,
+            requestor_callback,
+            permissions,
+            session,
+            telemetry_response_listeners,
+        )
+    }
+
+    /// Same as [EndpointBrokerState::handle_brokerage], but a given endpoint key replaces
+    /// the endpoint and fallback of the matched rule. Transforms of the rule still apply.
+    #[allow(clippy::too_many_arguments)]
+    pub fn handle_brokerage_with_endpoint(
+        &self,
+        rpc_request: RpcRequest,
+        endpoint_override: Option<String>,
+        extn_message: Option<ExtnMessage>,
+        requestor_callback: Option<BrokerCallback>,
+        permissions: Vec<FireboltPermission>,
+        session: Option<Session>,
+        telemetry_response_listeners: Vec<Sender<BrokerOutput>>,
+    ) -> bool {
+        let telemetry_response_listeners =
+            if self.telemetry_filter.is_telemetered(&rpc_request.method) {
+                telemetry_response_listeners
+            } else {
+                Vec::new()
+            };
+        self.broker_request(
+            rpc_request,
+            endpoint_override,
+            extn_message,
+            requestor_callback,
+            permissions,
+            session,
+            telemetry_response_listeners,
+            None,
+        )
+    }
+
+    /// Brokers the request, requests for an endpoint are held back in the given batch
+    /// instead of being sent when one is passed
+    #[allow(clippy::too_many_arguments)]
+    fn broker_request(
+        &self,
+        rpc_request: RpcRequest,
+        endpoint_override: Option<String>,
+        extn_message: Option<ExtnMessage>,
+        requestor_callback: Option<BrokerCallback>,
+        permissions: Vec<FireboltPermission>,
+        session: Option<Session>,
         telemetry_response_listeners: Vec<Sender<BrokerOutput>>,
+        batch: Option<&mut Vec<DispatchRequest>>,
     ) -> bool {
         let mut handled: bool = true;
         let callback = self.callback.clone();
         let mut broker_sender = None;
+        let mut broker_endpoint = None;
         let mut found_rule = None;
         LogSignal::new(
             "handle_brokerage".to_string(),
@@ -716,7 +1927,14 @@ impl EndpointBrokerState {
             rpc_request.ctx.clone(),
         )
         .emit_debug();
-        if let Some(rule) = self.rule_engine.get_rule(&rpc_request) {
+        let rule = self.rule_engine.get_rule(&rpc_request).map(|mut rule| {
+            if let Some(endpoint) = &endpoint_override {
+                rule.endpoint = Some(endpoint.clone());
+                rule.fallback = None;
+            }
+            rule
+        });
+        if let Some(rule) = rule {
             found_rule = Some(rule.clone());
 
             if let Some(endpoint) = rule.endpoint {
@@ -728,8 +1946,17 @@ impl EndpointBrokerState {
                 .with_diagnostic_context_item("rule_alias", &rule.alias)
                 .with_diagnostic_context_item("endpoint", &endpoint)
                 .emit_debug();
-                if let Some(endpoint) = self.get_sender(&endpoint) {
-                    broker_sender = Some(endpoint);
+                if let Some(sender) = self.get_sender(&endpoint) {
+                    broker_sender = Some(sender);
+                } else if let Some(fallback) = &rule.fallback {
+                    if let Some(sender) = self.get_sender(fallback) {
+                        warn!(
+                            "endpoint {} unavailable, routing {} to fallback {}",
+                            endpoint, rule.alias, fallback
+                        );
+                        broker_sender = Some(sender);
+                        broker_endpoint = Some(fallback.clone());
+                    }
                 }
             } else if rule.alias != "static" {
                 LogSignal::new(
@@ -751,12 +1978,68 @@ impl EndpointBrokerState {
                 rpc_request.ctx.clone(),
             )
             .emit_debug();
+            let counter = self.metrics_state.increment_counter(
+                RULE_NOT_FOUND_COUNTER,
+                HashMap::from([("method".to_string(), rpc_request.method.clone())]),
+            );
+            ObservabilityClient::report(OperationalMetricRequest::Counter(counter));
         }
         trace!("found rule {:?}", found_rule);
         if found_rule.is_some() {
             let rule = found_rule.unwrap();
 
-            if rule.alias == *"static" {
+            if self.is_blocked_by_maintenance(&rpc_request.method) {
+                warn!("{} rejected, broker is in maintenance", rpc_request.method);
+                self.handle_rejected_request(
+                    &rpc_request,
+                    rule,
+                    JsonRpcError {
+                        code: BROKER_MAINTENANCE_ERROR_CODE,
+                        message: "Service unavailable during maintenance".to_owned(),
+                        data: None,
+                    },
+                    callback,
+                    telemetry_response_listeners,
+                );
+            } else if !rule.is_app_allowed(&rpc_request.ctx.app_id) {
+                warn!(
+                    "{} is not allowed to call {}",
+                    rpc_request.ctx.app_id, rpc_request.method
+                );
+                self.handle_rejected_request(
+                    &rpc_request,
+                    rule,
+                    JsonRpcError {
+                        code: CAPABILITY_NOT_PERMITTED,
+                        message: format!(
+                            "{} is not permitted for {}",
+                            rpc_request.method, rpc_request.ctx.app_id
+                        ),
+                        data: None,
+                    },
+                    callback,
+                    telemetry_response_listeners,
+                );
+            } else if let Err(retry_after) = self.check_rate_limit(&rpc_request.ctx.app_id, &rule) {
+                warn!(
+                    "{} is over the rate limit of {}, retry after {}ms",
+                    rpc_request.ctx.app_id, rpc_request.method, retry_after
+                );
+                self.handle_rejected_request(
+                    &rpc_request,
+                    rule,
+                    JsonRpcError {
+                        code: RATE_LIMIT_ERROR_CODE,
+                        message: format!(
+                            "{} is rate limited for {}",
+                            rpc_request.method, rpc_request.ctx.app_id
+                        ),
+                        data: Some(json!({ "retryAfterMs": retry_after })),
+                    },
+                    callback,
+                    telemetry_response_listeners,
+                );
+            } else if rule.alias == *"static" {
                 trace!("handling static request for {:?}", rpc_request);
                 self.handle_static_request(
                     rpc_request.clone(),
@@ -778,48 +2061,129 @@ impl EndpointBrokerState {
             } else if broker_sender.is_some() {
                 trace!("handling not static request for {:?}", rpc_request);
                 let broker_sender = broker_sender.unwrap();
-                let (_, updated_request) = self.update_request(
+                let via_fallback = broker_endpoint.is_some();
+                let endpoint = broker_endpoint
+                    .or_else(|| rule.endpoint.clone())
+                    .unwrap_or("thunder".to_owned());
+                let mut dispatched_rule = rule.clone();
+                if via_fallback {
+                    // attributes the request to the fallback which serves it
+                    dispatched_rule.endpoint = Some(endpoint.clone());
+                }
+                if let Some(param) = self
+                    .rule_engine
+                    .rules
+                    .endpoints
+                    .get(&endpoint)
+                    .and_then(|e| e.gateway_secure_param.as_ref())
+                {
+                    dispatched_rule
+                        .transform
+                        .inject_request_param(param, &Value::Bool(rpc_request.ctx.gateway_secure));
+                }
+                let (id, mut updated_request) = self.update_request(
                     &rpc_request,
-                    rule,
+                    dispatched_rule,
                     extn_message,
                     requestor_callback,
                     telemetry_response_listeners,
                 );
                 capture_stage(&self.metrics_state, &rpc_request, "broker_request");
-                let thunder = self.get_sender("thunder");
-                let request_context = updated_request.rpc.ctx.clone();
-                tokio::spawn(async move {
-                    /*
-                    process "unlisten" requests here - the broker layers require state, which does not exist , as the
-                    state has already been deleted by the time the unlisten request is processed.
-                    */
-                    if updated_request.rpc.is_unlisten() {
-                        let result: JsonRpcApiResponse = updated_request.clone().rpc.into();
-                        LogSignal::new(
-                            "handle_brokerage".to_string(),
-                            "unlisten request".to_string(),
-                            request_context.clone(),
-                        )
-                        .emit_debug();
+                // the transform runs here so its failures are counted, the broker reuses it
+                let transformed =
+                    transform_request_params(&updated_request, Some(&self.metrics_state));
+                if self.is_request_traced(&updated_request.rpc) {
+                    self.trace_request_stage(
+                        &updated_request.rpc,
+                        "request",
+                        json!({
+                            "params": redact(&updated_request.rpc.get_params()),
+                            "alias": rule.alias
+                        }),
+                    );
+                    self.trace_request_stage(
+                        &updated_request.rpc,
+                        "request_transform",
+                        json!({
+                            "params": redact(&transformed.as_ref().ok().cloned()),
+                            "error": transformed.as_ref().err().map(|e| format!("{:?}", e)),
+                        }),
+                    );
+                }
+                updated_request.transformed_params = Some(transformed);
+                if let Some(pending_id) =
+                    self.coalesce_request(&rpc_request, &updated_request.rule, id)
+                {
+                    trace!(
+                        "coalesced request {} into in flight request {}",
+                        id,
+                        pending_id
+                    );
+                } else {
+                    let thunder = self.get_sender("thunder");
+                    let limiter = self
+                        .endpoint_limiters
+                        .read()
+                        .unwrap()
+                        .get(&endpoint)
+                        .cloned();
+                    let metric_tags = self.broker_metric_tags(&endpoint, &rule);
+                    self.broker_timers.write().unwrap().insert(
+                        id,
+                        Timer::start(
+                            BROKER_LATENCY_TIMER.to_owned(),
+                            Some(metric_tags.clone()),
+                            Some(TimerType::Remote),
+                        ),
+                    );
+                    let dispatch = DispatchRequest {
+                        id,
+                        endpoint,
+                        sender: broker_sender,
+                        metric_tags,
+                        inject_latency: rule
+                            .inject_latency_ms
+                            .filter(|_| Rule::latency_injection_enabled()),
+                        limiter,
+                        request: updated_request,
+                    };
+                    if let Some(batch) = batch {
+                        batch.push(dispatch);
+                        return handled;
+                    }
+                    let dispatcher = self.dispatcher();
+                    tokio::spawn(async move {
+                        if let Some(latency) = dispatch.inject_latency {
+                            warn!("injecting {}ms latency for {}", latency, dispatch.endpoint);
+                            tokio::time::sleep(tokio::time::Duration::from_millis(latency)).await;
+                        }
                         /*
-                        This is suboptimal, but the only way to handle this is to send the unlisten request to the thunder, and then
+                        process "unlisten" requests here - the broker layers require state, which does not exist , as the
+                        state has already been deleted by the time the unlisten request is processed.
                         */
-                        if let Some(thunder) = thunder {
-                            match thunder.send(updated_request.clone()).await {
-                                Ok(_) => callback.send_json_rpc_api_response(result).await,
-                                Err(e) => callback.send_error(updated_request, e).await,
+                        if dispatch.request.rpc.is_unlisten() {
+                            let updated_request = dispatch.request;
+                            let result: JsonRpcApiResponse = updated_request.clone().rpc.into();
+                            LogSignal::new(
+                                "handle_brokerage".to_string(),
+                                "unlisten request".to_string(),
+                                updated_request.rpc.ctx.clone(),
+                            )
+                            .emit_debug();
+                            /*
+                            This is suboptimal, but the only way to handle this is to send the unlisten request to the thunder, and then
+                            */
+                            if let Some(thunder) = thunder {
+                                match thunder.send(updated_request.clone()).await {
+                                    Ok(_) => callback.send_json_rpc_api_response(result).await,
+                                    Err(e) => callback.send_error(updated_request, e).await,
+                                }
                             }
+                        } else if dispatcher.acquire_permit(&dispatch).await {
+                            dispatcher.send_upstream(vec![dispatch]).await;
                         }
-                    } else if let Err(e) = broker_sender.send(updated_request.clone()).await {
-                        LogSignal::new(
-                            "handle_brokerage".to_string(),
-                            "broker send error".to_string(),
-                            request_context.clone(),
-                        )
-                        .emit_error();
-                        callback.send_error(updated_request, e).await
-                    }
-                });
+                    });
+                }
             } else {
                 handled = false;
             }
@@ -837,6 +2201,145 @@ impl EndpointBrokerState {
         handled
     }
 
+    /// Brokers the request and streams every output for it, including subsequent events of
+    /// a subscription, to the returned receiver. The receiver closes right away when the
+    /// request could not be brokered. Outputs arriving while [BROKER_STREAM_CAPACITY] outputs
+    /// are waiting to be received are dropped.
+    pub fn broker_stream(&self, rpc_request: RpcRequest) -> Receiver<BrokerOutput> {
+        let (tx, rx) = mpsc::channel(BROKER_STREAM_CAPACITY);
+        // the stream is not a telemetry listener, so the telemetry policy does not apply
+        if !self.broker_request(
+            rpc_request.clone(),
+            None,
+            None,
+            None,
+            vec![],
+            None,
+            vec![tx],
+            None,
+        ) {
+            warn!("broker_stream: could not broker {}", rpc_request.method);
+        }
+        rx
+    }
+
+    /// Brokers the listen requests of many events at once. Duplicate events are only
+    /// subscribed once. The requests for a thunder endpoint go upstream as one batch,
+    /// requests for other endpoints are sent one by one. Returns the outcome of every
+    /// subscription in order. A subscription fails when it is not brokered, is rejected or
+    /// is not acknowledged within [BULK_SUBSCRIBE_TIMEOUT_MS]. The acknowledgements are
+    /// delivered to the session as well, like those of individual listen requests.
+    pub async fn bulk_subscribe(
+        &self,
+        requests: Vec<(RpcRequest, Vec<FireboltPermission>)>,
+        session: Option<Session>,
+    ) -> Vec<BulkSubscribeResult> {
+        let (ack_tx, mut ack_rx) = mpsc::channel(requests.len().max(1));
+        let mut held = Vec::new();
+        let mut results: Vec<BulkSubscribeResult> = Vec::new();
+        let mut events = HashSet::new();
+        for (request, permissions) in requests {
+            if !events.insert(normalize_method(&request.method)) {
+                continue;
+            }
+            let handled = self.broker_request(
+                request.clone(),
+                None,
+                None,
+                None,
+                permissions,
+                session.clone(),
+                vec![ack_tx.clone()],
+                Some(&mut held),
+            );
+            results.push(BulkSubscribeResult {
+                event: request.method.clone(),
+                id: request.ctx.call_id,
+                success: false,
+                error: (!handled).then(|| {
+                    json!(JsonRpcError {
+                        code: RippleError::NotAvailable.to_jsonrpc_code(),
+                        message: format!("{} is not brokered", request.method),
+                        data: None,
+                    })
+                }),
+            });
+        }
+        drop(ack_tx);
+        self.send_held_requests(held);
+
+        let mut pending = results.iter().filter(|r| r.error.is_none()).count();
+        let deadline = tokio::time::Instant::now()
+            + tokio::time::Duration::from_millis(BULK_SUBSCRIBE_TIMEOUT_MS);
+        while pending > 0 {
+            let output = match tokio::time::timeout_at(deadline, ack_rx.recv()).await {
+                Ok(Some(output)) => output,
+                _ => break,
+            };
+            if let Some(result) = results
+                .iter_mut()
+                .find(|r| Some(r.id) == output.data.id && !r.success && r.error.is_none())
+            {
+                result.success = output.data.error.is_none();
+                result.error = output.data.error;
+                pending -= 1;
+            }
+        }
+        for result in results.iter_mut() {
+            if !result.success && result.error.is_none() {
+                result.error = Some(json!(JsonRpcError {
+                    code: RippleError::TimeoutError.to_jsonrpc_code(),
+                    message: format!("{} was not acknowledged in time", result.event),
+                    data: None,
+                }));
+            }
+        }
+        results
+    }
+
+    /// Sends the requests held back by [EndpointBrokerState::bulk_subscribe], the requests
+    /// for a thunder endpoint are sent as a single [BrokerRequest::batch]. Like individual
+    /// requests they are delayed by their injected latency and wait for a slot of their
+    /// endpoint, a batch goes out early when the endpoint has no free slot left.
+    fn send_held_requests(&self, held: Vec<DispatchRequest>) {
+        let mut endpoints: Vec<Vec<DispatchRequest>> = Vec::new();
+        for dispatch in held {
+            let is_thunder = matches!(
+                self.endpoint_protocol(&dispatch.endpoint),
+                Some(RuleEndpointProtocol::Thunder)
+            );
+            match endpoints
+                .iter_mut()
+                .find(|batch| is_thunder && batch[0].endpoint == dispatch.endpoint)
+            {
+                Some(batch) => batch.push(dispatch),
+                None => endpoints.push(vec![dispatch]),
+            }
+        }
+        for batch in endpoints {
+            let dispatcher = self.dispatcher();
+            tokio::spawn(async move {
+                if let Some(latency) = batch.iter().filter_map(|d| d.inject_latency).max() {
+                    warn!("injecting {}ms latency for {}", latency, batch[0].endpoint);
+                    tokio::time::sleep(tokio::time::Duration::from_millis(latency)).await;
+                }
+                let mut admitted = Vec::new();
+                for dispatch in batch {
+                    // the slots taken so far are only freed once their requests are sent
+                    if dispatch.limiter.as_ref().map_or(false, |l| l.is_busy()) {
+                        dispatcher
+                            .send_upstream(std::mem::take(&mut admitted))
+                            .await;
+                    }
+                    if dispatcher.acquire_permit(&dispatch).await {
+                        admitted.push(dispatch);
+                    }
+                }
+                dispatcher.send_upstream(admitted).await;
+            });
+        }
+    }
+
     pub fn handle_broker_response(&self, data: JsonRpcApiResponse) {
         if let Err(e) = self.callback.sender.try_send(BrokerOutput { data }) {
             error!("Cannot forward broker response {:?}", e)
@@ -847,12 +2350,109 @@ impl EndpointBrokerState {
         self.rule_engine.get_rule(rpc_request)
     }
 
-    // Method to cleanup all subscription on App termination
-    pub async fn cleanup_for_app(&self, app_id: &str) {
+    pub fn validate_rules(&self) -> Vec<(String, String)> {
+        self.rule_engine.validate()
+    }
+
+    pub fn check_rule_endpoints(&self) -> RippleResponse {
+        self.rule_engine.check_endpoints()
+    }
+
+    async fn cleanup_brokers(&self, session_id: &str) {
         let cleaners = { self.cleaner_list.read().unwrap().clone() };
         for cleaner in cleaners {
-            cleaner.cleanup_session(app_id).await
+            cleaner.cleanup_session(session_id).await
+        }
+    }
+
+    // Method to cleanup all subscription and pending requests on App termination
+    pub async fn cleanup_for_app(&self, app_id: &str) {
+        self.cleanup_brokers(app_id).await;
+        self.remove_requests(|request| request.rpc.ctx.get_id() == app_id);
+    }
+
+    /// Forcibly unsubscribes every subscription of a single session across brokers, other
+    /// sessions of the same app keep their subscriptions
+    pub async fn close_session(&self, session_id: &str) {
+        info!("closing subscriptions of session {}", session_id);
+        self.cleanup_brokers(session_id).await;
+        self.remove_requests(|request| {
+            request.rpc.is_subscription() && request.rpc.ctx.session_id == session_id
+        });
+    }
+
+    /// Methods each session is subscribed to, from the subscriptions known to the thunder
+    /// brokers and the listen requests awaiting events
+    pub fn list_subscriptions(&self) -> HashMap<String, Vec<String>> {
+        let mut subscriptions: HashMap<String, BTreeSet<String>> = HashMap::new();
+        // the maps are cloned so that no two locks are held at once
+        let subscription_maps: Vec<Arc<RwLock<BrokerSubMap>>> = self
+            .subscription_maps
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        for subscription_map in subscription_maps {
+            for (session_id, requests) in subscription_map.read().unwrap().iter() {
+                subscriptions.entry(session_id.clone()).or_default().extend(
+                    requests
+                        .iter()
+                        .map(|request| request.rpc.ctx.method.clone()),
+                );
+            }
+        }
+        for request in self.request_map.read().unwrap().values() {
+            if request.rpc.is_subscription() && request.rpc.is_listening() {
+                subscriptions
+                    .entry(request.rpc.ctx.session_id.clone())
+                    .or_default()
+                    .insert(request.rpc.ctx.method.clone());
+            }
+        }
+        subscriptions
+            .into_iter()
+            .filter(|(_, methods)| !methods.is_empty())
+            .map(|(session_id, methods)| (session_id, methods.into_iter().collect()))
+            .collect()
+    }
+
+    /// Retains a redacted copy of the output, dropping the oldest once the buffer is full
+    pub fn record_output(&self, output: &BrokerOutput) {
+        let mut data = output.data.clone();
+        if data.result.is_some() {
+            data.result = Some(Value::String(REDACTED.to_owned()));
+        }
+        if data.params.is_some() {
+            data.params = Some(Value::String(REDACTED.to_owned()));
+        }
+        let mut recent_outputs = self.recent_outputs.write().unwrap();
+        if recent_outputs.len() == RECENT_OUTPUTS_CAPACITY {
+            recent_outputs.pop_front();
         }
+        recent_outputs.push_back(BrokerOutput::new(data));
+    }
+
+    /// Last n recorded outputs, oldest first
+    pub fn recent_outputs(&self, n: usize) -> Vec<BrokerOutput> {
+        let recent_outputs = self.recent_outputs.read().unwrap();
+        recent_outputs
+            .iter()
+            .skip(recent_outputs.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+
+    // Method to cleanup the subscriptions and pending requests of all apps, used on
+    // shutdown and reset
+    pub async fn cleanup_all(&self) {
+        self.cleanup_brokers(CLEANUP_ALL_SENTINEL).await;
+        self.remove_requests(|_| true);
+    }
+
+    // Drops the broker of a single endpoint, its broker stops once all senders are gone
+    pub fn cleanup_endpoint(&self, key: &str) -> bool {
+        self.endpoint_map.write().unwrap().remove(key).is_some()
     }
 }
 
@@ -876,10 +2476,28 @@ pub trait EndpointBroker {
     /// Adds BrokerContext to a given request used by the Broker Implementations
     /// just before sending the data through the protocol
     fn update_request(rpc_request: &BrokerRequest) -> Result<String, RippleError> {
-        let v = Self::apply_request_rule(rpc_request)?;
-        trace!("transformed request {:?}", v);
         let id = rpc_request.rpc.ctx.call_id;
         let method = rpc_request.rule.alias.clone();
+        if rpc_request.rule.is_passthrough() {
+            // the app's params are embedded without reparsing so the endpoint gets them byte
+            // for byte
+            return Ok(match passthrough_params(&rpc_request.rpc.params_json)? {
+                Some(params) => format!(
+                    r#"{{"jsonrpc":"2.0","id":{},"method":{},"params":{}}}"#,
+                    id,
+                    Value::String(method),
+                    params
+                ),
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method
+                })
+                .to_string(),
+            });
+        }
+        let v = Self::apply_request_rule(rpc_request)?;
+        trace!("transformed request {:?}", v);
         if let Value::Null = v {
             Ok(json!({
                 "jsonrpc": "2.0",
@@ -900,52 +2518,11 @@ pub trait EndpointBroker {
 
     /// Generic method which takes the given parameters from RPC request and adds rules using rule engine
     fn apply_request_rule(rpc_request: &BrokerRequest) -> Result<Value, RippleError> {
-        if let Ok(mut params) = serde_json::from_str::<Vec<Value>>(&rpc_request.rpc.params_json) {
-            let last = if params.len() > 1 {
-                params.pop().unwrap()
-            } else {
-                Value::Null
-            };
-
-            if let Some(filter) = rpc_request
-                .rule
-                .transform
-                .get_transform_data(super::rules_engine::RuleTransformType::Request)
-            {
-                let transformed_request_res = jq_compile(
-                    last,
-                    &filter,
-                    format!("{}_request", rpc_request.rpc.ctx.method),
-                );
-
-                LogSignal::new(
-                    "endpoint_broker".to_string(),
-                    "apply_request_rule".to_string(),
-                    rpc_request.rpc.ctx.clone(),
-                )
-                .with_diagnostic_context_item("success", "true")
-                .with_diagnostic_context_item("result", &format!("{:?}", transformed_request_res))
-                .emit_debug();
-
-                return transformed_request_res;
-            }
-            LogSignal::new(
-                "endpoint_broker".to_string(),
-                "apply_request_rule".to_string(),
-                rpc_request.rpc.ctx.clone(),
-            )
-            .with_diagnostic_context_item("success", "true")
-            .with_diagnostic_context_item("result", &last.to_string())
-            .emit_debug();
-            return Ok(serde_json::to_value(&last).unwrap());
+        match &rpc_request.transformed_params {
+            Some(transformed) => transformed.clone(),
+            // requests which did not pass through handle_brokerage
+            None => transform_request_params(rpc_request, None),
         }
-        LogSignal::new(
-            "endpoint_broker".to_string(),
-            "apply_request_rule: parse error".to_string(),
-            rpc_request.rpc.ctx.clone(),
-        )
-        .emit_error();
-        Err(RippleError::ParseError)
     }
 
     /// Default handler method for the broker to remove the context and send it back to the
@@ -985,18 +2562,23 @@ pub struct BrokerOutputForwarder;
 
 impl BrokerOutputForwarder {
     pub fn start_forwarder(mut platform_state: PlatformState, mut rx: Receiver<BrokerOutput>) {
-        // set up the event utility
-        let event_utility = Arc::new(EventManagementUtility::new());
-        event_utility.register_custom_functions();
-        let event_utility_clone = event_utility.clone();
-
         tokio::spawn(async move {
-            while let Some(output) = rx.recv().await {
+            // events of id-less notifications, delivered before the next output is read
+            let mut notification_events = VecDeque::new();
+            loop {
+                let output = if let Some(event) = notification_events.pop_front() {
+                    event
+                } else if let Some(output) = rx.recv().await {
+                    platform_state.endpoint_state.record_output(&output);
+                    output
+                } else {
+                    break;
+                };
                 let output_c = output.clone();
                 let mut response = output.data.clone();
                 let mut is_event = false;
                 // First validate the id check if it could be an event
-                let id = if let Some(e) = output_c.get_event() {
+                let id = if let Some(e) = platform_state.endpoint_state.get_event_id(&output_c) {
                     is_event = true;
                     Some(e)
                 } else {
@@ -1004,7 +2586,21 @@ impl BrokerOutputForwarder {
                 };
 
                 if let Some(id) = id {
-                    if let Ok(broker_request) = platform_state.endpoint_state.get_request(id) {
+                    let broker_request = platform_state
+                        .endpoint_state
+                        .get_request(id)
+                        .ok()
+                        .or_else(|| take_awaited_request(id));
+                    if let Some(broker_request) = broker_request {
+                        if !is_event {
+                            platform_state.endpoint_state.release_permit(id);
+                            platform_state
+                                .endpoint_state
+                                .report_broker_latency(id, &response);
+                            platform_state
+                                .endpoint_state
+                                .fan_out_response(id, &output_c.data);
+                        }
                         LogSignal::new(
                             "start_forwarder".to_string(),
                             "broker request found".to_string(),
@@ -1024,6 +2620,16 @@ impl BrokerOutputForwarder {
                         let session_id = rpc_request.ctx.get_id();
                         let is_subscription = rpc_request.is_subscription();
                         let mut apply_response_needed = false;
+                        if !is_event {
+                            platform_state.endpoint_state.trace_request_stage(
+                                &rpc_request,
+                                "response",
+                                json!({
+                                    "result": redact(&response.result),
+                                    "error": response.error
+                                }),
+                            );
+                        }
 
                         // Step 1: Create the data
                         if let Some(result) = response.result.clone() {
@@ -1052,20 +2658,34 @@ impl BrokerOutputForwarder {
                                     continue;
                                 }
 
-                                if let Some(filter) =
-                                    broker_request.rule.transform.get_transform_data(
-                                        super::rules_engine::RuleTransformType::Event(
-                                            rpc_request.ctx.context.contains(&RPC_V2.into()),
-                                        ),
-                                    )
+                                let transform_type = RuleTransformType::Event(
+                                    rpc_request.ctx.context.contains(&RPC_V2.into()),
+                                );
+                                if let Some(filter) = broker_request
+                                    .rule
+                                    .transform
+                                    .get_transform_data(transform_type)
                                 {
-                                    apply_rule_for_event(
+                                    if !apply_rule_for_event(
                                         &broker_request,
                                         &result,
                                         &rpc_request,
                                         &filter,
                                         &mut response,
-                                    );
+                                    ) {
+                                        report_jq_failure(
+                                            &platform_state.metrics,
+                                            &broker_request.rule.alias,
+                                            transform_type,
+                                        );
+                                        if broker_request.rule.drops_failed_events() {
+                                            warn!(
+                                                "dropping event for {}, event transform failed",
+                                                rpc_request.ctx.method
+                                            );
+                                            continue;
+                                        }
+                                    }
                                 }
 
                                 if !apply_filter(&broker_request, &result, &rpc_request) {
@@ -1076,8 +2696,10 @@ impl BrokerOutputForwarder {
                                 if let Some(decorator_method) =
                                     broker_request.rule.transform.event_decorator_method.clone()
                                 {
-                                    if let Some(func) =
-                                        event_utility_clone.get_function(&decorator_method)
+                                    if let Some(func) = platform_state
+                                        .endpoint_state
+                                        .event_utility
+                                        .get_function(&decorator_method)
                                     {
                                         // spawn a tokio thread to run the function and continue the main thread.
                                         LogSignal::new(
@@ -1092,14 +2714,14 @@ impl BrokerOutputForwarder {
                                         let platform_state_c = platform_state.clone();
                                         let ctx = rpc_request.ctx.clone();
                                         tokio::spawn(async move {
-                                            if let Ok(value) = func(
+                                            if let Ok(Some(value)) = func(
                                                 platform_state_c.clone(),
                                                 ctx.clone(),
                                                 Some(result.clone()),
                                             )
                                             .await
                                             {
-                                                response.result = Some(value.expect("REASON"));
+                                                response.result = Some(value);
                                             }
                                             response.id = Some(request_id);
 
@@ -1154,7 +2776,15 @@ impl BrokerOutputForwarder {
                             apply_response_needed = true;
                         }
 
-                        if apply_response_needed {
+                        // Passthrough rules return the upstream result as is
+                        if apply_response_needed && broker_request.rule.is_passthrough() {
+                            apply_response_needed = false;
+                            if response.result.is_none() && response.error.is_none() {
+                                response.result = Some(Value::Null);
+                            }
+                        }
+
+                        if apply_response_needed {
                             // Apply response rule using params if there is any; otherwise, apply response rule using main broker request's response rule
                             let mut apply_response_using_main_req_needed = true;
                             if let Some(params) = output.data.params {
@@ -1163,32 +2793,80 @@ impl BrokerOutputForwarder {
                                         if key == "response" {
                                             if let Some(filter) = value.as_str() {
                                                 apply_response_using_main_req_needed = false;
-                                                apply_response(
+                                                if !apply_response(
                                                     filter.to_string(),
                                                     &rpc_request.ctx.method,
                                                     &mut response,
-                                                );
+                                                ) {
+                                                    // the filter came with the response, not
+                                                    // from the rule
+                                                    report_jq_failure(
+                                                        &platform_state.metrics,
+                                                        &composite_key_source(key),
+                                                        RuleTransformType::Response(
+                                                            rpc_request
+                                                                .ctx
+                                                                .context
+                                                                .contains(&RPC_V2.into()),
+                                                        ),
+                                                    );
+                                                }
                                             }
                                         }
                                     }
                                 }
                             }
                             if apply_response_using_main_req_needed {
-                                if let Some(filter) =
-                                    broker_request.rule.transform.get_transform_data(
-                                        super::rules_engine::RuleTransformType::Response,
-                                    )
+                                let transform_type = RuleTransformType::Response(
+                                    rpc_request.ctx.context.contains(&RPC_V2.into()),
+                                );
+                                if let Some(filter) = broker_request
+                                    .rule
+                                    .transform
+                                    .get_transform_data(transform_type)
                                 {
-                                    apply_response(filter, &rule_context_name, &mut response);
+                                    if !apply_response(filter, &rule_context_name, &mut response) {
+                                        report_jq_failure(
+                                            &platform_state.metrics,
+                                            &broker_request.rule.alias,
+                                            transform_type,
+                                        );
+                                    }
                                 } else if response.result.is_none() && response.error.is_none() {
                                     response.result = Some(Value::Null);
                                 }
                             }
+                            if let Some(result) = &response.result {
+                                if let Err(e) = broker_request.rule.validate_response(result) {
+                                    error!(
+                                        "{} response violates its response_schema: {}",
+                                        rule_context_name, e
+                                    );
+                                    response.error = Some(json!({
+                                        "code": RESPONSE_SCHEMA_VIOLATION_ERROR_CODE,
+                                        "message": "Response does not match the expected schema",
+                                        "data": e,
+                                    }));
+                                    response.result = None;
+                                }
+                            }
                         }
 
                         let request_id = rpc_request.ctx.call_id;
                         response.id = Some(request_id);
+                        if !is_event {
+                            platform_state.endpoint_state.trace_request_stage(
+                                &rpc_request,
+                                "response_transform",
+                                json!({
+                                    "result": redact(&response.result),
+                                    "error": response.error
+                                }),
+                            );
+                        }
 
+                        let deliver_to_session = workflow_callback.is_none()
+                            || broker_request.rule.delivers_workflow_responses_to_session();
                         if let Some(workflow_callback) = workflow_callback {
                             debug!("sending to workflow callback {:?}", response);
                             LogSignal::new(
@@ -1201,7 +2879,8 @@ impl BrokerOutputForwarder {
                                 .sender
                                 .send(BrokerOutput::new(response.clone()))
                                 .await;
-                        } else {
+                        }
+                        if deliver_to_session {
                             let tm_str = get_rpc_header(&rpc_request);
 
                             if is_event {
@@ -1214,14 +2893,11 @@ impl BrokerOutputForwarder {
                                 serde_json::to_string(&response).unwrap(),
                                 rpc_request.ctx.request_id.clone(),
                             );
-                            let mut status_code: i64 = 1;
-                            if let Some(e) = &response.error {
-                                if let Some(Value::Number(n)) = e.get("code") {
-                                    if let Some(v) = n.as_i64() {
-                                        status_code = v;
-                                    }
-                                }
-                            }
+                            let status_code = response
+                                .error
+                                .as_ref()
+                                .and_then(RippleError::jsonrpc_error_code)
+                                .unwrap_or(1);
 
                             platform_state.metrics.update_api_stats_ref(
                                 &rpc_request.ctx.request_id,
@@ -1268,8 +2944,13 @@ impl BrokerOutputForwarder {
                             }
                         }
 
+                        // a listener which stopped draining must not hold up the forwarder
                         for listener in telemetry_response_listeners {
-                            let _ = listener.send(BrokerOutput::new(response.clone())).await;
+                            if let Err(TrySendError::Full(_)) =
+                                listener.try_send(BrokerOutput::new(response.clone()))
+                            {
+                                warn!("dropping output {:?} for a full listener", response.id);
+                            }
                         }
                     } else {
                         error!(
@@ -1279,10 +2960,26 @@ impl BrokerOutputForwarder {
                         );
                     }
                 } else {
-                    error!(
-                        "Error couldnt broker the event {:?} due to a missing request id",
-                        output_c
-                    )
+                    let method = output_c.data.method.clone().unwrap_or_default();
+                    let subscribers = platform_state
+                        .endpoint_state
+                        .get_notification_subscribers(&method);
+                    if subscribers.is_empty() {
+                        error!(
+                            "Error couldnt broker the event {:?} due to a missing request id",
+                            output_c
+                        )
+                    }
+                    // notifications are delivered as events of each subscription, with the
+                    // notification params as the event result
+                    for id in subscribers {
+                        let mut event = output_c.data.clone();
+                        event.method = Some(format!("{}.{}", id, method));
+                        if event.result.is_none() {
+                            event.result = event.params.take();
+                        }
+                        notification_events.push_back(BrokerOutput::new(event));
+                    }
                 }
             }
         });
@@ -1342,8 +3039,9 @@ impl BrokerOutputForwarder {
         };
         // ==============================================================================================================
 
-        if let Ok(res) = BrokerUtils::process_internal_main_request(
+        if let Ok(res) = BrokerUtils::process_nested_internal_main_request(
             &mut platform_state_c,
+            &rpc_request.ctx,
             method.as_str(),
             params,
         )
@@ -1444,11 +3142,95 @@ async fn forward_extn_event(
     }
 }
 
+/// The app's params of a params_json, as they appear in it, None when the app sent no params
+fn passthrough_params(params_json: &str) -> Result<Option<String>, RippleError> {
+    let mut params =
+        serde_json::from_str::<Vec<&RawValue>>(params_json).map_err(|_| RippleError::ParseError)?;
+    // the first element is the CallContext
+    if params.len() > 1 {
+        Ok(params.pop().map(|p| p.get().to_owned()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Takes the given parameters from RPC request and adds rules using rule engine. Failed jq
+/// transforms are counted when metrics are given.
+fn transform_request_params(
+    rpc_request: &BrokerRequest,
+    metrics: Option<&MetricsState>,
+) -> Result<Value, RippleError> {
+    if rpc_request.rule.is_passthrough() {
+        return match passthrough_params(&rpc_request.rpc.params_json)? {
+            Some(params) => serde_json::from_str(&params).map_err(|_| RippleError::ParseError),
+            None => Ok(Value::Null),
+        };
+    }
+    if let Ok(mut params) = serde_json::from_str::<Vec<Value>>(&rpc_request.rpc.params_json) {
+        let last = if params.len() > 1 {
+            params.pop().unwrap()
+        } else {
+            Value::Null
+        };
+
+        if let Some(filter) = rpc_request
+            .rule
+            .transform
+            .get_transform_data(super::rules_engine::RuleTransformType::Request)
+        {
+            let transformed_request_res = jq_compile(
+                last,
+                &filter,
+                format!("{}_request", rpc_request.rpc.ctx.method),
+            );
+            if let (Err(_), Some(metrics)) = (&transformed_request_res, metrics) {
+                report_jq_failure(metrics, &rpc_request.rule.alias, RuleTransformType::Request);
+            }
+
+            LogSignal::new(
+                "endpoint_broker".to_string(),
+                "apply_request_rule".to_string(),
+                rpc_request.rpc.ctx.clone(),
+            )
+            .with_diagnostic_context_item("success", "true")
+            .with_diagnostic_context_item("result", &format!("{:?}", transformed_request_res))
+            .emit_debug();
+
+            return transformed_request_res;
+        }
+        LogSignal::new(
+            "endpoint_broker".to_string(),
+            "apply_request_rule".to_string(),
+            rpc_request.rpc.ctx.clone(),
+        )
+        .with_diagnostic_context_item("success", "true")
+        .with_diagnostic_context_item("result", &last.to_string())
+        .emit_debug();
+        return Ok(serde_json::to_value(&last).unwrap());
+    }
+    LogSignal::new(
+        "endpoint_broker".to_string(),
+        "apply_request_rule: parse error".to_string(),
+        rpc_request.rpc.ctx.clone(),
+    )
+    .emit_error();
+    Err(RippleError::ParseError)
+}
+
+/// Hides a recorded value which may carry user data, keeping whether it was present
+fn redact(value: &Option<Value>) -> Value {
+    match value {
+        Some(_) => Value::String(REDACTED.to_owned()),
+        None => Value::Null,
+    }
+}
+
+/// Applies the response filter to the response, returns false when the jq transform fails
 pub fn apply_response(
     result_response_filter: String,
     method: &str,
     response: &mut JsonRpcApiResponse,
-) {
+) -> bool {
     match serde_json::to_value(response.clone()) {
         Ok(input) => {
             match jq_compile(
@@ -1476,6 +3258,7 @@ pub fn apply_response(
                 Err(e) => {
                     response.error = Some(json!(e.to_string()));
                     error!("jq_compile error {:?}", e);
+                    return false;
                 }
             }
         }
@@ -1484,15 +3267,18 @@ pub fn apply_response(
             error!("json rpc response error {:?}", e);
         }
     }
+    true
 }
 
+/// Applies the event transform to the event result, returns false and leaves the
+/// response untouched when the transform fails
 pub fn apply_rule_for_event(
     broker_request: &BrokerRequest,
     result: &Value,
     rpc_request: &RpcRequest,
     filter: &str,
     response: &mut JsonRpcApiResponse,
-) {
+) -> bool {
     if let Ok(r) = jq_compile(
         result.clone(),
         filter,
@@ -1507,6 +3293,7 @@ pub fn apply_rule_for_event(
         .with_diagnostic_context_item("result", r.to_string().as_str())
         .emit_debug();
         response.result = Some(r);
+        true
     } else {
         LogSignal::new(
             "apply_rule_for_event".to_string(),
@@ -1515,6 +3302,7 @@ pub fn apply_rule_for_event(
         )
         .with_diagnostic_context_item("success", "false")
         .emit_debug();
+        false
     }
 }
 
@@ -1525,14 +3313,27 @@ fn apply_filter(broker_request: &BrokerRequest, result: &Value, rpc_request: &Rp
             &filter,
             format!("{}_event filter", rpc_request.ctx.method),
         ) {
-            if r.is_null() {
+            // get bool value for r, null drops the event
+            if r.is_null() || !r.as_bool().unwrap() {
                 return false;
-            } else {
-                // get bool value for r and return
-                return r.as_bool().unwrap();
             }
         }
     }
+    if let Some(filter) = broker_request.rule.subscription_filter.clone() {
+        // lets the app narrow down events using the params it subscribed with
+        let input = json!({
+            "params": rpc_request.get_params().unwrap_or(Value::Null),
+            "result": result,
+        });
+        match jq_compile(
+            input,
+            &filter,
+            format!("{}_subscription filter", rpc_request.ctx.method),
+        ) {
+            Ok(r) => return r == Value::Bool(true),
+            Err(e) => error!("subscription filter failed {:?}", e),
+        }
+    }
     true
 }
 
@@ -1541,6 +3342,7 @@ mod tests {
     use super::*;
     use crate::broker::rules_engine::RuleTransform;
     use ripple_sdk::{tokio::sync::mpsc::channel, Mockable};
+    use serde::Deserialize;
 
     #[tokio::test]
     async fn test_send_error() {
@@ -1558,16 +3360,42 @@ mod tests {
                         filter: None,
                         event_handler: None,
                         sources: None,
+                        inject_latency_ms: None,
+                        fallback: None,
+                        allowed_apps: None,
+                        event_transform_failure: None,
+                        capabilities: None,
+                        subscription_filter: None,
+                        workflow_session_delivery: None,
+                        response_schema: None,
+                        provider_fallback: None,
+                        rate_limit: None,
+                        notification_passthrough: None,
+                        passthrough: None,
                     },
                     subscription_processed: None,
                     workflow_callback: None,
                     telemetry_response_listeners: vec![],
+                    batch: Vec::new(),
+                    transformed_params: None,
                 },
                 RippleError::InvalidInput,
             )
             .await;
         let value = tr.recv().await.unwrap();
-        assert!(value.data.error.is_some())
+        assert!(value.data.error.is_some());
+        let invalid_input_code = value.data.error.unwrap()["code"].clone();
+
+        callback
+            .send_error(BrokerRequest::default(), RippleError::SendFailure)
+            .await;
+        let value = tr.recv().await.unwrap();
+        let send_failure_code = value.data.error.unwrap()["code"].clone();
+        assert_ne!(invalid_input_code, send_failure_code);
+        assert_eq!(
+            send_failure_code,
+            serde_json::json!(RippleError::SendFailure.to_jsonrpc_code())
+        );
     }
 
     mod broker_output {
@@ -1598,19 +3426,44 @@ mod tests {
     }
 
     mod endpoint_broker_state {
+        use std::{
+            collections::HashMap,
+            sync::{Arc, RwLock},
+            time::Duration,
+        };
+
         use ripple_sdk::{
-            api::gateway::rpc_gateway_api::RpcRequest, tokio, tokio::sync::mpsc::channel, Mockable,
+            api::{
+                firebolt::fb_capabilities::{
+                    CapabilityRole, FireboltCap, FireboltPermission, CAPABILITY_NOT_PERMITTED,
+                },
+                gateway::rpc_gateway_api::{JsonRpcApiResponse, RpcRequest, RPC_V2},
+                manifest::device_manifest::TelemetryMethodPolicy,
+            },
+            tokio,
+            tokio::sync::mpsc::{channel, Receiver, Sender},
+            utils::error::RippleError,
+            Mockable,
         };
 
         use crate::{
             broker::{
                 endpoint_broker::tests::RippleClient,
-                rules_engine::{Rule, RuleEngine, RuleSet, RuleTransform},
+                rules_engine::{
+                    composite_key_source, RateLimit, ResponseSchema, Rule, RuleEndpointConfig,
+                    RuleEngine, RuleSet, RuleTransform, JQ_TRANSFORM_FAILURE_COUNTER,
+                },
+            },
+            state::{
+                bootstrap_state::ChannelsState, metrics_state::MetricsState,
+                platform_state::PlatformState, session_state::Session,
             },
-            state::{bootstrap_state::ChannelsState, metrics_state::MetricsState},
         };
 
-        use super::EndpointBrokerState;
+        use super::{
+            BrokerCallback, BrokerOutput, BrokerOutputForwarder, BrokerRequest, BrokerSender,
+            EndpointBroker, EndpointBrokerState, HttpBroker,
+        };
 
         #[tokio::test]
         async fn get_request() {
@@ -1634,6 +3487,18 @@ mod tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    inject_latency_ms: None,
+                    fallback: None,
+                    allowed_apps: None,
+                    event_transform_failure: None,
+                    capabilities: None,
+                    subscription_filter: None,
+                    workflow_session_delivery: None,
+                    response_schema: None,
+                    provider_fallback: None,
+                    rate_limit: None,
+                    notification_passthrough: None,
+                    passthrough: None,
                 },
                 None,
                 None,
@@ -1649,6 +3514,18 @@ mod tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    inject_latency_ms: None,
+                    fallback: None,
+                    allowed_apps: None,
+                    event_transform_failure: None,
+                    capabilities: None,
+                    subscription_filter: None,
+                    workflow_session_delivery: None,
+                    response_schema: None,
+                    provider_fallback: None,
+                    rate_limit: None,
+                    notification_passthrough: None,
+                    passthrough: None,
                 },
                 None,
                 None,
@@ -1661,165 +3538,2170 @@ mod tests {
             // assert!(state.get_request(2).is_ok());
             // assert!(state.get_request(1).is_ok());
         }
-    }
 
-    #[tokio::test]
-    async fn test_apply_response_contains_error() {
-        let error = json!({"code":-32601,"message":"The service is in an illegal state!!!."});
-        let ctx = CallContext::new(
-            "session_id".to_string(),
-            "request_id".to_string(),
-            "app_id".to_string(),
-            1,
-            ApiProtocol::Bridge,
-            "method".to_string(),
-            Some("cid".to_string()),
-            true,
-        );
-        let rpc_request = RpcRequest::new("new_method".to_string(), "params".to_string(), ctx);
-        let mut data = JsonRpcApiResponse::mock();
-        data.error = Some(error);
-        let mut output: BrokerOutput = BrokerOutput::new(data.clone());
-        let filter = "if .result and .result.success then (.result.stbVersion | split(\"_\") [0]) elif .error then if .error.code == -32601 then {error: { code: -1, message: \"Unknown method.\" }} else \"Error occurred with a different code\" end else \"No result or recognizable error\" end".to_string();
-        //let mut response = JsonRpcApiResponse::mock();
-        //response.error = Some(error);
-        apply_response(filter, &rpc_request.ctx.method, &mut output.data);
-        //let msg = output.data.error.unwrap().get("message").unwrap().clone();
-        assert_eq!(
-            output.data.error.unwrap().get("message").unwrap().clone(),
-            json!("Unknown method.".to_string())
-        );
+        /// Broker state routing the "test.method" and "test.onevent" rules to a mock
+        /// endpoint whose requests are returned on the receiver
+        fn test_broker_state(
+            callback_tx: Sender<BrokerOutput>,
+        ) -> (EndpointBrokerState, Receiver<BrokerRequest>) {
+            let client = RippleClient::new(ChannelsState::new());
+            let mut rules = RuleSet::default();
+            for method in ["test.method", "test.onevent"] {
+                rules.rules.insert(
+                    method.to_owned(),
+                    Rule {
+                        alias: format!("{}.alias", method),
+                        transform: RuleTransform::default(),
+                        endpoint: Some("mock".to_owned()),
+                        filter: None,
+                        event_handler: None,
+                        sources: None,
+                        inject_latency_ms: None,
+                        fallback: None,
+                        allowed_apps: None,
+                        event_transform_failure: None,
+                        capabilities: None,
+                        subscription_filter: None,
+                        workflow_session_delivery: None,
+                        response_schema: None,
+                        provider_fallback: None,
+                        rate_limit: None,
+                        notification_passthrough: None,
+                        passthrough: None,
+                    },
+                );
+            }
+            let mut state = EndpointBrokerState::new(
+                MetricsState::default(),
+                callback_tx,
+                RuleEngine { rules },
+                client,
+            );
+            let (mock_tx, mock_rx) = channel(4);
+            state.add_endpoint("mock".to_owned(), BrokerSender { sender: mock_tx });
+            (state, mock_rx)
+        }
 
-        // securestorage.get code 22 in error response
-        let error = json!({"code":22,"message":"test error code 22"});
-        let mut data = JsonRpcApiResponse::mock();
-        data.error = Some(error);
-        let mut output: BrokerOutput = BrokerOutput::new(data);
-        let filter = "if .result and .result.success then .result.value elif .error.code==22 or .error.code==43 then null else .error end".to_string();
+        fn app_request(app_id: &str, method: &str, params: serde_json::Value) -> RpcRequest {
+            let mut request = RpcRequest::mock();
+            request.method = method.to_owned();
+            request.ctx.app_id = app_id.to_owned();
+            request.params_json = serde_json::json!([{}, params]).to_string();
+            request
+        }
 
-        apply_response(filter, &rpc_request.ctx.method, &mut output.data);
-        assert_eq!(output.data.error, None);
-        assert_eq!(output.data.result.unwrap(), serde_json::Value::Null);
+        #[tokio::test]
+        async fn test_send_and_wait() {
+            let (tx, tr) = channel(4);
+            let (mock_tx, mut mock_rx) = channel::<BrokerRequest>(4);
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = EndpointBrokerState::new(
+                MetricsState::default(),
+                tx.clone(),
+                RuleEngine::default(),
+                RippleClient::new(ChannelsState::new()),
+            );
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+            tokio::spawn(async move {
+                while let Some(request) = mock_rx.recv().await {
+                    // the mock never answers stalled requests
+                    if request.rpc.method == "test.stall" {
+                        continue;
+                    }
+                    let response = JsonRpcApiResponse {
+                        id: Some(request.rpc.ctx.call_id),
+                        result: Some(serde_json::json!({"friendlyName": "living room"})),
+                        ..Default::default()
+                    };
+                    let _ = tx.send(BrokerOutput::new(response)).await;
+                }
+            });
+            let sender = BrokerSender { sender: mock_tx };
+            let request = |method| {
+                let rule = Rule {
+                    transform: RuleTransform {
+                        response: Some(".result.friendlyName".to_owned()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                BrokerRequest::new(
+                    &app_request("internal", method, serde_json::json!({})),
+                    rule,
+                    None,
+                    vec![],
+                )
+            };
 
-        // securestorage.get code other than 22 or 43 in error response
-        let error = json!({"code":300,"message":"test error code 300"});
-        let mut data = JsonRpcApiResponse::mock();
-        data.error = Some(error.clone());
-        let mut output: BrokerOutput = BrokerOutput::new(data);
-        let filter = "if .result and .result.success then .result.value elif .error.code==22 or .error.code==43 then null else { error: .error } end".to_string();
-        apply_response(filter, &rpc_request.ctx.method, &mut output.data);
-        assert_eq!(output.data.error, Some(error));
-    }
+            let output = sender
+                .send_and_wait(request("test.method"), Duration::from_secs(2))
+                .await
+                .unwrap();
+            assert_eq!(output.data.result, Some(serde_json::json!("living room")));
 
-    #[tokio::test]
-    async fn test_apply_response_contains_result() {
-        // mock test
-        let ctx = CallContext::new(
-            "session_id".to_string(),
-            "request_id".to_string(),
-            "app_id".to_string(),
-            1,
-            ApiProtocol::Bridge,
-            "method".to_string(),
-            Some("cid".to_string()),
-            true,
-        );
-        let rpc_request = RpcRequest::new("new_method".to_string(), "params".to_string(), ctx);
-
-        // device.sku
-        let filter = "if .result and .result.success then (.result.stbVersion | split(\"_\") [0]) elif .error then if .error.code == -32601 then {\"error\":\"Unknown method.\"} else \"Error occurred with a different code\" end else \"No result or recognizable error\" end".to_string();
-        //let mut response = JsonRpcApiResponse::mock();
-        let result = json!({"stbVersion":"SCXI11BEI_VBN_24Q3_sprint_20240717150752sdy_FG","receiverVersion":"7.6.0.0","stbTimestamp":"Wed 17 Jul 2024 15:07:52 UTC","success":true});
-        //response.result = Some(result);
-        let mut data = JsonRpcApiResponse::mock();
-        data.result = Some(result);
-        let mut output: BrokerOutput = BrokerOutput::new(data.clone());
-        apply_response(filter, &rpc_request.ctx.method, &mut output.data);
-        assert_eq!(output.data.result.unwrap(), "SCXI11BEI".to_string());
-
-        // device.videoResolution
-        let result = json!("Resolution1080P");
-        let filter = "if .result then if .result | contains(\"480\") then ( [640, 480] ) elif .result | contains(\"576\") then ( [720, 576] ) elif .result | contains(\"1080\") then ( [1920, 1080] ) elif .result | contains(\"2160\") then ( [2160, 1440] ) end elif .error then if .error.code == -32601 then \"Unknown method.\" else \"Error occurred with a different code\" end else \"No result or recognizable error\" end".to_string();
-        let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
-        assert_eq!(response.result.unwrap(), json!([1920, 1080]));
+            let error = sender
+                .send_and_wait(request("test.stall"), Duration::from_millis(100))
+                .await
+                .unwrap_err();
+            assert_eq!(error, RippleError::TimeoutError);
+        }
 
-        // device.audio
-        let result = json!({"currentAudioFormat":"DOLBY AC3","supportedAudioFormat":["NONE","PCM","AAC","VORBIS","WMA","DOLBY AC3","DOLBY AC4","DOLBY MAT","DOLBY TRUEHD","DOLBY EAC3 ATMOS","DOLBY TRUEHD ATMOS","DOLBY MAT ATMOS","DOLBY AC4 ATMOS","UNKNOWN"],"success":true});
-        let filter = "if .result and .result.success then .result | {\"stereo\": (.supportedAudioFormat |  index(\"PCM\") > 0),\"dolbyDigital5.1\": (.supportedAudioFormat |  index(\"DOLBY AC3\") > 0),\"dolbyDigital5.1plus\": (.supportedAudioFormat |  index(\"DOLBY EAC3\") > 0),\"dolbyAtmos\": (.supportedAudioFormat |  index(\"DOLBY EAC3 ATMOS\") > 0)} elif .error then if .error.code == -32601 then \"Unknown method.\" else \"Error occurred with a different code\" end else \"No result or recognizable error\" end".to_string();
-        let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
-        assert_eq!(
-            response.result.unwrap(),
-            json!({"dolbyAtmos": true, "dolbyDigital5.1": true, "dolbyDigital5.1plus": false, "stereo": true})
-        );
+        #[tokio::test]
+        async fn test_broker_stream() {
+            let (tx, tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            // methods without telemetry still stream their outputs
+            let state = state.with_telemetry_policy(&TelemetryMethodPolicy {
+                allow: Some(vec![]),
+                deny: vec![],
+            });
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let mut stream = state.broker_stream(app_request(
+                "app1",
+                "test.onevent",
+                serde_json::json!({"listen": true}),
+            ));
+            let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+            for value in 1..=3 {
+                state.handle_broker_response(JsonRpcApiResponse {
+                    method: Some(format!("{}.onevent", id)),
+                    result: Some(serde_json::json!({ "value": value })),
+                    ..Default::default()
+                });
+            }
+            for value in 1..=3 {
+                let output = tokio::time::timeout(Duration::from_secs(2), stream.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    output.data.result,
+                    Some(serde_json::json!({ "value": value }))
+                );
+            }
 
-        // device.network
-        let result = json!({"interfaces":[{"interface":"ETHERNET","macAddress":
-        "f0:46:3b:5b:eb:14","enabled":true,"connected":false},{"interface":"WIFI","macAddress
-        ":"f0:46:3b:5b:eb:15","enabled":true,"connected":true}],"success":true});
+            // requests without a rule close the stream straight away
+            let mut stream =
+                state.broker_stream(app_request("app1", "unknown.method", serde_json::json!({})));
+            assert!(stream.recv().await.is_none());
+        }
 
-        let filter = "if .result and .result.success then (.result.interfaces | .[] | select(.connected) | {\"state\": \"connected\",\"type\": .interface | ascii_downcase }) elif .error then if .error.code == -32601 then \"Unknown method.\" else \"Error occurred with a different code\" end else \"No result or recognizable error\" end".to_string();
-        let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
-        assert_eq!(
-            response.result.unwrap(),
-            json!({"state":"connected", "type":"wifi"})
-        );
+        #[tokio::test]
+        async fn test_endpoint_max_concurrency() {
+            let (tx, tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            state.set_endpoint_concurrency("mock", 1, 2);
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            for value in 1..=3 {
+                assert!(state.handle_brokerage(
+                    app_request("app1", "test.method", serde_json::json!({ "value": value })),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                ));
+            }
+            for _ in 1..=3 {
+                let request = tokio::time::timeout(Duration::from_secs(2), mock_rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                // the next request is held back until this one is answered
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                assert!(mock_rx.try_recv().is_err());
+                state.handle_broker_response(JsonRpcApiResponse {
+                    id: Some(request.rpc.ctx.call_id),
+                    result: Some(serde_json::json!(true)),
+                    ..Default::default()
+                });
+            }
+        }
 
-        // device.name
-        let result = json!({"friendlyName": "my_device","success":true});
-        let filter = "if .result.success then (if .result.friendlyName | length == 0 then \"Living Room\" else .result.friendlyName end) else \"Living Room\" end".to_string();
-        let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
-        assert_eq!(response.result.unwrap(), json!("my_device"));
-
-        // localization.language
-        let result = json!({"success": true, "value": "{\"update_time\":\"2024-07-29T20:23:29.539132160Z\",\"value\":\"FR\"}"});
-        let filter = "if .result.success then (.result.value | fromjson | .value) else \"en\" end"
-            .to_string();
-        let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
+        #[tokio::test]
+        async fn test_cleanup_releases_concurrency_slot() {
+            let (tx, _tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            state.set_endpoint_concurrency("mock", 1, 2);
+            for (session_id, value) in [("session1", 1), ("session2", 2)] {
+                let mut request =
+                    app_request("app1", "test.method", serde_json::json!({ "value": value }));
+                request.ctx.cid = Some(session_id.to_owned());
+                state.handle_brokerage(request, None, None, vec![], None, vec![]);
+            }
+            let request = mock_rx.recv().await.unwrap();
+            assert_eq!(request.rpc.ctx.get_id(), "session1");
+            assert!(
+                tokio::time::timeout(Duration::from_millis(100), mock_rx.recv())
+                    .await
+                    .is_err()
+            );
 
-        assert_eq!(response.result.unwrap(), json!("FR"));
+            // the unanswered request of the closed session no longer holds the only slot
+            state.cleanup_for_app("session1").await;
+            let request = tokio::time::timeout(Duration::from_secs(2), mock_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(request.rpc.ctx.get_id(), "session2");
+        }
 
-        // secondscreen.friendlyName
-        let result = json!({"friendlyName": "my_device","success":true});
-        let filter = "if .result.success then (if .result.friendlyName | length == 0 then \"Living Room\" else .result.friendlyName end) else \"Living Room\" end".to_string();
-        let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
+        #[tokio::test]
+        async fn test_endpoint_max_queued() {
+            let (tx, mut tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            state.set_endpoint_concurrency("mock", 1, 1);
+
+            for value in 1..=3 {
+                assert!(state.handle_brokerage(
+                    app_request("app1", "test.method", serde_json::json!({ "value": value })),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                ));
+            }
+            // one request dispatched, one queued and the third rejected as busy
+            let output = tokio::time::timeout(Duration::from_secs(2), tr.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                output.data.error.unwrap()["code"],
+                serde_json::json!(super::RippleError::ServiceNotReady.to_jsonrpc_code())
+            );
+            assert!(mock_rx.recv().await.is_some());
+            assert!(mock_rx.try_recv().is_err());
+        }
 
-        assert_eq!(response.result.unwrap(), json!("my_device"));
+        #[tokio::test]
+        async fn test_endpoint_override() {
+            let (tx, _tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .transform
+                .request = Some("{ name: .value }".to_owned());
+            let (other_tx, mut other_rx) = channel(4);
+            state.add_endpoint("other".to_owned(), BrokerSender { sender: other_tx });
 
-        // advertising.setSkipRestriction
-        let result = json!({"success":true});
-        let filter = "if .result.success then null else { code: -32100, message: \"couldn't set skip restriction\" } end".to_string();
-        let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
+            assert!(state.handle_brokerage_with_endpoint(
+                app_request("app1", "test.method", serde_json::json!({"value": "a"})),
+                Some("other".to_owned()),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+            let request = tokio::time::timeout(Duration::from_secs(2), other_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(request.rule.endpoint.as_deref(), Some("other"));
+            assert_eq!(
+                request.rule.transform.request.as_deref(),
+                Some("{ name: .value }")
+            );
+            assert!(mock_rx.try_recv().is_err());
+
+            // unknown endpoints are not brokered
+            assert!(!state.handle_brokerage_with_endpoint(
+                app_request("app1", "test.method", serde_json::json!({"value": "b"})),
+                Some("missing".to_owned()),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_event_transform_failure_drop() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            let rule = state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.onevent")
+                .unwrap();
+            rule.transform.event = Some("undefined_fn(.)".to_owned());
+            rule.event_transform_failure =
+                Some(crate::broker::rules_engine::EventTransformFailure::Drop);
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let mut stream = state.broker_stream(app_request(
+                "app1",
+                "test.onevent",
+                serde_json::json!({"listen": true}),
+            ));
+            let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+            state.handle_broker_response(JsonRpcApiResponse {
+                method: Some(format!("{}.onevent", id)),
+                result: Some(serde_json::json!({ "value": 1 })),
+                ..Default::default()
+            });
+            assert!(
+                tokio::time::timeout(Duration::from_millis(200), stream.recv())
+                    .await
+                    .is_err()
+            );
+        }
+
+        #[tokio::test]
+        async fn test_allowed_apps() {
+            let (tx, mut tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .allowed_apps = Some(vec!["app1".to_owned()]);
+
+            assert!(state.handle_brokerage(
+                app_request("app1", "test.method", serde_json::json!({})),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+            assert_eq!(mock_rx.recv().await.unwrap().rpc.ctx.app_id, "app1");
+
+            assert!(state.handle_brokerage(
+                app_request("app2", "test.method", serde_json::json!({})),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+            let output = tr.recv().await.unwrap();
+            assert_eq!(
+                output.data.error.unwrap()["code"],
+                serde_json::json!(CAPABILITY_NOT_PERMITTED)
+            );
+            assert!(
+                tokio::time::timeout(Duration::from_millis(100), mock_rx.recv())
+                    .await
+                    .is_err()
+            );
+        }
+
+        #[tokio::test]
+        async fn test_rate_limit() {
+            let (tx, mut tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .rate_limit = Some(RateLimit {
+                requests: 2,
+                window_ms: 60000,
+            });
+
+            for n in 0..2 {
+                assert!(state.handle_brokerage(
+                    app_request("app1", "test.method", serde_json::json!({ "n": n })),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                ));
+                assert_eq!(mock_rx.recv().await.unwrap().rpc.ctx.app_id, "app1");
+            }
+
+            assert!(state.handle_brokerage(
+                app_request("app1", "test.method", serde_json::json!({ "n": 2 })),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+            let error = tr.recv().await.unwrap().data.error.unwrap();
+            assert_eq!(
+                error["code"],
+                serde_json::json!(super::RATE_LIMIT_ERROR_CODE)
+            );
+            assert!(error["data"]["retryAfterMs"].as_u64().unwrap() > 0);
+            assert!(
+                tokio::time::timeout(Duration::from_millis(100), mock_rx.recv())
+                    .await
+                    .is_err()
+            );
+
+            assert!(state.handle_brokerage(
+                app_request("app2", "test.method", serde_json::json!({ "n": 3 })),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+            assert_eq!(mock_rx.recv().await.unwrap().rpc.ctx.app_id, "app2");
+        }
+
+        #[tokio::test]
+        async fn test_rate_limit_windows_pruned() {
+            let (tx, _tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .rate_limit = Some(RateLimit {
+                requests: 1,
+                window_ms: 50,
+            });
+            for (n, app) in ["app1", "app2"].into_iter().enumerate() {
+                assert!(state.handle_brokerage(
+                    app_request(app, "test.method", serde_json::json!({ "n": n })),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                ));
+                mock_rx.recv().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(60)).await;
+            }
+            // the window of app1 ended before the one of app2 started
+            let windows: Vec<String> = state
+                .rate_windows
+                .read()
+                .unwrap()
+                .keys()
+                .map(|(app, _)| app.clone())
+                .collect();
+            assert_eq!(windows, vec!["app2".to_owned()]);
+        }
+
+        #[test]
+        fn test_get_next_id_unique() {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    std::thread::spawn(|| {
+                        (0..1000)
+                            .map(|_| EndpointBrokerState::get_next_id())
+                            .collect::<Vec<u64>>()
+                    })
+                })
+                .collect();
+            let ids: Vec<u64> = handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect();
+            let unique: std::collections::HashSet<u64> = ids.iter().cloned().collect();
+            assert_eq!(unique.len(), ids.len());
+            assert!(!unique.contains(&0));
+        }
+
+        #[tokio::test]
+        async fn test_request_trace() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .transform = RuleTransform {
+                request: Some("{ name: .value }".to_owned()),
+                response: Some(".result.friendlyName".to_owned()),
+                ..Default::default()
+            };
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            // distinct params keep the requests from being coalesced
+            let request = |session_id: &str, request_id: &str| {
+                let mut request = app_request(
+                    "app1",
+                    "test.method",
+                    serde_json::json!({"value": "tv", "session": session_id}),
+                );
+                request.ctx.session_id = session_id.to_owned();
+                request.ctx.request_id = request_id.to_owned();
+                request
+            };
+            state.enable_request_trace("traced");
+            for (session_id, request_id) in [("traced", "req1"), ("other", "req2")] {
+                assert!(state.handle_brokerage(
+                    request(session_id, request_id),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                ));
+                let dispatched = mock_rx.recv().await.unwrap();
+                // the transform run at dispatch is reused when the request is sent, traced or not
+                assert_eq!(
+                    dispatched.transformed_params,
+                    Some(Ok(serde_json::json!({"name": "tv"})))
+                );
+                state.handle_broker_response(JsonRpcApiResponse {
+                    id: Some(dispatched.rpc.ctx.call_id),
+                    result: Some(serde_json::json!({"friendlyName": "living room"})),
+                    ..Default::default()
+                });
+            }
+
+            let trace = tokio::time::timeout(Duration::from_secs(2), async {
+                loop {
+                    let trace = state.request_trace(Some("req1"));
+                    if trace.len() == 5 {
+                        return trace;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap();
+            let stages: Vec<&str> = trace.iter().map(|e| e.stage.as_str()).collect();
+            assert_eq!(
+                stages,
+                vec![
+                    "request",
+                    "request_transform",
+                    "upstream",
+                    "response",
+                    "response_transform"
+                ]
+            );
+            // params and results are redacted like the recent outputs
+            assert_eq!(
+                trace[0].data,
+                serde_json::json!({"params": super::REDACTED, "alias": "test.method.alias"})
+            );
+            assert_eq!(
+                trace[1].data,
+                serde_json::json!({"params": super::REDACTED, "error": null})
+            );
+            assert_eq!(trace[4].data["result"], serde_json::json!(super::REDACTED));
+            assert!(trace.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+            // requests of sessions without tracing are not recorded
+            assert!(state.request_trace(Some("req2")).is_empty());
+
+            state.disable_request_trace("traced");
+            assert!(!state.is_request_traced(&request("traced", "req3")));
+        }
+
+        #[tokio::test]
+        async fn test_forwarded_response_keeps_client_id() {
+            let (tx, tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let mut request = app_request("app1", "test.method", serde_json::json!({}));
+            request.ctx.call_id = 42;
+            let (listener_tx, mut listener_rx) = channel(1);
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![listener_tx],));
+            let internal_id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+            assert_ne!(internal_id, 42);
+            state.handle_broker_response(JsonRpcApiResponse {
+                id: Some(internal_id),
+                result: Some(serde_json::json!("ok")),
+                ..Default::default()
+            });
+            let output = tokio::time::timeout(Duration::from_secs(2), listener_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(output.data.id, Some(42));
+        }
+
+        #[tokio::test]
+        async fn test_rpc_v2_response_transform() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .transform = RuleTransform {
+                response: Some(".result.name".to_owned()),
+                rpcv2_response: Some("{ name: .result.name }".to_owned()),
+                ..Default::default()
+            };
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            for (rpc_v2, expected) in [
+                (false, serde_json::json!("tv")),
+                (true, serde_json::json!({"name": "tv"})),
+            ] {
+                // distinct params keep the requests from being coalesced
+                let mut request =
+                    app_request("app1", "test.method", serde_json::json!({ "v2": rpc_v2 }));
+                if rpc_v2 {
+                    request.ctx.context.push(RPC_V2.to_owned());
+                }
+                let (listener_tx, mut listener_rx) = channel(1);
+                assert!(state.handle_brokerage(
+                    request,
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![listener_tx]
+                ));
+                let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+                state.handle_broker_response(JsonRpcApiResponse {
+                    id: Some(id),
+                    result: Some(serde_json::json!({"name": "tv"})),
+                    ..Default::default()
+                });
+                let output = tokio::time::timeout(Duration::from_secs(2), listener_rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(output.data.result, Some(expected));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_endpoint_build_failure() {
+            let (tx, _tr) = channel(4);
+            let (mut state, _mock_rx) = test_broker_state(tx);
+            let mut build_failures = state.subscribe_build_failures();
+            let endpoint = crate::broker::rules_engine::RuleEndpointConfig {
+                protocol: super::RuleEndpointProtocol::UnixSocket,
+                url: "unix:///nonexistent/ripple-broker.sock".to_owned(),
+                jsonrpc: true,
+                ..Default::default()
+            }
+            .build()
+            .unwrap();
+            state.build_endpoint(
+                None,
+                super::BrokerConnectRequest::new("dead".to_owned(), endpoint, channel(2).0),
+            );
+
+            let failure = tokio::time::timeout(Duration::from_secs(2), build_failures.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(failure.endpoint, "dead");
+            assert!(state.get_build_failures().contains_key("dead"));
+            // retries of the same endpoint are not reported again
+            assert!(
+                tokio::time::timeout(Duration::from_millis(1500), build_failures.recv())
+                    .await
+                    .is_err()
+            );
+        }
 
-        assert_eq!(response.result.unwrap(), serde_json::Value::Null);
+        #[tokio::test]
+        async fn test_subscription_filter() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.onevent")
+                .unwrap()
+                .subscription_filter = Some(".result.channel == .params.channel".to_owned());
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let mut stream = state.broker_stream(app_request(
+                "app1",
+                "test.onevent",
+                serde_json::json!({"listen": true, "channel": "news"}),
+            ));
+            let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+            for channel in ["sports", "news"] {
+                state.handle_broker_response(JsonRpcApiResponse {
+                    method: Some(format!("{}.onevent", id)),
+                    result: Some(serde_json::json!({ "channel": channel })),
+                    ..Default::default()
+                });
+            }
+            let output = tokio::time::timeout(Duration::from_secs(2), stream.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                output.data.result,
+                Some(serde_json::json!({ "channel": "news" }))
+            );
+            assert!(
+                tokio::time::timeout(Duration::from_millis(200), stream.recv())
+                    .await
+                    .is_err()
+            );
+        }
+
+        #[tokio::test]
+        async fn test_registered_event_decorator() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.onevent")
+                .unwrap()
+                .transform
+                .event_decorator_method = Some("TestDecorator".to_owned());
+            state.get_event_utility().register_function(
+                "TestDecorator".to_owned(),
+                Arc::new(|_, _, value: Option<serde_json::Value>| {
+                    Box::pin(async move {
+                        Ok::<_, RippleError>(value.map(|v| serde_json::json!({ "decorated": v })))
+                    })
+                }),
+            );
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            let request = app_request("app1", "test.onevent", serde_json::json!({"listen": true}));
+            let (session_tx, mut session_rx) = channel(2);
+            platform_state.session_state.add_session(
+                request.ctx.get_id(),
+                Session::new("app1".to_owned(), Some(session_tx)),
+            );
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+            state.handle_broker_response(JsonRpcApiResponse {
+                method: Some(format!("{}.onevent", id)),
+                result: Some(serde_json::json!("event")),
+                ..Default::default()
+            });
+
+            let message = tokio::time::timeout(Duration::from_secs(2), session_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            let delivered: JsonRpcApiResponse = serde_json::from_str(&message.jsonrpc_msg).unwrap();
+            assert_eq!(
+                delivered.result,
+                Some(serde_json::json!({ "decorated": "event" }))
+            );
+            assert!(state
+                .get_event_utility()
+                .unregister_function("TestDecorator"));
+            assert!(!state
+                .get_event_utility()
+                .unregister_function("TestDecorator"));
+        }
+
+        #[tokio::test]
+        async fn test_extension_event_decorator() {
+            use crate::processor::app_events_processor::AppEventsProcessor;
+            use ripple_sdk::{
+                api::apps::{AppEventRequest, EventDecorator},
+                extn::{
+                    client::extn_processor::ExtnEventProcessor, extn_client_message::ExtnMessage,
+                },
+            };
+
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = EndpointBrokerState::default();
+            let utility = platform_state.endpoint_state.get_event_utility();
+            // extensions register their decorators through app events
+            AppEventsProcessor::process_event(
+                platform_state.clone(),
+                ExtnMessage::default(),
+                AppEventRequest::RegisterDecorator(EventDecorator {
+                    name: "ExtnDecorator".to_owned(),
+                    method: "extn.decorate".to_owned(),
+                }),
+            )
+            .await;
+            assert!(utility.get_function("ExtnDecorator").is_some());
+            AppEventsProcessor::process_event(
+                platform_state,
+                ExtnMessage::default(),
+                AppEventRequest::UnregisterDecorator("ExtnDecorator".to_owned()),
+            )
+            .await;
+            assert!(utility.get_function("ExtnDecorator").is_none());
+        }
+
+        #[tokio::test]
+        async fn test_notification_passthrough() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.onevent")
+                .unwrap()
+                .notification_passthrough = Some(true);
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            let mut sessions = Vec::new();
+            // more subscribers than the output channel can hold
+            for app_id in ["app1", "app2", "app3", "app4", "app5", "app6"] {
+                let mut request =
+                    app_request(app_id, "test.onevent", serde_json::json!({"listen": true}));
+                request.ctx.session_id = format!("{}_session", app_id);
+                request.ctx.cid = None;
+                let (session_tx, session_rx) = channel(2);
+                platform_state.session_state.add_session(
+                    request.ctx.get_id(),
+                    Session::new(app_id.to_owned(), Some(session_tx)),
+                );
+                sessions.push((request, session_rx));
+            }
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+            for (request, _) in &sessions {
+                assert!(state.handle_brokerage(request.clone(), None, None, vec![], None, vec![]));
+                mock_rx.recv().await.unwrap();
+            }
+
+            state.handle_broker_response(JsonRpcApiResponse {
+                method: Some("test.onevent.alias".to_owned()),
+                params: Some(serde_json::json!("broadcast")),
+                ..Default::default()
+            });
+
+            for (_, session_rx) in sessions.iter_mut() {
+                let message = tokio::time::timeout(Duration::from_secs(2), session_rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let delivered: JsonRpcApiResponse =
+                    serde_json::from_str(&message.jsonrpc_msg).unwrap();
+                assert_eq!(delivered.result, Some(serde_json::json!("broadcast")));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_jq_failure_counters() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .transform
+                .response = Some("if".to_owned());
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            let metrics = platform_state.metrics.clone();
+            let (session_tx, mut session_rx) = channel(2);
+            let session_id = app_request("app1", "test.method", serde_json::json!({}))
+                .ctx
+                .get_id();
+            platform_state.session_state.add_session(
+                session_id,
+                Session::new("app1".to_owned(), Some(session_tx)),
+            );
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            // the rule's broken response filter, then one handed back with the response
+            for (value, params) in [(1, None), (2, Some(serde_json::json!({"response": "if"})))] {
+                let request = app_request("app1", "test.method", serde_json::json!({"a": value}));
+                assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+                let dispatched = mock_rx.recv().await.unwrap();
+                state.handle_broker_response(JsonRpcApiResponse {
+                    id: Some(dispatched.rpc.ctx.call_id),
+                    result: Some(serde_json::json!({"value": value})),
+                    params,
+                    ..Default::default()
+                });
+                tokio::time::timeout(Duration::from_secs(2), session_rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            }
+
+            for source in [
+                "test.method.alias".to_owned(),
+                composite_key_source("response"),
+            ] {
+                let tags = HashMap::from([
+                    ("source".to_owned(), source),
+                    ("transform".to_owned(), "response".to_owned()),
+                ]);
+                let counter = metrics
+                    .get_counter(JQ_TRANSFORM_FAILURE_COUNTER, &tags)
+                    .unwrap();
+                assert_eq!(counter.value, 1);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_passthrough_rule() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            {
+                let rule = state
+                    .rule_engine
+                    .rules
+                    .rules
+                    .get_mut("test.method")
+                    .unwrap();
+                rule.passthrough = Some(true);
+                rule.transform.request = Some("{b: .a}".to_owned());
+                rule.transform.response = Some(".missing".to_owned());
+            }
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            let params_json = r#"[{},  {"z": 1, "a": [3,2, 1]}]"#;
+            let mut request = app_request("app1", "test.method", serde_json::json!({}));
+            request.params_json = params_json.to_owned();
+            let (session_tx, mut session_rx) = channel(2);
+            platform_state.session_state.add_session(
+                request.ctx.get_id(),
+                Session::new("app1".to_owned(), Some(session_tx)),
+            );
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            let dispatched = mock_rx.recv().await.unwrap();
+            let sent = HttpBroker::update_request(&dispatched).unwrap();
+            // only the app's params are sent, as the app wrote them
+            assert!(sent.ends_with(r#""params":{"z": 1, "a": [3,2, 1]}}"#));
+            let sent: serde_json::Value = serde_json::from_str(&sent).unwrap();
+            assert_eq!(sent["method"], serde_json::json!("test.method.alias"));
+            assert_eq!(sent["id"], serde_json::json!(dispatched.rpc.ctx.call_id));
+
+            state.handle_broker_response(JsonRpcApiResponse {
+                id: Some(dispatched.rpc.ctx.call_id),
+                result: Some(serde_json::json!({"raw": [1, 2]})),
+                ..Default::default()
+            });
+            let message = tokio::time::timeout(Duration::from_secs(2), session_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            let delivered: JsonRpcApiResponse = serde_json::from_str(&message.jsonrpc_msg).unwrap();
+            assert_eq!(delivered.result, Some(serde_json::json!({"raw": [1, 2]})));
+        }
+
+        #[tokio::test]
+        async fn test_workflow_session_delivery() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .workflow_session_delivery = Some(true);
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            let request = app_request("app1", "test.method", serde_json::json!({}));
+            let (session_tx, mut session_rx) = channel(2);
+            platform_state.session_state.add_session(
+                request.ctx.get_id(),
+                Session::new("app1".to_owned(), Some(session_tx)),
+            );
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let (workflow_tx, mut workflow_rx) = channel(2);
+            assert!(state.handle_brokerage(
+                request,
+                None,
+                Some(BrokerCallback {
+                    sender: workflow_tx
+                }),
+                vec![],
+                None,
+                vec![],
+            ));
+            let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+            state.handle_broker_response(JsonRpcApiResponse {
+                id: Some(id),
+                result: Some(serde_json::json!("done")),
+                ..Default::default()
+            });
+
+            let output = tokio::time::timeout(Duration::from_secs(2), workflow_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(output.data.result, Some(serde_json::json!("done")));
+            let message = tokio::time::timeout(Duration::from_secs(2), session_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            let delivered: JsonRpcApiResponse = serde_json::from_str(&message.jsonrpc_msg).unwrap();
+            assert_eq!(delivered.result, Some(serde_json::json!("done")));
+        }
+
+        #[tokio::test]
+        async fn test_response_schema_violation() {
+            let (tx, tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            let rule = state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap();
+            rule.transform.response = Some(".result | tostring".to_owned());
+            rule.response_schema = Some(ResponseSchema::new(serde_json::json!({"type": "number"})));
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let (workflow_tx, mut workflow_rx) = channel(2);
+            assert!(state.handle_brokerage(
+                app_request("app1", "test.method", serde_json::json!({})),
+                None,
+                Some(BrokerCallback {
+                    sender: workflow_tx
+                }),
+                vec![],
+                None,
+                vec![],
+            ));
+            let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+            state.handle_broker_response(JsonRpcApiResponse {
+                id: Some(id),
+                result: Some(serde_json::json!(42)),
+                ..Default::default()
+            });
+
+            let output = tokio::time::timeout(Duration::from_secs(2), workflow_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(output.data.result.is_none());
+            assert_eq!(
+                output.data.error.unwrap()["code"],
+                serde_json::json!(super::RESPONSE_SCHEMA_VIOLATION_ERROR_CODE)
+            );
+        }
+
+        #[tokio::test]
+        async fn test_maintenance_mode() {
+            let (tx, mut tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            let request = |method: &str| app_request("app1", method, serde_json::json!({}));
+            state.enable_maintenance_mode(vec!["Test.OnEvent".to_owned()]);
+            assert!(state.is_maintenance_mode());
+
+            assert!(state.handle_brokerage(
+                request("test.method"),
+                None,
+                None,
+                vec![],
+                None,
+                vec![]
+            ));
+            let output = tr.recv().await.unwrap();
+            assert_eq!(
+                output.data.error.unwrap()["code"],
+                serde_json::json!(-32001)
+            );
+            assert!(
+                tokio::time::timeout(Duration::from_millis(100), mock_rx.recv())
+                    .await
+                    .is_err()
+            );
+
+            // allowlisted methods are still brokered
+            assert!(state.handle_brokerage(
+                request("test.onevent"),
+                None,
+                None,
+                vec![],
+                None,
+                vec![]
+            ));
+            assert_eq!(mock_rx.recv().await.unwrap().rpc.method, "test.onevent");
+
+            state.disable_maintenance_mode();
+            assert!(!state.is_maintenance_mode());
+            assert!(state.handle_brokerage(
+                request("test.method"),
+                None,
+                None,
+                vec![],
+                None,
+                vec![]
+            ));
+            assert_eq!(mock_rx.recv().await.unwrap().rpc.method, "test.method");
+        }
+
+        #[tokio::test]
+        async fn test_telemetry_policy() {
+            let (tx, tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            let state = state.with_telemetry_policy(&TelemetryMethodPolicy {
+                allow: None,
+                deny: vec!["Test.Method".to_owned()],
+            });
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            for method in ["test.method", "test.onevent"] {
+                let (telemetry_tx, mut telemetry_rx) = channel(2);
+                assert!(state.handle_brokerage(
+                    app_request("app1", method, serde_json::json!({})),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![telemetry_tx],
+                ));
+                let id = mock_rx.recv().await.unwrap().rpc.ctx.call_id;
+                state.handle_broker_response(JsonRpcApiResponse {
+                    id: Some(id),
+                    result: Some(serde_json::json!(true)),
+                    ..Default::default()
+                });
+                let telemetry =
+                    tokio::time::timeout(Duration::from_millis(500), telemetry_rx.recv()).await;
+                if method == "test.method" {
+                    assert!(matches!(telemetry, Err(_) | Ok(None)));
+                } else {
+                    assert_eq!(
+                        telemetry.unwrap().unwrap().data.result,
+                        Some(serde_json::json!(true))
+                    );
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn test_gateway_secure_param() {
+            let (tx, _tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state.rule_engine.rules.endpoints.insert(
+                "mock".to_owned(),
+                RuleEndpointConfig {
+                    url: "ws://127.0.0.1:9998".to_owned(),
+                    gateway_secure_param: Some("secure".to_owned()),
+                    ..Default::default()
+                }
+                .build()
+                .unwrap(),
+            );
+            let mut request = app_request("app1", "test.method", serde_json::json!({"a": 1}));
+            request.ctx.gateway_secure = false;
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+
+            let dispatched = mock_rx.recv().await.unwrap();
+            assert_eq!(
+                HttpBroker::apply_request_rule(&dispatched).unwrap(),
+                serde_json::json!({"a": 1, "secure": false})
+            );
+
+            // the flag is added after the request transform of the rule
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .transform
+                .request = Some("{b: .a}".to_owned());
+            let request = app_request("app1", "test.method", serde_json::json!({"a": 1}));
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            let dispatched = mock_rx.recv().await.unwrap();
+            assert_eq!(
+                HttpBroker::apply_request_rule(&dispatched).unwrap(),
+                serde_json::json!({"b": 1, "secure": true})
+            );
+        }
+
+        #[tokio::test]
+        async fn test_broker_latency_tags() {
+            let (tx, _tr) = channel(4);
+            let (mut state, _mock_rx) = test_broker_state(tx);
+            let mut rule = state.rule_engine.rules.rules["test.method"].clone();
+            rule.alias = "org.rdk.System.1.getDeviceInfo".to_owned();
+            rule.endpoint = None;
+            state
+                .rule_engine
+                .rules
+                .rules
+                .insert("device.info".to_owned(), rule);
+            let (thunder_tx, mut thunder_rx) = channel(2);
+            state.add_endpoint("thunder".to_owned(), BrokerSender { sender: thunder_tx });
+
+            assert!(state.handle_brokerage(
+                app_request("app1", "device.info", serde_json::json!({})),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+            let id = thunder_rx.recv().await.unwrap().rpc.ctx.call_id;
+            let timer = state
+                .report_broker_latency(
+                    id,
+                    &JsonRpcApiResponse {
+                        id: Some(id),
+                        result: Some(serde_json::json!({})),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            assert_eq!(timer.name, super::BROKER_LATENCY_TIMER);
+            let tags = timer.tags.unwrap();
+            assert_eq!(tags["endpoint"], "thunder");
+            assert_eq!(tags["protocol"], "thunder");
+            assert_eq!(tags["callsign"], "org.rdk.System");
+            assert_eq!(tags["status"], "1");
+            // the timer is reported once
+            assert!(state
+                .report_broker_latency(id, &JsonRpcApiResponse::default())
+                .is_none());
+
+            // the timers of requests dropped without a response are discarded
+            // distinct params keep the request from being coalesced into the first one
+            let mut request = app_request("app1", "device.info", serde_json::json!({"n": 2}));
+            request.ctx.cid = Some("app1_session".to_owned());
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            thunder_rx.recv().await.unwrap();
+            assert_eq!(state.broker_timers.read().unwrap().len(), 1);
+            state.cleanup_for_app("app1_session").await;
+            assert!(state.broker_timers.read().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_bulk_subscribe() {
+            let (tx, tr) = channel(16);
+            let (mut state, _mock_rx) = test_broker_state(tx);
+            let mut requests = Vec::new();
+            for i in 0..10 {
+                let mut rule = state.rule_engine.rules.rules["test.onevent"].clone();
+                rule.alias = format!("org.rdk.System.onEvent{}", i);
+                rule.endpoint = None;
+                let event = format!("test.onevent{}", i);
+                state.rule_engine.rules.rules.insert(event.clone(), rule);
+                let mut request = app_request("app1", &event, serde_json::json!({"listen": true}));
+                request.ctx.call_id = EndpointBrokerState::get_next_id();
+                requests.push((request, vec![]));
+            }
+            // duplicates are only subscribed once
+            requests.push(requests[0].clone());
+            let (thunder_tx, mut thunder_rx) = channel(16);
+            state.add_endpoint("thunder".to_owned(), BrokerSender { sender: thunder_tx });
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let state_c = state.clone();
+            let results = tokio::spawn(async move { state_c.bulk_subscribe(requests, None).await });
+            let mut upstream = thunder_rx.recv().await.unwrap();
+            assert_eq!(upstream.batch.len(), 9);
+            assert!(thunder_rx.try_recv().is_err());
+
+            let batch = std::mem::take(&mut upstream.batch);
+            for request in std::iter::once(upstream).chain(batch) {
+                state.handle_broker_response(JsonRpcApiResponse {
+                    id: Some(request.rpc.ctx.call_id),
+                    result: Some(serde_json::json!(null)),
+                    ..Default::default()
+                });
+            }
+            let results = tokio::time::timeout(Duration::from_secs(2), results)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(results.len(), 10);
+            assert!(results.iter().all(|r| r.success && r.error.is_none()));
+        }
+
+        #[tokio::test]
+        async fn test_bulk_subscribe_concurrency() {
+            let (tx, tr) = channel(16);
+            let (mut state, _mock_rx) = test_broker_state(tx);
+            let mut requests = Vec::new();
+            for i in 0..10 {
+                let mut rule = state.rule_engine.rules.rules["test.onevent"].clone();
+                rule.alias = format!("org.rdk.System.onEvent{}", i);
+                rule.endpoint = None;
+                let event = format!("test.onevent{}", i);
+                state.rule_engine.rules.rules.insert(event.clone(), rule);
+                let mut request = app_request("app1", &event, serde_json::json!({"listen": true}));
+                request.ctx.call_id = 100 + i;
+                requests.push((request, vec![]));
+            }
+            let (thunder_tx, mut thunder_rx) = channel(16);
+            state.add_endpoint("thunder".to_owned(), BrokerSender { sender: thunder_tx });
+            state.set_endpoint_concurrency("thunder", 4, 16);
+            let mut platform_state =
+                <PlatformState as ripple_tdk::utils::test_utils::Mockable>::mock();
+            platform_state.endpoint_state = state.clone();
+            BrokerOutputForwarder::start_forwarder(platform_state, tr);
+
+            let state_c = state.clone();
+            let results = tokio::spawn(async move { state_c.bulk_subscribe(requests, None).await });
+            // the held requests wait for slots of the endpoint like individual requests
+            let mut sent = 0;
+            while sent < 10 {
+                let mut upstream = thunder_rx.recv().await.unwrap();
+                assert!(upstream.batch.len() < 4);
+                let batch = std::mem::take(&mut upstream.batch);
+                for request in std::iter::once(upstream).chain(batch) {
+                    sent += 1;
+                    state.handle_broker_response(JsonRpcApiResponse {
+                        id: Some(request.rpc.ctx.call_id),
+                        result: Some(serde_json::json!(null)),
+                        ..Default::default()
+                    });
+                }
+            }
+            let results = tokio::time::timeout(Duration::from_secs(2), results)
+                .await
+                .unwrap()
+                .unwrap();
+            // results keep the order of the events and echo their ids
+            let ids: Vec<u64> = results.iter().map(|r| r.id).collect();
+            assert_eq!(ids, (100..110).collect::<Vec<u64>>());
+            assert!(results.iter().all(|r| r.success));
+        }
+
+        #[tokio::test]
+        async fn test_fallback_endpoint() {
+            let (tx, _tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            let rule = state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap();
+            rule.endpoint = Some("dead".to_owned());
+            rule.fallback = Some("mock".to_owned());
+            assert!(state.handle_brokerage(
+                app_request("app1", "test.method", serde_json::json!({})),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+            let dispatched = mock_rx.recv().await.unwrap();
+            assert_eq!(dispatched.rpc.ctx.app_id, "app1");
+            assert_eq!(dispatched.rule.alias, "test.method.alias");
+
+            // without a fallback the request is not handled
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .fallback = None;
+            assert!(!state.handle_brokerage(
+                app_request("app2", "test.method", serde_json::json!({})),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_inject_latency() {
+            let (tx, _tr) = channel(4);
+            let (mut state, mut mock_rx) = test_broker_state(tx);
+            state
+                .rule_engine
+                .rules
+                .rules
+                .get_mut("test.method")
+                .unwrap()
+                .inject_latency_ms = Some(300);
+            let started = std::time::Instant::now();
+            assert!(state.handle_brokerage(
+                app_request("app1", "test.method", serde_json::json!({})),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            ));
+
+            // a short timeout trips before the delayed request reaches the endpoint
+            assert!(
+                tokio::time::timeout(Duration::from_millis(100), mock_rx.recv())
+                    .await
+                    .is_err()
+            );
+            let dispatched = tokio::time::timeout(Duration::from_secs(2), mock_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(dispatched.rpc.ctx.app_id, "app1");
+            assert!(started.elapsed() >= Duration::from_millis(300));
+        }
+
+        #[tokio::test]
+        async fn test_coalesce_identical_requests() {
+            let (tx, mut tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            let params = serde_json::json!({"key": "value"});
+            for session_id in ["session1", "session2"] {
+                let mut request = app_request("app1", "test.method", params.clone());
+                request.ctx.session_id = session_id.to_owned();
+                assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            }
+
+            let dispatched = mock_rx.recv().await.unwrap();
+            assert_eq!(dispatched.rpc.ctx.app_id, "app1");
+            assert!(
+                tokio::time::timeout(Duration::from_millis(100), mock_rx.recv())
+                    .await
+                    .is_err()
+            );
+
+            let mut response = JsonRpcApiResponse::mock();
+            response.id = Some(dispatched.rpc.ctx.call_id);
+            response.result = Some(serde_json::json!("result"));
+            state.fan_out_response(dispatched.rpc.ctx.call_id, &response);
+            let fanned_out = tr.recv().await.unwrap();
+            assert_ne!(fanned_out.data.id, Some(dispatched.rpc.ctx.call_id));
+            assert_eq!(fanned_out.data.result, Some(serde_json::json!("result")));
+
+            // once the response is received identical requests are dispatched again
+            state.handle_brokerage(
+                app_request("app3", "test.method", params),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            );
+            assert_eq!(mock_rx.recv().await.unwrap().rpc.ctx.app_id, "app3");
+        }
+
+        #[tokio::test]
+        async fn test_requests_of_other_apps_coalesced() {
+            let (tx, mut tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            for app_id in ["app1", "app2"] {
+                state.handle_brokerage(
+                    app_request(app_id, "test.method", serde_json::json!({"key": "other"})),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                );
+            }
+            let dispatched = mock_rx.recv().await.unwrap();
+            assert_eq!(dispatched.rpc.ctx.app_id, "app1");
+            assert!(
+                tokio::time::timeout(Duration::from_millis(100), mock_rx.recv())
+                    .await
+                    .is_err()
+            );
+
+            let mut response = JsonRpcApiResponse::mock();
+            response.id = Some(dispatched.rpc.ctx.call_id);
+            response.result = Some(serde_json::json!("result"));
+            state.fan_out_response(dispatched.rpc.ctx.call_id, &response);
+            let fanned_out = tr.recv().await.unwrap();
+            let waiting = state.get_request(fanned_out.data.id.unwrap()).unwrap();
+            assert_eq!(waiting.rpc.ctx.app_id, "app2");
+        }
+
+        #[tokio::test]
+        async fn test_cleanup_for_app_promotes_coalesced_request() {
+            let (tx, mut tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            for app_id in ["app1", "app2"] {
+                let mut request = app_request(app_id, "test.method", serde_json::json!({}));
+                request.ctx.cid = Some(app_id.to_owned());
+                state.handle_brokerage(request, None, None, vec![], None, vec![]);
+            }
+            let dispatched = mock_rx.recv().await.unwrap();
+            assert_eq!(dispatched.rpc.ctx.app_id, "app1");
+
+            // the request of app2 takes over the in flight request, nothing is failed
+            state.cleanup_for_app("app1").await;
+            assert!(tokio::time::timeout(Duration::from_millis(100), tr.recv())
+                .await
+                .is_err());
+            assert_eq!(state.in_flight_map.read().unwrap().len(), 1);
+            {
+                let request_map = state.request_map.read().unwrap();
+                assert_eq!(request_map.len(), 1);
+                let promoted = request_map.get(&dispatched.rpc.ctx.call_id).unwrap();
+                assert_eq!(promoted.rpc.ctx.app_id, "app2");
+            }
+
+            // once the app of the promoted request is gone too the request is forgotten
+            state.cleanup_for_app("app2").await;
+            assert!(state.in_flight_map.read().unwrap().is_empty());
+            assert!(state.coalesced_map.read().unwrap().is_empty());
+            assert!(state.request_map.read().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_cleanup_for_app_fails_its_coalesced_requests() {
+            let (tx, mut tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            for session_id in ["session1", "session2"] {
+                let mut request =
+                    app_request("app1", "test.method", serde_json::json!({"key": "cleanup"}));
+                request.ctx.session_id = session_id.to_owned();
+                request.ctx.cid = Some(session_id.to_owned());
+                state.handle_brokerage(request, None, None, vec![], None, vec![]);
+            }
+            mock_rx.recv().await.unwrap();
+
+            // a waiter of the cleaned up app is dropped, the in flight request is failed
+            state.cleanup_for_app("session2").await;
+            assert_eq!(state.request_map.read().unwrap().len(), 1);
+            state.cleanup_for_app("session1").await;
+            assert!(tokio::time::timeout(Duration::from_millis(100), tr.recv())
+                .await
+                .is_err());
+            assert!(state.in_flight_map.read().unwrap().is_empty());
+            assert!(state.coalesced_map.read().unwrap().is_empty());
+            assert!(state.request_map.read().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_subscriptions_not_coalesced() {
+            let (tx, _tr) = channel(4);
+            let (state, mut mock_rx) = test_broker_state(tx);
+            for app_id in ["app1", "app2"] {
+                state.handle_brokerage(
+                    app_request(app_id, "test.onevent", serde_json::json!({"listen": true})),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                );
+            }
+            assert_eq!(mock_rx.recv().await.unwrap().rpc.ctx.app_id, "app1");
+            assert_eq!(mock_rx.recv().await.unwrap().rpc.ctx.app_id, "app2");
+        }
+
+        #[tokio::test]
+        async fn test_rule_not_found_counter() {
+            let (tx, _tr) = channel(2);
+            let (state, _mock_rx) = test_broker_state(tx);
+            let tags = HashMap::from([("method".to_owned(), "test.unknown".to_owned())]);
+            for count in 1..=2 {
+                assert!(!state.handle_brokerage(
+                    app_request("app1", "test.unknown", serde_json::json!({})),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                ));
+                let counter = state
+                    .metrics_state
+                    .get_counter(super::RULE_NOT_FOUND_COUNTER, &tags)
+                    .unwrap();
+                assert_eq!(counter.get(), count);
+                assert_eq!(counter.tags, Some(tags.clone()));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_cleanup_all() {
+            let (tx, _tr) = channel(4);
+            let (state, _mock_rx) = test_broker_state(tx);
+            let (cleaner_tx, mut cleaner_rx) = channel(2);
+            state
+                .cleaner_list
+                .write()
+                .unwrap()
+                .push(super::BrokerCleaner {
+                    cleaner: Some(cleaner_tx),
+                });
+            for app_id in ["app1", "app2"] {
+                state.handle_brokerage(
+                    app_request(app_id, "test.onevent", serde_json::json!({"listen": true})),
+                    None,
+                    None,
+                    vec![],
+                    None,
+                    vec![],
+                );
+            }
+            state.handle_brokerage(
+                app_request("app1", "test.method", serde_json::json!({})),
+                None,
+                None,
+                vec![],
+                None,
+                vec![],
+            );
+            assert_eq!(state.request_map.read().unwrap().len(), 3);
+
+            state.cleanup_all().await;
+            assert_eq!(
+                cleaner_rx.recv().await.unwrap(),
+                super::CLEANUP_ALL_SENTINEL
+            );
+            assert!(state.request_map.read().unwrap().is_empty());
+            assert!(state.in_flight_map.read().unwrap().is_empty());
+            assert!(state.coalesced_map.read().unwrap().is_empty());
+
+            assert!(state.cleanup_endpoint("mock"));
+            assert!(state.get_sender("mock").is_none());
+            assert!(!state.cleanup_endpoint("mock"));
+        }
+
+        #[tokio::test]
+        async fn test_close_session() {
+            let (tx, _tr) = channel(4);
+            let (state, _mock_rx) = test_broker_state(tx);
+            let (cleaner_tx, mut cleaner_rx) = channel(2);
+            state
+                .cleaner_list
+                .write()
+                .unwrap()
+                .push(super::BrokerCleaner {
+                    cleaner: Some(cleaner_tx),
+                });
+            for (session_id, method) in [
+                ("session1", "test.onevent"),
+                ("session1", "test.method"),
+                ("session2", "test.onevent"),
+            ] {
+                let mut request = app_request("app1", method, serde_json::json!({"listen": true}));
+                request.ctx.session_id = session_id.to_owned();
+                state.handle_brokerage(request, None, None, vec![], None, vec![]);
+            }
+            assert_eq!(state.request_map.read().unwrap().len(), 3);
+
+            state.close_session("session1").await;
+            assert_eq!(cleaner_rx.recv().await.unwrap(), "session1");
+            // the session's pending request which is not a subscription is kept
+            let request_map = state.request_map.read().unwrap();
+            assert_eq!(request_map.len(), 2);
+            assert!(request_map.values().all(|request| {
+                !request.rpc.is_subscription() || request.rpc.ctx.session_id == "session2"
+            }));
+        }
+
+        #[tokio::test]
+        async fn test_list_subscriptions() {
+            let (tx, _tr) = channel(4);
+            let (mut state, _mock_rx) = test_broker_state(tx);
+            let rule = state.rule_engine.rules.rules["test.onevent"].clone();
+            state
+                .rule_engine
+                .rules
+                .rules
+                .insert("test.onother".to_owned(), rule);
+            for (session_id, method) in [
+                ("session1", "test.onevent"),
+                ("session1", "test.onother"),
+                ("session2", "test.onevent"),
+            ] {
+                let mut request = app_request("app1", method, serde_json::json!({"listen": true}));
+                request.ctx.session_id = session_id.to_owned();
+                request.ctx.method = method.to_owned();
+                state.handle_brokerage(request, None, None, vec![], None, vec![]);
+            }
+            // subscriptions known to a thunder broker
+            let mut thunder_request = BrokerRequest::default();
+            thunder_request.rpc.ctx.method = "device.onNameChanged".to_owned();
+            state.subscription_maps.write().unwrap().insert(
+                "thunder".to_owned(),
+                Arc::new(RwLock::new(HashMap::from([(
+                    "session2".to_owned(),
+                    vec![thunder_request],
+                )]))),
+            );
+
+            let subscriptions = state.list_subscriptions();
+            assert_eq!(subscriptions.len(), 2);
+            assert_eq!(
+                subscriptions["session1"],
+                vec!["test.onevent".to_owned(), "test.onother".to_owned()]
+            );
+            assert_eq!(
+                subscriptions["session2"],
+                vec!["device.onNameChanged".to_owned(), "test.onevent".to_owned()]
+            );
+        }
+
+        #[test]
+        fn test_get_event_id() {
+            let state = EndpointBrokerState::default();
+            let mut listen = BrokerRequest::default();
+            listen.rpc.ctx.call_id = 4;
+            listen.rpc.method = "device.onNameChanged".to_owned();
+            listen.rpc.params_json = serde_json::json!([{}, {"listen": true}]).to_string();
+            let mut request = BrokerRequest::default();
+            request.rpc.ctx.call_id = 5;
+            request.rpc.method = "device.name".to_owned();
+            {
+                let mut request_map = state.request_map.write().unwrap();
+                request_map.insert(4, listen);
+                request_map.insert(5, request);
+            }
+
+            let event = |method: &str| {
+                let mut data = JsonRpcApiResponse::mock();
+                data.method = Some(method.to_owned());
+                BrokerOutput::default().with_jsonrpc_response(data).clone()
+            };
+            assert_eq!(state.get_event_id(&event("4.onnamechanged")), Some(4));
+            // numeric looking method names are not events without a subscription
+            assert_eq!(state.get_event_id(&event("4k.enabled")), None);
+            assert_eq!(state.get_event_id(&event("5.enabled")), None);
+            assert_eq!(state.get_event_id(&event("6.enabled")), None);
+            // the request is still waiting for its response
+            assert!(state.request_map.read().unwrap().contains_key(&5));
+        }
+
+        #[tokio::test]
+        async fn test_health_snapshot() {
+            let (callback_tx, mut callback_rx) = channel(4);
+            let (mut state, _mock_rx) = test_broker_state(callback_tx);
+            let mut down_rule = state.rule_engine.rules.rules["test.method"].clone();
+            down_rule.alias = "test.down.alias".to_owned();
+            down_rule.endpoint = Some("down".to_owned());
+            state
+                .rule_engine
+                .rules
+                .rules
+                .insert("test.down".to_owned(), down_rule);
+            let (down_tx, down_rx) = channel(1);
+            drop(down_rx);
+            state.add_endpoint("down".to_owned(), BrokerSender { sender: down_tx });
+
+            let request = app_request("app", "test.method", serde_json::json!({}));
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            let request = app_request("app", "test.down", serde_json::json!({}));
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            assert!(callback_rx.recv().await.unwrap().data.error.is_some());
+
+            let snapshot = state.health_snapshot();
+            let mock = &snapshot["mock"];
+            assert!(mock.connected);
+            assert!(mock.last_error.is_none());
+            assert_eq!(mock.pending_requests, 1);
+            let down = &snapshot["down"];
+            assert!(!down.connected);
+            assert!(down.last_error.is_some());
+
+            let json = serde_json::to_value(&snapshot).unwrap();
+            assert_eq!(json["down"]["connected"], serde_json::json!(false));
+            assert_eq!(json["mock"]["pendingRequests"], serde_json::json!(1));
+
+            // subscriptions are not pending, requests served by a fallback are pending on it
+            let mut fallback_rule = state.rule_engine.rules.rules["test.method"].clone();
+            fallback_rule.alias = "test.fallback.alias".to_owned();
+            fallback_rule.endpoint = Some("missing".to_owned());
+            fallback_rule.fallback = Some("mock".to_owned());
+            state
+                .rule_engine
+                .rules
+                .rules
+                .insert("test.fallback".to_owned(), fallback_rule);
+            for (method, params) in [
+                ("test.onevent", serde_json::json!({"listen": true})),
+                ("test.fallback", serde_json::json!({})),
+            ] {
+                let request = app_request("app", method, params);
+                assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            }
+            let snapshot = state.health_snapshot();
+            assert_eq!(snapshot["mock"].pending_requests, 2);
+            assert!(!snapshot.contains_key("missing"));
+
+            // the last error is cleared once the endpoint takes requests again
+            let (up_tx, mut up_rx) = channel(1);
+            state.add_endpoint("down".to_owned(), BrokerSender { sender: up_tx });
+            let request = app_request("app", "test.down", serde_json::json!({"retry": true}));
+            assert!(state.handle_brokerage(request, None, None, vec![], None, vec![]));
+            up_rx.recv().await.unwrap();
+            tokio::time::timeout(Duration::from_secs(1), async {
+                while state.health_snapshot()["down"].last_error.is_some() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_reconnect_send_failure_is_fatal() {
+            let mut state = EndpointBrokerState::default();
+            let (tx, _rx) = channel(2);
+            state.add_endpoint("thunder".to_owned(), BrokerSender { sender: tx });
+            let (reconnect_tx, reconnect_rx) = channel(2);
+            drop(reconnect_rx);
+            let mut request = super::BrokerConnectRequest::new(
+                "thunder".to_owned(),
+                Default::default(),
+                reconnect_tx,
+            );
+            request.fatal_endpoints = state.fatal_endpoints.clone();
+            assert_eq!(state.get_endpoint_health().get("thunder"), Some(&true));
+
+            assert!(request.send_reconnect(request.clone()).await.is_err());
+            assert_eq!(state.get_fatal_endpoints(), vec!["thunder".to_owned()]);
+            assert_eq!(state.get_endpoint_health().get("thunder"), Some(&false));
+        }
+
+        #[test]
+        fn test_recent_outputs() {
+            let state = EndpointBrokerState::default();
+            let total = super::RECENT_OUTPUTS_CAPACITY as u64 + 5;
+            for id in 0..total {
+                state.record_output(&BrokerOutput::new(JsonRpcApiResponse {
+                    id: Some(id),
+                    result: Some(serde_json::json!({"secret": id})),
+                    ..Default::default()
+                }));
+            }
+
+            let recent = state.recent_outputs(usize::MAX);
+            assert_eq!(recent.len(), super::RECENT_OUTPUTS_CAPACITY);
+            let ids: Vec<u64> = recent.iter().map(|o| o.data.id.unwrap()).collect();
+            assert_eq!(ids, (5..total).collect::<Vec<u64>>());
+            assert_eq!(
+                recent[0].data.result,
+                Some(serde_json::json!(super::REDACTED))
+            );
+
+            let last: Vec<u64> = state
+                .recent_outputs(2)
+                .iter()
+                .map(|o| o.data.id.unwrap())
+                .collect();
+            assert_eq!(last, vec![total - 2, total - 1]);
+        }
+
+        #[tokio::test]
+        async fn test_broker_backpressure_counter() {
+            let metrics_state = MetricsState::default();
+            let (tx, mut rx) = channel(1);
+            let sender = BrokerSender { sender: tx };
+            let tags = HashMap::from([("endpoint".to_owned(), "mock".to_owned())]);
+
+            // fill the channel so the next request has to wait
+            sender.send(BrokerRequest::default()).await.unwrap();
+            let blocked = {
+                let (sender, metrics_state, tags) =
+                    (sender.clone(), metrics_state.clone(), tags.clone());
+                tokio::spawn(async move {
+                    sender
+                        .send_with_backpressure(&metrics_state, &tags, BrokerRequest::default())
+                        .await
+                })
+            };
+            while metrics_state
+                .get_counter(super::BROKER_BLOCKED_COUNTER, &tags)
+                .is_none()
+            {
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(
+                metrics_state
+                    .get_counter(super::BROKER_BLOCKED_COUNTER, &tags)
+                    .unwrap()
+                    .get(),
+                1
+            );
+
+            rx.recv().await.unwrap();
+            assert!(blocked.await.unwrap().is_ok());
+
+            drop(rx);
+            assert!(sender
+                .send_with_backpressure(&metrics_state, &tags, BrokerRequest::default())
+                .await
+                .is_err());
+            assert_eq!(
+                metrics_state
+                    .get_counter(super::BROKER_SEND_FAILURE_COUNTER, &tags)
+                    .unwrap()
+                    .get(),
+                1
+            );
+        }
+
+        #[tokio::test]
+        async fn test_provider_timeout() {
+            let (tx, mut tr) = channel(2);
+            let client = RippleClient::new(ChannelsState::new());
+            let cap = "xrn:firebolt:capability:test:provider".to_owned();
+            let state = EndpointBrokerState::new(
+                MetricsState::default(),
+                tx,
+                RuleEngine {
+                    rules: RuleSet::default(),
+                },
+                client,
+            )
+            .with_provider_timeouts(HashMap::from([(cap.clone(), 10)]));
+            let permission = vec![FireboltPermission {
+                cap: FireboltCap::Full(cap),
+                role: CapabilityRole::Provide,
+            }];
+            let rule = Rule {
+                alias: "provided".to_owned(),
+                transform: RuleTransform::default(),
+                endpoint: None,
+                filter: None,
+                event_handler: None,
+                sources: None,
+                inject_latency_ms: None,
+                fallback: None,
+                allowed_apps: None,
+                event_transform_failure: None,
+                capabilities: None,
+                subscription_filter: None,
+                workflow_session_delivery: None,
+                response_schema: None,
+                provider_fallback: None,
+                rate_limit: None,
+                notification_passthrough: None,
+                passthrough: None,
+            };
+
+            // Register a provider session which never responds
+            let (provider_tx, mut provider_rx) = channel(2);
+            let mut provide_request = RpcRequest::mock();
+            provide_request.method = "test.provide".to_owned();
+            state.handle_provided_request(
+                &provide_request,
+                rule.clone(),
+                state.callback.clone(),
+                permission.clone(),
+                Some(Session::new("provider".to_owned(), Some(provider_tx))),
+                vec![],
+            );
+            let registered = tr.recv().await.unwrap();
+            assert!(registered.data.error.is_none());
+
+            let mut request = RpcRequest::mock();
+            request.method = "test.method".to_owned();
+            request.ctx.method = "test.method".to_owned();
+            state.handle_provided_request(
+                &request,
+                rule.clone(),
+                state.callback.clone(),
+                permission.clone(),
+                None,
+                vec![],
+            );
+            let provided = provider_rx.recv().await.unwrap();
+            assert!(provided.jsonrpc_msg.contains("test.method"));
+            let output = tr.recv().await.unwrap();
+            let error = output.data.error.unwrap();
+            assert_eq!(error["code"], super::PROVIDER_TIMEOUT_ERROR_CODE);
+            assert!(state.get_request(output.data.id.unwrap()).is_ok());
+
+            // only the timed out request is retired, the provider keeps receiving requests
+            state.handle_provided_request(
+                &request,
+                rule,
+                state.callback.clone(),
+                permission,
+                None,
+                vec![],
+            );
+            let provided = provider_rx.recv().await.unwrap();
+            assert!(provided.jsonrpc_msg.contains("test.method"));
+            let output = tr.recv().await.unwrap();
+            assert_eq!(
+                output.data.error.unwrap()["code"],
+                super::PROVIDER_TIMEOUT_ERROR_CODE
+            );
+        }
+
+        #[tokio::test]
+        async fn test_legacy_messsage_key() {
+            let (tx, mut tr) = channel(2);
+            let state = EndpointBrokerState::new(
+                MetricsState::default(),
+                tx,
+                RuleEngine {
+                    rules: RuleSet::default(),
+                },
+                RippleClient::new(ChannelsState::new()),
+            )
+            .with_legacy_messsage_key(true);
+            let rule = Rule {
+                alias: "provided".to_owned(),
+                ..Default::default()
+            };
+            state.handle_provided_request(
+                &RpcRequest::mock(),
+                rule,
+                state.callback.clone(),
+                vec![],
+                None,
+                vec![],
+            );
+            let error = tr.recv().await.unwrap().data.error.unwrap();
+            assert_eq!(error["message"], "capability not available");
+            assert_eq!(error["messsage"], "capability not available");
+        }
+
+        #[tokio::test]
+        async fn test_provider_fallback() {
+            let (tx, mut tr) = channel(2);
+            let state = EndpointBrokerState::new(
+                MetricsState::default(),
+                tx,
+                RuleEngine {
+                    rules: RuleSet::default(),
+                },
+                RippleClient::new(ChannelsState::new()),
+            );
+            let permission = vec![FireboltPermission {
+                cap: FireboltCap::Full(
+                    "xrn:firebolt:capability:usergrant:acknowledgechallenge".to_owned(),
+                ),
+                role: CapabilityRole::Use,
+            }];
+            let mut rule = Rule {
+                alias: "provided".to_owned(),
+                ..Default::default()
+            };
+            let mut request = RpcRequest::mock();
+            request.method = "acknowledgechallenge.challenge".to_owned();
+
+            // without a fallback the request fails
+            state.handle_provided_request(
+                &request,
+                rule.clone(),
+                state.callback.clone(),
+                permission.clone(),
+                None,
+                vec![],
+            );
+            let error = tr.recv().await.unwrap().data.error.unwrap();
+            assert_eq!(
+                error["message"],
+                "xrn:firebolt:capability:usergrant:acknowledgechallenge not available"
+            );
+            assert!(error.get("messsage").is_none());
+
+            rule.provider_fallback = Some(serde_json::json!({"granted": false}));
+            state.handle_provided_request(
+                &request,
+                rule,
+                state.callback.clone(),
+                permission,
+                None,
+                vec![],
+            );
+            let output = tr.recv().await.unwrap();
+            assert!(output.data.error.is_none());
+            assert_eq!(
+                output.data.result,
+                Some(serde_json::json!({"granted": false}))
+            );
+        }
+    }
+
+    /// A case of `src/broker/test/apply-response-tests.json`: a rule filter, the response it is
+    /// applied to and the `result`/`error` expected afterwards
+    #[derive(Deserialize)]
+    struct ApplyResponseTest {
+        name: String,
+        filter: String,
+        response: Value,
+        expected: Value,
+    }
+
+    #[test]
+    fn test_apply_response_fixtures() {
+        let tests_file = std::fs::File::open("src/broker/test/apply-response-tests.json").unwrap();
+        let tests: Vec<ApplyResponseTest> = serde_json::from_reader(tests_file).unwrap();
+        assert!(!tests.is_empty());
+        for test in tests {
+            let mut response = JsonRpcApiResponse::mock();
+            response.result = test.response.get("result").cloned();
+            response.error = test.response.get("error").cloned();
+            apply_response(test.filter, &test.name, &mut response);
+
+            let mut actual = serde_json::Map::new();
+            if let Some(result) = response.result {
+                actual.insert("result".to_owned(), result);
+            }
+            if let Some(error) = response.error {
+                actual.insert("error".to_owned(), error);
+            }
+            assert_eq!(Value::Object(actual), test.expected, "{}", test.name);
+        }
+    }
 
-        // securestorage.get
-        let result = json!({"value": "some_value","success": true,"ttl": 100});
-        let filter = "if .result.success then .result.value elif .error.code==22 or .error.code==43 then \"null\" else .error end".to_string();
+    #[test]
+    fn test_apply_response_jq_failure() {
         let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
-        assert_eq!(response.result.unwrap(), "some_value");
+        response.result = Some(json!({"a": 1}));
+        assert!(!apply_response(
+            "if".to_owned(),
+            "test.method",
+            &mut response
+        ));
+        assert!(response.error.is_some());
 
-        // localization.countryCode
-        let result = json!({"territory": "USA","success": true});
-        let filter = "if .result.success then if .result.territory == \"ITA\" then \"IT\" elif .result.territory == \"GBR\" then \"GB\" elif .result.territory == \"IRL\" then \"IE\" elif .result.territory == \"DEU\" then \"DE\" elif .result.territory == \"AUS\" then \"AU\" else \"GB\" end end".to_string();
         let mut response = JsonRpcApiResponse::mock();
-        response.result = Some(result);
-        apply_response(filter, &rpc_request.ctx.method, &mut response);
-        assert_eq!(response.result.unwrap(), "GB");
+        response.result = Some(json!({"a": 1}));
+        assert!(apply_response(
+            ".result.a".to_owned(),
+            "test.method",
+            &mut response
+        ));
+        assert_eq!(response.result, Some(json!(1)));
     }
 }
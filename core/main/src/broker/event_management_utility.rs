@@ -19,7 +19,10 @@ use std::{collections::HashMap, pin::Pin};
 
 use futures::Future;
 use ripple_sdk::{
-    api::gateway::rpc_gateway_api::{ApiProtocol, CallContext, RpcRequest},
+    api::{
+        apps::EventDecorator,
+        gateway::rpc_gateway_api::{ApiProtocol, CallContext, RpcRequest},
+    },
     extn::extn_client_message::ExtnResponse,
     log::info,
     utils::error::RippleError,
@@ -29,7 +32,8 @@ use std::sync::{Arc, Mutex};
 
 use crate::state::platform_state::PlatformState;
 
-type DecoratorFunctionType = Arc<
+/// Async decorator of an event result, returns the result delivered to the subscriber
+pub type DecoratorFunctionType = Arc<
     dyn Fn(
             PlatformState,
             CallContext,
@@ -42,6 +46,15 @@ pub struct EventManagementUtility {
     pub functions: Mutex<HashMap<String, DecoratorFunctionType>>,
 }
 
+impl std::fmt::Debug for EventManagementUtility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let functions: Vec<String> = self.functions.lock().unwrap().keys().cloned().collect();
+        f.debug_struct("EventManagementUtility")
+            .field("functions", &functions)
+            .finish()
+    }
+}
+
 impl Default for EventManagementUtility {
     fn default() -> Self {
         Self::new()
@@ -53,6 +66,13 @@ impl EventManagementUtility {
             functions: Mutex::new(HashMap::new()),
         }
     }
+    /// Utility with the built-in decorator functions registered
+    pub fn with_custom_functions() -> Self {
+        let utility = Self::new();
+        utility.register_custom_functions();
+        utility
+    }
+
     pub fn register_custom_functions(&self) {
         // Add a custom function to event utility based on the tag
         self.register_function(
@@ -65,14 +85,68 @@ impl EventManagementUtility {
             }),
         );
     }
+    /// Registers a decorator under the given name, replacing any decorator registered
+    /// under it before
     pub fn register_function(&self, name: String, function: DecoratorFunctionType) {
         self.functions.lock().unwrap().insert(name, function);
     }
 
+    /// Registers a decorator an extension serves, see [EventDecorator]
+    pub fn register_extn_decorator(&self, decorator: EventDecorator) {
+        let method = decorator.method;
+        self.register_function(
+            decorator.name,
+            Arc::new(move |state, ctx, value| {
+                Box::pin(EventManagementUtility::extn_event_decorator(
+                    state,
+                    ctx,
+                    method.clone(),
+                    value,
+                ))
+            }),
+        );
+    }
+
+    /// Removes the decorator registered under the given name, events of rules still naming
+    /// it are delivered undecorated. Returns false when no such decorator was registered.
+    pub fn unregister_function(&self, name: &str) -> bool {
+        self.functions.lock().unwrap().remove(name).is_some()
+    }
+
     pub fn get_function(&self, name: &str) -> Option<DecoratorFunctionType> {
         self.functions.lock().unwrap().get(name).cloned()
     }
 
+    /// Passes the event result to the extension method of a registered [EventDecorator]
+    async fn extn_event_decorator(
+        platform_state: PlatformState,
+        ctx: CallContext,
+        method: String,
+        value: Option<Value>,
+    ) -> Result<Option<Value>, RippleError> {
+        let mut new_ctx = ctx.clone();
+        new_ctx.protocol = ApiProtocol::Extn;
+        new_ctx.method = method.clone();
+
+        let rpc_request = RpcRequest {
+            ctx: new_ctx.clone(),
+            method,
+            params_json: RpcRequest::prepend_ctx(value, &new_ctx),
+        };
+        let resp = platform_state
+            .get_client()
+            .get_extn_client()
+            .main_internal_request(rpc_request)
+            .await?;
+        Ok(resp
+            .payload
+            .extract::<ExtnResponse>()
+            .and_then(|r| match r {
+                ExtnResponse::Value(val) => Some(val),
+                _ => None,
+            }))
+    }
+
     pub async fn advertising_policy_event_decorator(
         mut platform_state: PlatformState,
         ctx: CallContext,
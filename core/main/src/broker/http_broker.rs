@@ -15,11 +15,22 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::vec;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Duration,
+    vec,
+};
 
-use hyper::{client::HttpConnector, Body, Client, Method, Request, Response, Uri};
+use hyper::{
+    client::HttpConnector,
+    header::{HeaderName, HeaderValue, ETAG, IF_NONE_MATCH},
+    Body, Client, Method, Request, Response, StatusCode, Uri,
+};
 use ripple_sdk::{
-    api::{gateway::rpc_gateway_api::JsonRpcApiError, observability::log_signal::LogSignal},
+    api::{
+        gateway::rpc_gateway_api::{JsonRpcApiError, JsonRpcApiResponse},
+        observability::log_signal::LogSignal,
+    },
     log::{debug, error},
     tokio::{self, sync::mpsc},
     utils::error::RippleError,
@@ -45,10 +56,9 @@ async fn send_http_request(
     method: Method,
     uri: &Uri,
     path: &str,
+    headers: &HashMap<String, String>,
+    body: Body,
 ) -> Result<Response<Body>, RippleError> {
-    /*
-    TODO? we may need to support body for POST request in the future
-    */
     let http_request = Request::new(Body::empty());
     let (mut parts, _) = http_request.into_parts();
     //TODO, need to refactor to support other methods
@@ -67,8 +77,19 @@ async fn send_http_request(
     let (uri_parts, _) = new_request.into_parts();
 
     parts.uri = uri_parts.uri;
+    for (name, value) in headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                parts.headers.insert(name, value);
+            }
+            _ => error!("http_broker skipping invalid header {}", name),
+        }
+    }
 
-    let http_request = Request::from_parts(parts, Body::empty());
+    let http_request = Request::from_parts(parts, body);
 
     debug!(
         "http_broker sending {} request={}",
@@ -95,6 +116,137 @@ async fn send_broker_response(callback: &BrokerCallback, request: &BrokerRequest
         }
     }
 }
+/// Percent-encodes everything but the unreserved URI characters
+fn encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Fills `${params.<path>}` placeholders of a rule alias such as
+/// `devices/${params.id}/status` from the transformed request params
+fn expand_path_template(template: &str, params: &serde_json::Value) -> Result<String, RippleError> {
+    let mut path = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        path.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            RippleError::BrokerError(format!("unterminated placeholder in {}", template))
+        })? + start;
+        let placeholder = &rest[start + 2..end];
+        let value = placeholder
+            .strip_prefix("params.")
+            .and_then(|field| {
+                field
+                    .split('.')
+                    .try_fold(params, |value, key| value.get(key))
+            })
+            .ok_or_else(|| {
+                RippleError::BrokerError(format!(
+                    "unknown placeholder ${{{}}} in {}",
+                    placeholder, template
+                ))
+            })?;
+        let value = match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        path.push_str(&encode_uri_component(&value));
+        rest = &rest[end + 1..];
+    }
+    path.push_str(rest);
+    Ok(path)
+}
+
+/// Number of urls whose ETag and body are kept, the least recently used url is dropped first
+const ETAG_CACHE_CAPACITY: usize = 64;
+/// Bodies larger than this are not cached
+const ETAG_CACHE_MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Last ETag and body returned for each url, so unchanged resources are revalidated with
+/// `If-None-Match` instead of being downloaded again.
+#[derive(Default)]
+struct EtagCache {
+    entries: HashMap<String, (String, Vec<u8>)>,
+    /// Cached urls, least recently used first
+    recency: VecDeque<String>,
+}
+
+impl EtagCache {
+    fn key(uri: &Uri, path: &str) -> String {
+        format!("{}{}", uri, path)
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(index) = self.recency.iter().position(|k| k == key) {
+            self.recency.remove(index);
+        }
+        self.recency.push_back(key.to_owned());
+    }
+
+    fn insert(&mut self, key: String, etag: String, body: Vec<u8>) {
+        self.touch(&key);
+        self.entries.insert(key, (etag, body));
+        while self.entries.len() > ETAG_CACHE_CAPACITY {
+            if let Some(oldest) = self.recency.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if self.entries.remove(key).is_some() {
+            self.recency.retain(|k| k != key);
+        }
+    }
+
+    /// Returns the request headers with `If-None-Match` added when the url has a cached ETag
+    fn conditional_headers(
+        &self,
+        key: &str,
+        headers: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        let mut headers = headers.clone();
+        if let Some((etag, _)) = self.entries.get(key) {
+            headers.insert(IF_NONE_MATCH.to_string(), etag.clone());
+        }
+        headers
+    }
+
+    /// Serves the cached body for a 304 and remembers the body of successful responses carrying
+    /// an ETag. The returned status is the one callers should treat the body as having.
+    fn resolve(
+        &mut self,
+        key: String,
+        status: StatusCode,
+        etag: Option<&HeaderValue>,
+        body: Vec<u8>,
+    ) -> (StatusCode, Vec<u8>) {
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some((_, cached)) = self.entries.get(&key) {
+                debug!("http_broker serving cached body for {}", key);
+                let cached = cached.clone();
+                self.touch(&key);
+                return (StatusCode::OK, cached);
+            }
+        } else if status.is_success() {
+            match etag.and_then(|e| e.to_str().ok()) {
+                Some(etag) if body.len() <= ETAG_CACHE_MAX_BODY_SIZE => {
+                    self.insert(key, etag.to_owned(), body.clone());
+                }
+                _ => self.remove(&key),
+            }
+        }
+        (status, body)
+    }
+}
+
 fn error_string_to_json(msg: &str) -> serde_json::Value {
     serde_json::json!({
         "error": msg
@@ -113,6 +265,116 @@ async fn body_to_bytes(body: Body) -> Vec<u8> {
     }
 }
 
+impl HttpBroker {
+    /// Collects the requests arriving within `window` milliseconds of the first one into a
+    /// single JSON-RPC batch POST, then hands each element of the batch response back
+    /// to the callback, which correlates them by id.
+    fn start_batch(
+        client: Client<HttpConnector>,
+        uri: Uri,
+        mut headers: HashMap<String, String>,
+        window: u64,
+        mut tr: mpsc::Receiver<BrokerRequest>,
+        callback: BrokerCallback,
+    ) {
+        headers.insert("content-type".to_owned(), "application/json".to_owned());
+        tokio::spawn(async move {
+            while let Some(first) = tr.recv().await {
+                let mut batch = vec![first];
+                let deadline = tokio::time::Instant::now() + Duration::from_millis(window);
+                while let Ok(Some(request)) = tokio::time::timeout_at(deadline, tr.recv()).await {
+                    batch.push(request);
+                }
+
+                let mut payload = vec![];
+                let mut pending = vec![];
+                for request in batch {
+                    match Self::update_request(&request)
+                        .and_then(|r| serde_json::from_str(&r).map_err(|_| RippleError::ParseError))
+                    {
+                        Ok(value) => {
+                            payload.push(value);
+                            pending.push(request);
+                        }
+                        Err(e) => Self::send_batch_failure(&callback, &request, format!("{:?}", e)),
+                    }
+                }
+                if payload.is_empty() {
+                    continue;
+                }
+                debug!("http_broker sending batch of {} requests", payload.len());
+
+                let body = Body::from(serde_json::Value::Array(payload).to_string());
+                let response = match send_http_request(
+                    &client,
+                    Method::POST,
+                    &uri,
+                    "",
+                    &headers,
+                    body,
+                )
+                .await
+                {
+                    Ok(response) => serde_json::from_slice::<Vec<serde_json::Value>>(
+                        &body_to_bytes(response.into_body()).await,
+                    )
+                    .map_err(|e| RippleError::BrokerError(e.to_string())),
+                    Err(e) => Err(e),
+                };
+                match response {
+                    Ok(responses) => {
+                        let answered: HashSet<u64> = responses
+                            .iter()
+                            .filter_map(|response| response.get("id").and_then(|id| id.as_u64()))
+                            .collect();
+                        for response in responses {
+                            let _ = Self::handle_jsonrpc_response(
+                                response.to_string().as_bytes(),
+                                callback.clone(),
+                                None,
+                            );
+                        }
+                        // requests left out of the batch response would wait forever
+                        for request in pending {
+                            if !answered.contains(&request.rpc.ctx.call_id) {
+                                let msg = format!(
+                                    "No response for request {} in batch response of http service={}",
+                                    request.rpc.ctx.call_id, uri
+                                );
+                                Self::send_batch_failure(&callback, &request, msg);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let msg = format!(
+                            "An error message from calling the downstream http service={} in http broker {:?}",
+                            uri, e
+                        );
+                        for request in pending {
+                            Self::send_batch_failure(&callback, &request, msg.clone());
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn send_batch_failure(callback: &BrokerCallback, request: &BrokerRequest, msg: String) {
+        LogSignal::new(
+            "http_broker".to_string(),
+            "Batch request failed".to_string(),
+            request.rpc.ctx.clone(),
+        )
+        .with_diagnostic_context_item("error", &msg)
+        .emit_error();
+        let error: JsonRpcApiResponse = JsonRpcApiError::default()
+            .with_id(request.rpc.ctx.call_id)
+            .with_message(msg)
+            .into();
+        Self::send_broker_failure_response(callback, error);
+    }
+}
+
 impl EndpointBroker for HttpBroker {
     fn get_broker(
         _ps: Option<PlatformState>,
@@ -121,20 +383,54 @@ impl EndpointBroker for HttpBroker {
         _broker_state: &mut EndpointBrokerState,
     ) -> Self {
         let endpoint = request.endpoint.clone();
-        let (tx, mut tr) = mpsc::channel(10);
+        let (tx, mut tr) = mpsc::channel(endpoint.get_channel_capacity());
         let broker = BrokerSender { sender: tx };
         let client = Client::new();
+        let mut etags = EtagCache::default();
+        let headers = endpoint.get_headers();
+
+        if let Some(window) = endpoint.jsonrpc_batch {
+            match endpoint.get_url().parse() {
+                Ok(uri) => Self::start_batch(client, uri, headers, window, tr, callback),
+                Err(e) => error!(
+                    "broker url {:?} in endpoint is invalid, cannot start http broker. error={}",
+                    endpoint, e
+                ),
+            }
+            return Self {
+                sender: broker,
+                cleaner: BrokerCleaner { cleaner: None },
+            };
+        }
 
         let _ =  endpoint.get_url().parse().map_err(|e| error!("broker url {:?} in endpoint is invalid, cannot start http broker. error={}",endpoint,e) ).map(|uri| tokio::spawn(async move {
             while let Some(request) = tr.recv().await {
                 LogSignal::new("http_broker".to_string(), format!("received request - start processing request={:?}", request), request.rpc.ctx.clone())
                     .with_diagnostic_context_item("rule_alias", request.rule.alias.as_str()).emit_debug();
-                match send_http_request(&client, Method::GET, &uri, &request.clone().rule.alias)
+                let path = if request.rule.alias.contains("${") {
+                    match Self::apply_request_rule(&request).and_then(|params| expand_path_template(&request.rule.alias, &params)) {
+                        Ok(path) => path,
+                        Err(e) => {
+                            LogSignal::new("http_broker".to_string(), "Prepare request failed".to_string(), request.rpc.ctx.clone())
+                                .with_diagnostic_context_item("error", &format!("{:?}", e))
+                                .emit_error();
+                            callback.send_error(request, e).await;
+                            continue;
+                        }
+                    }
+                } else {
+                    request.rule.alias.clone()
+                };
+                let key = EtagCache::key(&uri, &path);
+                let request_headers = etags.conditional_headers(&key, &headers);
+                match send_http_request(&client, Method::GET, &uri, &path, &request_headers, Body::empty())
                     .await
                 {
                     Ok(response) => {
-                        let (parts, body) = response.into_parts();
+                        let (mut parts, body) = response.into_parts();
                         let body = body_to_bytes(body).await;
+                        let (status, body) = etags.resolve(key, parts.status, parts.headers.get(ETAG), body);
+                        parts.status = status;
                         let mut request = request;
                         if let Ok(json_str) = serde_json::from_slice::<serde_json::Value>(&body).map(|v| vec![v])
                             .and_then(|v| serde_json::to_string(&v))
@@ -239,4 +535,362 @@ mod tests {
         assert!(broker.get_sender().sender.is_closed());
         assert!(broker.get_cleaner().cleaner.is_none());
     }
+
+    #[tokio::test]
+    async fn test_send_http_request_with_headers() {
+        use crate::broker::rules_engine::{RuleEndpointConfig, RuleEndpointProtocol};
+        use ripple_sdk::tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n{}")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_lowercase()
+        });
+
+        // cargo sets CARGO_PKG_NAME for test runs, setting a variable here would race
+        // with tests reading the environment on other threads
+        let endpoint = RuleEndpointConfig {
+            protocol: RuleEndpointProtocol::Http,
+            url: format!("http://127.0.0.1:{}", port),
+            headers: HashMap::from([
+                (
+                    "Authorization".to_owned(),
+                    "${ENV:CARGO_PKG_NAME}".to_owned(),
+                ),
+                ("x-static".to_owned(), "value".to_owned()),
+            ]),
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let logged = format!("{:?}", endpoint);
+        assert!(logged.contains("Authorization"));
+        assert!(!logged.contains("CARGO_PKG_NAME"));
+        assert!(!logged.contains("value"));
+        let uri: Uri = endpoint.get_url().parse().unwrap();
+        let response = send_http_request(
+            &Client::new(),
+            Method::GET,
+            &uri,
+            "/test",
+            &endpoint.get_headers(),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert!(response.status().is_success());
+
+        let received = server.await.unwrap();
+        assert!(received.contains(&format!("authorization: {}", env!("CARGO_PKG_NAME"))));
+        assert!(received.contains("x-static: value"));
+    }
+
+    #[test]
+    fn test_expand_path_template() {
+        let params = serde_json::json!({"id": "a b/c", "device": {"port": 8}});
+        assert_eq!(
+            expand_path_template("devices/${params.id}/ports/${params.device.port}", &params)
+                .unwrap(),
+            "devices/a%20b%2Fc/ports/8"
+        );
+        assert_eq!(
+            expand_path_template("devices/status", &params).unwrap(),
+            "devices/status"
+        );
+        match expand_path_template("devices/${params.missing}", &params) {
+            Err(RippleError::BrokerError(msg)) => assert!(msg.contains("${params.missing}")),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(expand_path_template("devices/${id}", &params).is_err());
+        assert!(expand_path_template("devices/${params.id", &params).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_templated_path() {
+        use crate::broker::rules_engine::{Rule, RuleEndpointConfig, RuleEndpointProtocol};
+        use ripple_sdk::{
+            api::gateway::rpc_gateway_api::RpcRequest,
+            tokio::{
+                io::{AsyncReadExt, AsyncWriteExt},
+                net::TcpListener,
+            },
+            Mockable,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n{}")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let endpoint = RuleEndpointConfig {
+            protocol: RuleEndpointProtocol::Http,
+            url: format!("http://127.0.0.1:{}", port),
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (reconnect_tx, _) = mpsc::channel(1);
+        let (tx, mut rx) = mpsc::channel(10);
+        let broker = HttpBroker::get_broker(
+            None,
+            BrokerConnectRequest::new("http".to_owned(), endpoint, reconnect_tx),
+            BrokerCallback { sender: tx },
+            &mut EndpointBrokerState::default(),
+        );
+        let rule = Rule {
+            alias: "devices/${params.id}/status".to_owned(),
+            ..Default::default()
+        };
+        let mut rpc = RpcRequest::mock();
+        rpc.params_json =
+            RpcRequest::prepend_ctx(Some(serde_json::json!({"id": "tv 1"})), &rpc.ctx);
+        broker
+            .get_sender()
+            .send(BrokerRequest::new(&rpc, rule.clone(), None, vec![]))
+            .await
+            .unwrap();
+        let received = server.await.unwrap();
+        assert!(received.contains("/devices/tv%201/status "));
+        assert!(rx.recv().await.unwrap().data.error.is_none());
+
+        // requests with unknown placeholders are failed without calling the service
+        let rule = Rule {
+            alias: "devices/${params.serial}/status".to_owned(),
+            ..Default::default()
+        };
+        broker
+            .get_sender()
+            .send(BrokerRequest::new(&rpc, rule, None, vec![]))
+            .await
+            .unwrap();
+        assert!(rx.recv().await.unwrap().data.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_jsonrpc_batch() {
+        use crate::broker::rules_engine::{Rule, RuleEndpointConfig, RuleEndpointProtocol};
+        use ripple_sdk::{
+            api::gateway::rpc_gateway_api::RpcRequest,
+            tokio::{
+                io::{AsyncReadExt, AsyncWriteExt},
+                net::TcpListener,
+            },
+            Mockable,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = vec![];
+            let mut buf = vec![0; 4096];
+            let (head, body) = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&received).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|v| v.parse().unwrap())
+                        })
+                        .unwrap();
+                    if body.len() >= length {
+                        break (head.to_owned(), body.to_owned());
+                    }
+                }
+            };
+            let requests: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+            let responses: Vec<serde_json::Value> = requests
+                .iter()
+                .filter(|r| r["method"] != "batch.unanswered")
+                .map(
+                    |r| serde_json::json!({"jsonrpc": "2.0", "id": r["id"], "result": r["method"]}),
+                )
+                .collect();
+            let response = serde_json::Value::Array(responses).to_string();
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                        response.len(),
+                        response
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            (head, requests.len())
+        });
+
+        let endpoint = RuleEndpointConfig {
+            protocol: RuleEndpointProtocol::Http,
+            url: format!("http://127.0.0.1:{}", port),
+            jsonrpc_batch: Some(100),
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (reconnect_tx, _) = mpsc::channel(1);
+        let (tx, mut rx) = mpsc::channel(10);
+        let broker = HttpBroker::get_broker(
+            None,
+            BrokerConnectRequest::new("http".to_owned(), endpoint, reconnect_tx),
+            BrokerCallback { sender: tx },
+            &mut EndpointBrokerState::default(),
+        );
+        for id in 1..=4 {
+            let mut rpc = RpcRequest::mock();
+            rpc.ctx.call_id = id;
+            rpc.params_json = "[{}]".to_owned();
+            let rule = Rule {
+                alias: if id == 4 {
+                    "batch.unanswered".to_owned()
+                } else {
+                    format!("batch.method{}", id)
+                },
+                ..Default::default()
+            };
+            broker
+                .get_sender()
+                .send(BrokerRequest::new(&rpc, rule, None, vec![]))
+                .await
+                .unwrap();
+        }
+
+        let (head, count) = server.await.unwrap();
+        assert!(head.starts_with("POST / "));
+        assert_eq!(count, 4);
+        let mut ids = vec![];
+        for _ in 0..4 {
+            let output = rx.recv().await.unwrap();
+            if output.data.id == Some(4) {
+                assert!(output.data.error.is_some());
+                continue;
+            }
+            assert_eq!(
+                output.data.result,
+                Some(serde_json::json!(format!(
+                    "batch.method{}",
+                    output.data.id.unwrap()
+                )))
+            );
+            ids.push(output.data.id.unwrap());
+        }
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_etag_cache_bounds() {
+        let etag = HeaderValue::from_static("\"v1\"");
+        let mut cache = EtagCache::default();
+        for i in 0..ETAG_CACHE_CAPACITY {
+            cache.resolve(format!("url{}", i), StatusCode::OK, Some(&etag), vec![1]);
+        }
+        // serving url0 makes url1 the least recently used
+        let (status, _) = cache.resolve("url0".to_owned(), StatusCode::NOT_MODIFIED, None, vec![]);
+        assert_eq!(status, StatusCode::OK);
+        cache.resolve("new".to_owned(), StatusCode::OK, Some(&etag), vec![1]);
+        assert_eq!(cache.entries.len(), ETAG_CACHE_CAPACITY);
+        assert!(cache.entries.contains_key("url0"));
+        assert!(!cache.entries.contains_key("url1"));
+
+        // a body over the size cap replaces the cached one instead of being stored
+        let large = vec![0; ETAG_CACHE_MAX_BODY_SIZE + 1];
+        cache.resolve("url0".to_owned(), StatusCode::OK, Some(&etag), large);
+        assert!(!cache.entries.contains_key("url0"));
+        assert_eq!(cache.recency.len(), cache.entries.len());
+    }
+
+    #[tokio::test]
+    async fn test_etag_not_modified() {
+        use crate::broker::rules_engine::{Rule, RuleEndpointConfig, RuleEndpointProtocol};
+        use ripple_sdk::{
+            api::gateway::rpc_gateway_api::RpcRequest,
+            tokio::{
+                io::{AsyncReadExt, AsyncWriteExt},
+                net::TcpListener,
+            },
+            Mockable,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let mut requests = vec![];
+            for response in [
+                "HTTP/1.1 200 OK\r\netag: \"v1\"\r\nconnection: close\r\ncontent-length: 15\r\n\r\n{\"cached\":true}",
+                "HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\nconnection: close\r\n\r\n",
+            ] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+
+        let endpoint = RuleEndpointConfig {
+            protocol: RuleEndpointProtocol::Http,
+            url: format!("http://127.0.0.1:{}", port),
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (reconnect_tx, _) = mpsc::channel(1);
+        let (tx, mut rx) = mpsc::channel(10);
+        let broker = HttpBroker::get_broker(
+            None,
+            BrokerConnectRequest::new("http".to_owned(), endpoint, reconnect_tx),
+            BrokerCallback { sender: tx },
+            &mut EndpointBrokerState::default(),
+        );
+        let rule = Rule {
+            alias: "resource".to_owned(),
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            broker
+                .get_sender()
+                .send(BrokerRequest::new(
+                    &RpcRequest::mock(),
+                    rule.clone(),
+                    None,
+                    vec![],
+                ))
+                .await
+                .unwrap();
+            let output = rx.recv().await.unwrap();
+            assert_eq!(
+                output.data.result,
+                Some(serde_json::json!({"cached": true}))
+            );
+        }
+
+        let requests = server.await.unwrap();
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+    }
 }
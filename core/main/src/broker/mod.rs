@@ -19,11 +19,13 @@ pub mod endpoint_broker;
 pub mod event_management_utility;
 pub mod extn_broker;
 pub mod http_broker;
+pub mod mqtt_broker;
 pub mod provider_broker_state;
 pub mod rules_engine;
 #[cfg(test)]
 pub mod test;
 pub mod thunder;
 pub mod thunder_broker;
+pub mod unix_socket_broker;
 pub mod websocket_broker;
 pub mod workflow_broker;
@@ -38,6 +38,7 @@ use super::endpoint_broker::BrokerRequest;
 #[derive(Debug, Clone, Default)]
 pub struct ProvideBrokerState {
     capability_map: Arc<RwLock<HashMap<String, Session>>>,
+    provider_timeouts: Arc<RwLock<HashMap<String, u64>>>,
 }
 
 pub enum ProviderResult {
@@ -47,6 +48,19 @@ pub enum ProviderResult {
 }
 
 impl ProvideBrokerState {
+    pub fn new(provider_timeouts: HashMap<String, u64>) -> Self {
+        Self {
+            capability_map: Arc::new(RwLock::new(HashMap::new())),
+            provider_timeouts: Arc::new(RwLock::new(provider_timeouts)),
+        }
+    }
+
+    /// Returns the time in milliseconds a provider for the given permissions has to respond
+    pub fn get_provider_timeout(&self, permission: &[FireboltPermission]) -> Option<u64> {
+        let p = Self::get_permission(permission)?;
+        self.provider_timeouts.read().unwrap().get(&p).cloned()
+    }
+
     pub fn check_provider_request(
         &self,
         request: &RpcRequest,
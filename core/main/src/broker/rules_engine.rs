@@ -14,9 +14,12 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 //
-use jaq_interpret::{Ctx, FilterT, ParseCtx, RcIter, Val};
+use jaq_interpret::{Ctx, Filter, FilterT, ParseCtx, RcIter, Val};
+use openrpc_validator::jsonschema::JSONSchema;
 use ripple_sdk::api::{
-    gateway::rpc_gateway_api::RpcRequest, manifest::extn_manifest::ExtnManifest,
+    firebolt::{fb_metrics::Counter, fb_telemetry::OperationalMetricRequest},
+    gateway::rpc_gateway_api::RpcRequest,
+    manifest::extn_manifest::ExtnManifest,
 };
 
 use ripple_sdk::{
@@ -26,15 +29,43 @@ use ripple_sdk::{
     utils::error::RippleError,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::{fs, path::Path};
+use std::collections::{HashMap, HashSet};
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use crate::broker::thunder_broker::DEFAULT_COMPOSITE_KEY;
+use crate::service::observability::ObservabilityClient;
+use crate::state::metrics_state::MetricsState;
+
+/// Counter of jq filters served from the compiled filter cache
+pub const JQ_CACHE_HIT_COUNTER: &str = "jq_filter_cache_hit";
+/// Counter of jq filters which had to be compiled
+pub const JQ_CACHE_MISS_COUNTER: &str = "jq_filter_cache_miss";
+/// Counter of failed jq transforms, tagged with the filter source and the transform type
+pub const JQ_TRANSFORM_FAILURE_COUNTER: &str = "jq_transform_failure";
+/// Number of compiled jq filters kept, filters beyond it are compiled on every use
+const JQ_FILTER_CACHE_CAPACITY: usize = 256;
 
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct RuleSet {
     pub endpoints: HashMap<String, RuleEndpoint>,
+    /// Rules keyed by Firebolt method name. Method names are case-insensitive: keys are
+    /// normalized with [normalize_method] when appended and lookups normalize the requested
+    /// method the same way, so `Device.SKU` and `device.sku` resolve to the same rule.
     pub rules: HashMap<String, Rule>,
 }
 
+/// Normalized form of a Firebolt method name used for rule lookups
+pub fn normalize_method(method: &str) -> String {
+    method.to_lowercase()
+}
+
 impl RuleSet {
     pub fn append(&mut self, rule_set: RuleSet) {
         self.endpoints.extend(rule_set.endpoints);
@@ -42,20 +73,232 @@ impl RuleSet {
             .rules
             .into_iter()
             .map(|(k, v)| {
-                trace!("Loading JQ Rule for {}", k.to_lowercase());
-                (k.to_lowercase(), v)
+                let method = normalize_method(&k);
+                trace!("Loading JQ Rule for {}", method);
+                (method, v)
             })
             .collect();
         self.rules.extend(rules);
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+const DEFAULT_BROKER_CHANNEL_CAPACITY: usize = 10;
+const DEFAULT_MAX_QUEUED_REQUESTS: usize = 10;
+
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "RuleEndpointConfig")]
 pub struct RuleEndpoint {
     pub protocol: RuleEndpointProtocol,
     pub url: String,
+    pub jsonrpc: bool,
+    /// Static headers sent with every request to this endpoint. Values of the
+    /// form `${ENV:NAME}` are read from the environment variable `NAME`.
+    pub headers: HashMap<String, String>,
+    /// For http endpoints, requests arriving within this many milliseconds of
+    /// each other are sent upstream as a single JSON-RPC batch POST.
+    pub jsonrpc_batch: Option<u64>,
+    /// Capacity of the request channel of the broker serving this endpoint
+    pub channel_capacity: Option<usize>,
+    /// For thunder endpoints, params_json keys which are kept with the request and handed
+    /// back with its response. Defaults to `response`, the only supported key.
+    pub composite_keys: Option<Vec<String>>,
+    /// Requests dispatched to this endpoint at once, later requests wait until an
+    /// earlier one is answered. Unlimited when not set.
+    pub max_concurrency: Option<usize>,
+    /// Requests allowed to wait for [RuleEndpoint::max_concurrency], requests beyond it
+    /// fail straight away
+    pub max_queued: Option<usize>,
+    /// For mqtt endpoints, quality of service of subscriptions and publishes, 0 or 1.
+    /// Defaults to 0.
+    pub qos: Option<u8>,
+    /// Param under which the `gateway_secure` flag of the originating connection is added
+    /// to the transformed params of every request to this endpoint
+    pub gateway_secure_param: Option<String>,
+}
+
+impl Default for RuleEndpoint {
+    // same defaults as an endpoint of a rule file, see [RuleEndpointConfig]
+    fn default() -> Self {
+        Self {
+            protocol: RuleEndpointProtocol::default(),
+            url: String::default(),
+            jsonrpc: default_autostart(),
+            headers: HashMap::new(),
+            jsonrpc_batch: None,
+            channel_capacity: None,
+            composite_keys: None,
+            max_concurrency: None,
+            max_queued: None,
+            qos: None,
+            gateway_secure_param: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for RuleEndpoint {
+    // header values may carry credentials, so only the header names are printed
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut header_names: Vec<&String> = self.headers.keys().collect();
+        header_names.sort();
+        f.debug_struct("RuleEndpoint")
+            .field("protocol", &self.protocol)
+            .field("url", &self.url)
+            .field("jsonrpc", &self.jsonrpc)
+            .field("headers", &header_names)
+            .field("jsonrpc_batch", &self.jsonrpc_batch)
+            .field("channel_capacity", &self.channel_capacity)
+            .field("composite_keys", &self.composite_keys)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("max_queued", &self.max_queued)
+            .field("qos", &self.qos)
+            .field("gateway_secure_param", &self.gateway_secure_param)
+            .finish()
+    }
+}
+
+/// Builder of a [RuleEndpoint]. Endpoints of rule files are read into it and checked by
+/// [RuleEndpointConfig::build], so options which do not apply to the protocol of the
+/// endpoint are rejected instead of being silently ignored. Fields left unset get the
+/// defaults documented on [RuleEndpoint].
+#[derive(Debug, Deserialize, Clone)]
+pub struct RuleEndpointConfig {
+    #[serde(default)]
+    pub protocol: RuleEndpointProtocol,
+    pub url: String,
+    /// Defaults to true
     #[serde(default = "default_autostart")]
     pub jsonrpc: bool,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Http only
+    #[serde(default)]
+    pub jsonrpc_batch: Option<u64>,
+    /// At least 1, defaults to 10
+    #[serde(default)]
+    pub channel_capacity: Option<usize>,
+    /// Thunder only, `response` is the only supported key
+    #[serde(default)]
+    pub composite_keys: Option<Vec<String>>,
+    /// At least 1, unlimited when not set
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /// Requires max_concurrency, defaults to 10
+    #[serde(default)]
+    pub max_queued: Option<usize>,
+    /// Mqtt only, 0 or 1
+    #[serde(default)]
+    pub qos: Option<u8>,
+    #[serde(default)]
+    pub gateway_secure_param: Option<String>,
+}
+
+impl Default for RuleEndpointConfig {
+    fn default() -> Self {
+        Self {
+            protocol: RuleEndpointProtocol::default(),
+            url: String::default(),
+            jsonrpc: default_autostart(),
+            headers: HashMap::new(),
+            jsonrpc_batch: None,
+            channel_capacity: None,
+            composite_keys: None,
+            max_concurrency: None,
+            max_queued: None,
+            qos: None,
+            gateway_secure_param: None,
+        }
+    }
+}
+
+impl RuleEndpointConfig {
+    /// Validates the combination of options, returning a description of the first problem
+    /// found
+    pub fn build(self) -> Result<RuleEndpoint, String> {
+        let protocol = self.protocol.as_str();
+        let schemes: &[&str] = match self.protocol {
+            RuleEndpointProtocol::Websocket | RuleEndpointProtocol::Thunder => &["ws://", "wss://"],
+            RuleEndpointProtocol::Http => &["http://", "https://"],
+            _ => &[],
+        };
+        if !schemes.is_empty() && !schemes.iter().any(|scheme| self.url.starts_with(scheme)) {
+            return Err(format!(
+                "{} endpoint url {} must start with {}",
+                protocol,
+                self.url,
+                schemes.join(" or ")
+            ));
+        }
+        let protocol_only = [
+            (
+                "jsonrpc_batch",
+                self.jsonrpc_batch.is_some(),
+                RuleEndpointProtocol::Http,
+            ),
+            (
+                "composite_keys",
+                self.composite_keys.is_some(),
+                RuleEndpointProtocol::Thunder,
+            ),
+            ("qos", self.qos.is_some(), RuleEndpointProtocol::Mqtt),
+        ];
+        for (option, set, only) in protocol_only {
+            if set && only.as_str() != protocol {
+                return Err(format!(
+                    "{} only applies to {} endpoints, not {}",
+                    option,
+                    only.as_str(),
+                    protocol
+                ));
+            }
+        }
+        // only the response filter is applied to the response the keys are handed back with
+        if let Some(key) = self
+            .composite_keys
+            .iter()
+            .flatten()
+            .find(|key| key.as_str() != DEFAULT_COMPOSITE_KEY)
+        {
+            return Err(format!(
+                "composite key {} is not supported, only {} is",
+                key, DEFAULT_COMPOSITE_KEY
+            ));
+        }
+        if let Some(qos) = self.qos.filter(|qos| *qos > 1) {
+            return Err(format!("qos must be 0 or 1, not {}", qos));
+        }
+        for (option, value) in [
+            ("channel_capacity", self.channel_capacity),
+            ("max_concurrency", self.max_concurrency),
+        ] {
+            if value == Some(0) {
+                return Err(format!("{} must be at least 1", option));
+            }
+        }
+        if self.max_queued.is_some() && self.max_concurrency.is_none() {
+            return Err("max_queued requires max_concurrency".to_owned());
+        }
+        Ok(RuleEndpoint {
+            protocol: self.protocol,
+            url: self.url,
+            jsonrpc: self.jsonrpc,
+            headers: self.headers,
+            jsonrpc_batch: self.jsonrpc_batch,
+            channel_capacity: self.channel_capacity,
+            composite_keys: self.composite_keys,
+            max_concurrency: self.max_concurrency,
+            max_queued: self.max_queued,
+            qos: self.qos,
+            gateway_secure_param: self.gateway_secure_param,
+        })
+    }
+}
+
+impl TryFrom<RuleEndpointConfig> for RuleEndpoint {
+    type Error = String;
+
+    fn try_from(config: RuleEndpointConfig) -> Result<Self, Self::Error> {
+        config.build()
+    }
 }
 
 impl RuleEndpoint {
@@ -69,6 +312,40 @@ impl RuleEndpoint {
         }
         self.url.clone()
     }
+
+    pub fn get_channel_capacity(&self) -> usize {
+        self.channel_capacity
+            .unwrap_or(DEFAULT_BROKER_CHANNEL_CAPACITY)
+    }
+
+    pub fn get_max_queued(&self) -> usize {
+        self.max_queued.unwrap_or(DEFAULT_MAX_QUEUED_REQUESTS)
+    }
+
+    pub fn get_qos(&self) -> u8 {
+        self.qos.unwrap_or(0).min(1)
+    }
+
+    pub fn get_headers(&self) -> HashMap<String, String> {
+        self.headers
+            .iter()
+            .filter_map(|(name, value)| {
+                match value
+                    .strip_prefix("${ENV:")
+                    .and_then(|v| v.strip_suffix('}'))
+                {
+                    Some(var) => match std::env::var(var) {
+                        Ok(resolved) => Some((name.clone(), resolved)),
+                        Err(_) => {
+                            warn!("env var {} for endpoint header {} is not set", var, name);
+                            None
+                        }
+                    },
+                    None => Some((name.clone(), value.clone())),
+                }
+            })
+            .collect()
+    }
 }
 
 fn default_autostart() -> bool {
@@ -85,6 +362,22 @@ pub enum RuleEndpointProtocol {
     Thunder,
     Workflow,
     Extn,
+    UnixSocket,
+    Mqtt,
+}
+
+impl RuleEndpointProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleEndpointProtocol::Websocket => "websocket",
+            RuleEndpointProtocol::Http => "http",
+            RuleEndpointProtocol::Thunder => "thunder",
+            RuleEndpointProtocol::Workflow => "workflow",
+            RuleEndpointProtocol::Extn => "extn",
+            RuleEndpointProtocol::UnixSocket => "unixsocket",
+            RuleEndpointProtocol::Mqtt => "mqtt",
+        }
+    }
 }
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct JsonDataSource {
@@ -92,6 +385,8 @@ pub struct JsonDataSource {
     pub namespace: Option<String>,
     pub method: String,
     pub params: Option<String>,
+    // per step timeout in milliseconds, steps without one wait until their response arrives
+    pub timeout: Option<u64>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -108,6 +403,167 @@ pub struct Rule {
     pub endpoint: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sources: Option<Vec<JsonDataSource>>,
+    // Test only: delays brokering of the request by the given milliseconds to simulate a slow
+    // backend. Only honored in tests and local_dev builds, other builds report it as invalid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inject_latency_ms: Option<u64>,
+    // Endpoint used when the primary endpoint is unavailable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+    // When set, only these apps may call the method
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_apps: Option<Vec<String>>,
+    // What happens to an event whose event transform fails, delivered as is by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_transform_failure: Option<EventTransformFailure>,
+    // Firebolt capabilities the method touches, used for auditing only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Vec<String>>,
+    // Event filter evaluated against {"params": subscription params, "result": event result}
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_filter: Option<String>,
+    // Also deliver the response to the session when it is sent to a workflow callback
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workflow_session_delivery: Option<bool>,
+    // JSON schema the transformed response result must satisfy, only checked when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<ResponseSchema>,
+    // Result of provided methods when no provider is available, the request fails when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_fallback: Option<Value>,
+    // Requests each app may make within a window, counted per app and rule alias
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+    // Deliver id-less upstream notifications named like the alias to every listening subscriber
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification_passthrough: Option<bool>,
+    // Send the app's params to the endpoint and return its result as is, without transforms.
+    // Its endpoint may not set gateway_secure_param, such rules are rejected when they load.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passthrough: Option<bool>,
+}
+
+/// Requests an app may make to a rule within a time window, see [Rule::rate_limit]. Rules
+/// allowing no requests or with an empty window are rejected when they load.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "RateLimitConfig")]
+pub struct RateLimit {
+    pub requests: u32,
+    pub window_ms: u64,
+}
+
+#[derive(Deserialize)]
+struct RateLimitConfig {
+    requests: u32,
+    window_ms: u64,
+}
+
+impl TryFrom<RateLimitConfig> for RateLimit {
+    type Error = String;
+
+    fn try_from(config: RateLimitConfig) -> Result<Self, Self::Error> {
+        if config.requests == 0 {
+            return Err("rate_limit requests must be greater than 0".to_owned());
+        }
+        if config.window_ms == 0 {
+            return Err("rate_limit window_ms must be greater than 0".to_owned());
+        }
+        Ok(RateLimit {
+            requests: config.requests,
+            window_ms: config.window_ms,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTransformFailure {
+    /// Send the untransformed backend payload
+    #[default]
+    Deliver,
+    /// Drop the event
+    Drop,
+}
+
+impl Rule {
+    pub fn is_app_allowed(&self, app_id: &str) -> bool {
+        self.allowed_apps
+            .as_ref()
+            .map_or(true, |apps| apps.iter().any(|app| app == app_id))
+    }
+
+    pub fn drops_failed_events(&self) -> bool {
+        self.event_transform_failure.unwrap_or_default() == EventTransformFailure::Drop
+    }
+
+    pub fn delivers_workflow_responses_to_session(&self) -> bool {
+        self.workflow_session_delivery.unwrap_or(false)
+    }
+
+    pub fn routes_notifications(&self) -> bool {
+        self.notification_passthrough.unwrap_or(false)
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        self.passthrough.unwrap_or(false)
+    }
+
+    /// Whether [Rule::inject_latency_ms] is honored by this build
+    pub fn latency_injection_enabled() -> bool {
+        cfg!(any(test, feature = "local_dev"))
+    }
+
+    /// Checks a transformed result against the rule's response_schema, returning the
+    /// violations found. Rules without a schema accept any result.
+    pub fn validate_response(&self, result: &Value) -> Result<(), String> {
+        match &self.response_schema {
+            Some(schema) => schema.validate(result),
+            None => Ok(()),
+        }
+    }
+}
+
+/// JSON schema a rule's transformed result must satisfy, compiled once when the rule is loaded
+#[derive(Debug, Clone)]
+pub struct ResponseSchema {
+    schema: Value,
+    compiled: Result<Arc<JSONSchema>, String>,
+}
+
+impl ResponseSchema {
+    pub fn new(schema: Value) -> Self {
+        let compiled = JSONSchema::compile(&schema)
+            .map(Arc::new)
+            .map_err(|e| format!("invalid response_schema: {}", e));
+        ResponseSchema { schema, compiled }
+    }
+
+    /// Why the schema could not be compiled, if it could not
+    pub fn compile_error(&self) -> Option<&str> {
+        self.compiled.as_ref().err().map(String::as_str)
+    }
+
+    fn validate(&self, result: &Value) -> Result<(), String> {
+        let compiled = self.compiled.as_ref().map_err(Clone::clone)?;
+        compiled.validate(result).map_err(|errors| {
+            errors
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        })
+    }
+}
+
+impl Serialize for ResponseSchema {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.schema.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ResponseSchema {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(ResponseSchema::new)
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -120,6 +576,9 @@ pub struct RuleTransform {
     pub event: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rpcv2_event: Option<String>,
+    /// Response transform for Firebolt RPC v2 requests, `response` applies when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpcv2_response: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub event_decorator_method: Option<String>,
 }
@@ -161,6 +620,42 @@ impl RuleTransform {
         }
     }
 
+    /// Extends the request program so that the transformed params carry `key` set to
+    /// `value`. Params which are neither objects nor null are left as they are.
+    pub fn inject_request_param(&mut self, key: &str, value: &Value) {
+        let program = self.request.take().unwrap_or(".".to_owned());
+        let _ = self.request.insert(format!(
+            "({}) | if . == null or type == \"object\" then . + {{{}: {}}} else . end",
+            program,
+            Value::String(key.to_owned()),
+            value
+        ));
+    }
+
+    /// Every jq program of the transform, with the request placeholders substituted so
+    /// that the programs can be compiled ahead of any request
+    fn jq_programs(&self) -> Vec<(&'static str, String)> {
+        let event = self.event.clone().unwrap_or(".".to_owned());
+        [
+            ("request", &self.request),
+            ("response", &self.response),
+            ("event", &self.event),
+            ("rpcv2_event", &self.rpcv2_event),
+            ("rpcv2_response", &self.rpcv2_response),
+        ]
+        .into_iter()
+        .filter_map(|(name, program)| {
+            program.as_ref().map(|p| {
+                (
+                    name,
+                    p.replace("$context.appId", "appId")
+                        .replace("$event", &event),
+                )
+            })
+        })
+        .collect()
+    }
+
     pub fn get_transform_data(&self, typ: RuleTransformType) -> Option<String> {
         match typ {
             RuleTransformType::Request => self.request.clone(),
@@ -171,17 +666,87 @@ impl RuleTransform {
                     self.event.clone()
                 }
             }
-            RuleTransformType::Response => self.response.clone(),
+            RuleTransformType::Response(rpc_v2) => {
+                if rpc_v2 {
+                    self.rpcv2_response.clone().or(self.response.clone())
+                } else {
+                    self.response.clone()
+                }
+            }
         }
     }
 }
 
+/// How [crate::broker::endpoint_broker::EndpointBrokerState::handle_brokerage] would
+/// handle a request
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleRouting {
+    Static,
+    Provided,
+    Brokered,
+    /// No rule matched, the request falls through to the Ripple handlers
+    Unmatched,
+}
+
+/// Outcome of [RuleEngine::explain], nothing is dispatched to produce it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleExplanation {
+    pub method: String,
+    pub alias: Option<String>,
+    pub endpoint: Option<String>,
+    pub routing: RuleRouting,
+    pub request_transform: Option<String>,
+    /// Request params after the request transform, or as is without one
+    pub transformed_params: Option<Value>,
+    pub transform_error: Option<String>,
+}
+
+/// Effective rule of a method for an app, see [RuleEngine::resolve]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSummary {
+    pub method: String,
+    /// Key of the rule in the ruleset, a wildcard like `device.*` or the normalized method
+    pub matched: String,
+    pub alias: String,
+    /// Endpoint the request is brokered to, None unless the routing is brokered
+    pub endpoint: Option<String>,
+    pub fallback: Option<String>,
+    pub routing: RuleRouting,
+    /// Transform with the app context substituted
+    pub transform: RuleTransform,
+}
+
+fn rule_routing(rule: &Rule) -> RuleRouting {
+    if rule.alias == "static" {
+        RuleRouting::Static
+    } else if rule.alias.eq_ignore_ascii_case("provided") {
+        RuleRouting::Provided
+    } else {
+        RuleRouting::Brokered
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub enum RuleTransformType {
     Request,
-    Response,
+    /// Response transform, the v2 one when true
+    Response(bool),
     Event(bool),
 }
 
+impl RuleTransformType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleTransformType::Request => "request",
+            RuleTransformType::Response(_) => "response",
+            RuleTransformType::Event(_) => "event",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RuleEngine {
     pub rules: RuleSet,
@@ -252,31 +817,208 @@ impl RuleEngine {
     pub fn has_rule(&self, request: &RpcRequest) -> bool {
         self.rules
             .rules
-            .contains_key(&request.ctx.method.to_lowercase())
+            .contains_key(&normalize_method(&request.ctx.method))
     }
 
+    /// Finds the rule for the request method, ignoring case. Exact matches take precedence
+    /// over wildcard rules ending in `.*`.
     pub fn get_rule(&self, rpc_request: &RpcRequest) -> Option<Rule> {
-        let method = rpc_request.method.to_lowercase();
-        if let Some(mut rule) = self.rules.rules.get(&method).cloned() {
+        if let Some((_, rule)) = self.find_rule(&rpc_request.method) {
+            let mut rule = rule.clone();
             rule.transform.apply_context(rpc_request);
             return Some(rule);
-        } else {
-            for (key, value) in &self.rules.rules {
-                if key.ends_with(".*") && method.starts_with(&key[..key.len() - 2]) {
-                    let mut rule = value.clone();
-                    rule.transform.apply_context(rpc_request);
-                    return Some(rule);
+        }
+        trace!(
+            "Rule not available for {}, hence falling back to extension handler",
+            rpc_request.method
+        );
+        None
+    }
+
+    /// Key and rule matching the method, see [RuleEngine::get_rule]
+    fn find_rule(&self, method: &str) -> Option<(String, &Rule)> {
+        let method = normalize_method(method);
+        if let Some(rule) = self.rules.rules.get(&method) {
+            return Some((method, rule));
+        }
+        self.rules.rules.iter().find_map(|(key, value)| {
+            let key = normalize_method(key);
+            (key.ends_with(".*") && method.starts_with(&key[..key.len() - 2]))
+                .then_some((key, value))
+        })
+    }
+
+    /// Effective rule for a method called by the app, after wildcard matching, the
+    /// allowed_apps restriction and the `$context.appId` substitution. Rules loaded from
+    /// later rule files have already replaced earlier ones when the engine was built.
+    /// Returns None when no rule applies and the method falls through to the Ripple
+    /// handlers, or when the app may not call it.
+    pub fn resolve(&self, method: &str, app_id: &str) -> Option<RuleSummary> {
+        let (matched, rule) = self.find_rule(method)?;
+        if !rule.is_app_allowed(app_id) {
+            return None;
+        }
+        let mut rpc_request = RpcRequest {
+            method: method.to_owned(),
+            ..Default::default()
+        };
+        rpc_request.ctx.app_id = app_id.to_owned();
+        let mut rule = rule.clone();
+        rule.transform.apply_context(&rpc_request);
+        let routing = rule_routing(&rule);
+        Some(RuleSummary {
+            method: method.to_owned(),
+            matched,
+            alias: rule.alias,
+            endpoint: matches!(routing, RuleRouting::Brokered)
+                .then(|| rule.endpoint.unwrap_or("thunder".to_owned())),
+            fallback: rule.fallback,
+            routing,
+            transform: rule.transform,
+        })
+    }
+    /// Describes the rule, endpoint and request transform Ripple would use for the request
+    /// without dispatching it
+    pub fn explain(&self, rpc_request: &RpcRequest) -> RuleExplanation {
+        let mut explanation = RuleExplanation {
+            method: rpc_request.method.clone(),
+            alias: None,
+            endpoint: None,
+            routing: RuleRouting::Unmatched,
+            request_transform: None,
+            transformed_params: None,
+            transform_error: None,
+        };
+        let rule = match self.get_rule(rpc_request) {
+            Some(rule) => rule,
+            None => return explanation,
+        };
+        explanation.routing = rule_routing(&rule);
+        if explanation.routing == RuleRouting::Brokered {
+            // rules without an endpoint are brokered to thunder
+            explanation.endpoint = Some(rule.endpoint.clone().unwrap_or("thunder".to_owned()));
+        }
+        explanation.alias = Some(rule.alias.clone());
+
+        let params = rpc_request.get_params().unwrap_or(Value::Null);
+        match rule
+            .transform
+            .get_transform_data(RuleTransformType::Request)
+        {
+            Some(filter) => {
+                match jq_compile(params, &filter, format!("{}_explain", rpc_request.method)) {
+                    Ok(transformed) => explanation.transformed_params = Some(transformed),
+                    Err(e) => explanation.transform_error = Some(format!("{:?}", e)),
                 }
+                explanation.request_transform = Some(filter);
             }
-            trace!(
-                "Rule not available for {}, hence falling back to extension handler",
-                rpc_request.method
-            );
+            None => explanation.transformed_params = Some(params),
         }
-        None
+        explanation
     }
+
     pub fn get_rule_by_method(&self, method: &str) -> Option<Rule> {
-        self.rules.rules.get(&method.to_lowercase()).cloned()
+        self.rules.rules.get(&normalize_method(method)).cloned()
+    }
+
+    /// Firebolt capabilities declared by the rules in the ruleset
+    pub fn referenced_capabilities(&self) -> HashSet<String> {
+        self.rules
+            .rules
+            .values()
+            .filter_map(|rule| rule.capabilities.as_ref())
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Compiles every transform and filter jq program and checks that endpoint and
+    /// fallback references resolve, returning `(rule_alias, error)` for each problem
+    pub fn validate(&self) -> Vec<(String, String)> {
+        let mut errors = Vec::new();
+        for rule in self.rules.rules.values() {
+            let mut programs = rule.transform.jq_programs();
+            if let Some(filter) = &rule.filter {
+                programs.push(("filter", filter.clone()));
+            }
+            for (name, program) in programs {
+                if let Err(errs) = compile_jq_filter(&program) {
+                    errors.push((
+                        rule.alias.clone(),
+                        format!("invalid {} jq program: {}", name, errs.join(", ")),
+                    ));
+                }
+            }
+            if rule.inject_latency_ms.is_some() && !Rule::latency_injection_enabled() {
+                errors.push((
+                    rule.alias.clone(),
+                    "inject_latency_ms is only supported in local_dev builds".to_owned(),
+                ));
+            }
+            if let Some(e) = rule
+                .response_schema
+                .as_ref()
+                .and_then(ResponseSchema::compile_error)
+            {
+                errors.push((rule.alias.clone(), e.to_owned()));
+            }
+        }
+        errors.extend(self.endpoint_errors());
+        errors.sort();
+        errors
+    }
+
+    fn endpoint_errors(&self) -> Vec<(String, String)> {
+        let mut errors = Vec::new();
+        for rule in self.rules.rules.values() {
+            for (name, endpoint) in [("endpoint", &rule.endpoint), ("fallback", &rule.fallback)] {
+                if let Some(endpoint) = endpoint {
+                    if !self.rules.endpoints.contains_key(endpoint) {
+                        errors.push((
+                            rule.alias.clone(),
+                            format!("{} {} is not defined", name, endpoint),
+                        ));
+                    }
+                }
+            }
+            // the params of passthrough rules are sent untouched, so nothing can be injected
+            if rule.is_passthrough() {
+                let endpoint = rule.endpoint.as_deref().unwrap_or("thunder");
+                for endpoint in std::iter::once(endpoint).chain(rule.fallback.as_deref()) {
+                    if let Some(param) = self
+                        .rules
+                        .endpoints
+                        .get(endpoint)
+                        .and_then(|e| e.gateway_secure_param.as_ref())
+                    {
+                        errors.push((
+                            rule.alias.clone(),
+                            format!(
+                                "passthrough rules cannot use endpoint {} which injects {}",
+                                endpoint, param
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        errors.sort();
+        errors
+    }
+
+    /// Fails when a rule references an endpoint or fallback missing from the loaded
+    /// endpoints, such rules could never be brokered, or when a passthrough rule uses an
+    /// endpoint with a gateway_secure_param
+    pub fn check_endpoints(&self) -> Result<(), RippleError> {
+        let errors = self.endpoint_errors();
+        for (alias, err) in errors.iter() {
+            error!("invalid rule {}: {}", alias, err);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RippleError::InvalidInput)
+        }
     }
 }
 /// Compiles and executes a JQ filter on a given JSON input value.
@@ -320,28 +1062,16 @@ pub fn jq_compile(input: Value, filter: &str, reference: String) -> Result<Value
         filter, input, reference
     );
     let start = Utc::now().timestamp_millis();
-    // start out only from core filters,
-    // which do not include filters in the standard library
-    // such as `map`, `select` etc.
-
-    let mut defs = ParseCtx::new(Vec::new());
-    defs.insert_natives(jaq_core::core());
-    defs.insert_defs(jaq_std::std());
-    // parse the filter
-    let (f, errs) = jaq_parse::parse(filter, jaq_parse::main());
-    if !errs.is_empty() {
-        error!("Error in rule {:?}", errs);
-        return Err(RippleError::RuleError);
-    }
-    // compile the filter in the context of the given definitions
-    let f = defs.compile(f.unwrap());
-    if !defs.errs.is_empty() {
-        error!("Error in rule {}", reference);
-        for (err, _) in defs.errs {
-            error!("reference={} {}", reference, err);
+    let f = match jq_filter_cache().get_or_compile(filter) {
+        Ok(f) => f,
+        Err(errs) => {
+            error!("Error in rule {}", reference);
+            for err in errs {
+                error!("reference={} {}", reference, err);
+            }
+            return Err(RippleError::RuleError);
         }
-        return Err(RippleError::RuleError);
-    }
+    };
 
     let inputs = RcIter::new(core::iter::empty());
     // iterator over the output values
@@ -357,6 +1087,106 @@ pub fn jq_compile(input: Value, filter: &str, reference: String) -> Result<Value
 
     Err(RippleError::ParseError)
 }
+/// Compiled jq filters keyed by their program text. Hits and misses are reported as
+/// cumulative counters so the capacity can be sized.
+#[derive(Default)]
+struct JqFilterCache {
+    filters: Mutex<HashMap<String, Filter>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl JqFilterCache {
+    fn get_or_compile(&self, filter: &str) -> Result<Filter, Vec<String>> {
+        if let Some(f) = self.filters.lock().unwrap().get(filter).cloned() {
+            let hits = self.hits.fetch_add(1, Ordering::Relaxed) + 1;
+            ObservabilityClient::report(OperationalMetricRequest::Counter(Counter::new(
+                JQ_CACHE_HIT_COUNTER.to_string(),
+                hits,
+                None,
+            )));
+            return Ok(f);
+        }
+        let misses = self.misses.fetch_add(1, Ordering::Relaxed) + 1;
+        ObservabilityClient::report(OperationalMetricRequest::Counter(Counter::new(
+            JQ_CACHE_MISS_COUNTER.to_string(),
+            misses,
+            None,
+        )));
+        let f = compile_jq_filter(filter)?;
+        let mut filters = self.filters.lock().unwrap();
+        if filters.len() < JQ_FILTER_CACHE_CAPACITY {
+            filters.insert(filter.to_owned(), f.clone());
+        }
+        Ok(f)
+    }
+
+    /// Cumulative `(hits, misses)`
+    fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+fn jq_filter_cache() -> &'static JqFilterCache {
+    static JQ_FILTER_CACHE: OnceLock<JqFilterCache> = OnceLock::new();
+    JQ_FILTER_CACHE.get_or_init(JqFilterCache::default)
+}
+
+/// Cumulative `(hits, misses)` of the compiled jq filter cache used by [jq_compile]
+pub fn jq_filter_cache_stats() -> (u64, u64) {
+    jq_filter_cache().stats()
+}
+
+/// Reports a failed jq transform as the cumulative [JQ_TRANSFORM_FAILURE_COUNTER] of the
+/// given metrics state, returning the reported counter. `source` names where the filter
+/// came from, the rule alias for rule transforms or [composite_key_source] for filters
+/// handed back with an endpoint response.
+pub fn report_jq_failure(
+    metrics: &MetricsState,
+    source: &str,
+    transform_type: RuleTransformType,
+) -> Counter {
+    let counter = metrics.increment_counter(
+        JQ_TRANSFORM_FAILURE_COUNTER,
+        HashMap::from([
+            ("source".to_owned(), source.to_owned()),
+            ("transform".to_owned(), transform_type.as_str().to_owned()),
+        ]),
+    );
+    ObservabilityClient::report(OperationalMetricRequest::Counter(counter.clone()));
+    counter
+}
+
+/// Source of a filter handed back with an endpoint response under the given composite key
+pub fn composite_key_source(key: &str) -> String {
+    format!("composite_key:{}", key)
+}
+
+/// Parses and compiles a JQ filter, returning every parse or compile error found
+fn compile_jq_filter(filter: &str) -> Result<Filter, Vec<String>> {
+    // start out only from core filters,
+    // which do not include filters in the standard library
+    // such as `map`, `select` etc.
+
+    let mut defs = ParseCtx::new(Vec::new());
+    defs.insert_natives(jaq_core::core());
+    defs.insert_defs(jaq_std::std());
+    // parse the filter
+    let (f, errs) = jaq_parse::parse(filter, jaq_parse::main());
+    if !errs.is_empty() {
+        return Err(errs.iter().map(|e| e.to_string()).collect());
+    }
+    // compile the filter in the context of the given definitions
+    let f = defs.compile(f.unwrap());
+    if !defs.errs.is_empty() {
+        return Err(defs.errs.iter().map(|(e, _)| e.to_string()).collect());
+    }
+    Ok(f)
+}
+
 pub fn compose_json_values(values: Vec<Value>) -> Value {
     if values.len() == 1 {
         return values[0].clone();
@@ -382,7 +1212,7 @@ pub fn make_name_json_safe(name: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ripple_sdk::serde_json::json;
+    use ripple_sdk::{serde_json::json, Mockable};
 
     #[test]
     fn test_jq_compile() {
@@ -485,4 +1315,479 @@ mod tests {
         .unwrap()
         .contains("nested"));
     }
+
+    #[test]
+    fn test_validate() {
+        let engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {
+                    "http": {"protocol": "http", "url": "http://localhost"}
+                },
+                "rules": {
+                    "device.name": {
+                        "alias": "org.rdk.System.getFriendlyName",
+                        "transform": {"response": ".result.friendlyName"}
+                    },
+                    "device.model": {
+                        "alias": "org.rdk.System.getModel",
+                        "transform": {"response": "if .result then"},
+                        "endpoint": "http"
+                    },
+                    "device.sku": {
+                        "alias": "org.rdk.System.getSku",
+                        "response_schema": {"type": "nonsense"}
+                    },
+                    "device.onNameChanged": {
+                        "alias": "org.rdk.System.onFriendlyNameChanged",
+                        "transform": {
+                            "event": "{\"appId\": \"$context.appId\", \"name\": .value}",
+                            "rpcv2_event": "{\"event\": $event}"
+                        },
+                        "filter": "undefined_fn(.)",
+                        "endpoint": "missing",
+                        "fallback": "http"
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        assert!(engine.check_endpoints().is_err());
+        let errors = engine.validate();
+        let aliases: Vec<&str> = errors.iter().map(|(alias, _)| alias.as_str()).collect();
+        assert_eq!(
+            aliases,
+            vec![
+                "org.rdk.System.getModel",
+                "org.rdk.System.getSku",
+                "org.rdk.System.onFriendlyNameChanged",
+                "org.rdk.System.onFriendlyNameChanged",
+            ]
+        );
+        assert!(errors[0].1.starts_with("invalid response jq program"));
+        assert!(errors[1].1.starts_with("invalid response_schema"));
+        assert_eq!(errors[2].1, "endpoint missing is not defined");
+        assert!(errors[3].1.starts_with("invalid filter jq program"));
+        // the schema is compiled when the rule loads and reported on every response
+        let sku = engine.get_rule_by_method("device.sku").unwrap();
+        assert!(sku.validate_response(&json!("sku")).is_err());
+    }
+
+    #[test]
+    fn test_get_rule_ignores_case() {
+        let engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {},
+                "rules": {
+                    "Device.SKU": {"alias": "org.rdk.System.getSystemVersions"},
+                    "Localization.*": {"alias": "org.rdk.UserPreferences"}
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let mut request = RpcRequest::mock();
+        for method in ["Device.SKU", "device.sku", "DEVICE.Sku"] {
+            request.method = method.to_owned();
+            request.ctx.method = method.to_owned();
+            assert_eq!(
+                engine.get_rule(&request).unwrap().alias,
+                "org.rdk.System.getSystemVersions"
+            );
+            assert!(engine.has_rule(&request));
+            assert!(engine.get_rule_by_method(method).is_some());
+        }
+        request.method = "localization.Language".to_owned();
+        assert_eq!(
+            engine.get_rule(&request).unwrap().alias,
+            "org.rdk.UserPreferences"
+        );
+    }
+
+    #[test]
+    fn test_jq_filter_cache_stats() {
+        let cache = JqFilterCache::default();
+        for filter in [".a", ".a", ".b", ".a", "if"] {
+            let _ = cache.get_or_compile(filter);
+        }
+        // invalid filters are not cached
+        assert!(cache.get_or_compile("if").is_err());
+        assert_eq!(cache.stats(), (2, 4));
+        assert_eq!(cache.filters.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_report_jq_failure() {
+        let metrics = MetricsState::default();
+        let alias = "org.rdk.Test.reportJqFailure";
+        let counter = report_jq_failure(&metrics, alias, RuleTransformType::Response(false));
+        assert_eq!(
+            counter.name,
+            format!("{}_counter", JQ_TRANSFORM_FAILURE_COUNTER)
+        );
+        assert_eq!(counter.value, 1);
+        let tags = counter.tags.unwrap();
+        assert_eq!(tags.get("source").unwrap(), alias);
+        assert_eq!(tags.get("transform").unwrap(), "response");
+
+        assert_eq!(
+            report_jq_failure(&metrics, alias, RuleTransformType::Response(true)).value,
+            2
+        );
+        // counted separately per transform type and source
+        assert_eq!(
+            report_jq_failure(&metrics, alias, RuleTransformType::Event(false)).value,
+            1
+        );
+        assert_eq!(
+            report_jq_failure(
+                &metrics,
+                &composite_key_source("response"),
+                RuleTransformType::Response(false)
+            )
+            .value,
+            1
+        );
+    }
+
+    #[test]
+    fn test_check_endpoints() {
+        let mut engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {
+                    "http": {"protocol": "http", "url": "http://localhost"}
+                },
+                "rules": {
+                    "device.name": {"alias": "org.rdk.System.getFriendlyName"},
+                    "device.model": {"alias": "org.rdk.System.getModel", "endpoint": "http"}
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert!(engine.check_endpoints().is_ok());
+
+        engine.rules.append(
+            RuleEngine::load_from_content(
+                json!({
+                    "endpoints": {},
+                    "rules": {
+                        "device.sku": {"alias": "org.rdk.System.getSku", "endpoint": "undefined"}
+                    }
+                })
+                .to_string(),
+            )
+            .unwrap()
+            .1,
+        );
+        assert_eq!(
+            engine.check_endpoints().unwrap_err(),
+            RippleError::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_check_passthrough_endpoints() {
+        let engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {
+                    "http": {"protocol": "http", "url": "http://localhost"},
+                    "secure": {
+                        "protocol": "http",
+                        "url": "http://localhost",
+                        "gateway_secure_param": "secure"
+                    }
+                },
+                "rules": {
+                    "device.name": {
+                        "alias": "device.name",
+                        "endpoint": "secure"
+                    },
+                    "device.model": {
+                        "alias": "device.model",
+                        "endpoint": "http",
+                        "passthrough": true
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert!(engine.check_endpoints().is_ok());
+
+        let engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {
+                    "http": {"protocol": "http", "url": "http://localhost"},
+                    "secure": {
+                        "protocol": "http",
+                        "url": "http://localhost",
+                        "gateway_secure_param": "secure"
+                    }
+                },
+                "rules": {
+                    "device.model": {
+                        "alias": "device.model",
+                        "endpoint": "http",
+                        "fallback": "secure",
+                        "passthrough": true
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert!(engine.check_endpoints().is_err());
+        assert_eq!(
+            engine.validate(),
+            vec![(
+                "device.model".to_owned(),
+                "passthrough rules cannot use endpoint secure which injects secure".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_referenced_capabilities() {
+        let engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {},
+                "rules": {
+                    "device.name": {
+                        "alias": "org.rdk.System.getFriendlyName",
+                        "capabilities": ["xrn:firebolt:capability:device:name"]
+                    },
+                    "device.setName": {
+                        "alias": "org.rdk.System.setFriendlyName",
+                        "capabilities": [
+                            "xrn:firebolt:capability:device:name",
+                            "xrn:firebolt:capability:device:info"
+                        ]
+                    },
+                    "device.model": {"alias": "org.rdk.System.getModel"}
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            engine.referenced_capabilities(),
+            HashSet::from([
+                "xrn:firebolt:capability:device:name".to_owned(),
+                "xrn:firebolt:capability:device:info".to_owned()
+            ])
+        );
+        assert!(RuleEngine::default().referenced_capabilities().is_empty());
+    }
+
+    #[test]
+    fn test_explain() {
+        let engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {},
+                "rules": {
+                    "device.setName": {
+                        "alias": "org.rdk.System.setFriendlyName",
+                        "endpoint": "system",
+                        "transform": {"request": "{ friendlyName: .value }"}
+                    },
+                    "device.sku": {"alias": "org.rdk.System.getSystemVersions"},
+                    "device.platform": {"alias": "static", "filter": "\"WPE\""}
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let mut request = RpcRequest::mock();
+        request.method = "device.setName".to_owned();
+        request.params_json = RpcRequest::prepend_ctx(Some(json!({"value": "den"})), &request.ctx);
+        let explanation = engine.explain(&request);
+        assert_eq!(explanation.routing, RuleRouting::Brokered);
+        assert_eq!(
+            explanation.alias.as_deref(),
+            Some("org.rdk.System.setFriendlyName")
+        );
+        assert_eq!(explanation.endpoint.as_deref(), Some("system"));
+        assert_eq!(
+            explanation.request_transform.as_deref(),
+            Some("{ friendlyName: .value }")
+        );
+        assert_eq!(
+            explanation.transformed_params,
+            Some(json!({"friendlyName": "den"}))
+        );
+
+        request.method = "device.sku".to_owned();
+        assert_eq!(
+            engine.explain(&request).endpoint.as_deref(),
+            Some("thunder")
+        );
+        request.method = "device.platform".to_owned();
+        let explanation = engine.explain(&request);
+        assert_eq!(explanation.routing, RuleRouting::Static);
+        assert!(explanation.endpoint.is_none());
+        request.method = "device.unknown".to_owned();
+        assert_eq!(engine.explain(&request).routing, RuleRouting::Unmatched);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut engine = RuleEngine::load_from_string_literal(
+            json!({
+                "endpoints": {},
+                "rules": {
+                    "device.*": {"alias": "org.rdk.System.getDeviceInfo"},
+                    "device.name": {
+                        "alias": "org.rdk.System.getFriendlyName",
+                        "endpoint": "system",
+                        "transform": {"request": "{ app: \"$context.appId\" }"},
+                        "allowed_apps": ["app1"]
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        // rules of a later rule file replace earlier ones
+        engine.rules.append(
+            serde_json::from_value(json!({
+                "endpoints": {},
+                "rules": {"Device.Name": {
+                    "alias": "org.rdk.System.getFriendlyName2",
+                    "endpoint": "system2",
+                    "transform": {"request": "{ app: \"$context.appId\" }"},
+                    "allowed_apps": ["app1"]
+                }}
+            }))
+            .unwrap(),
+        );
+
+        let summary = engine.resolve("device.Name", "app1").unwrap();
+        assert_eq!(summary.matched, "device.name");
+        assert_eq!(summary.alias, "org.rdk.System.getFriendlyName2");
+        assert_eq!(summary.endpoint.as_deref(), Some("system2"));
+        assert_eq!(summary.routing, RuleRouting::Brokered);
+        assert_eq!(
+            summary.transform.request.as_deref(),
+            Some("{ app: \"app1\" }")
+        );
+        // other apps may not call the method
+        assert!(engine.resolve("device.name", "app2").is_none());
+
+        let summary = engine.resolve("device.sku", "app2").unwrap();
+        assert_eq!(summary.matched, "device.*");
+        assert_eq!(summary.alias, "org.rdk.System.getDeviceInfo");
+        assert_eq!(summary.endpoint.as_deref(), Some("thunder"));
+        assert!(engine.resolve("account.id", "app1").is_none());
+    }
+
+    #[test]
+    fn test_rule_endpoint_config() {
+        let endpoint: RuleEndpoint = serde_json::from_value(json!({
+            "protocol": "http",
+            "url": "https://example.com",
+            "jsonrpc_batch": 20
+        }))
+        .unwrap();
+        assert!(endpoint.jsonrpc);
+        assert!(RuleEndpoint::default().jsonrpc);
+        assert_eq!(endpoint.jsonrpc_batch, Some(20));
+        assert_eq!(
+            endpoint.get_channel_capacity(),
+            DEFAULT_BROKER_CHANNEL_CAPACITY
+        );
+        assert_eq!(endpoint.get_max_queued(), DEFAULT_MAX_QUEUED_REQUESTS);
+        assert!(endpoint.max_concurrency.is_none());
+
+        let endpoint = RuleEndpointConfig {
+            protocol: RuleEndpointProtocol::Mqtt,
+            url: "mqtt://broker:1883".to_owned(),
+            qos: Some(1),
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        assert_eq!(endpoint.get_qos(), 1);
+
+        for (config, error) in [
+            (
+                json!({"protocol": "thunder", "url": "http://127.0.0.1:9998/jsonrpc"}),
+                "thunder endpoint url http://127.0.0.1:9998/jsonrpc must start with ws:// or wss://",
+            ),
+            (
+                json!({"protocol": "websocket", "url": "ws://host", "jsonrpc_batch": 20}),
+                "jsonrpc_batch only applies to http endpoints, not websocket",
+            ),
+            (
+                json!({"protocol": "http", "url": "http://host", "composite_keys": ["a"]}),
+                "composite_keys only applies to thunder endpoints, not http",
+            ),
+            (
+                json!({"protocol": "thunder", "url": "ws://host", "composite_keys": ["event"]}),
+                "composite key event is not supported, only response is",
+            ),
+            (
+                json!({"protocol": "mqtt", "url": "mqtt://host", "qos": 2}),
+                "qos must be 0 or 1, not 2",
+            ),
+            (
+                json!({"protocol": "http", "url": "http://host", "channel_capacity": 0}),
+                "channel_capacity must be at least 1",
+            ),
+            (
+                json!({"protocol": "http", "url": "http://host", "max_queued": 5}),
+                "max_queued requires max_concurrency",
+            ),
+        ] {
+            let config: RuleEndpointConfig = serde_json::from_value(config).unwrap();
+            assert_eq!(config.build().unwrap_err(), error);
+        }
+        // rule files with an invalid endpoint are rejected
+        assert!(RuleEngine::load_from_content(
+            json!({
+                "endpoints": {"mqtt": {"protocol": "websocket", "url": "ws://host", "qos": 1}},
+                "rules": {}
+            })
+            .to_string()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_config() {
+        let limit: RateLimit =
+            serde_json::from_value(json!({"requests": 5, "window_ms": 1000})).unwrap();
+        assert_eq!(
+            limit,
+            RateLimit {
+                requests: 5,
+                window_ms: 1000
+            }
+        );
+        for (config, error) in [
+            (
+                json!({"requests": 0, "window_ms": 1000}),
+                "rate_limit requests must be greater than 0",
+            ),
+            (
+                json!({"requests": 5, "window_ms": 0}),
+                "rate_limit window_ms must be greater than 0",
+            ),
+        ] {
+            let err = serde_json::from_value::<RateLimit>(config.clone()).unwrap_err();
+            assert_eq!(err.to_string(), error);
+            // rule files with an invalid rate limit are rejected
+            assert!(RuleEngine::load_from_content(
+                json!({
+                    "endpoints": {},
+                    "rules": {"device.name": {"alias": "org.rdk.System.getFriendlyName", "rate_limit": config}}
+                })
+                .to_string()
+            )
+            .is_err());
+        }
+    }
 }
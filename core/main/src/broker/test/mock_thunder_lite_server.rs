@@ -372,7 +372,7 @@ macro_rules! process_broker_output {
             .clone()
             .rule
             .transform
-            .get_transform_data(rules_engine::RuleTransformType::Response)
+            .get_transform_data(rules_engine::RuleTransformType::Response(false))
         {
             apply_response(filter, &rule_context_name, &mut response);
         } else if response.result.is_none() && response.error.is_none() {
@@ -23,6 +23,7 @@ use ripple_sdk::{
     api::gateway::rpc_gateway_api::JsonRpcApiResponse,
     chrono::{DateTime, Duration, Utc},
     log::{error, info, warn},
+    tokio,
     utils::error::RippleError,
 };
 use serde::{Deserialize, Serialize};
@@ -122,6 +123,7 @@ pub struct ThunderPluginState {
 pub struct StatusManager {
     pub status: Arc<RwLock<HashMap<String, ThunderPluginState>>>,
     pub inprogress_plugins_request: Arc<RwLock<HashMap<u64, String>>>,
+    activation_timeout: std::time::Duration,
 }
 
 impl Default for StatusManager {
@@ -135,9 +137,17 @@ impl StatusManager {
         Self {
             status: Arc::new(RwLock::new(HashMap::new())),
             inprogress_plugins_request: Arc::new(RwLock::new(HashMap::new())),
+            activation_timeout: std::time::Duration::from_secs(
+                DEFAULT_PLUGIN_ACTIVATION_TIMEOUT as u64,
+            ),
         }
     }
 
+    pub fn with_activation_timeout(mut self, activation_timeout: std::time::Duration) -> Self {
+        self.activation_timeout = activation_timeout;
+        self
+    }
+
     fn get_controller_call_sign() -> String {
         "Controller.1.".to_string()
     }
@@ -207,6 +217,44 @@ impl StatusManager {
         (Vec::new(), false)
     }
 
+    // Fail the pending request with a plugin unavailable error if the plugin has not
+    // activated within the activation timeout, so the caller is not left waiting forever.
+    pub fn fail_pending_request_on_timeout(
+        &self,
+        plugin_name: String,
+        call_id: u64,
+        callback: BrokerCallback,
+    ) {
+        let status = self.status.clone();
+        let activation_timeout = self.activation_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(activation_timeout).await;
+            let expired = {
+                let mut status = status.write().unwrap();
+                status.get_mut(&plugin_name).and_then(|plugin_state| {
+                    plugin_state
+                        .pending_requests
+                        .iter()
+                        .position(|r| r.rpc.ctx.call_id == call_id)
+                        .map(|index| plugin_state.pending_requests.remove(index))
+                })
+            };
+            if let Some(request) = expired {
+                error!(
+                    "Plugin {} did not activate in {:?}, failing request {}",
+                    plugin_name, activation_timeout, call_id
+                );
+                callback
+                    .send_error(request, Self::plugin_unavailable(&plugin_name))
+                    .await;
+            }
+        });
+    }
+
+    pub fn plugin_unavailable(plugin_name: &str) -> RippleError {
+        RippleError::BrokerError(format!("plugin {} unavailable", plugin_name))
+    }
+
     pub fn get_all_pending_broker_requests(&self, plugin_name: String) -> Vec<BrokerRequest> {
         let status = self.status.read().unwrap();
         if let Some(plugin_state) = status.get(&plugin_name) {
@@ -606,6 +654,33 @@ mod tests {
         assert_eq!(status.unwrap().state, State::Missing);
     }
 
+    #[tokio::test]
+    async fn test_pending_request_fails_after_activation_timeout() {
+        let status_manager =
+            StatusManager::new().with_activation_timeout(std::time::Duration::from_millis(100));
+        let (tx, mut tr) = channel(2);
+        let callback = BrokerCallback { sender: tx };
+        status_manager.update_status("TestPlugin".to_string(), State::Activation);
+
+        let mut request = BrokerRequest::default();
+        request.rpc.ctx.call_id = 42;
+        status_manager.add_broker_request_to_pending_list("TestPlugin".to_string(), request);
+        status_manager.fail_pending_request_on_timeout("TestPlugin".to_string(), 42, callback);
+
+        let output = tokio::time::timeout(std::time::Duration::from_secs(2), tr.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.data.id, Some(42));
+        assert!(output.data.error.unwrap()["message"]
+            .as_str()
+            .unwrap()
+            .contains("plugin TestPlugin unavailable"));
+        assert!(status_manager
+            .get_all_pending_broker_requests("TestPlugin".to_string())
+            .is_empty());
+    }
+
     // Uncomment and use the following unit test only for local testing. Not use as part of the CI/CD pipeline.
     /*
     use ripple_sdk::{
@@ -24,7 +24,10 @@ use std::{
 };
 
 use ripple_sdk::{
-    api::{device::device_peristence::StorageData, gateway::rpc_gateway_api::JsonRpcApiResponse},
+    api::{
+        device::device_peristence::StorageData,
+        gateway::rpc_gateway_api::{JsonRpcApiResponse, RPC_V2},
+    },
     log::{debug, error, info},
     tokio::{
         self,
@@ -746,10 +749,11 @@ impl UserDataMigrator {
         let mut response = output_from_thunder_plugin.clone().data;
         let data_for_callback = response.clone();
 
+        let rpc_v2 = request.rpc.ctx.context.contains(&RPC_V2.into());
         if let Some(filter) = request
             .rule
             .transform
-            .get_transform_data(RuleTransformType::Response)
+            .get_transform_data(RuleTransformType::Response(rpc_v2))
         {
             endpoint_broker::apply_response(filter, &request.rule.alias, &mut response);
         }
@@ -17,7 +17,7 @@
 use super::{
     endpoint_broker::{
         BrokerCallback, BrokerCleaner, BrokerConnectRequest, BrokerOutput, BrokerRequest,
-        BrokerSender, BrokerSubMap, EndpointBroker, EndpointBrokerState,
+        BrokerSender, BrokerSubMap, EndpointBroker, EndpointBrokerState, CLEANUP_ALL_SENTINEL,
     },
     thunder::thunder_plugins_status_mgr::StatusManager,
     thunder::user_data_migrator::UserDataMigrator,
@@ -42,12 +42,16 @@ use serde_json::Value;
 use std::time::SystemTime;
 use std::{
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
     time::Duration,
     vec,
 };
 
 pub const COMPOSITE_REQUEST_TIME_OUT: u64 = 8;
+pub const DEFAULT_COMPOSITE_KEY: &str = "response";
 
 #[derive(Clone)]
 pub struct ThunderBroker {
@@ -60,6 +64,20 @@ pub struct ThunderBroker {
     custom_callback_list: Arc<Mutex<HashMap<u64, BrokerCallback>>>,
     composite_request_list: Arc<Mutex<HashMap<u64, CompositeRequest>>>,
     composite_request_purge_started: Arc<Mutex<bool>>,
+    composite_request_counters: Arc<CompositeRequestCounters>,
+    composite_keys: Vec<String>,
+    upstream_subscriptions: Arc<RwLock<HashMap<String, UpstreamSubscription>>>,
+    #[cfg(test)]
+    disconnect_notify: Arc<tokio::sync::Notify>,
+}
+
+/// A single Thunder event registration shared by every app listening to the same
+/// callsign and event
+#[derive(Debug, Clone)]
+struct UpstreamSubscription {
+    // call id the registration was made with, Thunder prefixes events with it
+    upstream_id: u64,
+    subscribers: Vec<u64>,
 }
 
 #[derive(Clone)]
@@ -68,6 +86,32 @@ pub struct CompositeRequest {
     pub rpc_request: RpcRequest,
 }
 
+/// Running totals of the composite request lifecycle, used to debug response params
+/// which never get applied
+#[derive(Debug, Default)]
+struct CompositeRequestCounters {
+    registered: AtomicU64,
+    unregistered: AtomicU64,
+    purged: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompositeRequestStats {
+    pub registered: u64,
+    pub unregistered: u64,
+    pub purged: u64,
+}
+
+impl CompositeRequestCounters {
+    fn snapshot(&self) -> CompositeRequestStats {
+        CompositeRequestStats {
+            registered: self.registered.load(Ordering::Relaxed),
+            unregistered: self.unregistered.load(Ordering::Relaxed),
+            purged: self.purged.load(Ordering::Relaxed),
+        }
+    }
+}
+
 impl CompositeRequest {
     pub fn new(time_stamp: SystemTime, rpc_request: RpcRequest) -> CompositeRequest {
         CompositeRequest {
@@ -94,9 +138,136 @@ impl ThunderBroker {
             custom_callback_list: Arc::new(Mutex::new(HashMap::new())),
             composite_request_list: Arc::new(Mutex::new(HashMap::new())),
             composite_request_purge_started: Arc::new(Mutex::new(false)),
+            composite_request_counters: Arc::new(CompositeRequestCounters::default()),
+            composite_keys: vec![DEFAULT_COMPOSITE_KEY.to_owned()],
+            upstream_subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(test)]
+            disconnect_notify: Arc::new(tokio::sync::Notify::new()),
         }
     }
 
+    /// Test seam which drops the Thunder connection as if the socket had disconnected
+    #[cfg(test)]
+    pub fn simulate_disconnect(&self) {
+        self.disconnect_notify.notify_one();
+    }
+
+    #[cfg(test)]
+    async fn disconnected(&self) {
+        self.disconnect_notify.notified().await
+    }
+
+    #[cfg(not(test))]
+    async fn disconnected(&self) {
+        std::future::pending().await
+    }
+
+    fn register_request(callsign: &str, event: &str, id: u64) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": format!("{}.register", callsign),
+            "params": {
+                "event": event,
+                "id": format!("{}", id)
+            }
+        })
+        .to_string()
+    }
+
+    fn unregister_request(callsign: &str, event: &str, id: u64) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": format!("{}.unregister", callsign),
+            "params": {
+                "event": event,
+                "id": format!("{}", id)
+            }
+        })
+        .to_string()
+    }
+
+    /// Adds a subscriber to the upstream registration of the event, returning the register
+    /// request only for the first subscriber
+    fn acquire_upstream_subscription(
+        &self,
+        callsign: &str,
+        event: &str,
+        subscriber_id: u64,
+    ) -> Option<String> {
+        let mut upstream_subscriptions = self.upstream_subscriptions.write().unwrap();
+        let subscription = upstream_subscriptions
+            .entry(format!("{}.{}", callsign, event))
+            .or_insert(UpstreamSubscription {
+                upstream_id: subscriber_id,
+                subscribers: Vec::new(),
+            });
+        subscription.subscribers.push(subscriber_id);
+        if subscription.subscribers.len() == 1 {
+            Some(Self::register_request(
+                callsign,
+                event,
+                subscription.upstream_id,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Removes a subscriber from the upstream registration of the event, returning the
+    /// unregister request once the last subscriber is gone
+    fn release_upstream_subscription(
+        &self,
+        callsign: &str,
+        event: &str,
+        subscriber_id: u64,
+    ) -> Option<String> {
+        let key = format!("{}.{}", callsign, event);
+        let mut upstream_subscriptions = self.upstream_subscriptions.write().unwrap();
+        let subscription = upstream_subscriptions.get_mut(&key)?;
+        let index = subscription
+            .subscribers
+            .iter()
+            .position(|id| *id == subscriber_id)?;
+        subscription.subscribers.remove(index);
+        if subscription.subscribers.is_empty() {
+            let upstream_id = subscription.upstream_id;
+            upstream_subscriptions.remove(&key);
+            Some(Self::unregister_request(callsign, event, upstream_id))
+        } else {
+            None
+        }
+    }
+
+    /// Copies an event of a shared upstream registration for every subscriber, other
+    /// messages are returned unchanged
+    fn fan_out_event(&self, result: &[u8]) -> Vec<Vec<u8>> {
+        if let Ok(data) = serde_json::from_slice::<JsonRpcApiResponse>(result) {
+            if let Some((upstream_id, event)) = data.method.as_ref().and_then(|m| m.split_once('.'))
+            {
+                if let Ok(upstream_id) = upstream_id.parse::<u64>() {
+                    let upstream_subscriptions = self.upstream_subscriptions.read().unwrap();
+                    if let Some(subscription) = upstream_subscriptions
+                        .values()
+                        .find(|s| s.upstream_id == upstream_id)
+                    {
+                        return subscription
+                            .subscribers
+                            .iter()
+                            .filter_map(|id| {
+                                let mut event_data = data.clone();
+                                event_data.method = Some(format!("{}.{}", id, event));
+                                serde_json::to_vec(&event_data).ok()
+                            })
+                            .collect();
+                    }
+                }
+            }
+        }
+        vec![result.to_vec()]
+    }
+
     fn with_data_migtator(mut self) -> Self {
         self.data_migrator = UserDataMigrator::create();
         self
@@ -108,8 +279,18 @@ impl ThunderBroker {
 
     pub async fn register_composite_request(&self, id: u64, request: RpcRequest) {
         let mut composite_request_list = self.composite_request_list.lock().await;
+        LogSignal::new(
+            "thunder_broker".to_string(),
+            "composite request registered".to_string(),
+            request.ctx.clone(),
+        )
+        .with_diagnostic_context_item("composite_id", &id.to_string())
+        .emit_debug();
         let composite_req = CompositeRequest::new(SystemTime::now(), request);
         composite_request_list.insert(id, composite_req);
+        self.composite_request_counters
+            .registered
+            .fetch_add(1, Ordering::Relaxed);
         let purge_thread_started = self.composite_request_purge_started.lock().await;
         if *purge_thread_started {
             self.start_purge_composite_request_timer();
@@ -118,7 +299,79 @@ impl ThunderBroker {
 
     pub async fn unregister_composite_request(&self, id: u64) {
         let mut composite_request_list = self.composite_request_list.lock().await;
-        composite_request_list.remove(&id);
+        if let Some(removed) = composite_request_list.remove(&id) {
+            LogSignal::new(
+                "thunder_broker".to_string(),
+                "composite request unregistered".to_string(),
+                removed.rpc_request.ctx,
+            )
+            .with_diagnostic_context_item("composite_id", &id.to_string())
+            .emit_debug();
+            self.composite_request_counters
+                .unregistered
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get_composite_request_stats(&self) -> CompositeRequestStats {
+        self.composite_request_counters.snapshot()
+    }
+
+    /// Removes composite requests older than [COMPOSITE_REQUEST_TIME_OUT] seconds and
+    /// returns how many were purged
+    async fn purge_expired_composite_requests(
+        composite_request_list: &Mutex<HashMap<u64, CompositeRequest>>,
+        counters: &CompositeRequestCounters,
+    ) -> usize {
+        let mut composite_request_list = composite_request_list.lock().await;
+        let mut keys_to_remove = Vec::new();
+        for (key, value) in composite_request_list.iter() {
+            match value.time_stamp.elapsed() {
+                Ok(elapsed) => {
+                    if elapsed.as_secs() > COMPOSITE_REQUEST_TIME_OUT {
+                        keys_to_remove.push(*key);
+                    }
+                }
+                Err(e) => {
+                    error!("Error while calculating elapsed time {:?}", e);
+                }
+            }
+        }
+        // remove request from the list
+        for key in keys_to_remove.iter() {
+            if let Some(removed) = composite_request_list.remove(key) {
+                LogSignal::new(
+                    "thunder_broker".to_string(),
+                    "composite request purged".to_string(),
+                    removed.rpc_request.ctx,
+                )
+                .with_diagnostic_context_item("composite_id", &key.to_string())
+                .emit_debug();
+                counters.purged.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        keys_to_remove.len()
+    }
+
+    /// Collects the composite keys found in the given params_json, `None` when the
+    /// request carries none of them
+    fn get_composite_params(&self, params_json: &str) -> Option<Value> {
+        let params = serde_json::from_str::<Value>(params_json).ok()?;
+        let mut composite_params = serde_json::Map::new();
+        for param in params.as_array()? {
+            if let Some(param) = param.as_object() {
+                for (key, value) in param {
+                    if self.composite_keys.contains(key) {
+                        composite_params.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        if composite_params.is_empty() {
+            None
+        } else {
+            Some(Value::Object(composite_params))
+        }
     }
 
     async fn get_composite_request(&self, id: Option<u64>) -> Option<RpcRequest> {
@@ -153,6 +406,7 @@ impl ThunderBroker {
     // Start a timer to purge individual composite request that are older than 8 seconds
     fn start_purge_composite_request_timer(&self) {
         let composite_request_list = self.composite_request_list.clone();
+        let counters = self.composite_request_counters.clone();
         let mut interval = time::interval(Duration::from_millis(3000));
         let purge_thread_started = self.composite_request_purge_started.clone();
         tokio::spawn(async move {
@@ -161,26 +415,8 @@ impl ThunderBroker {
             // iterate each individual composite request and check if timestamp is greater than 8 seconds
             loop {
                 interval.tick().await;
-                let mut composite_request_list = composite_request_list.lock().await;
-                let mut keys_to_remove = Vec::new();
-                for (key, value) in composite_request_list.iter() {
-                    match value.time_stamp.elapsed() {
-                        Ok(elapsed) => {
-                            if elapsed.as_secs() > COMPOSITE_REQUEST_TIME_OUT {
-                                keys_to_remove.push(*key);
-                            }
-                        }
-                        Err(e) => {
-                            error!("Error while calculating elapsed time {:?}", e);
-                        }
-                    }
-                }
-                // remove request from the list
-                for key in keys_to_remove {
-                    composite_request_list.remove(&key);
-                    debug!("Removed composite request with id {}", key);
-                }
-                if composite_request_list.is_empty() {
+                Self::purge_expired_composite_requests(&composite_request_list, &counters).await;
+                if composite_request_list.lock().await.is_empty() {
                     *purge_thread_started.lock().await = false;
                     debug!("Composite request list is empty, stop timer");
                     break;
@@ -191,7 +427,8 @@ impl ThunderBroker {
 
     fn start(request: BrokerConnectRequest, callback: BrokerCallback) -> Self {
         let endpoint = request.endpoint.clone();
-        let (broker_request_tx, mut broker_request_rx) = mpsc::channel(10);
+        let (broker_request_tx, mut broker_request_rx) =
+            mpsc::channel(endpoint.get_channel_capacity());
         let (c_tx, mut c_tr) = mpsc::channel(2);
         let broker_sender = BrokerSender {
             sender: broker_request_tx,
@@ -200,13 +437,17 @@ impl ThunderBroker {
         let cleaner = BrokerCleaner {
             cleaner: Some(c_tx.clone()),
         };
-        let thunder_broker =
+        let mut thunder_broker =
             Self::new(broker_sender, subscription_map, cleaner, callback).with_data_migtator();
+        if let Some(composite_keys) = &endpoint.composite_keys {
+            thunder_broker.composite_keys = composite_keys.clone();
+        }
         let broker_c = thunder_broker.clone();
         let broker_for_cleanup = thunder_broker.clone();
         let broker_for_reconnect = thunder_broker.clone();
         tokio::spawn(async move {
-            let (ws_tx, mut ws_rx) = BrokerUtils::get_ws_broker(&endpoint.get_url(), None).await;
+            let (ws_tx, mut ws_rx) =
+                BrokerUtils::get_ws_broker(&endpoint.get_url(), None, Some(&request)).await;
 
             let ws_tx_wrap = Arc::new(Mutex::new(ws_tx));
             // send the first request to the broker. This is the controller statechange subscription request
@@ -223,6 +464,8 @@ impl ThunderBroker {
                     .await;
                 let _flush = ws_tx.flush().await;
             }
+            // subscriptions carried over from a previous connection are registered again
+            broker_c.resubscribe();
             tokio::pin! {
                 let read = ws_rx.next();
             }
@@ -245,7 +488,10 @@ impl ThunderBroker {
                                         // send the incoming text without context back to the sender
                                         let id = Self::get_id_from_result(t.as_bytes());
                                         let composite_resp_params = Self::get_composite_response_params_by_id(broker_c.clone(), id).await;
-                                        let _ = Self::handle_jsonrpc_response(t.as_bytes(),broker_c.get_broker_callback(id).await, composite_resp_params);
+                                        let callback = broker_c.get_broker_callback(id).await;
+                                        for result in broker_c.fan_out_event(t.as_bytes()) {
+                                            let _ = Self::handle_jsonrpc_response(&result, callback.clone(), composite_resp_params.clone());
+                                        }
                                     };
                                 }
                             },
@@ -259,91 +505,89 @@ impl ThunderBroker {
                     },
                     Some(mut request) = broker_request_rx.recv() => {
                         debug!("Got request from receiver for broker {:?}", request);
-                        diagnostic_context.lock().await.replace(request.clone());
-
-                        match broker_c.check_and_generate_plugin_activation_request(&request) {
-                            Ok(requests) => {
-                                if !requests.is_empty() {
-                                    let mut ws_tx = ws_tx_wrap.lock().await;
-                                    for r in requests {
-                                        let _feed = ws_tx.feed(tokio_tungstenite::tungstenite::Message::Text(r)).await;
-                                        let _flush = ws_tx.flush().await;
-                                    }
-                                }
-                                else {
-                                    // empty request means plugin is activated and ready to process the request
-                                    // Intercept the request for data migration
-                                    let mut request_consumed = false;
-                                    if let Some(user_data_migrator) = broker_c.data_migrator.clone() {
-                                        request_consumed = user_data_migrator.intercept_broker_request(&broker_c, ws_tx_wrap.clone(), &mut request).await;
+                        // the requests of a batch are written upstream with a single flush
+                        let batch = std::mem::take(&mut request.batch);
+                        let mut outbound = Vec::new();
+                        for mut request in std::iter::once(request).chain(batch) {
+                            diagnostic_context.lock().await.replace(request.clone());
+
+                            match broker_c.check_and_generate_plugin_activation_request(&request) {
+                                Ok(requests) => {
+                                    if !requests.is_empty() {
+                                        outbound.extend(requests);
                                     }
+                                    else {
+                                        // empty request means plugin is activated and ready to process the request
+                                        // Intercept the request for data migration
+                                        let mut request_consumed = false;
+                                        if let Some(user_data_migrator) = broker_c.data_migrator.clone() {
+                                            request_consumed = user_data_migrator.intercept_broker_request(&broker_c, ws_tx_wrap.clone(), &mut request).await;
+                                        }
+
+                                        // If the request is not consumed by the data migrator, continue with the request
+                                        if !request_consumed {
 
-                                    // If the request is not consumed by the data migrator, continue with the request
-                                    if !request_consumed {
-
-                                        match broker_c.prepare_request(&request) {
-                                            Ok(updated_request) => {
-
-                                                LogSignal::new("thunder_broker".to_string(),"sending message to thunder".to_string(), request.rpc.ctx.clone())
-                                                    .with_diagnostic_context_item("updated_request", &format!("{:?}", updated_request))
-                                                    .emit_debug();
-
-                                                // Add composite request to thunder broker; this is for later params_json referencing when response is received
-                                                // response key in params_json is used for response rule transformation.
-                                                if !request.rpc.params_json.is_empty() {
-                                                    let pp: &str = request.rpc.params_json.as_str();
-                                                    let pp_json = &serde_json::from_str::<Value>(pp).unwrap();
-                                                    for pp in pp_json.as_array().unwrap() {
-                                                        for (key, _value) in pp.as_object().unwrap() {
-                                                            if key == "response" {
-                                                                broker_c.register_composite_request(request.rpc.ctx.call_id, request.rpc.clone()).await;
-                                                            }
-                                                        }
+                                            match broker_c.prepare_request(&request) {
+                                                Ok(updated_request) => {
+
+                                                    LogSignal::new("thunder_broker".to_string(),"sending message to thunder".to_string(), request.rpc.ctx.clone())
+                                                        .with_diagnostic_context_item("updated_request", &format!("{:?}", updated_request))
+                                                        .emit_debug();
+
+                                                    // Add composite request to thunder broker; this is for later params_json referencing when response is received
+                                                    // composite keys in params_json (response by default) are used for response rule transformation.
+                                                    if broker_c.get_composite_params(&request.rpc.params_json).is_some() {
+                                                        broker_c.register_composite_request(request.rpc.ctx.call_id, request.rpc.clone()).await;
                                                     }
+                                                    outbound.extend(updated_request);
                                                 }
-                                                let binding = ws_tx_wrap.clone();
-                                                let mut ws_tx = binding.lock().await;
-                                                for r in updated_request {
-                                                    let _ = ws_tx.feed(tokio_tungstenite::tungstenite::Message::Text(r)).await;
-
-                                                    let _ = ws_tx.flush().await;
+                                                Err(e) => {
+                                                    LogSignal::new("thunder_broker".to_string(), "Prepare request failed".to_string(), request.rpc.ctx.clone())
+                                                        .with_diagnostic_context_item("error", &format!("{:?}", e))
+                                                        .emit_error();
+                                                    broker_c.get_default_callback().send_error(request,e).await
                                                 }
                                             }
-                                            Err(e) => {
-                                                LogSignal::new("thunder_broker".to_string(), "Prepare request failed".to_string(), request.rpc.ctx.clone())
-                                                    .with_diagnostic_context_item("error", &format!("{:?}", e))
-                                                    .emit_error();
-                                                broker_c.get_default_callback().send_error(request,e).await
-                                            }
                                         }
                                     }
                                 }
-                            }
-                            Err(e) => {
-                                match e {
-                                    RippleError::ServiceNotReady => {
-                                        info!("Thunder Service not ready, request is now in pending list {:?}", request);
-                                    },
-                                    _ =>
-                                    broker_c.get_default_callback().send_error(request,e).await
+                                Err(e) => {
+                                    match e {
+                                        RippleError::ServiceNotReady => {
+                                            info!("Thunder Service not ready, request is now in pending list {:?}", request);
+                                        },
+                                        _ =>
+                                        broker_c.get_default_callback().send_error(request,e).await
+                                    }
                                 }
                             }
                         }
+                        if !outbound.is_empty() {
+                            let mut ws_tx = ws_tx_wrap.lock().await;
+                            for r in outbound {
+                                let _ = ws_tx.feed(tokio_tungstenite::tungstenite::Message::Text(r)).await;
+                            }
+                            let _ = ws_tx.flush().await;
+                        }
 
                 },
+                    _ = broker_c.disconnected() => {
+                        break;
+                    }
                     Some(cleanup_request) = c_tr.recv() => {
-                        let value = {
-                            broker_for_cleanup.subscription_map.write().unwrap().remove(&cleanup_request)
-                        };
-                        if let Some(mut cleanup) = value {
-                            let sender = broker_for_cleanup.get_sender();
-                            while let Some(mut v) = cleanup.pop() {
-                                v.rpc = v.rpc.get_unsubscribe();
-                                if (sender.send(v).await).is_err() {
-                                    error!("Cleanup Error for {}",&cleanup_request);
-                                }
+                        let mut cleanup = broker_for_cleanup.take_subscriptions(&cleanup_request);
+                        let sender = broker_for_cleanup.get_sender();
+                        while let Some(mut v) = cleanup.pop() {
+                            let (callsign, method) = Self::get_callsign_and_method_from_alias(&v.rule.alias);
+                            if let Some(unregister) = method.and_then(|m| broker_for_cleanup.release_upstream_subscription(&callsign, m, v.rpc.ctx.call_id)) {
+                                let mut ws_tx = ws_tx_wrap.lock().await;
+                                let _ = ws_tx.feed(tokio_tungstenite::tungstenite::Message::Text(unregister)).await;
+                                let _ = ws_tx.flush().await;
+                            }
+                            v.rpc = v.rpc.get_unsubscribe();
+                            if (sender.send(v).await).is_err() {
+                                error!("Cleanup Error for {}",&cleanup_request);
                             }
-
                         }
 
                     }
@@ -354,17 +598,42 @@ impl ThunderBroker {
             // Thunder Disconnected try reconnecting.
             {
                 let mut subs = broker_for_reconnect.subscription_map.write().unwrap();
-                for (k, v) in subs.drain().take(1) {
+                for (k, v) in subs.drain() {
                     let _ = reconnect_request.sub_map.insert(k, v);
                 }
             }
-            if request.reconnector.send(reconnect_request).await.is_err() {
+            if request.send_reconnect(reconnect_request).await.is_err() {
                 error!("Error reconnecting to thunder");
             }
         });
         thunder_broker
     }
 
+    /// Queues every subscription in the subscription map so that it is registered with Thunder
+    /// again, used when the broker starts with the subscriptions of a lost connection
+    fn resubscribe(&self) {
+        let requests: Vec<BrokerRequest> = self
+            .subscription_map
+            .read()
+            .unwrap()
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        if requests.is_empty() {
+            return;
+        }
+        info!("Resubscribing {} thunder subscriptions", requests.len());
+        let sender = self.get_sender();
+        tokio::spawn(async move {
+            for request in requests {
+                if let Err(e) = sender.send(request).await {
+                    error!("Error resubscribing thunder subscription {:?}", e);
+                }
+            }
+        });
+    }
+
     fn update_response(response: &JsonRpcApiResponse, params: Option<Value>) -> JsonRpcApiResponse {
         let mut new_response = response.clone();
         if response.params.is_some() {
@@ -382,20 +651,8 @@ impl ThunderBroker {
     ) -> Option<Value> {
         /* Get composite req params by call_id*/
         let rpc_req = broker.get_composite_request(id).await;
-        let mut new_param: Option<Value> = None;
-        if let Some(request) = rpc_req {
-            let pp: &str = request.params_json.as_str();
-            let pp_json = &serde_json::from_str::<Value>(pp).unwrap();
-
-            // iterate pp_json array and extract object with response key
-            for pp in pp_json.as_array().unwrap() {
-                for (key, value) in pp.as_object().unwrap() {
-                    if key == "response" {
-                        new_param = Some(json!({"response": value}));
-                    }
-                }
-            }
-        }
+        let new_param =
+            rpc_req.and_then(|request| broker.get_composite_params(&request.params_json));
         // remove composite request from list
         if let Some(id) = id {
             broker.unregister_composite_request(id).await;
@@ -409,7 +666,7 @@ impl ThunderBroker {
             .and_then(|data| data.id)
     }
 
-    fn get_callsign_and_method_from_alias(alias: &str) -> (String, Option<&str>) {
+    pub(crate) fn get_callsign_and_method_from_alias(alias: &str) -> (String, Option<&str>) {
         let mut collection: Vec<&str> = alias.split('.').collect();
         let method = collection.pop();
 
@@ -423,6 +680,20 @@ impl ThunderBroker {
         let callsign = collection.join(".");
         (callsign, method)
     }
+    pub fn get_subscription_map(&self) -> Arc<RwLock<BrokerSubMap>> {
+        self.subscription_map.clone()
+    }
+
+    /// Removes the subscriptions of the given session, or of every session for the cleanup sentinel
+    fn take_subscriptions(&self, session_id: &str) -> Vec<BrokerRequest> {
+        let mut sub_map = self.subscription_map.write().unwrap();
+        if session_id == CLEANUP_ALL_SENTINEL {
+            sub_map.drain().flat_map(|(_, v)| v).collect()
+        } else {
+            sub_map.remove(session_id).unwrap_or_default()
+        }
+    }
+
     fn unsubscribe(&self, request: &BrokerRequest) -> Option<BrokerRequest> {
         let mut sub_map = self.subscription_map.write().unwrap();
         trace!(
@@ -491,8 +762,7 @@ impl ThunderBroker {
         let status = match self.status_manager.get_status(callsign.clone()) {
             Some(v) => v.clone(),
             None => {
-                self.status_manager
-                    .add_broker_request_to_pending_list(callsign.clone(), rpc_request.clone());
+                self.add_to_pending_list(callsign.clone(), rpc_request);
                 // PluginState is not available with StateManager,  create an internal thunder request to activate the plugin
                 let request = self
                     .status_manager
@@ -504,7 +774,7 @@ impl ThunderBroker {
 
         if status.state.is_missing() {
             error!("Plugin {} is missing", callsign);
-            return Err(RippleError::ServiceError);
+            return Err(StatusManager::plugin_unavailable(&callsign));
         }
 
         if status.state.is_activating() {
@@ -512,15 +782,13 @@ impl ThunderBroker {
                 "Plugin {} is activating Adding broker request to pending list",
                 callsign
             );
-            self.status_manager
-                .add_broker_request_to_pending_list(callsign.clone(), rpc_request.clone());
+            self.add_to_pending_list(callsign.clone(), rpc_request);
             return Err(RippleError::ServiceNotReady);
         }
 
         if !status.state.is_activated() {
             // add the broker request to pending list
-            self.status_manager
-                .add_broker_request_to_pending_list(callsign.clone(), rpc_request.clone());
+            self.add_to_pending_list(callsign.clone(), rpc_request);
             // create an internal thunder request to activate the plugin
             let request = self
                 .status_manager
@@ -530,6 +798,17 @@ impl ThunderBroker {
         }
         Ok(requests)
     }
+
+    // Park the request until the plugin activates, failing it if activation does not complete in time
+    fn add_to_pending_list(&self, callsign: String, rpc_request: &BrokerRequest) {
+        self.status_manager
+            .add_broker_request_to_pending_list(callsign.clone(), rpc_request.clone());
+        self.status_manager.fail_pending_request_on_timeout(
+            callsign,
+            rpc_request.rpc.ctx.call_id,
+            self.get_default_callback(),
+        );
+    }
 }
 
 impl EndpointBroker for ThunderBroker {
@@ -564,56 +843,44 @@ impl EndpointBroker for ThunderBroker {
         // the JsonRpc specification
         if rpc_request.rpc.is_subscription() && !rpc_request.rpc.is_unlisten() {
             let listen = rpc_request.rpc.is_listening();
-            // If there was an existing app and method combo for the same subscription just unregister that
+            // If there was an existing app and method combo for the same subscription release it,
+            // Thunder is only unregistered once no other app listens to the event
             if let Some(cleanup) = self.subscribe(rpc_request) {
-                requests.push(
-                    json!({
-                        "jsonrpc": "2.0",
-                        "id": cleanup.rpc.ctx.call_id,
-                        "method": format!("{}.unregister", callsign),
-                        "params": {
-                            "event": method,
-                            "id": format!("{}", cleanup.rpc.ctx.call_id)
-                        }
-                    })
-                    .to_string(),
-                )
+                if let Some(unregister) =
+                    self.release_upstream_subscription(&callsign, method, cleanup.rpc.ctx.call_id)
+                {
+                    requests.push(unregister)
+                }
             }
 
-            // Given unregistration is already performed by previous step just do registration
             if listen {
-                requests.push(
-                    json!({
-                        "jsonrpc": "2.0",
-                        "id": id,
-                        "method": format!("{}.register", callsign),
-                        "params": json!({
-                            "event": method,
-                            "id": format!("{}", id)
-                        })
-                    })
-                    .to_string(),
-                )
+                if let Some(register) = self.acquire_upstream_subscription(&callsign, method, id) {
+                    requests.push(register)
+                } else {
+                    // Thunder is already registered for another app, acknowledge the listen request here
+                    Self::send_broker_success_response(
+                        &self.default_callback,
+                        JsonRpcApiResponse {
+                            jsonrpc: "2.0".to_owned(),
+                            id: Some(id),
+                            result: Some(json!(0)),
+                            ..Default::default()
+                        },
+                    );
+                }
             }
         } else if rpc_request.rpc.is_unlisten() {
             if let Some(cleanup) = self.unsubscribe(rpc_request) {
                 trace!(
-                    "Unregistering thunder listener for call_id {} and method {}",
+                    "Releasing thunder listener for call_id {} and method {}",
                     cleanup.rpc.ctx.call_id,
                     method
                 );
-                requests.push(
-                    json!({
-                        "jsonrpc": "2.0",
-                        "id": cleanup.rpc.ctx.call_id,
-                        "method": format!("{}.unregister", callsign),
-                        "params": {
-                            "event": method,
-                            "id": format!("{}", cleanup.rpc.ctx.call_id)
-                        }
-                    })
-                    .to_string(),
-                )
+                if let Some(unregister) =
+                    self.release_upstream_subscription(&callsign, method, cleanup.rpc.ctx.call_id)
+                {
+                    requests.push(unregister)
+                }
             }
         } else {
             // Simple request and response handling
@@ -653,7 +920,7 @@ mod tests {
                 apply_response, apply_rule_for_event, BrokerCallback, BrokerConnectRequest,
                 BrokerOutput, BrokerRequest, EndpointBroker,
             },
-            rules_engine::{self, Rule, RuleEndpoint, RuleEndpointProtocol, RuleTransform},
+            rules_engine::{self, Rule, RuleEndpointConfig, RuleEndpointProtocol, RuleTransform},
             test::mock_thunder_lite_server::MockThunderLiteServer,
         },
         create_and_send_broker_request, create_and_send_broker_request_with_jq_transform,
@@ -666,17 +933,20 @@ mod tests {
         uuid::Uuid,
     };
     use serde_json::json;
-    use std::time::Duration;
+    use std::{collections::HashSet, time::Duration};
     use tokio::sync::mpsc;
 
     #[macro_export]
     macro_rules! setup_thunder_broker {
         ($server_handle:expr) => {{
-            let endpoint = RuleEndpoint {
+            let endpoint = RuleEndpointConfig {
                 protocol: RuleEndpointProtocol::Thunder,
                 url: $server_handle.get_address(),
                 jsonrpc: true,
-            };
+                ..Default::default()
+            }
+            .build()
+            .unwrap();
             let (reconnect_tx, _rec_rx) = mpsc::channel(2);
 
             let request = BrokerConnectRequest::new("thunder".to_owned(), endpoint, reconnect_tx);
@@ -756,11 +1026,14 @@ mod tests {
         // setup mock websocket server
         let port = MockWebsocket::start(send_data, Vec::new(), tx, on_close).await;
 
-        let endpoint = RuleEndpoint {
+        let endpoint = RuleEndpointConfig {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
-        };
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
         let (tx, _) = mpsc::channel(1);
         let request = BrokerConnectRequest::new("somekey".to_owned(), endpoint, tx);
         let callback = BrokerCallback { sender };
@@ -786,13 +1059,242 @@ mod tests {
                 filter: event_filter,
                 event_handler: event_handler_fn,
                 sources: None,
+                inject_latency_ms: None,
+                fallback: None,
+                allowed_apps: None,
+                event_transform_failure: None,
+                capabilities: None,
+                subscription_filter: None,
+                workflow_session_delivery: None,
+                response_schema: None,
+                provider_fallback: None,
+                rate_limit: None,
+                notification_passthrough: None,
+                passthrough: None,
             },
             subscription_processed: None,
             workflow_callback: None,
             telemetry_response_listeners: vec![],
+            batch: Vec::new(),
+            transformed_params: None,
         }
     }
 
+    #[tokio::test]
+    async fn test_shared_upstream_subscription() {
+        let server_handle = setup_and_start_mock_thunder_lite_server!();
+        let (thunder_broker, mut rx) = setup_thunder_broker!(server_handle);
+        let listen_request = |listen: bool, call_id: u64, session_id: &str| {
+            let mut request = create_mock_broker_request(
+                "device.onNameChanged",
+                "org.rdk.System.onFriendlyNameChanged",
+                Some(json!({ "listen": listen })),
+                None,
+                None,
+                None,
+            );
+            request.rpc.ctx.call_id = call_id;
+            request.rpc.ctx.session_id = session_id.to_owned();
+            request
+        };
+        let methods = |requests: Vec<String>| -> Vec<String> {
+            requests
+                .iter()
+                .map(|r| {
+                    serde_json::from_str::<Value>(r).unwrap()["method"]
+                        .as_str()
+                        .unwrap()
+                        .to_owned()
+                })
+                .collect()
+        };
+
+        // first subscriber registers with thunder, the second one shares the registration
+        let requests = thunder_broker
+            .prepare_request(&listen_request(true, 101, "app1"))
+            .unwrap();
+        assert_eq!(methods(requests), vec!["org.rdk.System.register"]);
+        let requests = thunder_broker
+            .prepare_request(&listen_request(true, 102, "app2"))
+            .unwrap();
+        assert!(requests.is_empty());
+        let ack = test_read_single_response(&mut rx).await.unwrap();
+        assert_eq!(ack.data.id, Some(102));
+
+        // events of the shared registration reach both apps
+        let event = json!({
+            "jsonrpc": "2.0",
+            "method": "101.onFriendlyNameChanged",
+            "params": {"friendlyName": "ripple"}
+        })
+        .to_string();
+        let fanned_out: Vec<Option<String>> = thunder_broker
+            .fan_out_event(event.as_bytes())
+            .iter()
+            .map(|e| {
+                serde_json::from_slice::<JsonRpcApiResponse>(e)
+                    .unwrap()
+                    .method
+            })
+            .collect();
+        assert_eq!(
+            fanned_out,
+            vec![
+                Some("101.onFriendlyNameChanged".to_owned()),
+                Some("102.onFriendlyNameChanged".to_owned())
+            ]
+        );
+
+        // the registration stays alive until the last subscriber unlistens
+        let requests = thunder_broker
+            .prepare_request(&listen_request(false, 103, "app1"))
+            .unwrap();
+        assert!(requests.is_empty());
+        let requests = thunder_broker
+            .prepare_request(&listen_request(false, 104, "app2"))
+            .unwrap();
+        assert_eq!(requests.len(), 1);
+        let unregister = serde_json::from_str::<Value>(&requests[0]).unwrap();
+        assert_eq!(unregister["method"], "org.rdk.System.unregister");
+        assert_eq!(unregister["params"]["id"], "101");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_preserves_subscriptions() {
+        let server_handle = setup_and_start_mock_thunder_lite_server!();
+        let endpoint = RuleEndpointConfig {
+            protocol: RuleEndpointProtocol::Thunder,
+            url: server_handle.get_address(),
+            jsonrpc: true,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (reconnect_tx, mut reconnect_rx) = mpsc::channel(2);
+        let request = BrokerConnectRequest::new("thunder".to_owned(), endpoint, reconnect_tx);
+        let (tx, _rx) = mpsc::channel(16);
+        let thunder_broker = ThunderBroker::get_broker(
+            None,
+            request,
+            BrokerCallback { sender: tx },
+            &mut EndpointBrokerState::default(),
+        );
+
+        let expected: HashMap<String, Vec<u64>> = HashMap::from([
+            ("app1".to_owned(), vec![1, 2]),
+            ("app2".to_owned(), vec![3]),
+        ]);
+        {
+            let mut subs = thunder_broker.subscription_map.write().unwrap();
+            for (app, call_ids) in expected.iter() {
+                let requests = call_ids
+                    .iter()
+                    .map(|call_id| {
+                        let mut request = create_mock_broker_request(
+                            "module.onEvent",
+                            "org.rdk.mock_plugin.onEvent",
+                            Some(json!({"listen": true})),
+                            None,
+                            None,
+                            None,
+                        );
+                        request.rpc.ctx.call_id = *call_id;
+                        request
+                    })
+                    .collect();
+                subs.insert(app.clone(), requests);
+            }
+        }
+
+        thunder_broker.simulate_disconnect();
+        let reconnect_request = tokio::time::timeout(Duration::from_secs(2), reconnect_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let sub_map: HashMap<String, Vec<u64>> = reconnect_request
+            .sub_map
+            .iter()
+            .map(|(app, requests)| {
+                (
+                    app.clone(),
+                    requests.iter().map(|r| r.rpc.ctx.call_id).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(sub_map, expected);
+        assert_eq!(reconnect_request.key, "thunder");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_resubscribes_upstream() {
+        let server_handle = setup_and_start_mock_thunder_lite_server!();
+        let endpoint = RuleEndpointConfig {
+            protocol: RuleEndpointProtocol::Thunder,
+            url: server_handle.get_address(),
+            jsonrpc: true,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (reconnect_tx, _reconnect_rx) = mpsc::channel(2);
+        let mut request = BrokerConnectRequest::new("thunder".to_owned(), endpoint, reconnect_tx);
+        let events = [
+            ("app1", 1, "device.onNameChanged", "onFriendlyNameChanged"),
+            ("app1", 2, "device.onHdcpChanged", "onHdcpChanged"),
+            ("app2", 3, "device.onNetworkChanged", "onNetworkChanged"),
+        ];
+        for (app, call_id, method, event) in events {
+            let mut sub = create_mock_broker_request(
+                method,
+                &format!("org.rdk.System.{}", event),
+                Some(json!({"listen": true})),
+                None,
+                None,
+                None,
+            );
+            sub.rpc.ctx.call_id = call_id;
+            sub.rpc.ctx.session_id = app.to_owned();
+            request.sub_map.entry(app.to_owned()).or_default().push(sub);
+        }
+        let (tx, _rx) = mpsc::channel(16);
+        let thunder_broker = ThunderBroker::get_broker(
+            None,
+            request,
+            BrokerCallback { sender: tx },
+            &mut EndpointBrokerState::default(),
+        );
+
+        let registered = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let registered: HashSet<String> = thunder_broker
+                    .upstream_subscriptions
+                    .read()
+                    .unwrap()
+                    .keys()
+                    .cloned()
+                    .collect();
+                if registered.len() == events.len() {
+                    return registered;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            registered,
+            HashSet::from([
+                "org.rdk.System.onFriendlyNameChanged".to_owned(),
+                "org.rdk.System.onHdcpChanged".to_owned(),
+                "org.rdk.System.onNetworkChanged".to_owned(),
+            ])
+        );
+        // the subscriptions stay in the map and are not duplicated
+        let subs = thunder_broker.subscription_map.read().unwrap();
+        assert_eq!(subs.get("app1").map(|v| v.len()), Some(2));
+        assert_eq!(subs.get("app2").map(|v| v.len()), Some(1));
+    }
+
     #[tokio::test]
     async fn test_thunder_brokerage() {
         // Set up and start the mock thunder lite server
@@ -882,6 +1384,7 @@ mod tests {
             response:Some("if .result and .result.success then (.result.value | fromjson | .value) else \"none\" end".to_string()), 
             event: Some("(.value | fromjson | .value)".to_string()),
             rpcv2_event: None,
+            rpcv2_response: None,
             event_decorator_method: None
         };
 
@@ -909,6 +1412,7 @@ mod tests {
             response:Some("if .result and .result.success then null else { error: { code: -32100, message: \"couldn't set skip restriction\" }} end".to_string()), 
             event: None,
             rpcv2_event: None,
+            rpcv2_response: None,
             event_decorator_method: None
         };
 
@@ -1013,6 +1517,18 @@ mod tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    inject_latency_ms: None,
+                    fallback: None,
+                    allowed_apps: None,
+                    event_transform_failure: None,
+                    capabilities: None,
+                    subscription_filter: None,
+                    workflow_session_delivery: None,
+                    response_schema: None,
+                    provider_fallback: None,
+                    rate_limit: None,
+                    notification_passthrough: None,
+                    passthrough: None,
                 },
                 subscription_processed: Some(false),
                 workflow_callback: None,
@@ -1070,6 +1586,18 @@ mod tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    inject_latency_ms: None,
+                    fallback: None,
+                    allowed_apps: None,
+                    event_transform_failure: None,
+                    capabilities: None,
+                    subscription_filter: None,
+                    workflow_session_delivery: None,
+                    response_schema: None,
+                    provider_fallback: None,
+                    rate_limit: None,
+                    notification_passthrough: None,
+                    passthrough: None,
                 },
                 subscription_processed: Some(true),
                 workflow_callback: None,
@@ -1185,6 +1713,94 @@ mod tests {
         assert_eq!(composite_request_list.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_composite_request_lifecycle_stats() {
+        let server_handle = setup_and_start_mock_thunder_lite_server!();
+        let (thunder_broker, _) = setup_thunder_broker!(server_handle);
+        let broker_request = create_mock_broker_request(
+            "FireboltModuleName.testGetter",
+            "org.rdk.mock_plugin.getter",
+            None,
+            None,
+            None,
+            None,
+        );
+
+        thunder_broker
+            .register_composite_request(1, broker_request.rpc.clone())
+            .await;
+        thunder_broker
+            .register_composite_request(2, broker_request.rpc.clone())
+            .await;
+        thunder_broker.unregister_composite_request(1).await;
+        // unknown ids are not counted
+        thunder_broker.unregister_composite_request(3).await;
+
+        // age the remaining request past the timeout
+        thunder_broker
+            .composite_request_list
+            .lock()
+            .await
+            .get_mut(&2)
+            .unwrap()
+            .time_stamp = SystemTime::now() - Duration::from_secs(COMPOSITE_REQUEST_TIME_OUT + 1);
+        let purged = ThunderBroker::purge_expired_composite_requests(
+            &thunder_broker.composite_request_list,
+            &thunder_broker.composite_request_counters,
+        )
+        .await;
+        assert_eq!(purged, 1);
+        assert!(thunder_broker
+            .composite_request_list
+            .lock()
+            .await
+            .is_empty());
+        assert_eq!(
+            thunder_broker.get_composite_request_stats(),
+            CompositeRequestStats {
+                registered: 2,
+                unregistered: 1,
+                purged: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configured_composite_keys() {
+        let server_handle = setup_and_start_mock_thunder_lite_server!();
+        let (mut thunder_broker, _) = setup_thunder_broker!(server_handle);
+        let mut broker_request = create_mock_broker_request(
+            "FireboltModuleName.testGetter",
+            "org.rdk.mock_plugin.getter",
+            None,
+            None,
+            None,
+            None,
+        );
+        broker_request.rpc.params_json = json!([{}, {"event": ".value"}]).to_string();
+        assert!(thunder_broker
+            .get_composite_params(&broker_request.rpc.params_json)
+            .is_none());
+
+        thunder_broker.composite_keys = vec!["event".to_owned()];
+        assert_eq!(
+            thunder_broker.get_composite_params(&broker_request.rpc.params_json),
+            Some(json!({"event": ".value"}))
+        );
+        thunder_broker
+            .register_composite_request(1, broker_request.rpc.clone())
+            .await;
+        let params =
+            ThunderBroker::get_composite_response_params_by_id(thunder_broker.clone(), Some(1))
+                .await;
+        assert_eq!(params, Some(json!({"event": ".value"})));
+        assert!(thunder_broker
+            .composite_request_list
+            .lock()
+            .await
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_start_purge_composite_request_timer() {
         let server_handle = setup_and_start_mock_thunder_lite_server!();
@@ -1290,4 +1906,27 @@ mod tests {
         // let _ = sub_map.insert(app_id.clone(), existing_requests);
         assert_eq!(subscription_map.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_take_all_subscriptions() {
+        let server_handle = setup_and_start_mock_thunder_lite_server!();
+        let (thunder_broker, _) = setup_thunder_broker!(server_handle);
+        for session_id in ["app1", "app2"] {
+            let mut request = create_mock_broker_request(
+                "FireboltModuleName.onEvent",
+                "org.rdk.mock_plugin.onValueChanged",
+                Some(json!({"listen": true})),
+                None,
+                None,
+                None,
+            );
+            request.rpc.ctx.session_id = session_id.to_owned();
+            thunder_broker.subscribe(&request);
+        }
+        assert_eq!(thunder_broker.subscription_map.read().unwrap().len(), 2);
+
+        let taken = thunder_broker.take_subscriptions(CLEANUP_ALL_SENTINEL);
+        assert_eq!(taken.len(), 2);
+        assert!(thunder_broker.subscription_map.read().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,323 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use crate::broker::broker_utils::BrokerUtils;
+
+use super::endpoint_broker::{
+    BrokerCallback, BrokerCleaner, BrokerConnectRequest, BrokerRequest, BrokerSender, BrokerSubMap,
+    EndpointBroker, CLEANUP_ALL_SENTINEL,
+};
+use crate::broker::endpoint_broker::EndpointBrokerState;
+use crate::state::platform_state::PlatformState;
+use ripple_sdk::{
+    api::observability::log_signal::LogSignal,
+    log::{debug, error},
+    tokio::{self, io::AsyncWriteExt, net::unix::OwnedWriteHalf, sync::mpsc},
+    utils::error::RippleError,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Broker for services exposing JSON-RPC over a unix domain socket.
+/// Requests and responses are framed as newline delimited JSON.
+pub struct UnixSocketBroker {
+    sender: BrokerSender,
+    cleaner: BrokerCleaner,
+}
+
+impl UnixSocketBroker {
+    fn start(request: BrokerConnectRequest, callback: BrokerCallback) -> Self {
+        let endpoint = request.endpoint.clone();
+        let (tx, mut tr) = mpsc::channel::<BrokerRequest>(endpoint.get_channel_capacity());
+        let (cleaner_tx, mut cleaner_tr) = mpsc::channel::<String>(2);
+        let broker = BrokerSender { sender: tx };
+        tokio::spawn(async move {
+            let (mut lines, mut write) =
+                BrokerUtils::get_unix_broker(&endpoint.get_url(), Some(&request)).await;
+            // requests written to the socket which are still waiting for their response
+            let mut pending: HashMap<u64, BrokerRequest> = HashMap::new();
+            // subscriptions per session, registered again when the socket reconnects
+            let mut subscriptions: BrokerSubMap = request.sub_map.clone();
+            let carried_over: Vec<BrokerRequest> =
+                subscriptions.values().flatten().cloned().collect();
+            let mut connected = true;
+            for request in carried_over {
+                if let Err(RippleError::SendFailure) =
+                    Self::write_request(&mut write, &request).await
+                {
+                    connected = false;
+                    break;
+                }
+            }
+            while connected {
+                tokio::select! {
+                    line = lines.next_line() => {
+                        match line {
+                            Ok(Some(l)) => {
+                                if let Some(id) = Self::get_response_id(&l) {
+                                    pending.remove(&id);
+                                }
+                                if let Err(e) = Self::handle_jsonrpc_response(l.as_bytes(), callback.clone(), None) {
+                                    error!("error forwarding {}", e);
+                                }
+                            }
+                            Ok(None) => {
+                                error!("Broker unix socket closed");
+                                connected = false;
+                            }
+                            Err(e) => {
+                                error!("Broker unix socket error on read {:?}", e);
+                                connected = false;
+                            }
+                        }
+                    },
+                    Some(request) = tr.recv() => {
+                        LogSignal::new(
+                            "unix_socket_broker".to_string(),
+                            format!("Got request from receiver for broker: {:?}", request),
+                            request.rpc.ctx.clone(),
+                        )
+                        .emit_debug();
+                        if let Err(e) = Self::write_request(&mut write, &request).await {
+                            connected = e != RippleError::SendFailure;
+                            callback.send_error(request, e).await;
+                        } else if request.rpc.is_subscription() {
+                            let session_subscriptions = subscriptions.entry(request.get_id()).or_default();
+                            session_subscriptions.retain(|s| s.rpc.ctx.method != request.rpc.ctx.method);
+                            if request.rpc.is_listening() {
+                                session_subscriptions.push(request);
+                            }
+                        } else {
+                            pending.insert(request.rpc.ctx.call_id, request);
+                        }
+                    },
+                    Some(session_id) = cleaner_tr.recv() => {
+                        let cleanup: Vec<BrokerRequest> = if session_id == CLEANUP_ALL_SENTINEL {
+                            subscriptions.drain().flat_map(|(_, requests)| requests).collect()
+                        } else {
+                            subscriptions.remove(&session_id).unwrap_or_default()
+                        };
+                        for mut subscription in cleanup {
+                            debug!("unsubscribing {} of {}", subscription.rpc.ctx.method, session_id);
+                            subscription.rpc = subscription.rpc.get_unsubscribe();
+                            if let Err(RippleError::SendFailure) = Self::write_request(&mut write, &subscription).await {
+                                connected = false;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+
+            // requests sent on the lost connection will never get their response
+            for (_, request) in pending.drain() {
+                callback.send_error(request, RippleError::NoResponse).await;
+            }
+            let mut reconnect_request = request.clone();
+            reconnect_request.sub_map = subscriptions;
+            if request.send_reconnect(reconnect_request).await.is_err() {
+                error!("Error reconnecting to unix socket {}", endpoint.get_url());
+            }
+        });
+
+        Self {
+            sender: broker,
+            cleaner: BrokerCleaner {
+                cleaner: Some(cleaner_tx),
+            },
+        }
+    }
+
+    /// Writes the request as a single frame, fails with [RippleError::SendFailure] once the
+    /// socket is broken
+    async fn write_request(
+        write: &mut OwnedWriteHalf,
+        request: &BrokerRequest,
+    ) -> Result<(), RippleError> {
+        let frame = format!("{}\n", Self::update_request(request)?);
+        write.write_all(frame.as_bytes()).await.map_err(|e| {
+            error!("Broker unix socket error on write {:?}", e);
+            RippleError::SendFailure
+        })
+    }
+
+    fn get_response_id(line: &str) -> Option<u64> {
+        serde_json::from_str::<Value>(line)
+            .ok()?
+            .get("id")
+            .and_then(Value::as_u64)
+    }
+}
+
+impl EndpointBroker for UnixSocketBroker {
+    fn get_broker(
+        _ps: Option<PlatformState>,
+        request: BrokerConnectRequest,
+        callback: BrokerCallback,
+        _broker_state: &mut EndpointBrokerState,
+    ) -> Self {
+        Self::start(request, callback)
+    }
+
+    fn get_sender(&self) -> BrokerSender {
+        self.sender.clone()
+    }
+
+    fn get_cleaner(&self) -> BrokerCleaner {
+        self.cleaner.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::broker::rules_engine::{Rule, RuleEndpointConfig, RuleEndpointProtocol};
+    use ripple_sdk::{
+        api::gateway::rpc_gateway_api::RpcRequest,
+        tokio::{
+            io::{AsyncBufReadExt, BufReader},
+            net::UnixListener,
+        },
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Starts a unix socket server which echoes the params of each request as its result
+    fn start_echo_server(path: &str) {
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": request["params"]
+                });
+                let frame = format!("{}\n", response);
+                write.write_all(frame.as_bytes()).await.unwrap();
+            }
+        });
+    }
+
+    fn socket_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!(
+                "ripple_unix_broker_{}_{}.sock",
+                name,
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn broker_request(call_id: u64) -> BrokerRequest {
+        let mut rpc = RpcRequest::get_new_internal("some_method".to_owned(), None);
+        rpc.params_json = json!([{}, {"key": "value"}]).to_string();
+        rpc.ctx.call_id = call_id;
+        BrokerRequest {
+            rpc,
+            rule: Rule {
+                alias: "some.method".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn unix_socket_fails_pending_and_reconnects() {
+        let path = socket_path("reconnect");
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        // the server reads a single request and closes the connection without answering
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, _write) = stream.into_split();
+            let _ = BufReader::new(read).lines().next_line().await;
+        });
+
+        let endpoint = RuleEndpointConfig {
+            url: format!("unix://{}", path),
+            protocol: RuleEndpointProtocol::UnixSocket,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (reconnect_tx, mut reconnect_rx) = mpsc::channel(1);
+        let request = BrokerConnectRequest::new("somekey".to_owned(), endpoint, reconnect_tx);
+        let (sender, mut rec) = mpsc::channel(1);
+        let broker = UnixSocketBroker::start(request, BrokerCallback { sender });
+        broker
+            .get_sender()
+            .sender
+            .send(broker_request(11))
+            .await
+            .unwrap();
+
+        let v = tokio::time::timeout(Duration::from_secs(2), rec.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(v.data.id, Some(11));
+        assert_eq!(
+            v.data.error.unwrap()["code"],
+            RippleError::NoResponse.to_jsonrpc_code()
+        );
+        let reconnect = tokio::time::timeout(Duration::from_secs(2), reconnect_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reconnect.key, "somekey");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn unix_socket_round_trip() {
+        let path = socket_path("round_trip");
+        start_echo_server(&path);
+
+        let endpoint = RuleEndpointConfig {
+            url: format!("unix://{}", path),
+            protocol: RuleEndpointProtocol::UnixSocket,
+            jsonrpc: true,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (tx, _) = mpsc::channel(1);
+        let request = BrokerConnectRequest::new("somekey".to_owned(), endpoint, tx);
+        let (sender, mut rec) = mpsc::channel(1);
+        let broker = UnixSocketBroker::start(request, BrokerCallback { sender });
+
+        let request = broker_request(10);
+        broker.get_sender().sender.send(request).await.unwrap();
+
+        let v = tokio::time::timeout(Duration::from_secs(2), rec.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(v.data.id, Some(10));
+        assert_eq!(v.data.result.unwrap()["key"], "value");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -19,7 +19,7 @@ use crate::broker::broker_utils::BrokerUtils;
 
 use super::endpoint_broker::{
     BrokerCallback, BrokerCleaner, BrokerConnectRequest, BrokerOutputForwarder, BrokerRequest,
-    BrokerSender, EndpointBroker,
+    BrokerSender, EndpointBroker, CLEANUP_ALL_SENTINEL,
 };
 use crate::broker::endpoint_broker::EndpointBrokerState;
 use crate::state::platform_state::PlatformState;
@@ -41,7 +41,7 @@ pub struct WebsocketBroker {
 impl WebsocketBroker {
     fn start(request: BrokerConnectRequest, callback: BrokerCallback) -> Self {
         let endpoint = request.endpoint.clone();
-        let (tx, mut tr) = mpsc::channel(10);
+        let (tx, mut tr) = mpsc::channel(endpoint.get_channel_capacity());
         let (cleaner_tx, mut cleaner_tr) = mpsc::channel::<String>(1);
         let non_json_rpc_map: Arc<RwLock<HashMap<String, Vec<mpsc::Sender<String>>>>> =
             Arc::new(RwLock::new(HashMap::new()));
@@ -50,7 +50,7 @@ impl WebsocketBroker {
         tokio::spawn(async move {
             if endpoint.jsonrpc {
                 let (mut ws_tx, mut ws_rx) =
-                    BrokerUtils::get_ws_broker(&endpoint.get_url(), None).await;
+                    BrokerUtils::get_ws_broker(&endpoint.get_url(), None, Some(&request)).await;
 
                 tokio::pin! {
                     let read = ws_rx.next();
@@ -103,8 +103,14 @@ impl WebsocketBroker {
                 tokio::spawn(async move {
                     while let Some(v) = cleaner_tr.recv().await {
                         {
-                            if let Some(cleaner_list) =
-                                { cleaner_clone.write().unwrap().remove(&v) }
+                            let cleaner_list: Vec<_> = {
+                                let mut map = cleaner_clone.write().unwrap();
+                                if v == CLEANUP_ALL_SENTINEL {
+                                    map.drain().flat_map(|(_, senders)| senders).collect()
+                                } else {
+                                    map.remove(&v).unwrap_or_default()
+                                }
+                            };
                             {
                                 for sender in cleaner_list {
                                     if sender.try_send(v.clone()).is_err() {
@@ -157,7 +163,7 @@ impl WSNotificationBroker {
             let app_id = request_c.get_id();
             let alias = request_c.rule.alias.clone();
             let (mut ws_tx, mut ws_rx) =
-                BrokerUtils::get_ws_broker(&url, Some(alias.clone())).await;
+                BrokerUtils::get_ws_broker(&url, Some(alias.clone()), None).await;
 
             tokio::pin! {
                 let read = ws_rx.next();
@@ -231,7 +237,7 @@ mod tests {
     use crate::{
         broker::{
             endpoint_broker::{BrokerOutput, BrokerRequest},
-            rules_engine::{Rule, RuleEndpoint, RuleTransform},
+            rules_engine::{Rule, RuleEndpointConfig, RuleTransform},
         },
         utils::test_utils::{MockWebsocket, WSMockData},
     };
@@ -249,11 +255,14 @@ mod tests {
         // setup mock websocket server
         let port = MockWebsocket::start(send_data, Vec::new(), tx, on_close).await;
 
-        let endpoint = RuleEndpoint {
+        let endpoint = RuleEndpointConfig {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
-        };
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
         let (tx, _) = mpsc::channel(1);
         let request = BrokerConnectRequest::new("somekey".to_owned(), endpoint, tx);
         let callback = BrokerCallback { sender };
@@ -278,10 +287,24 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                inject_latency_ms: None,
+                fallback: None,
+                allowed_apps: None,
+                event_transform_failure: None,
+                capabilities: None,
+                subscription_filter: None,
+                workflow_session_delivery: None,
+                response_schema: None,
+                provider_fallback: None,
+                rate_limit: None,
+                notification_passthrough: None,
+                passthrough: None,
             },
             workflow_callback: None,
             subscription_processed: None,
             telemetry_response_listeners: vec![],
+            batch: Vec::new(),
+            transformed_params: None,
         };
 
         broker.sender.send(request).await.unwrap();
@@ -325,10 +348,24 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                inject_latency_ms: None,
+                fallback: None,
+                allowed_apps: None,
+                event_transform_failure: None,
+                capabilities: None,
+                subscription_filter: None,
+                workflow_session_delivery: None,
+                response_schema: None,
+                provider_fallback: None,
+                rate_limit: None,
+                notification_passthrough: None,
+                passthrough: None,
             },
             workflow_callback: None,
             subscription_processed: None,
             telemetry_response_listeners: vec![],
+            batch: Vec::new(),
+            transformed_params: None,
         };
 
         broker.sender.send(request).await.unwrap();
@@ -355,10 +392,24 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                inject_latency_ms: None,
+                fallback: None,
+                allowed_apps: None,
+                event_transform_failure: None,
+                capabilities: None,
+                subscription_filter: None,
+                workflow_session_delivery: None,
+                response_schema: None,
+                provider_fallback: None,
+                rate_limit: None,
+                notification_passthrough: None,
+                passthrough: None,
             },
             workflow_callback: None,
             subscription_processed: None,
             telemetry_response_listeners: vec![],
+            batch: Vec::new(),
+            transformed_params: None,
         };
         let id = request.get_id();
 
@@ -378,11 +429,14 @@ mod tests {
         // setup mock websocket server
         let port = MockWebsocket::start(send_data, Vec::new(), tx, on_close).await;
 
-        let endpoint = RuleEndpoint {
+        let endpoint = RuleEndpointConfig {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
-        };
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
 
         let request = BrokerRequest {
             rpc: RpcRequest::get_new_internal("some_method".to_owned(), None),
@@ -393,10 +447,24 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                inject_latency_ms: None,
+                fallback: None,
+                allowed_apps: None,
+                event_transform_failure: None,
+                capabilities: None,
+                subscription_filter: None,
+                workflow_session_delivery: None,
+                response_schema: None,
+                provider_fallback: None,
+                rate_limit: None,
+                notification_passthrough: None,
+                passthrough: None,
             },
             workflow_callback: None,
             subscription_processed: None,
             telemetry_response_listeners: vec![],
+            batch: Vec::new(),
+            transformed_params: None,
         };
         WSNotificationBroker::start(request, callback, endpoint.get_url().clone())
     }
@@ -471,17 +539,34 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                inject_latency_ms: None,
+                fallback: None,
+                allowed_apps: None,
+                event_transform_failure: None,
+                capabilities: None,
+                subscription_filter: None,
+                workflow_session_delivery: None,
+                response_schema: None,
+                provider_fallback: None,
+                rate_limit: None,
+                notification_passthrough: None,
+                passthrough: None,
             },
             workflow_callback: None,
             subscription_processed: None,
             telemetry_response_listeners: vec![],
+            batch: Vec::new(),
+            transformed_params: None,
         };
         let port: u32 = 34743;
-        let endpoint = RuleEndpoint {
+        let endpoint = RuleEndpointConfig {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
-        };
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
         let sender = WSNotificationBroker::start(request, callback, endpoint.get_url().clone());
         sender.send("test".to_owned()).await.unwrap();
         let v = tokio::time::timeout(Duration::from_secs(2), rec.recv()).await;
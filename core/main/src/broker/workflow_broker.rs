@@ -6,9 +6,10 @@ use super::rules_engine::JsonDataSource;
 use crate::broker::endpoint_broker::{BrokerOutput, EndpointBrokerState};
 use crate::broker::rules_engine::{compose_json_values, make_name_json_safe};
 use crate::state::platform_state::PlatformState;
-use futures::future::{join_all, BoxFuture};
+use futures::future::{try_join_all, BoxFuture};
 use futures::FutureExt;
 use serde_json::json;
+use std::time::Duration;
 
 use ripple_sdk::api::gateway::rpc_gateway_api::{JsonRpcApiError, JsonRpcApiResponse, RpcRequest};
 use ripple_sdk::utils::error::RippleError;
@@ -26,6 +27,8 @@ pub enum SubBrokerErr {
     RpcError(RippleError),
 
     JsonRpcApiError(JsonRpcApiError),
+
+    StepError { step: String, error: RippleError },
 }
 pub type SubBrokerResult = Result<JsonRpcApiResponse, SubBrokerErr>;
 
@@ -35,23 +38,46 @@ async fn subbroker_call(
     source: JsonDataSource,
 ) -> Result<serde_json::Value, SubBrokerErr> {
     let (brokered_tx, mut brokered_rx) = mpsc::channel::<BrokerOutput>(10);
+    let step_callback = BrokerCallback {
+        sender: brokered_tx,
+    };
+    // steps are named by the alias of their rule, the method when no rule matches
+    let step = endpoint_broker
+        .get_rule(&rpc_request)
+        .map_or(source.method.clone(), |rule| rule.alias);
     endpoint_broker.handle_brokerage(
         rpc_request,
         None,
-        Some(BrokerCallback {
-            sender: brokered_tx,
-        }),
+        Some(step_callback.clone()),
         Vec::new(),
         None,
         vec![],
     );
 
-    match brokered_rx.recv().await {
+    let received = match source.timeout {
+        Some(timeout) => {
+            let step_timeout = Duration::from_millis(timeout);
+            match tokio::time::timeout(step_timeout, brokered_rx.recv()).await {
+                Ok(received) => received,
+                Err(_) => {
+                    error!("Workflow step {} timed out after {:?}", step, step_timeout);
+                    endpoint_broker.cancel_requests(&step_callback);
+                    return Err(SubBrokerErr::StepError {
+                        step,
+                        error: RippleError::TimeoutError,
+                    });
+                }
+            }
+        }
+        None => brokered_rx.recv().await,
+    };
+    match received {
         Some(msg) => {
             if msg.is_error() {
-                Err(SubBrokerErr::RpcError(RippleError::BrokerError(
-                    msg.get_error_string(),
-                )))
+                Err(SubBrokerErr::StepError {
+                    step,
+                    error: RippleError::BrokerError(msg.get_error_string()),
+                })
             } else {
                 Ok(json!({make_name_json_safe(
                     &source
@@ -63,9 +89,10 @@ async fn subbroker_call(
         }
         None => {
             error!("Failed to receive message");
-            Err(SubBrokerErr::RpcError(RippleError::BrokerError(
-                "Failed to receive message".to_string(),
-            )))
+            Err(SubBrokerErr::StepError {
+                step,
+                error: RippleError::BrokerError("Failed to receive message".to_string()),
+            })
         }
     }
 }
@@ -134,26 +161,21 @@ impl WorkflowBroker {
             endpoint_broker.clone(),
         );
         /*
-        workflow steps are currently all or nothing/sudden death: if one step fails, the whole workflow fails
+        workflow steps are all or nothing/sudden death: if one step fails or times out, the whole workflow fails
         */
 
         // Define your batch size here
         let batch_size = 10;
         let mut results = vec![];
         for chunk in futures.chunks_mut(batch_size) {
-            let vec = join_all(chunk.iter_mut().map(|f| f.as_mut()).collect::<Vec<_>>()).await;
-            for res in vec {
-                match res {
-                    Ok(success) => {
-                        results.push(success);
-                    }
-                    Err(e) => {
-                        error!(
-                            "Error {:?} in subbroker call for workflow: {} id: {}",
-                            e, broker_request.rpc.method, broker_request.rpc.ctx.call_id
-                        );
-                        results.push(json!({"error": format!("{:?}", e)}));
-                    }
+            match try_join_all(chunk.iter_mut().map(|f| f.as_mut()).collect::<Vec<_>>()).await {
+                Ok(mut successes) => results.append(&mut successes),
+                Err(e) => {
+                    error!(
+                        "Error {:?} in subbroker call for workflow: {} id: {}",
+                        e, broker_request.rpc.method, broker_request.rpc.ctx.call_id
+                    );
+                    return Err(e);
                 }
             }
         }
@@ -212,6 +234,21 @@ impl WorkflowBroker {
                                         boo,
                                     );
                                 }
+                                SubBrokerErr::StepError { step, error } => {
+                                    let boo = JsonRpcApiError::default()
+                                        .with_code(-32001)
+                                        .with_message(format!(
+                                            "workflow step {} failed {:?}: for api {}",
+                                            step, error, broker_request.rpc.method
+                                        ))
+                                        .with_id(broker_request.rpc.ctx.call_id);
+
+                                    Self::log_error_and_send_broker_failure_response(
+                                        broker_request,
+                                        &callback,
+                                        boo,
+                                    );
+                                }
                             },
                         }
                     }
@@ -281,7 +318,7 @@ pub mod tests {
             ..Default::default()
         };
         let source = JsonDataSource {
-            method: "module.method".to_string(),
+            method: "mock.rule".to_string(),
             namespace: Some("module".to_string()),
             ..Default::default()
         };
@@ -293,6 +330,8 @@ pub mod tests {
             subscription_processed: None,
             workflow_callback: Some(callback),
             telemetry_response_listeners: vec![],
+            batch: Vec::new(),
+            transformed_params: None,
         }
     }
     pub fn rule_engine() -> RuleEngine {
@@ -312,6 +351,14 @@ pub mod tests {
                               "response": "\"Sky\""
                             }
                         },
+                        "mock.rule": {
+                            "alias": "mock.method",
+                            "endpoint": "mock"
+                        },
+                        "stall.rule": {
+                            "alias": "stall.method",
+                            "endpoint": "stall"
+                        },
                         "module.method": {
                             "alias": "workflow",
                             "endpoint": "workflow",
@@ -329,21 +376,74 @@ pub mod tests {
     pub fn endppoint_broker_state() -> EndpointBrokerState {
         EndpointBrokerState::default().with_rules_engine(rule_engine())
     }
+    /*
+    "mock" answers every step straight back to its workflow callback, "stall" never answers
+    */
+    fn endpoint_broker_state_with_steps() -> EndpointBrokerState {
+        use super::*;
+
+        let mut state = endppoint_broker_state();
+        let (mock_tx, mut mock_rx) = mpsc::channel::<BrokerRequest>(10);
+        tokio::spawn(async move {
+            while let Some(request) = mock_rx.recv().await {
+                let response = JsonRpcApiResponse::default().with_result(Some(json!("Sky")));
+                if let Some(callback) = request.workflow_callback {
+                    let _ = callback.sender.send(BrokerOutput::new(response)).await;
+                }
+            }
+        });
+        state.add_endpoint("mock".to_owned(), BrokerSender { sender: mock_tx });
+        let (stall_tx, stall_rx) = mpsc::channel::<BrokerRequest>(10);
+        tokio::spawn(async move {
+            let _stall_rx = stall_rx;
+            std::future::pending::<()>().await
+        });
+        state.add_endpoint("stall".to_owned(), BrokerSender { sender: stall_tx });
+        state
+    }
 
     #[tokio::test]
     pub async fn test_run_workflow() {
-        /*
-        THIS IS A WORK IN PROGRESS... endpoint_broker is highly coupled, making it difficult to test without a full integration test
-        */
         use super::*;
 
         let (tx, mut _rx) = mpsc::channel::<BrokerOutput>(10);
         let callback = BrokerCallback { sender: tx };
         let request = broker_request(callback);
-        let broker = endppoint_broker_state();
+        let broker = endpoint_broker_state_with_steps();
 
         let foo = WorkflowBroker::run_workflow(&request, broker);
         let foo = foo.await;
         assert!(foo.is_ok());
+        assert_eq!(foo.unwrap().result, Some(json!({"module": "Sky"})));
+    }
+
+    #[tokio::test]
+    pub async fn test_run_workflow_step_timeout() {
+        use super::*;
+
+        let (tx, _rx) = mpsc::channel::<BrokerOutput>(10);
+        let mut request = broker_request(BrokerCallback { sender: tx });
+        request.rule.sources = Some(vec![
+            JsonDataSource {
+                method: "mock.rule".to_string(),
+                ..Default::default()
+            },
+            JsonDataSource {
+                method: "stall.rule".to_string(),
+                timeout: Some(100),
+                ..Default::default()
+            },
+        ]);
+
+        let state = endpoint_broker_state_with_steps();
+        match WorkflowBroker::run_workflow(&request, state.clone()).await {
+            Err(SubBrokerErr::StepError { step, error }) => {
+                assert_eq!(step, "stall.method");
+                assert_eq!(error, RippleError::TimeoutError);
+            }
+            other => panic!("expected step timeout, got {:?}", other),
+        }
+        // the timed out step no longer waits for its response
+        assert_eq!(state.health_snapshot()["stall"].pending_requests, 0);
     }
 }
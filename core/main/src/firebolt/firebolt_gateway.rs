@@ -134,7 +134,18 @@ impl FireboltGateway {
                         .endpoint_state
                         .cleanup_for_app(&cid)
                         .await;
-                    self.state.platform_state.session_state.clear_session(&cid);
+                    let session_state = &self.state.platform_state.session_state;
+                    let app_id = session_state.get_app_id(cid.clone());
+                    session_state.clear_session(&cid);
+                    if let Some(app_id) = app_id {
+                        if !session_state.has_app_session(&app_id) {
+                            self.state
+                                .platform_state
+                                .cap_state
+                                .grant_state
+                                .cleanup_for_app(&app_id);
+                        }
+                    }
                 }
                 HandleRpc { request } => self.handle(request, None).await,
                 HandleRpcForExtn { msg } => {
@@ -16,6 +16,7 @@
 //
 
 use std::{
+    io::Write,
     net::SocketAddr,
     sync::{Arc, RwLock},
 };
@@ -28,6 +29,7 @@ use crate::{
         session_state::Session,
     },
 };
+use flate2::{write::DeflateEncoder, Compression};
 use futures::SinkExt;
 use futures::StreamExt;
 use jsonrpsee::types::{error::INVALID_REQUEST_CODE, ErrorObject, ErrorResponse, Id};
@@ -51,6 +53,10 @@ use tokio_tungstenite::{
     tungstenite::{self, Message},
     WebSocketStream,
 };
+
+/// Responses larger than this are deflated for sessions which connected with `compression=deflate`.
+pub const COMPRESSION_THRESHOLD_BYTES: usize = 16 * 1024;
+
 #[allow(dead_code)]
 pub struct FireboltWs {}
 
@@ -60,6 +66,7 @@ pub struct ClientIdentity {
     session_id: String,
     app_id: String,
     rpc_v2: bool,
+    compression: bool,
 }
 
 struct ConnectionCallbackConfig {
@@ -154,10 +161,18 @@ impl tungstenite::handshake::server::Callback for ConnectionCallback {
             None => false,
         };
 
+        // Clients which can inflate raw deflate frames opt in with compression=deflate, large
+        // responses are then sent to them as binary messages.
+        let compression = match get_query(request, "compression", false)? {
+            Some(e) => e == "deflate",
+            None => false,
+        };
+
         let cid = ClientIdentity {
             session_id: session_id.clone(),
             app_id,
             rpc_v2,
+            compression,
         };
         oneshot_send_and_log(cfg.next, cid, "ResolveClientIdentity");
         /*
@@ -222,6 +237,22 @@ impl FireboltWs {
         }
     }
 
+    /// Builds the websocket message for a response. Large responses are deflated into a binary
+    /// message when the session supports compression, everything else is sent as text.
+    pub fn encode_response(msg: &str, compression: bool) -> Message {
+        if compression && msg.len() > COMPRESSION_THRESHOLD_BYTES {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            match encoder
+                .write_all(msg.as_bytes())
+                .and_then(|_| encoder.finish())
+            {
+                Ok(compressed) => return Message::Binary(compressed),
+                Err(e) => error!("Failed to compress response, sending as text {:?}", e),
+            }
+        }
+        Message::Text(msg.to_owned())
+    }
+
     async fn handle_connection(
         _client_addr: SocketAddr,
         ws_stream: WebSocketStream<TcpStream>,
@@ -279,11 +310,15 @@ impl FireboltWs {
         let (mut sender, mut receiver) = ws_stream.split();
         let mut platform_state = state.clone();
         let context_clone = ctx.clone();
+        let compression = identity.compression;
 
         tokio::spawn(async move {
             while let Some(api_message) = resp_rx.recv().await {
                 let send_result = sender
-                    .send(Message::Text(api_message.jsonrpc_msg.clone()))
+                    .send(FireboltWs::encode_response(
+                        &api_message.jsonrpc_msg,
+                        compression,
+                    ))
                     .await;
                 match send_result {
                     Ok(_) => {
@@ -401,3 +436,46 @@ impl FireboltWs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use serde_json::json;
+    use std::io::Read;
+
+    #[test]
+    fn test_encode_response_compression() {
+        let large = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": vec!["some repeated value"; 2000]
+        })
+        .to_string();
+        assert!(large.len() > COMPRESSION_THRESHOLD_BYTES);
+
+        match FireboltWs::encode_response(&large, true) {
+            Message::Binary(compressed) => {
+                assert!(compressed.len() < large.len());
+                let mut inflated = String::new();
+                DeflateDecoder::new(compressed.as_slice())
+                    .read_to_string(&mut inflated)
+                    .unwrap();
+                assert_eq!(inflated, large);
+            }
+            other => panic!("expected a compressed response, got {:?}", other),
+        }
+
+        // sessions without compression support always get text
+        assert_eq!(
+            FireboltWs::encode_response(&large, false),
+            Message::Text(large.clone())
+        );
+
+        let small = json!({"jsonrpc": "2.0", "id": 2, "result": true}).to_string();
+        assert_eq!(
+            FireboltWs::encode_response(&small, true),
+            Message::Text(small)
+        );
+    }
+}
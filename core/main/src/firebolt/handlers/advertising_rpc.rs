@@ -17,7 +17,7 @@
 use crate::{
     firebolt::rpc::RippleRPCProvider,
     processor::storage::storage_manager::StorageManager,
-    service::apps::app_events::{AppEventDecorationError, AppEventDecorator},
+    service::apps::app_events::{AppEventDecorationError, AppEventDecorator, AppEvents},
     state::platform_state::PlatformState,
     utils::rpc_utils::rpc_err,
 };
@@ -31,15 +31,19 @@ use ripple_sdk::{
     api::{
         firebolt::{
             fb_advertising::{
-                AdConfigRequestParams, AdConfigResponse, AdIdRequestParams,
-                AdvertisingFrameworkConfig, AdvertisingRequest, AdvertisingResponse, GetAdConfig,
+                AdConfigRequestParams, AdConfigResponse, AdIdRequestParams, AdIdResponse,
+                AdvertisingFrameworkConfig, AdvertisingPolicyEvent, AdvertisingRequest,
+                AdvertisingResponse, GetAdConfig,
             },
             fb_capabilities::{CapabilityRole, FireboltCap, RoleInfo},
         },
         gateway::rpc_gateway_api::CallContext,
-        storage_property::StorageProperty,
+        storage_property::{
+            StorageProperty, StoragePropertyData, EVENT_ADVERTISING_POLICY_CHANGED,
+            KEY_APP_ADVERTISING_POLICY, NAMESPACE_ADVERTISING,
+        },
     },
-    log::{debug, error},
+    log::{debug, error, warn},
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -47,10 +51,10 @@ use std::collections::HashMap;
 
 use super::{
     capabilities_rpc::is_permitted,
+    device_rpc::{DeviceImpl, DeviceServer},
     privacy_rpc::{self, PrivacyImpl},
 };
 
-const ADVERTISING_APP_BUNDLE_ID_SUFFIX: &str = "Comcast";
 //{"xifa":"00000000-0000-0000-0000-000000000000","xifaType":"sessionId","lmt":"0"}
 const IFA_ZERO_BASE64: &str = "eyJ4aWZhIjoiMDAwMDAwMDAtMDAwMC0wMDAwLTAwMDAtMDAwMDAwMDAwMDAwIiwieGlmYVR5cGUiOiJzZXNzaW9uSWQiLCJsbXQiOiIwIn0K";
 
@@ -80,6 +84,48 @@ pub struct AdvertisingPolicy {
     pub limit_ad_tracking: bool,
 }
 
+/// Advertising policy of a single app, stored as json in the advertising namespace scoped to
+/// the app id. Settings left out fall back to the global values.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvertisingPolicyOverride {
+    pub skip_restriction: Option<String>,
+    pub limit_ad_tracking: Option<bool>,
+}
+
+impl AdvertisingPolicy {
+    fn with_override(self, app_override: Option<AdvertisingPolicyOverride>) -> AdvertisingPolicy {
+        let Some(app_override) = app_override else {
+            return self;
+        };
+        AdvertisingPolicy {
+            skip_restriction: match app_override.skip_restriction {
+                Some(value) if SKIP_RESTRICTIONS.contains(&value.as_str()) => value,
+                Some(value) => {
+                    warn!(
+                        "Invalid skip restriction {} in app override, keeping {}",
+                        value, self.skip_restriction
+                    );
+                    self.skip_restriction
+                }
+                None => self.skip_restriction,
+            },
+            limit_ad_tracking: app_override
+                .limit_ad_tracking
+                .unwrap_or(self.limit_ad_tracking),
+        }
+    }
+}
+
+impl From<AdvertisingPolicy> for AdvertisingPolicyEvent {
+    fn from(policy: AdvertisingPolicy) -> Self {
+        AdvertisingPolicyEvent {
+            skip_restriction: policy.skip_restriction,
+            limit_ad_tracking: policy.limit_ad_tracking,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct AdvertisingIdRPCRequest {
     pub options: Option<ScopeOption>,
@@ -137,19 +183,68 @@ pub trait Advertising {
     async fn reset_identifier(&self, ctx: CallContext) -> RpcResult<()>;
 }
 const NONE: &str = "none";
-async fn get_advertisting_policy(platform_state: &PlatformState) -> AdvertisingPolicy {
+const SKIP_RESTRICTIONS: [&str; 4] = [NONE, "adsUnwatched", "adsAll", "all"];
+
+fn validate_skip_restriction(value: String) -> String {
+    if SKIP_RESTRICTIONS.contains(&value.as_str()) {
+        value
+    } else {
+        warn!(
+            "Invalid skip restriction {} in storage, defaulting to {}",
+            value, NONE
+        );
+        String::from(NONE)
+    }
+}
+
+async fn get_advertising_policy_override(
+    platform_state: &PlatformState,
+    app_id: &str,
+) -> Option<AdvertisingPolicyOverride> {
+    let data = StoragePropertyData {
+        namespace: NAMESPACE_ADVERTISING.to_string(),
+        key: KEY_APP_ADVERTISING_POLICY,
+        value: String::new(),
+        scope: Some(app_id.to_string()),
+    };
+    let raw = StorageManager::get_string_for_scope(platform_state, &data)
+        .await
+        .ok()?;
+    match serde_json::from_str(&raw) {
+        Ok(app_override) => Some(app_override),
+        Err(e) => {
+            warn!(
+                "Invalid advertising policy for {} in storage {:?}",
+                app_id, e
+            );
+            None
+        }
+    }
+}
+
+/// Resolves the advertising policy of the app, or the global policy when no app is given
+async fn get_advertisting_policy(
+    platform_state: &PlatformState,
+    app_id: Option<&str>,
+) -> AdvertisingPolicy {
+    let app_override = match app_id {
+        Some(app_id) => get_advertising_policy_override(platform_state, app_id).await,
+        None => None,
+    };
     AdvertisingPolicy {
         skip_restriction: StorageManager::get_string(
             platform_state,
             StorageProperty::SkipRestriction,
         )
         .await
+        .map(validate_skip_restriction)
         .unwrap_or_else(|_| String::from(NONE)),
         limit_ad_tracking: !privacy_rpc::PrivacyImpl::get_allow_app_content_ad_targeting(
             platform_state,
         )
         .await,
     }
+    .with_override(app_override)
 }
 
 #[derive(Clone)]
@@ -162,21 +257,65 @@ impl AppEventDecorator for AdvertisingPolicyEventDecorator {
     async fn decorate(
         &self,
         ps: &PlatformState,
-        _ctx: &CallContext,
+        ctx: &CallContext,
         _event_name: &str,
         _val_in: &Value,
     ) -> Result<Value, AppEventDecorationError> {
-        Ok(serde_json::to_value(get_advertisting_policy(ps).await)?)
+        Ok(serde_json::to_value(AdvertisingPolicyEvent::from(
+            get_advertisting_policy(ps, Some(&ctx.app_id)).await,
+        ))?)
     }
     fn dec_clone(&self) -> Box<dyn AppEventDecorator + Send + Sync> {
         Box::new(self.clone())
     }
 }
 
+/// Notifies advertising policy listeners so they re-read the policy
+async fn emit_advertising_policy_changed(platform_state: &PlatformState) {
+    let event = AdvertisingPolicyEvent::from(get_advertisting_policy(platform_state, None).await);
+    emit_advertising_policy(platform_state, &event).await;
+}
+
+async fn emit_advertising_policy(platform_state: &PlatformState, event: &AdvertisingPolicyEvent) {
+    match serde_json::to_value(event) {
+        Ok(value) => {
+            AppEvents::emit(platform_state, EVENT_ADVERTISING_POLICY_CHANGED, &value).await
+        }
+        Err(e) => error!("could not generate advertising policy event {:?}", e),
+    }
+}
+
 pub struct AdvertisingImpl {
     pub state: PlatformState,
 }
 
+fn get_advertising_id(ad_id: AdIdResponse, allow_ad_targeting: bool) -> AdvertisingId {
+    // lmt always reflects the current privacy setting rather than the value cached upstream
+    AdvertisingId {
+        ifa: ad_id.ifa,
+        ifa_type: ad_id.ifa_type,
+        lmt: if allow_ad_targeting { "0" } else { "1" }.to_string(),
+    }
+}
+
+/// Device attributes used for ad targeting, empty when ad tracking is limited
+fn get_device_ad_attributes(
+    allow_ad_targeting: bool,
+    model: Option<String>,
+    device_type: String,
+) -> Value {
+    let mut attributes = serde_json::Map::new();
+    if allow_ad_targeting {
+        if let Some(model) = model {
+            attributes.insert("model".to_string(), model.into());
+        }
+        if !device_type.is_empty() {
+            attributes.insert("deviceType".to_string(), device_type.into());
+        }
+    }
+    Value::Object(attributes)
+}
+
 fn get_scope_option_map(options: &Option<ScopeOption>) -> HashMap<String, String> {
     let mut scope_option_map = HashMap::new();
     if let Some(scope_opt) = options {
@@ -200,8 +339,9 @@ impl AdvertisingServer for AdvertisingImpl {
                     .ok_or_else(|| Error::Custom(String::from("no session available")))?,
             ))
             .await
-            .map(|_ok| ())
-            .map_err(|err| err.into())
+            .map_err(|err| -> Error { err.into() })?;
+        emit_advertising_policy_changed(&self.state).await;
+        Ok(())
     }
 
     async fn advertising_id(
@@ -238,12 +378,9 @@ impl AdvertisingServer for AdvertisingImpl {
                 if let Some(AdvertisingResponse::AdIdObject(obj)) =
                     payload.payload.extract::<AdvertisingResponse>()
                 {
-                    let ad_id = AdvertisingId {
-                        ifa: obj.ifa,
-                        ifa_type: obj.ifa_type,
-                        lmt: obj.lmt,
-                    };
-                    return Ok(ad_id);
+                    let allow_ad_targeting =
+                        PrivacyImpl::get_allow_app_content_ad_targeting(&self.state).await;
+                    return Ok(get_advertising_id(obj, allow_ad_targeting));
                 }
             }
 
@@ -260,7 +397,10 @@ impl AdvertisingServer for AdvertisingImpl {
     fn app_bundle_id(&self, ctx: CallContext) -> RpcResult<String> {
         Ok(format!(
             "{}.{}",
-            ctx.app_id, ADVERTISING_APP_BUNDLE_ID_SUFFIX
+            ctx.app_id,
+            self.state
+                .get_device_manifest()
+                .get_advertising_app_bundle_id_suffix()
         ))
     }
 
@@ -381,31 +521,20 @@ impl AdvertisingServer for AdvertisingImpl {
     }
 
     async fn device_attributes(&self, ctx: CallContext) -> RpcResult<Value> {
-        let afc = self.config(ctx.clone(), Default::default()).await?;
-        let buff = base64.decode(afc.device_ad_attributes).unwrap_or_default();
-        match String::from_utf8(buff) {
-            Ok(mut b_string) => {
-                /*
-                dealing with: https://ccp.sys.comcast.net/browse/OTTX-28561
-                This is actually caused by ad-platform-service getting a 404
-                from Freewheel and returning an error.
-                */
-
-                if b_string.trim().is_empty() {
-                    b_string = "{}".to_string();
-                };
-
-                match serde_json::from_str(b_string.as_str()) {
-                    Ok(js) => Ok(js),
-                    Err(_e) => Err(Error::Custom(String::from("Invalid JSON"))),
-                }
-            }
-            Err(_e) => Err(Error::Custom(String::from("Found invalid UTF-8"))),
+        let allow_ad_targeting = PrivacyImpl::get_allow_app_content_ad_targeting(&self.state).await;
+        if !allow_ad_targeting {
+            return Ok(get_device_ad_attributes(false, None, String::default()));
         }
+        let device = DeviceImpl {
+            state: self.state.clone(),
+        };
+        let model = device.model(ctx.clone()).await.ok();
+        let device_type = device.typ(ctx).await.unwrap_or_default();
+        Ok(get_device_ad_attributes(true, model, device_type))
     }
 
-    async fn policy(&self, _ctx: CallContext) -> RpcResult<AdvertisingPolicy> {
-        Ok(get_advertisting_policy(&self.state).await)
+    async fn policy(&self, ctx: CallContext) -> RpcResult<AdvertisingPolicy> {
+        Ok(get_advertisting_policy(&self.state, Some(&ctx.app_id)).await)
     }
 }
 
@@ -418,8 +547,22 @@ impl RippleRPCProvider<AdvertisingImpl> for AdvertisingRPCProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::firebolt::handlers::advertising_rpc::AdvertisingImpl;
-    use ripple_sdk::{api::gateway::rpc_gateway_api::JsonRpcApiRequest, tokio};
+    use crate::{
+        firebolt::handlers::advertising_rpc::AdvertisingImpl,
+        service::extn::ripple_client::RippleClient,
+        state::{bootstrap_state::ChannelsState, session_state::Session},
+    };
+    use ripple_sdk::{
+        api::{
+            firebolt::fb_general::ListenRequest,
+            gateway::rpc_gateway_api::JsonRpcApiRequest,
+            manifest::{
+                device_manifest::{DeviceManifest, RippleConfiguration},
+                extn_manifest::ExtnManifest,
+            },
+        },
+        tokio,
+    };
     use ripple_tdk::utils::test_utils::Mockable;
     use serde::{Deserialize, Serialize};
     use serde_json::json;
@@ -477,4 +620,170 @@ mod tests {
 
         assert!(ad_module.raw_json_request(&request).await.is_ok());
     }
+
+    #[tokio::test]
+    pub async fn test_app_bundle_id_configured_suffix() {
+        let device_manifest = DeviceManifest {
+            configuration: RippleConfiguration {
+                advertising_app_bundle_id_suffix: "Partner".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let state = PlatformState::new(
+            ExtnManifest::default(),
+            device_manifest,
+            RippleClient::new(ChannelsState::new()),
+            vec![],
+            None,
+        );
+        let ad_impl = AdvertisingImpl { state };
+
+        let mut ctx = CallContext::mock();
+        ctx.app_id = "someApp".to_string();
+        assert_eq!(ad_impl.app_bundle_id(ctx).unwrap(), "someApp.Partner");
+    }
+
+    #[test]
+    fn test_get_advertising_id_lmt() {
+        let ad_id_response = AdIdResponse {
+            ifa: "01234567-89AB-CDEF-GH01-23456789ABCD".to_string(),
+            ifa_type: "idfa".to_string(),
+            lmt: "0".to_string(),
+        };
+
+        let ad_id = get_advertising_id(ad_id_response.clone(), true);
+        assert_eq!(ad_id.lmt, "0");
+        let ad_id = get_advertising_id(ad_id_response, false);
+        assert_eq!(ad_id.lmt, "1");
+
+        let value = serde_json::to_value(ad_id).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "ifa": "01234567-89AB-CDEF-GH01-23456789ABCD",
+                "ifaType": "idfa",
+                "ifa_type": "idfa",
+                "lmt": "1"
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_device_ad_attributes() {
+        assert_eq!(
+            get_device_ad_attributes(true, Some("xi6".to_string()), "tv".to_string()),
+            json!({"model": "xi6", "deviceType": "tv"})
+        );
+        assert_eq!(
+            get_device_ad_attributes(true, None, String::default()),
+            json!({})
+        );
+    }
+
+    #[test]
+    fn test_get_device_ad_attributes_limit_ad_tracking() {
+        assert_eq!(
+            get_device_ad_attributes(false, Some("xi6".to_string()), "tv".to_string()),
+            json!({})
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_advertising_id_without_session() {
+        let ad_module = (AdvertisingImpl {
+            state: PlatformState::mock(),
+        })
+        .into_rpc();
+
+        let request = test_request(
+            "advertising.advertisingId".to_string(),
+            Some(CallContext::mock()),
+            Some(
+                json!({"request": {"options": {"scope": {"type": "browse", "id": "paidPlacement"}}}}),
+            ),
+        );
+
+        let (response, _) = ad_module.raw_json_request(&request).await.unwrap();
+        assert!(response.result.contains("Account session is not available"));
+    }
+
+    #[tokio::test]
+    pub async fn test_emit_advertising_policy() {
+        let state = PlatformState::mock();
+        let ctx = CallContext::mock();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        state.session_state.add_session(
+            ctx.session_id.clone(),
+            Session::new(ctx.app_id.clone(), Some(tx)),
+        );
+        AppEvents::add_listener(
+            &state,
+            EVENT_ADVERTISING_POLICY_CHANGED.to_string(),
+            ctx.clone(),
+            ListenRequest { listen: true },
+        );
+
+        // limit ad tracking turned on
+        let event = AdvertisingPolicyEvent::from(AdvertisingPolicy {
+            skip_restriction: NONE.to_string(),
+            limit_ad_tracking: true,
+        });
+        emit_advertising_policy(&state, &event).await;
+
+        let message = rx.recv().await.unwrap();
+        let message: Value = serde_json::from_str(&message.jsonrpc_msg).unwrap();
+        let received: AdvertisingPolicyEvent =
+            serde_json::from_value(message["result"].clone()).unwrap();
+        assert_eq!(received, event);
+    }
+
+    #[test]
+    fn test_validate_skip_restriction() {
+        assert_eq!(
+            validate_skip_restriction("adsUnwatched".to_owned()),
+            "adsUnwatched"
+        );
+        assert_eq!(validate_skip_restriction("bogus".to_owned()), NONE);
+    }
+
+    #[test]
+    fn test_advertising_policy_override() {
+        let global = || AdvertisingPolicy {
+            skip_restriction: NONE.to_string(),
+            limit_ad_tracking: false,
+        };
+        let overrides: HashMap<&str, AdvertisingPolicyOverride> = HashMap::from([(
+            "partnerApp",
+            serde_json::from_value(json!({"skipRestriction": "adsAll", "limitAdTracking": true}))
+                .unwrap(),
+        )]);
+
+        let policy = global().with_override(overrides.get("partnerApp").cloned());
+        assert_eq!(policy.skip_restriction, "adsAll");
+        assert!(policy.limit_ad_tracking);
+
+        let policy = global().with_override(overrides.get("otherApp").cloned());
+        assert_eq!(policy.skip_restriction, NONE);
+        assert!(!policy.limit_ad_tracking);
+
+        let partial = AdvertisingPolicyOverride {
+            limit_ad_tracking: Some(true),
+            ..Default::default()
+        };
+        let policy = global().with_override(Some(partial));
+        assert_eq!(policy.skip_restriction, NONE);
+        assert!(policy.limit_ad_tracking);
+
+        let invalid = AdvertisingPolicyOverride {
+            skip_restriction: Some("bogus".to_owned()),
+            ..Default::default()
+        };
+        let policy = AdvertisingPolicy {
+            skip_restriction: "all".to_string(),
+            limit_ad_tracking: false,
+        }
+        .with_override(Some(invalid));
+        assert_eq!(policy.skip_restriction, "all");
+    }
 }
@@ -0,0 +1,230 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+use std::collections::HashMap;
+
+use jsonrpsee::{core::RpcResult, proc_macros::rpc, RpcModule};
+use ripple_sdk::{
+    api::gateway::{
+        rpc_error::RpcError,
+        rpc_gateway_api::{ApiProtocol, CallContext, RpcRequest},
+    },
+    async_trait::async_trait,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    broker::{
+        endpoint_broker::{BulkSubscribeResult, RequestTraceEntry},
+        rules_engine::{RuleExplanation, RuleSummary},
+    },
+    firebolt::{
+        firebolt_gatekeeper::FireboltGatekeeper, firebolt_gateway::JsonRpcError,
+        rpc::RippleRPCProvider,
+    },
+    state::platform_state::PlatformState,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExplainRuleRequest {
+    pub method: String,
+    pub params: Option<Value>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveRuleRequest {
+    pub method: String,
+    /// App calling the method, the caller of this request when not given
+    pub app_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceRequestRequest {
+    /// Session to start or stop tracing, left unchanged when not given
+    pub session_id: Option<String>,
+    pub enabled: Option<bool>,
+    /// Only returns the stages of this request
+    pub request_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BulkSubscribeEvent {
+    /// Firebolt event to listen to, e.g. `device.onNameChanged`
+    pub event: String,
+    /// Id the app listens with, events of the subscription are delivered with this id
+    pub id: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BulkSubscribeRequest {
+    pub events: Vec<BulkSubscribeEvent>,
+}
+
+/// Diagnostics of the endpoint broker, these methods are not part of the Firebolt
+/// OpenRPC and are only reachable through internal requests. `ripple.bulkSubscribe` is the
+/// exception, apps call it as declared in `ripple-open-rpc.json`.
+#[rpc(server)]
+pub trait Broker {
+    #[method(name = "ripple.explainRule")]
+    async fn explain_rule(
+        &self,
+        ctx: CallContext,
+        request: ExplainRuleRequest,
+    ) -> RpcResult<RuleExplanation>;
+    #[method(name = "ripple.resolveRule")]
+    async fn resolve_rule(
+        &self,
+        ctx: CallContext,
+        request: ResolveRuleRequest,
+    ) -> RpcResult<Option<RuleSummary>>;
+    #[method(name = "ripple.traceRequest")]
+    async fn trace_request(
+        &self,
+        ctx: CallContext,
+        request: TraceRequestRequest,
+    ) -> RpcResult<Vec<RequestTraceEntry>>;
+    #[method(name = "ripple.listSubscriptions")]
+    async fn list_subscriptions(&self, ctx: CallContext)
+        -> RpcResult<HashMap<String, Vec<String>>>;
+    #[method(name = "ripple.bulkSubscribe")]
+    async fn bulk_subscribe(
+        &self,
+        ctx: CallContext,
+        request: BulkSubscribeRequest,
+    ) -> RpcResult<Vec<BulkSubscribeResult>>;
+}
+
+pub struct BrokerImpl {
+    pub state: PlatformState,
+}
+
+#[async_trait]
+impl BrokerServer for BrokerImpl {
+    async fn explain_rule(
+        &self,
+        mut ctx: CallContext,
+        request: ExplainRuleRequest,
+    ) -> RpcResult<RuleExplanation> {
+        ctx.method = request.method.clone();
+        let params_json = RpcRequest::prepend_ctx(request.params, &ctx);
+        let rpc_request = RpcRequest::new(request.method, params_json, ctx);
+        Ok(self.state.endpoint_state.explain_rule(&rpc_request))
+    }
+
+    async fn resolve_rule(
+        &self,
+        ctx: CallContext,
+        request: ResolveRuleRequest,
+    ) -> RpcResult<Option<RuleSummary>> {
+        let app_id = request.app_id.unwrap_or(ctx.app_id);
+        Ok(self
+            .state
+            .endpoint_state
+            .resolve_rule(&request.method, &app_id))
+    }
+
+    async fn trace_request(
+        &self,
+        _ctx: CallContext,
+        request: TraceRequestRequest,
+    ) -> RpcResult<Vec<RequestTraceEntry>> {
+        let endpoint_state = &self.state.endpoint_state;
+        if let (Some(session_id), Some(enabled)) = (&request.session_id, request.enabled) {
+            if enabled {
+                endpoint_state.enable_request_trace(session_id);
+            } else {
+                endpoint_state.disable_request_trace(session_id);
+            }
+        }
+        Ok(endpoint_state.request_trace(request.request_id.as_deref()))
+    }
+
+    async fn list_subscriptions(
+        &self,
+        _ctx: CallContext,
+    ) -> RpcResult<HashMap<String, Vec<String>>> {
+        Ok(self.state.endpoint_state.list_subscriptions())
+    }
+
+    async fn bulk_subscribe(
+        &self,
+        ctx: CallContext,
+        request: BulkSubscribeRequest,
+    ) -> RpcResult<Vec<BulkSubscribeResult>> {
+        let mut requests = Vec::new();
+        let mut outcomes = Vec::new();
+        for BulkSubscribeEvent { event, id } in request.events {
+            let mut event_ctx = ctx.clone();
+            event_ctx.method = event.clone();
+            event_ctx.call_id = id;
+            let params_json = RpcRequest::prepend_ctx(Some(json!({"listen": true})), &event_ctx);
+            let rpc_request = RpcRequest::new(event, params_json, event_ctx);
+            let gated = if ctx.protocol == ApiProtocol::Extn {
+                Ok(Vec::new())
+            } else {
+                FireboltGatekeeper::gate(self.state.clone(), rpc_request.clone()).await
+            };
+            match gated {
+                Ok(permissions) => {
+                    outcomes.push((rpc_request.method.clone(), id, None));
+                    requests.push((rpc_request, permissions));
+                }
+                Err(e) => {
+                    let caps = e.caps.iter().map(|x| x.as_str()).collect();
+                    let denied = BulkSubscribeResult {
+                        event: rpc_request.method.clone(),
+                        id,
+                        success: false,
+                        error: Some(json!(JsonRpcError {
+                            code: e.reason.get_rpc_error_code(),
+                            message: e.reason.get_rpc_error_message(caps),
+                            data: None,
+                        })),
+                    };
+                    outcomes.push((rpc_request.method, id, Some(denied)));
+                }
+            }
+        }
+        let session = self.state.session_state.get_session(&ctx);
+        let mut brokered = self
+            .state
+            .endpoint_state
+            .bulk_subscribe(requests, session)
+            .await;
+        // denied events keep their place, duplicate events have no outcome of their own
+        Ok(outcomes
+            .into_iter()
+            .filter_map(|(event, id, denied)| {
+                denied.or_else(|| {
+                    let index = brokered
+                        .iter()
+                        .position(|result| result.event == event && result.id == id)?;
+                    Some(brokered.remove(index))
+                })
+            })
+            .collect())
+    }
+}
+
+pub struct BrokerRPCProvider;
+impl RippleRPCProvider<BrokerImpl> for BrokerRPCProvider {
+    fn provide(state: PlatformState) -> RpcModule<BrokerImpl> {
+        (BrokerImpl { state }).into_rpc()
+    }
+}
@@ -380,7 +380,7 @@ impl ProviderRegistrar {
         info!("callback_error: method={}", context.method);
         let params_sequence = params.sequence();
 
-        if let Some(attributes) = context.provider_relation_set.attributes {
+        if let Some(attributes) = &context.provider_relation_set.attributes {
             if let Some(provider_response) = ProviderRegistrar::get_provider_response(
                 attributes.error_payload_type.clone(),
                 params_sequence,
@@ -23,6 +23,7 @@ pub mod handlers {
     pub mod advertising_rpc;
     pub mod audio_description_rpc;
     pub mod authentication_rpc;
+    pub mod broker_rpc;
     pub mod capabilities_rpc;
     pub mod closed_captions_rpc;
     pub mod device_rpc;
@@ -81,6 +81,18 @@ impl ExtnEventProcessor for AppEventsProcessor {
             AppEventRequest::Register(ctx, event, request) => {
                 AppEvents::add_listener(&state, event, ctx, request);
             }
+            AppEventRequest::RegisterDecorator(decorator) => {
+                state
+                    .endpoint_state
+                    .get_event_utility()
+                    .register_extn_decorator(decorator);
+            }
+            AppEventRequest::UnregisterDecorator(name) => {
+                state
+                    .endpoint_state
+                    .get_event_utility()
+                    .unregister_function(&name);
+            }
         }
         None
     }
@@ -28,9 +28,11 @@ use ripple_sdk::{
         DefaultExtnStreamer, ExtnRequestProcessor, ExtnStreamProcessor, ExtnStreamer,
     },
     extn::extn_client_message::{ExtnMessage, ExtnResponse},
-    log::debug,
+    log::{debug, error},
     tokio::sync::mpsc::{Receiver as MReceiver, Sender as MSender},
 };
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 #[derive(Debug)]
 pub struct StoreUserGrantsProcessor {
     state: PlatformState,
@@ -63,6 +65,12 @@ impl ExtnStreamProcessor for StoreUserGrantsProcessor {
 }
 
 impl StoreUserGrantsProcessor {
+    fn now() -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+    }
+
     async fn process_get_request(
         state: &PlatformState,
         msg: ExtnMessage,
@@ -72,7 +80,7 @@ impl StoreUserGrantsProcessor {
         let result = state
             .cap_state
             .grant_state
-            .get_grant_status(&app_id, &permission);
+            .get_effective_grant_status(&app_id, &permission);
         if let Some(granted) = result {
             Self::respond(
                 state.get_client().get_extn_client(),
@@ -85,6 +93,11 @@ impl StoreUserGrantsProcessor {
             .await
             .is_ok()
         } else {
+            // expired grants are never reported, purge any left behind for this app
+            state
+                .cap_state
+                .grant_state
+                .delete_expired_entries_for_app(app_id);
             Self::respond(
                 state.get_client().get_extn_client(),
                 msg,
@@ -94,12 +107,53 @@ impl StoreUserGrantsProcessor {
             .is_ok()
         }
     }
+    async fn process_get_by_app_request(
+        state: &PlatformState,
+        msg: ExtnMessage,
+        app_id: String,
+    ) -> bool {
+        let now = Self::now();
+        let user_grants = state
+            .cap_state
+            .grant_state
+            .get_grant_entries_for_app_id(app_id.clone())
+            .iter()
+            .map(|entry| UserGrantInfo::from_grant_entry(entry, Some(app_id.clone())))
+            .filter(|user_grant_info| !user_grant_info.is_expired(now))
+            .collect();
+        Self::respond(
+            state.get_client().get_extn_client(),
+            msg,
+            ExtnResponse::UserGrants(user_grants),
+        )
+        .await
+        .is_ok()
+    }
+
     async fn process_set_request(
         state: &PlatformState,
         msg: ExtnMessage,
         user_grant_info: UserGrantInfo,
     ) -> bool {
         debug!("Processor is handling set request: {:?}", user_grant_info);
+        if let Err(e) = user_grant_info.validate() {
+            error!(
+                "Rejecting user grant with expiry before last modified time: {:?}",
+                user_grant_info
+            );
+            return Self::handle_error(state.get_client().get_extn_client(), msg, e).await;
+        }
+        if user_grant_info.is_expired(Self::now()) {
+            // an expired grant would never be reported, so it is not stored at all
+            debug!("Ignoring expired user grant: {:?}", user_grant_info);
+            return Self::respond(
+                state.get_client().get_extn_client(),
+                msg,
+                ExtnResponse::None(()),
+            )
+            .await
+            .is_ok();
+        }
         let app_id = user_grant_info.app_name.to_owned();
         let grant_entry = GrantEntry {
             role: user_grant_info.role,
@@ -115,6 +169,7 @@ impl StoreUserGrantsProcessor {
                     .as_secs()
                     .saturating_sub(user_grant_info.last_modified_time.as_secs())
             }),
+            persistence: user_grant_info.persistence,
         };
         state
             .cap_state
@@ -163,6 +218,18 @@ impl StoreUserGrantsProcessor {
         .await
         .is_ok()
     }
+
+    async fn process_clear_expired_request(state: &PlatformState, msg: ExtnMessage) -> bool {
+        debug!("Processor is handling clear expired request");
+        state.cap_state.grant_state.delete_all_expired_entries();
+        Self::respond(
+            state.get_client().get_extn_client(),
+            msg,
+            ExtnResponse::None(()),
+        )
+        .await
+        .is_ok()
+    }
 }
 
 #[async_trait]
@@ -181,6 +248,9 @@ impl ExtnRequestProcessor for StoreUserGrantsProcessor {
             UserGrantsStoreRequest::GetUserGrants(app_id, permission) => {
                 Self::process_get_request(&state, msg, app_id, permission).await
             }
+            UserGrantsStoreRequest::GetUserGrantsByApp(app_id) => {
+                Self::process_get_by_app_request(&state, msg, app_id).await
+            }
             UserGrantsStoreRequest::SetUserGrants(user_grant_info) => {
                 Self::process_set_request(&state, msg, user_grant_info).await
             }
@@ -190,6 +260,9 @@ impl ExtnRequestProcessor for StoreUserGrantsProcessor {
             UserGrantsStoreRequest::ClearUserGrants(persistence_type) => {
                 Self::process_clear_request(&state, msg, persistence_type).await
             }
+            UserGrantsStoreRequest::ClearExpiredUserGrants() => {
+                Self::process_clear_expired_request(&state, msg).await
+            }
         }
     }
 }
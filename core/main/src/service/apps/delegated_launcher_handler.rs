@@ -51,7 +51,6 @@ use ripple_sdk::{
 use ripple_sdk::{
     api::{
         apps::{AppRequest, AppResponse},
-        device::device_user_grants_data::GrantLifespan,
         firebolt::{
             fb_discovery::LaunchRequest,
             fb_lifecycle_management::{
@@ -1127,9 +1126,10 @@ impl DelegatedLauncherHandler {
         }
 
         if state == LifecycleState::Inactive || state == LifecycleState::Unloading {
-            self.platform_state.clone().cap_state.grant_state.custom_delete_entries(app_id.into(), |grant_entry| -> bool {
-                !(matches!(&grant_entry.lifespan, Some(entry_lifespan) if entry_lifespan == &GrantLifespan::AppActive))
-            });
+            self.platform_state
+                .cap_state
+                .grant_state
+                .cleanup_for_app(app_id);
         }
         warn!(
             "set_state app_id:{} prev state:{:?} state{:?}",
@@ -26,15 +26,15 @@ use ripple_sdk::{
             },
             fb_openrpc::FireboltOpenRpcMethod,
             provider::{
-                FocusRequest, ProviderRequest, ProviderRequestPayload, ProviderResponse,
-                ProviderResponsePayload,
+                ChallengeResponse, FocusRequest, GenericProviderError, ProviderAttributes,
+                ProviderRequest, ProviderRequestPayload, ProviderResponse, ProviderResponsePayload,
             },
         },
         gateway::rpc_gateway_api::{CallContext, CallerSession},
     },
     log::{debug, error, info, warn},
     serde_json,
-    tokio::sync::oneshot,
+    tokio::{self, sync::oneshot, time::sleep},
     utils::channel_utils::oneshot_send_and_log,
     uuid::Uuid,
 };
@@ -42,6 +42,7 @@ use ripple_sdk::{
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use crate::{
@@ -64,6 +65,31 @@ pub struct ProviderBrokerState {
     provider_methods: Arc<RwLock<HashMap<String, ProviderMethod>>>,
     active_sessions: Arc<RwLock<HashMap<String, ProviderSession>>>,
     request_queue: Arc<RwLock<ArrayVec<ProviderBrokerRequest, REQUEST_QUEUE_CAPACITY>>>,
+    challenge_timeouts: Arc<RwLock<HashMap<String, u64>>>,
+}
+
+impl ProviderBrokerState {
+    pub fn new(challenge_timeouts: HashMap<String, u64>) -> Self {
+        Self {
+            challenge_timeouts: Arc::new(RwLock::new(challenge_timeouts)),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_challenge_timeout(&self, capability: String, timeout_ms: u64) {
+        self.challenge_timeouts
+            .write()
+            .unwrap()
+            .insert(capability, timeout_ms);
+    }
+
+    fn get_challenge_timeout(&self, capability: &str) -> Option<u64> {
+        self.challenge_timeouts
+            .read()
+            .unwrap()
+            .get(capability)
+            .cloned()
+    }
 }
 
 impl std::fmt::Debug for ProviderBrokerState {
@@ -86,6 +112,7 @@ struct ProviderSession {
     provider: ProviderMethod,
     _capability: String,
     focused: bool,
+    attributes: Option<Arc<ProviderAttributes>>,
 }
 
 #[derive(Debug)]
@@ -242,8 +269,14 @@ impl ProviderBroker {
             let event_name = provider_method.event_name.clone();
             let req_params = request.request.clone();
             let app_id_opt = request.app_id.clone();
+            let timeout = pst
+                .provider_broker_state
+                .get_challenge_timeout(&Self::get_challenged_capability(&request));
             let c_id =
                 ProviderBroker::start_provider_session(pst, request, provider_method.clone());
+            if let Some(timeout_ms) = timeout {
+                ProviderBroker::start_session_timer(pst, c_id.clone(), timeout_ms);
+            }
             if let Some(app_id) = app_id_opt {
                 debug!("Sending request to specific app {}", app_id);
                 AppEvents::emit_to_app(
@@ -296,6 +329,7 @@ impl ProviderBroker {
                     tx: request.tx,
                 },
                 provider,
+                attributes: request.request.get_attributes(),
                 _capability: request.capability,
                 focused: false,
             },
@@ -303,6 +337,41 @@ impl ProviderBroker {
         c_id
     }
 
+    fn get_challenged_capability(request: &ProviderBrokerRequest) -> String {
+        match &request.request {
+            ProviderRequestPayload::AckChallenge(challenge) => challenge.capability.clone(),
+            ProviderRequestPayload::PinChallenge(challenge) => challenge
+                .capability
+                .clone()
+                .unwrap_or_else(|| request.capability.clone()),
+            _ => request.capability.clone(),
+        }
+    }
+
+    fn start_session_timer(pst: &PlatformState, c_id: String, timeout_ms: u64) {
+        let pst = pst.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(timeout_ms)).await;
+            let session = pst
+                .provider_broker_state
+                .active_sessions
+                .write()
+                .unwrap()
+                .remove(&c_id);
+            if let Some(session) = session {
+                warn!(
+                    "Provider session {} timed out after {}ms, leaving challenge unanswered",
+                    c_id, timeout_ms
+                );
+                oneshot_send_and_log(
+                    session.caller.tx,
+                    ProviderResponsePayload::ChallengeResponse(ChallengeResponse { granted: None }),
+                    "ProviderTimeout",
+                );
+            }
+        });
+    }
+
     fn queue_provider_request(pst: &PlatformState, request: ProviderBrokerRequest) {
         // Remove any duplicate requests.
         ProviderBroker::remove_request(pst, &request.capability);
@@ -321,9 +390,35 @@ impl ProviderBroker {
             resp.correlation_id, resp.result
         );
         let mut active_sessions = pst.provider_broker_state.active_sessions.write().unwrap();
+        let mut result = resp.result;
+        if let Some(attributes) = active_sessions
+            .get(&resp.correlation_id)
+            .and_then(|session| session.attributes.clone())
+        {
+            if let Err(e) = attributes.validate_response(&result) {
+                error!(
+                    "Rejected provider response {}: expected {} or {} but got {} {:?}",
+                    resp.correlation_id,
+                    attributes.response_payload_type,
+                    attributes.error_payload_type,
+                    result.payload_type(),
+                    e
+                );
+                // the caller still gets an answer, the session ends with an error
+                result = ProviderResponsePayload::GenericError(GenericProviderError {
+                    code: e.to_jsonrpc_code(),
+                    message: format!(
+                        "Provider responded with {} instead of {}",
+                        result.payload_type(),
+                        attributes.response_payload_type
+                    ),
+                    data: None,
+                });
+            }
+        }
         match active_sessions.remove(&resp.correlation_id) {
             Some(session) => {
-                oneshot_send_and_log(session.caller.tx, resp.result, "ProviderResponse");
+                oneshot_send_and_log(session.caller.tx, result, "ProviderResponse");
                 if session.focused {
                     let app_id = session.provider.provider.app_id;
                     let event = LifecycleManagementEventRequest::Provide(
@@ -429,3 +524,138 @@ impl ProviderBroker {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::MockRuntime;
+    use ripple_sdk::{
+        api::firebolt::{
+            fb_keyboard::KeyboardSessionResponse,
+            provider::{
+                Challenge, ChallengeRequestor, ACK_CHALLENGE_CAPABILITY, ACK_CHALLENGE_EVENT,
+            },
+        },
+        utils::error::RippleError,
+    };
+
+    const ACK_CHALLENGE_METHOD: &str = "acknowledgechallenge.challenge";
+
+    fn ack_challenge_request(
+        capability: &str,
+        tx: oneshot::Sender<ProviderResponsePayload>,
+    ) -> ProviderBrokerRequest {
+        ProviderBrokerRequest {
+            capability: ACK_CHALLENGE_CAPABILITY.to_owned(),
+            method: ACK_CHALLENGE_METHOD.to_owned(),
+            caller: CallerSession::default(),
+            request: ProviderRequestPayload::AckChallenge(Challenge {
+                capability: capability.to_owned(),
+                requestor: ChallengeRequestor {
+                    id: "app_id".to_owned(),
+                    name: "app_name".to_owned(),
+                },
+            }),
+            tx,
+            app_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_challenge_timeout_per_capability() {
+        let runtime = MockRuntime::new();
+        let pst = runtime.platform_state;
+        ProviderBroker::register_or_unregister_provider(
+            &pst,
+            ACK_CHALLENGE_CAPABILITY.to_owned(),
+            ACK_CHALLENGE_METHOD.to_owned(),
+            ACK_CHALLENGE_EVENT.to_owned(),
+            runtime.call_context,
+            ListenRequest { listen: true },
+        )
+        .await;
+
+        let short_cap = "xrn:firebolt:capability:test:short";
+        let long_cap = "xrn:firebolt:capability:test:long";
+        pst.provider_broker_state
+            .set_challenge_timeout(short_cap.to_owned(), 10);
+        pst.provider_broker_state
+            .set_challenge_timeout(long_cap.to_owned(), 60000);
+
+        let (short_tx, short_rx) = oneshot::channel();
+        let (long_tx, mut long_rx) = oneshot::channel();
+        ProviderBroker::invoke_method(&pst, ack_challenge_request(short_cap, short_tx)).await;
+        ProviderBroker::invoke_method(&pst, ack_challenge_request(long_cap, long_tx)).await;
+
+        let response = tokio::time::timeout(Duration::from_secs(1), short_rx)
+            .await
+            .expect("short challenge should time out")
+            .unwrap();
+        assert_eq!(response.as_challenge_response().unwrap().granted, None);
+        assert!(long_rx.try_recv().is_err());
+        assert_eq!(
+            pst.provider_broker_state
+                .active_sessions
+                .read()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_response_type_mismatch_rejected() {
+        let runtime = MockRuntime::new();
+        let pst = runtime.platform_state;
+        ProviderBroker::register_or_unregister_provider(
+            &pst,
+            ACK_CHALLENGE_CAPABILITY.to_owned(),
+            ACK_CHALLENGE_METHOD.to_owned(),
+            ACK_CHALLENGE_EVENT.to_owned(),
+            runtime.call_context,
+            ListenRequest { listen: true },
+        )
+        .await;
+
+        let (tx, rx) = oneshot::channel();
+        ProviderBroker::invoke_method(
+            &pst,
+            ack_challenge_request("xrn:firebolt:capability:test:mismatch", tx),
+        )
+        .await;
+        let correlation_id = pst
+            .provider_broker_state
+            .active_sessions
+            .read()
+            .unwrap()
+            .keys()
+            .next()
+            .cloned()
+            .unwrap();
+
+        ProviderBroker::provider_response(
+            &pst,
+            ProviderResponse {
+                correlation_id: correlation_id.clone(),
+                result: ProviderResponsePayload::KeyboardResult(KeyboardSessionResponse {
+                    text: "text".to_owned(),
+                    canceled: false,
+                }),
+            },
+        )
+        .await;
+        match rx.await.unwrap() {
+            ProviderResponsePayload::GenericError(error) => {
+                assert_eq!(error.code, RippleError::InvalidOutput.to_jsonrpc_code())
+            }
+            payload => panic!("expected an error, got {:?}", payload),
+        }
+        assert!(pst
+            .provider_broker_state
+            .active_sessions
+            .read()
+            .unwrap()
+            .get(&correlation_id)
+            .is_none());
+    }
+}
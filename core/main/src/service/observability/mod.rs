@@ -1,11 +1,8 @@
-use std::sync::Arc;
+use ripple_sdk::{api::firebolt::fb_telemetry::OperationalMetricRequest, log::debug};
 
-use crate::state::platform_state::PlatformState;
-use ripple_sdk::api::firebolt::fb_telemetry::OperationalMetricRequest;
-static mut PLATFORM_STATE: Option<Arc<PlatformState>> = None;
 pub struct ObservabilityClient {}
 impl ObservabilityClient {
-    pub fn report(platform_state: &PlatformState, payload: OperationalMetricRequest) {
-        println!("payload: {:?}", payload);
+    pub fn report(payload: OperationalMetricRequest) {
+        debug!("payload: {:?}", payload);
     }
 }
@@ -30,7 +30,7 @@ use ripple_sdk::{
             device_user_grants_data::{
                 AutoApplyPolicy, GrantActiveState, GrantEntry, GrantLifespan, GrantPolicy,
                 GrantPrivacySetting, GrantScope, GrantStateModify, GrantStatus, GrantStep,
-                PolicyPersistenceType,
+                PolicyPersistenceType, PreGrant,
             },
         },
         distributor::distributor_usergrants::{
@@ -44,25 +44,28 @@ use ripple_sdk::{
             fb_lifecycle::LifecycleState,
             fb_openrpc::{CapabilitySet, FireboltOpenRpcMethod},
             fb_pin::{PinChallengeConfiguration, PinChallengeRequest},
+            fb_telemetry::OperationalMetricRequest,
             provider::{
                 Challenge, ChallengeRequestor, ProviderRequestPayload, ProviderResponsePayload,
             },
         },
         gateway::rpc_gateway_api::{AppIdentification, CallerSession},
         manifest::device_manifest::DeviceManifest,
+        observability::log_signal::{ContextAsJson, LogSignal},
         usergrant_entry::UserGrantInfo,
     },
     framework::file_store::FileStore,
     log::{debug, error, trace, warn},
     serde_json::Value,
-    tokio::sync::oneshot,
+    tokio::sync::{mpsc, oneshot},
     utils::error::RippleError,
 };
 use serde::Deserialize;
 
 use crate::{
     firebolt::{firebolt_gatekeeper::FireboltGatekeeper, handlers::privacy_rpc::PrivacyImpl},
-    state::{cap::cap_state::CapState, platform_state::PlatformState},
+    service::observability::ObservabilityClient,
+    state::{cap::cap_state::CapState, metrics_state::MetricsState, platform_state::PlatformState},
 };
 
 use super::apps::provider_broker::{ProviderBroker, ProviderBrokerRequest};
@@ -70,16 +73,105 @@ use super::apps::provider_broker::{ProviderBroker, ProviderBrokerRequest};
 pub struct UserGrants {}
 
 type GrantAppMap = Arc<RwLock<FileStore<HashMap<String, HashSet<GrantEntry>>>>>;
+/// Permissions of each app waiting for a user grant, counted per concurrent request
+type PendingGrantMap = Arc<RwLock<HashMap<String, HashMap<FireboltPermission, usize>>>>;
+
+/// Cumulative counter of grant changes, tagged with the capability, role and status transition
+pub const GRANT_CHANGE_COUNTER: &str = "grant_change";
+
+/// Record of a change to a grant stored in the grant state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrantAuditEntry {
+    pub timestamp: Duration,    // Duration since Unix epoch
+    pub app_id: Option<String>, // None is for device
+    pub capability: String,
+    pub role: CapabilityRole,
+    pub previous_status: Option<GrantStatus>, // None when there was no stored grant
+    pub status: Option<GrantStatus>,          // None when the grant was removed
+}
+
+impl GrantAuditEntry {
+    fn status_str(status: &Option<GrantStatus>) -> &'static str {
+        status.as_ref().map_or("none", |s| s.as_string())
+    }
+
+    // app ids are left out so the number of counters stays bounded
+    fn get_tags(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("capability".to_owned(), self.capability.clone()),
+            ("role".to_owned(), self.role.as_string().to_owned()),
+            (
+                "previous_status".to_owned(),
+                Self::status_str(&self.previous_status).to_owned(),
+            ),
+            (
+                "status".to_owned(),
+                Self::status_str(&self.status).to_owned(),
+            ),
+        ])
+    }
+}
+
+impl std::fmt::Display for GrantAuditEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "app_name={}, capability={}, role={}, status={}->{}",
+            self.app_id.as_deref().unwrap_or("device"),
+            self.capability,
+            self.role.as_string(),
+            Self::status_str(&self.previous_status),
+            Self::status_str(&self.status)
+        )
+    }
+}
+
+impl ContextAsJson for GrantAuditEntry {
+    fn as_json(&self) -> Value {
+        let mut map = serde_json::Map::new();
+        for (key, value) in self.get_tags() {
+            map.insert(key, Value::String(value));
+        }
+        map.insert(
+            "app_name".to_owned(),
+            Value::String(self.app_id.clone().unwrap_or_else(|| "device".to_owned())),
+        );
+        map.insert(
+            "timestamp".to_owned(),
+            Value::Number((self.timestamp.as_millis() as u64).into()),
+        );
+        Value::Object(map)
+    }
+}
+
+/// Keeps the entries for which `keep` returns true, returning the removed entries
+fn retain_entries<F>(entries: &mut HashSet<GrantEntry>, mut keep: F) -> Vec<GrantEntry>
+where
+    F: FnMut(&GrantEntry) -> bool,
+{
+    let mut removed = Vec::new();
+    entries.retain(|entry| {
+        let kept = keep(entry);
+        if !kept {
+            removed.push(entry.clone());
+        }
+        kept
+    });
+    removed
+}
 
 #[derive(Debug, Clone)]
 pub struct GrantState {
     device_grants: Arc<RwLock<FileStore<HashSet<GrantEntry>>>>,
     grant_app_map: GrantAppMap,
     caps_needing_grants: Vec<String>,
+    pending_grants: PendingGrantMap,
+    audit_sink: Arc<RwLock<Option<mpsc::Sender<GrantAuditEntry>>>>,
+    metrics: MetricsState,
 }
 
 impl GrantState {
-    pub fn new(manifest: DeviceManifest) -> GrantState {
+    pub fn new(manifest: DeviceManifest, metrics: MetricsState) -> GrantState {
         let saved_dir = manifest.clone().configuration.saved_dir;
         let dir_path = Path::new(&saved_dir).join("device_grants");
         let device_grant_path = dir_path.into_os_string().into_string();
@@ -96,10 +188,23 @@ impl GrantState {
             FileStore::new(app_grant_path.unwrap(), HashMap::new())
         };
 
-        GrantState {
+        let grant_state = GrantState {
             grant_app_map: Arc::new(RwLock::new(app_grant_store)),
             caps_needing_grants: manifest.get_caps_requiring_grant(),
             device_grants: Arc::new(RwLock::new(dev_grant_store)),
+            pending_grants: Arc::new(RwLock::new(HashMap::new())),
+            audit_sink: Arc::new(RwLock::new(None)),
+            metrics,
+        };
+        grant_state.seed_pre_grants(&manifest.get_pre_grants());
+        grant_state
+    }
+
+    /// Stores the grants configured in the device manifest so they are not challenged.
+    pub fn seed_pre_grants(&self, pre_grants: &[PreGrant]) {
+        for pre_grant in pre_grants {
+            debug!("Seeding pre grant {:?}", pre_grant);
+            self.update_grant_entry(pre_grant.app_id.clone(), pre_grant.as_grant_entry());
         }
     }
 
@@ -270,51 +375,119 @@ impl GrantState {
         }
     }
 
+    /// Sets the sender which receives a [GrantAuditEntry] for every change to the stored grants.
+    pub fn set_audit_sink(&self, sink: mpsc::Sender<GrantAuditEntry>) {
+        let _ = self.audit_sink.write().unwrap().replace(sink);
+    }
+
+    /// Records the transition of a grant from `previous_status` to the status of `entry`,
+    /// as a log signal and a counter on the observability path and on the audit sink.
+    fn audit(
+        &self,
+        app_id: Option<String>,
+        entry: &GrantEntry,
+        previous_status: Option<GrantStatus>,
+    ) {
+        if previous_status.is_none() && entry.status.is_none() {
+            return;
+        }
+        let audit_entry = GrantAuditEntry {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
+            app_id,
+            capability: entry.capability.clone(),
+            role: entry.role,
+            previous_status,
+            status: entry.status.clone(),
+        };
+        LogSignal::new(
+            "user_grants".to_string(),
+            "grant_changed".into(),
+            audit_entry.clone(),
+        )
+        .emit();
+        let counter = self
+            .metrics
+            .increment_counter(GRANT_CHANGE_COUNTER, audit_entry.get_tags());
+        ObservabilityClient::report(OperationalMetricRequest::Counter(counter));
+        if let Some(sink) = self.audit_sink.read().unwrap().as_ref() {
+            if let Err(e) = sink.try_send(audit_entry) {
+                error!("Couldnt record grant audit entry {:?}", e);
+            }
+        }
+    }
+
     pub fn update_grant_entry(
         &self,
         app_id: Option<String>, // None is for device
         new_entry: GrantEntry,
     ) {
         if let Some(app_id) = app_id {
-            let mut grant_state = self.grant_app_map.write().unwrap();
-            //Get a mutable reference to the value associated with a key, create it if it doesn't exist,
-            let entries = grant_state.value.entry(app_id).or_default();
-            if entries.contains(&new_entry) {
-                entries.remove(&new_entry);
-            }
-            if new_entry.status.is_some() {
-                entries.insert(new_entry);
-            }
-            grant_state.sync();
+            let previous_status = {
+                let mut grant_state = self.grant_app_map.write().unwrap();
+                //Get a mutable reference to the value associated with a key, create it if it doesn't exist,
+                let entries = grant_state.value.entry(app_id.clone()).or_default();
+                let previous = entries.take(&new_entry).and_then(|e| e.status);
+                if new_entry.status.is_some() {
+                    entries.insert(new_entry.clone());
+                }
+                grant_state.sync();
+                previous
+            };
+            self.audit(Some(app_id), &new_entry, previous_status);
         } else {
-            self.add_device_entry(new_entry)
+            let previous_status = self.add_device_entry(new_entry.clone());
+            self.audit(None, &new_entry, previous_status);
+        }
+    }
+
+    /// Audits the removal of the given entries of the app, or of the device for None
+    fn audit_removed(&self, app_id: Option<String>, removed: Vec<GrantEntry>) {
+        for entry in removed {
+            let previous_status = entry.status.clone();
+            let removed = GrantEntry {
+                status: None,
+                ..entry
+            };
+            self.audit(app_id.clone(), &removed, previous_status);
         }
     }
 
     pub fn clear_local_entries(&self, ps: &PlatformState, persistence_type: PolicyPersistenceType) {
-        let mut app_grant_state = self.grant_app_map.write().unwrap();
-        for (_, entries) in app_grant_state.value.iter_mut() {
-            entries.retain(|entry| {
-                !self.check_grant_policy_persistence(
-                    ps,
-                    entry.capability.clone(),
-                    entry.role,
-                    persistence_type.clone(),
-                )
-            });
+        let keep = |entry: &GrantEntry| !self.is_entry_persisted_in(ps, entry, &persistence_type);
+        let mut cleared = Vec::new();
+        {
+            let mut app_grant_state = self.grant_app_map.write().unwrap();
+            for (app_id, entries) in app_grant_state.value.iter_mut() {
+                cleared.push((Some(app_id.clone()), retain_entries(entries, keep)));
+            }
+            app_grant_state.sync();
+        }
+        {
+            let mut device_grant_state = self.device_grants.write().unwrap();
+            cleared.push((None, retain_entries(&mut device_grant_state.value, keep)));
+            device_grant_state.sync();
         }
-        app_grant_state.sync();
 
-        let mut device_grant_state = self.device_grants.write().unwrap();
-        device_grant_state.value.retain(|entry: &GrantEntry| {
-            !self.check_grant_policy_persistence(
+        for (app_id, removed) in cleared {
+            self.audit_removed(app_id, removed);
+        }
+    }
+
+    fn is_entry_persisted_in(
+        &self,
+        ps: &PlatformState,
+        entry: &GrantEntry,
+        persistence_type: &PolicyPersistenceType,
+    ) -> bool {
+        match &entry.persistence {
+            Some(persistence) => persistence == persistence_type,
+            None => self.check_grant_policy_persistence(
                 ps,
                 entry.capability.clone(),
                 entry.role,
                 persistence_type.clone(),
-            )
-        });
-        device_grant_state.sync();
+            ),
+        }
     }
 
     pub fn check_grant_policy_persistence(
@@ -346,34 +519,45 @@ impl GrantState {
     where
         F: FnMut(&GrantEntry) -> bool,
     {
-        let mut deleted = false;
-        let mut grant_state = self.grant_app_map.write().unwrap();
-        let entries = match grant_state.value.get_mut(&app_id) {
-            Some(entries) => entries,
-            None => return false,
+        let removed = {
+            let mut grant_state = self.grant_app_map.write().unwrap();
+            let entries = match grant_state.value.get_mut(&app_id) {
+                Some(entries) => entries,
+                None => return false,
+            };
+            let removed = retain_entries(entries, restrict_function);
+            grant_state.sync();
+            removed
         };
-        let prev_len = entries.len();
-        entries.retain(restrict_function);
-        if entries.len() < prev_len {
-            deleted = true;
-        }
-        grant_state.sync();
+        let deleted = !removed.is_empty();
+        self.audit_removed(Some(app_id), removed);
         deleted
     }
 
+    /// Revokes the grants of the app which only last while the app is active, called when the
+    /// app is unloaded or its last session ends.
+    pub fn cleanup_for_app(&self, app_id: &str) -> bool {
+        debug!("Clearing app active grants for {}", app_id);
+        self.custom_delete_entries(app_id.to_owned(), |entry| {
+            !matches!(entry.lifespan, Some(GrantLifespan::AppActive))
+        })
+    }
+
     /**
      *  Delete all matching entries based on the lifespan
      */
     pub fn delete_all_entries_for_lifespan(&self, lifespan: &GrantLifespan) -> bool {
+        let keep = |entry: &GrantEntry| entry.lifespan.as_ref().map_or(false, |l| l != lifespan);
         let mut deleted = false;
+        let mut removed = Vec::new();
         {
             let mut grant_state = self.grant_app_map.write().unwrap();
 
-            for set in grant_state.value.values_mut() {
-                let prev_len = set.len();
-                set.retain(|entry| entry.lifespan.as_ref().map_or(false, |l| l != lifespan));
-                if set.len() < prev_len {
+            for (app_id, set) in grant_state.value.iter_mut() {
+                let app_removed = retain_entries(set, keep);
+                if !app_removed.is_empty() {
                     deleted = true;
+                    removed.push((Some(app_id.clone()), app_removed));
                 }
             }
             if deleted {
@@ -382,12 +566,10 @@ impl GrantState {
         }
         {
             let mut grant_state = self.device_grants.write().unwrap();
-            let prev_len = grant_state.value.len();
-            grant_state
-                .value
-                .retain(|entry| entry.lifespan.as_ref().map_or(false, |l| l != lifespan));
-            if grant_state.value.len() < prev_len {
+            let device_removed = retain_entries(&mut grant_state.value, keep);
+            if !device_removed.is_empty() {
                 deleted = true;
+                removed.push((None, device_removed));
             }
 
             if deleted {
@@ -395,58 +577,60 @@ impl GrantState {
             }
         }
 
+        for (app_id, removed) in removed {
+            self.audit_removed(app_id, removed);
+        }
         deleted
     }
 
     pub fn delete_expired_entries_for_app(&self, app_id: String) -> bool {
-        let mut deleted = false;
-        let mut grant_state = self.grant_app_map.write().unwrap();
-        let entries = match grant_state.value.get_mut(&app_id) {
-            Some(entries) => entries,
-            None => return false,
-        };
-        let prev_len = entries.len();
-        entries.retain(|entry| !entry.has_expired());
-        if entries.len() < prev_len {
-            deleted = true;
-        }
-        grant_state.sync();
-        deleted
+        self.custom_delete_entries(app_id, |entry| !entry.has_expired())
     }
 
     pub fn delete_expired_entries_for_device(&self) -> bool {
-        let mut deleted = false;
-        let mut grant_state = self.device_grants.write().unwrap();
-        let prev_len = grant_state.value.len();
-        grant_state.value.retain(|entry| !entry.has_expired());
-        if grant_state.value.len() < prev_len {
-            deleted = true;
-        }
-        grant_state.sync();
+        let removed = {
+            let mut grant_state = self.device_grants.write().unwrap();
+            let removed = retain_entries(&mut grant_state.value, |entry| !entry.has_expired());
+            grant_state.sync();
+            removed
+        };
+        let deleted = !removed.is_empty();
+        self.audit_removed(None, removed);
         deleted
     }
 
     pub fn delete_all_expired_entries(&self) -> bool {
         // delete expired entries for app
-        let mut grant_state = self.grant_app_map.write().unwrap();
-        for (_, entries) in grant_state.value.iter_mut() {
-            entries.retain(|entry| !entry.has_expired());
+        let mut removed = Vec::new();
+        {
+            let mut grant_state = self.grant_app_map.write().unwrap();
+            for (app_id, entries) in grant_state.value.iter_mut() {
+                removed.push((
+                    app_id.clone(),
+                    retain_entries(entries, |entry| !entry.has_expired()),
+                ));
+            }
+            grant_state.sync();
+        }
+        for (app_id, removed) in removed {
+            self.audit_removed(Some(app_id), removed);
         }
-        grant_state.sync();
 
         // delete expired entries for device
         self.delete_expired_entries_for_device();
         true
     }
 
-    fn add_device_entry(&self, entry: GrantEntry) {
+    // Returns the status of the device grant which was replaced or removed
+    fn add_device_entry(&self, entry: GrantEntry) -> Option<GrantStatus> {
         let mut device_grants = self.device_grants.write().unwrap();
-        if entry.status.is_none() {
-            device_grants.value.remove(&entry);
+        let previous = if entry.status.is_none() {
+            device_grants.value.take(&entry)
         } else {
-            device_grants.value.replace(entry);
-        }
+            device_grants.value.replace(entry)
+        };
         device_grants.sync();
+        previous.and_then(|e| e.status)
     }
 
     pub fn get_grant_status(
@@ -479,6 +663,108 @@ impl GrantState {
         None
     }
 
+    /// Tracks a permission the app has requested while the user grant is being resolved.
+    /// Concurrent requests for the same permission are counted, each has to be resolved.
+    pub fn add_pending_grant(&self, app_id: &str, permission: &FireboltPermission) {
+        *self
+            .pending_grants
+            .write()
+            .unwrap()
+            .entry(app_id.to_owned())
+            .or_default()
+            .entry(permission.clone())
+            .or_default() += 1;
+    }
+
+    /// Resolves one request added with [GrantState::add_pending_grant], the permission stays
+    /// pending while other requests for it are still being resolved.
+    pub fn resolve_pending_grant(&self, app_id: &str, permission: &FireboltPermission) {
+        let mut pending_grants = self.pending_grants.write().unwrap();
+        if let Some(permissions) = pending_grants.get_mut(app_id) {
+            if let Some(count) = permissions.get_mut(permission) {
+                *count -= 1;
+                if *count == 0 {
+                    permissions.remove(permission);
+                }
+            }
+            if permissions.is_empty() {
+                pending_grants.remove(app_id);
+            }
+        }
+    }
+
+    /// Returns the permissions requested by the app which are not granted or denied yet.
+    pub fn get_pending_grants(&self, app_id: &str) -> Vec<FireboltPermission> {
+        self.pending_grants
+            .read()
+            .unwrap()
+            .get(app_id)
+            .map(|permissions| permissions.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Applies the lifespan rules of a grant after it has been used, removing it when its
+    /// lifespan is Once. Returns true if the grant was consumed.
+    pub fn consume(&self, app_id: Option<String>, permission: &FireboltPermission) -> bool {
+        let entry = GrantEntry::get(permission.role, permission.cap.as_str());
+        let is_once = |e: &GrantEntry| matches!(e.lifespan, Some(GrantLifespan::Once));
+        if let Some(app_id) = app_id {
+            let mut grant_state = self.grant_app_map.write().unwrap();
+            let consumed = match grant_state.value.get_mut(&app_id) {
+                Some(entries) => match entries.get(&entry) {
+                    Some(stored) if is_once(stored) => entries.remove(&entry),
+                    _ => false,
+                },
+                None => false,
+            };
+            if consumed {
+                grant_state.sync();
+            }
+            consumed
+        } else {
+            let mut device_grants = self.device_grants.write().unwrap();
+            let consumed = match device_grants.value.get(&entry) {
+                Some(stored) if is_once(stored) => device_grants.value.remove(&entry),
+                _ => false,
+            };
+            if consumed {
+                device_grants.sync();
+            }
+            consumed
+        }
+    }
+
+    /// Resolves the grant status for the permission taking role implication into account.
+    /// An exact match always wins, otherwise an allowed grant for an implying role
+    /// (e.g. Manage for a Use request) is treated as allowed.
+    pub fn get_effective_grant_status(
+        &self,
+        app_id: &str,
+        permission: &FireboltPermission,
+    ) -> Option<GrantStatus> {
+        let result = self.get_grant_status(app_id, permission);
+        if result.is_some() {
+            return result;
+        }
+        [
+            CapabilityRole::Use,
+            CapabilityRole::Manage,
+            CapabilityRole::Provide,
+        ]
+        .iter()
+        .filter(|role| **role != permission.role && role.implies(&permission.role))
+        .find_map(|role| {
+            let implied = FireboltPermission {
+                cap: permission.cap.clone(),
+                role: *role,
+            };
+            match self.get_grant_status(app_id, &implied) {
+                Some(GrantStatus::Allowed) => Some(GrantStatus::Allowed),
+                _ => None,
+            }
+        })
+    }
+
     fn get_generic_grant_status(
         &self,
         role: CapabilityRole,
@@ -581,6 +867,7 @@ impl GrantState {
                     caps: vec![permission.cap.clone()],
                 })?,
                 GrantActiveState::PendingGrant => {
+                    grant_state.add_pending_grant(&app_id, &permission);
                     let result = GrantPolicyEnforcer::determine_grant_policies_for_permission(
                         state,
                         caller_session,
@@ -588,6 +875,7 @@ impl GrantState {
                         &permission,
                     )
                     .await;
+                    grant_state.resolve_pending_grant(&app_id, &permission);
 
                     match result {
                         Ok(_) => {}
@@ -801,6 +1089,7 @@ impl GrantState {
                         last_modified_time: SystemTime::now()
                             .duration_since(SystemTime::UNIX_EPOCH)
                             .unwrap(),
+                        persistence: None,
                     };
 
                     match modify_operation {
@@ -939,6 +1228,7 @@ impl GrantState {
                 last_modified_time: SystemTime::now()
                     .duration_since(SystemTime::UNIX_EPOCH)
                     .unwrap(),
+                persistence: None,
             };
             debug!("user grant modified with new entry:{:?}", new_entry.clone());
             platform_state
@@ -1065,6 +1355,7 @@ impl GrantPolicyEnforcer {
                     },
                     app_name: app_id.to_owned(),
                     lifespan: policy.lifespan.to_owned(),
+                    persistence: None,
                 }
             } else {
                 // Deleting user grant after grant has been remove from grant policy flow goes in here
@@ -1077,6 +1368,7 @@ impl GrantPolicyEnforcer {
                     expiry_time: None,
                     app_name: app_id.to_owned(),
                     lifespan: GrantLifespan::Forever,
+                    persistence: None,
                 }
             };
 
@@ -1965,6 +2257,401 @@ mod tests {
 
     use super::*;
 
+    mod test_grant_state {
+        use super::*;
+        use crate::utils::test_utils::{fb_perm, MockRuntime};
+        use ripple_sdk::tokio;
+
+        #[tokio::test]
+        async fn test_effective_grant_status_manage_implies_use() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state;
+            let app_id = "effective_grant_app";
+            let cap = "xrn:firebolt:capability:test:effective";
+            grant_state.update_grant_entry(
+                Some(app_id.to_owned()),
+                GrantEntry {
+                    status: Some(GrantStatus::Allowed),
+                    ..GrantEntry::get(CapabilityRole::Manage, cap.to_owned())
+                },
+            );
+
+            let use_perm = fb_perm(cap, Some(CapabilityRole::Use));
+            assert_eq!(grant_state.get_grant_status(app_id, &use_perm), None);
+            assert_eq!(
+                grant_state.get_effective_grant_status(app_id, &use_perm),
+                Some(GrantStatus::Allowed)
+            );
+
+            let provide_perm = fb_perm(cap, Some(CapabilityRole::Provide));
+            assert_eq!(
+                grant_state.get_effective_grant_status(app_id, &provide_perm),
+                None
+            );
+        }
+
+        #[tokio::test]
+        async fn test_pre_grants_skip_challenge() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state;
+            let device_cap = "xrn:firebolt:capability:test:predevice";
+            let app_cap = "xrn:firebolt:capability:test:preapp";
+            let pre_grants: Vec<PreGrant> = serde_json::from_value(serde_json::json!([
+                {"capability": device_cap, "status": "Allowed"},
+                {"capability": app_cap, "role": "manage", "status": "Allowed", "appId": "kiosk_app"}
+            ]))
+            .unwrap();
+            grant_state.seed_pre_grants(&pre_grants);
+
+            let device_perm = fb_perm(device_cap, Some(CapabilityRole::Use));
+            assert!(matches!(
+                grant_state.get_grant_state("any_app", &device_perm, None),
+                GrantActiveState::ActiveGrant(Ok(()))
+            ));
+            let app_perm = fb_perm(app_cap, Some(CapabilityRole::Manage));
+            assert!(matches!(
+                grant_state.get_grant_state("kiosk_app", &app_perm, None),
+                GrantActiveState::ActiveGrant(Ok(()))
+            ));
+            assert!(matches!(
+                grant_state.get_grant_state("other_app", &app_perm, None),
+                GrantActiveState::PendingGrant
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_grant_audit_entries() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state;
+            let cap = "xrn:firebolt:capability:test:audit";
+            // entries persisted by earlier runs would show up as the previous status
+            grant_state.update_grant_entry(
+                Some("audit_app".to_owned()),
+                GrantEntry::get(CapabilityRole::Use, cap.to_owned()),
+            );
+            grant_state.update_grant_entry(
+                None,
+                GrantEntry::get(CapabilityRole::Manage, cap.to_owned()),
+            );
+            let (tx, mut rx) = mpsc::channel(4);
+            grant_state.set_audit_sink(tx);
+            grant_state.update_grant_entry(
+                Some("audit_app".to_owned()),
+                GrantEntry {
+                    status: Some(GrantStatus::Allowed),
+                    ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                },
+            );
+            grant_state.update_grant_entry(
+                None,
+                GrantEntry {
+                    status: Some(GrantStatus::Denied),
+                    ..GrantEntry::get(CapabilityRole::Manage, cap.to_owned())
+                },
+            );
+
+            let first = rx.recv().await.unwrap();
+            assert_eq!(first.app_id, Some("audit_app".to_owned()));
+            assert_eq!(first.capability, cap);
+            assert_eq!(first.role, CapabilityRole::Use);
+            assert_eq!(first.previous_status, None);
+            assert_eq!(first.status, Some(GrantStatus::Allowed));
+            let second = rx.recv().await.unwrap();
+            assert_eq!(second.app_id, None);
+            assert_eq!(second.role, CapabilityRole::Manage);
+            assert_eq!(second.previous_status, None);
+            assert_eq!(second.status, Some(GrantStatus::Denied));
+            assert!(second.timestamp >= first.timestamp);
+            assert!(rx.try_recv().is_err());
+        }
+
+        #[tokio::test]
+        async fn test_grant_audit_status_transitions() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state.clone();
+            let (tx, mut rx) = mpsc::channel(4);
+            grant_state.set_audit_sink(tx);
+            let cap = "xrn:firebolt:capability:test:transition";
+            for status in [GrantStatus::Allowed, GrantStatus::Denied] {
+                grant_state.update_grant_entry(
+                    Some("audit_app".to_owned()),
+                    GrantEntry {
+                        status: Some(status),
+                        persistence: Some(PolicyPersistenceType::Account),
+                        ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                    },
+                );
+            }
+            grant_state
+                .clear_local_entries(&runtime.platform_state, PolicyPersistenceType::Account);
+
+            let granted = rx.recv().await.unwrap();
+            assert_eq!(granted.previous_status, None);
+            assert_eq!(granted.status, Some(GrantStatus::Allowed));
+            let denied = rx.recv().await.unwrap();
+            assert_eq!(denied.previous_status, Some(GrantStatus::Allowed));
+            assert_eq!(denied.status, Some(GrantStatus::Denied));
+            assert_eq!(
+                denied.as_json().get("status").unwrap(),
+                &Value::String("denied".to_owned())
+            );
+            let cleared = rx.recv().await.unwrap();
+            assert_eq!(cleared.app_id, Some("audit_app".to_owned()));
+            assert_eq!(cleared.previous_status, Some(GrantStatus::Denied));
+            assert_eq!(cleared.status, None);
+            assert!(rx.try_recv().is_err());
+
+            // the counter is cumulative and not tagged with the app
+            let tags = denied.get_tags();
+            assert!(!tags.contains_key("app_name"));
+            let counter = runtime
+                .platform_state
+                .metrics
+                .get_counter(GRANT_CHANGE_COUNTER, &tags)
+                .unwrap();
+            assert_eq!(counter.value, 1);
+            grant_state.update_grant_entry(
+                Some("other_audit_app".to_owned()),
+                GrantEntry {
+                    status: Some(GrantStatus::Allowed),
+                    ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                },
+            );
+            grant_state.update_grant_entry(
+                Some("other_audit_app".to_owned()),
+                GrantEntry {
+                    status: Some(GrantStatus::Denied),
+                    ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                },
+            );
+            let counter = runtime
+                .platform_state
+                .metrics
+                .get_counter(GRANT_CHANGE_COUNTER, &tags)
+                .unwrap();
+            assert_eq!(counter.value, 2);
+        }
+
+        #[tokio::test]
+        async fn test_grant_audit_removals() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state.clone();
+            let (tx, mut rx) = mpsc::channel(64);
+            let app_id = "removal_app";
+            for (cap, lifespan) in [
+                (
+                    "xrn:firebolt:capability:test:app_active",
+                    GrantLifespan::AppActive,
+                ),
+                (
+                    "xrn:firebolt:capability:test:power_active",
+                    GrantLifespan::PowerActive,
+                ),
+                ("xrn:firebolt:capability:test:once", GrantLifespan::Once),
+            ] {
+                grant_state.update_grant_entry(
+                    Some(app_id.to_owned()),
+                    GrantEntry {
+                        status: Some(GrantStatus::Allowed),
+                        lifespan: Some(lifespan),
+                        ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                    },
+                );
+            }
+            grant_state.set_audit_sink(tx);
+
+            assert!(grant_state.cleanup_for_app(app_id));
+            assert!(grant_state.delete_all_entries_for_lifespan(&GrantLifespan::PowerActive));
+            assert!(grant_state.delete_expired_entries_for_app(app_id.to_owned()));
+            // the grant stores are shared with other tests, so only this app's entries count
+            let mut removed = Vec::new();
+            while let Ok(entry) = rx.try_recv() {
+                if entry.app_id.as_deref() == Some(app_id) {
+                    removed.push(entry);
+                }
+            }
+            assert_eq!(
+                removed
+                    .iter()
+                    .map(|entry| entry.capability.as_str())
+                    .collect::<Vec<_>>(),
+                vec![
+                    "xrn:firebolt:capability:test:app_active",
+                    "xrn:firebolt:capability:test:power_active",
+                    "xrn:firebolt:capability:test:once",
+                ]
+            );
+            for entry in removed {
+                assert_eq!(entry.previous_status, Some(GrantStatus::Allowed));
+                assert_eq!(entry.status, None);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_cleanup_app_active_grants() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state;
+            let app_id = "app_active_app";
+            let permission = |cap: &str| FireboltPermission {
+                cap: FireboltCap::Full(cap.to_owned()),
+                role: CapabilityRole::Use,
+            };
+            let active_cap = "xrn:firebolt:capability:test:active";
+            let forever_cap = "xrn:firebolt:capability:test:forever";
+            for (cap, lifespan) in [
+                (active_cap, GrantLifespan::AppActive),
+                (forever_cap, GrantLifespan::Forever),
+            ] {
+                grant_state.update_grant_entry(
+                    Some(app_id.to_owned()),
+                    GrantEntry {
+                        status: Some(GrantStatus::Allowed),
+                        lifespan: Some(lifespan),
+                        ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                    },
+                );
+            }
+            assert_eq!(
+                grant_state.get_grant_status(app_id, &permission(active_cap)),
+                Some(GrantStatus::Allowed)
+            );
+
+            assert!(grant_state.cleanup_for_app(app_id));
+            assert_eq!(
+                grant_state.get_grant_status(app_id, &permission(active_cap)),
+                None
+            );
+            assert_eq!(
+                grant_state.get_grant_status(app_id, &permission(forever_cap)),
+                Some(GrantStatus::Allowed)
+            );
+            assert!(!grant_state.cleanup_for_app(app_id));
+        }
+
+        #[tokio::test]
+        async fn test_get_grant_entries_for_app_id() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state;
+            let app_id = "grant_entries_app";
+            for cap in [
+                "xrn:firebolt:capability:test:first",
+                "xrn:firebolt:capability:test:second",
+            ] {
+                grant_state.update_grant_entry(
+                    Some(app_id.to_owned()),
+                    GrantEntry {
+                        status: Some(GrantStatus::Allowed),
+                        lifespan: Some(GrantLifespan::Forever),
+                        ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                    },
+                );
+            }
+
+            assert_eq!(
+                grant_state
+                    .get_grant_entries_for_app_id(app_id.to_owned())
+                    .len(),
+                2
+            );
+            assert!(grant_state
+                .get_grant_entries_for_app_id("unknown_app".to_owned())
+                .is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_consume_applies_lifespan() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state;
+            let app_id = "consume_app";
+            let once_cap = "xrn:firebolt:capability:test:once";
+            let forever_cap = "xrn:firebolt:capability:test:forever";
+            for (cap, lifespan) in [
+                (once_cap, GrantLifespan::Once),
+                (forever_cap, GrantLifespan::Forever),
+            ] {
+                grant_state.update_grant_entry(
+                    Some(app_id.to_owned()),
+                    GrantEntry {
+                        status: Some(GrantStatus::Allowed),
+                        lifespan: Some(lifespan),
+                        ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                    },
+                );
+            }
+
+            let once_perm = fb_perm(once_cap, None);
+            let forever_perm = fb_perm(forever_cap, None);
+            assert!(grant_state.consume(Some(app_id.to_owned()), &once_perm));
+            assert!(!grant_state.consume(Some(app_id.to_owned()), &forever_perm));
+
+            let grant_app_map = grant_state.grant_app_map.read().unwrap();
+            let entries = grant_app_map.value.get(app_id).unwrap();
+            assert!(!entries.contains(&GrantEntry::get(CapabilityRole::Use, once_cap.to_owned())));
+            assert!(entries.contains(&GrantEntry::get(
+                CapabilityRole::Use,
+                forever_cap.to_owned()
+            )));
+        }
+
+        #[tokio::test]
+        async fn test_clear_local_entries_per_grant_persistence() {
+            let runtime = MockRuntime::new();
+            let platform_state = runtime.platform_state;
+            let grant_state = platform_state.cap_state.grant_state.clone();
+            let app_id = "persistence_app";
+            let account_cap = "xrn:firebolt:capability:test:account";
+            let device_cap = "xrn:firebolt:capability:test:device";
+            for (cap, persistence) in [
+                (account_cap, PolicyPersistenceType::Account),
+                (device_cap, PolicyPersistenceType::Device),
+            ] {
+                grant_state.update_grant_entry(
+                    Some(app_id.to_owned()),
+                    GrantEntry {
+                        status: Some(GrantStatus::Allowed),
+                        lifespan: Some(GrantLifespan::Forever),
+                        persistence: Some(persistence),
+                        ..GrantEntry::get(CapabilityRole::Use, cap.to_owned())
+                    },
+                );
+            }
+
+            grant_state.clear_local_entries(&platform_state, PolicyPersistenceType::Account);
+
+            let account_perm = fb_perm(account_cap, None);
+            let device_perm = fb_perm(device_cap, None);
+            assert_eq!(grant_state.get_grant_status(app_id, &account_perm), None);
+            assert_eq!(
+                grant_state.get_grant_status(app_id, &device_perm),
+                Some(GrantStatus::Allowed)
+            );
+        }
+
+        #[tokio::test]
+        async fn test_pending_grants() {
+            let runtime = MockRuntime::new();
+            let grant_state = runtime.platform_state.cap_state.grant_state;
+            let app_id = "pending_app";
+            let perm = fb_perm("xrn:firebolt:capability:test:pending", None);
+
+            assert!(grant_state.get_pending_grants(app_id).is_empty());
+            grant_state.add_pending_grant(app_id, &perm);
+            assert_eq!(grant_state.get_pending_grants(app_id), vec![perm.clone()]);
+            assert!(grant_state.get_pending_grants("other_app").is_empty());
+
+            grant_state.resolve_pending_grant(app_id, &perm);
+            assert!(grant_state.get_pending_grants(app_id).is_empty());
+
+            // a permission requested twice stays pending until both requests resolve
+            grant_state.add_pending_grant(app_id, &perm);
+            grant_state.add_pending_grant(app_id, &perm);
+            grant_state.resolve_pending_grant(app_id, &perm);
+            assert_eq!(grant_state.get_pending_grants(app_id), vec![perm.clone()]);
+            grant_state.resolve_pending_grant(app_id, &perm);
+            assert!(grant_state.get_pending_grants(app_id).is_empty());
+        }
+    }
+
     mod test_grant_policy_enforcer {
         use super::*;
         use crate::{
@@ -23,7 +23,7 @@ use std::{
 
 use crate::{
     service::{apps::app_events::AppEvents, user_grants::GrantState},
-    state::platform_state::PlatformState,
+    state::{metrics_state::MetricsState, platform_state::PlatformState},
 };
 use ripple_sdk::{api::firebolt::fb_capabilities::RolePermission, serde_json};
 use ripple_sdk::{
@@ -57,12 +57,12 @@ pub struct CapState {
 }
 
 impl CapState {
-    pub fn new(manifest: DeviceManifest) -> Self {
+    pub fn new(manifest: DeviceManifest, metrics: MetricsState) -> Self {
         CapState {
             generic: GenericCapState::new(manifest.clone()),
             permitted_state: PermittedState::new(manifest.clone()),
             primed_listeners: Arc::new(RwLock::new(HashSet::new())),
-            grant_state: GrantState::new(manifest),
+            grant_state: GrantState::new(manifest, metrics),
         }
     }
 
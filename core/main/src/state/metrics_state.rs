@@ -18,6 +18,7 @@
 use std::{
     collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use jsonrpsee::tracing::debug;
@@ -26,7 +27,10 @@ use ripple_sdk::{
         context::RippleContextUpdateRequest,
         device::device_info_request::{DeviceInfoRequest, DeviceResponse, FirmwareInfo},
         distributor::distributor_privacy::{DataEventType, PrivacySettingsData},
-        firebolt::{fb_metrics::MetricsContext, fb_openrpc::FireboltSemanticVersion},
+        firebolt::{
+            fb_metrics::{Counter, MetricsContext},
+            fb_openrpc::FireboltSemanticVersion,
+        },
         gateway::rpc_gateway_api::rpc_value_result_to_string_result,
         manifest::device_manifest::DataGovernanceConfig,
         observability::metrics_util::ApiStats,
@@ -68,6 +72,7 @@ pub struct MetricsState {
     pub context: Arc<RwLock<MetricsContext>>,
     operational_telemetry_listeners: Arc<RwLock<HashSet<String>>>,
     api_stats_map: Arc<RwLock<HashMap<String, ApiStats>>>,
+    counters: Arc<RwLock<HashMap<String, Counter>>>,
 }
 
 impl MetricsState {
@@ -477,6 +482,28 @@ impl MetricsState {
         Self::send_context_update_request(state);
     }
 
+    fn get_counter_key(name: &str, tags: &HashMap<String, String>) -> String {
+        let mut tags: Vec<_> = tags.iter().collect();
+        tags.sort();
+        format!("{}{:?}", name, tags)
+    }
+
+    /// Increments the counter for the given name and tags, returning its updated value
+    pub fn increment_counter(&self, name: &str, tags: HashMap<String, String>) -> Counter {
+        let key = Self::get_counter_key(name, &tags);
+        let mut counters = self.counters.write().unwrap();
+        let counter = counters
+            .entry(key)
+            .or_insert_with(|| Counter::new(name.to_string(), 0, Some(tags)));
+        counter.increment();
+        counter.clone()
+    }
+
+    pub fn get_counter(&self, name: &str, tags: &HashMap<String, String>) -> Option<Counter> {
+        let key = Self::get_counter_key(name, tags);
+        self.counters.read().unwrap().get(&key).cloned()
+    }
+
     pub fn operational_telemetry_listener(&self, target: &str, listen: bool) {
         let mut listeners = self.operational_telemetry_listeners.write().unwrap();
         if listen {
@@ -548,4 +575,41 @@ impl MetricsState {
         let api_stats_map = self.api_stats_map.read().unwrap();
         api_stats_map.get(request_id).cloned()
     }
+
+    /// Stages captured for the request with their durations, empty for unknown requests
+    pub fn get_stages(&self, request_id: &str) -> Vec<(String, Duration)> {
+        let api_stats_map = self.api_stats_map.read().unwrap();
+        api_stats_map
+            .get(request_id)
+            .map(|stats| stats.stats.get_stages())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::router_utils::capture_stage;
+    use ripple_sdk::api::gateway::rpc_gateway_api::RpcRequest;
+
+    #[test]
+    fn test_get_stages() {
+        let mut state = MetricsState::default();
+        let request = RpcRequest::internal("device.name", None);
+        state.add_api_stats(&request.ctx.request_id, &request.method);
+
+        std::thread::sleep(Duration::from_millis(20));
+        capture_stage(&state, &request, "static_rule_request");
+        std::thread::sleep(Duration::from_millis(20));
+        capture_stage(&state, &request, "broker_request");
+
+        let stages = state.get_stages(&request.ctx.request_id);
+        let names: Vec<&str> = stages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["static_rule_request", "broker_request"]);
+        for (_, duration) in stages {
+            assert!(duration >= Duration::from_millis(20));
+            assert!(duration < Duration::from_secs(5));
+        }
+        assert!(state.get_stages("unknown").is_empty());
+    }
 }
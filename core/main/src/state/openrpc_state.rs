@@ -24,7 +24,7 @@ use ripple_sdk::{
             fb_capabilities::FireboltPermission,
             fb_openrpc::{
                 CapabilitySet, FireboltOpenRpc, FireboltOpenRpcMethod, FireboltSemanticVersion,
-                FireboltVersionManifest,
+                FireboltVersionManifest, OpenRPCParser,
             },
             provider::ProviderAttributes,
         },
@@ -49,7 +49,7 @@ pub enum ApiSurface {
 #[derive(Debug, Clone, Default)]
 pub struct ProviderRelationSet {
     pub capability: Option<String>,
-    pub attributes: Option<&'static ProviderAttributes>,
+    pub attributes: Option<Arc<ProviderAttributes>>,
     pub event: bool,
     pub provides: Option<String>,
     pub provides_to: Option<String>,
@@ -142,6 +142,11 @@ impl OpenRpcState {
             json_schema_cache: Arc::new(RwLock::new(HashMap::new())),
         };
         v.build_provider_relation_sets(&firebolt_open_rpc.methods);
+        // methods Ripple itself offers to apps besides the Firebolt ones
+        let ripple_methods: OpenRPCParser =
+            serde_json::from_str(include_str!("../firebolt/ripple-open-rpc.json"))
+                .expect("Failed parsing the Ripple open RPC file");
+        v.add_open_rpc(ripple_methods.into());
         for path in extn_sdks {
             if v.add_extension_open_rpc(&path).is_err() {
                 error!("Error adding extn_sdk from {path}");
@@ -533,7 +538,7 @@ fn load_extension_open_rpc(path: String) -> Option<String> {
 mod tests {
     use ripple_sdk::api::manifest::extn_manifest::default_providers;
 
-    use crate::state::openrpc_state::OpenRpcState;
+    use crate::state::openrpc_state::{ApiSurface, OpenRpcState};
 
     #[test]
     fn test_provider_support() {
@@ -549,4 +554,16 @@ mod tests {
         assert!(state.is_provider_enabled("integratedPlayer."));
         assert!(state.is_provider_enabled("integratedplayer."));
     }
+
+    #[test]
+    fn test_ripple_methods() {
+        let state = OpenRpcState::new(None, Vec::new(), default_providers());
+        let perms = state
+            .get_perms_for_method("ripple.bulkSubscribe", vec![ApiSurface::Firebolt])
+            .unwrap();
+        assert!(!perms.is_empty());
+        assert!(state
+            .get_perms_for_method("ripple.explainRule", vec![ApiSurface::Firebolt])
+            .is_none());
+    }
 }
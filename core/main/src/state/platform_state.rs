@@ -17,6 +17,7 @@
 
 use ripple_sdk::{
     api::{
+        firebolt::provider::ProviderAttributes,
         gateway::rpc_gateway_api::RpcRequest,
         manifest::{
             app_library::AppLibraryState,
@@ -34,6 +35,7 @@ use ripple_sdk::{
     utils::error::RippleError,
     uuid::Uuid,
 };
+use serde::Serialize;
 use std::collections::HashMap;
 
 use crate::{
@@ -90,6 +92,25 @@ impl From<String> for DeviceSessionIdentifier {
     }
 }
 
+/// Aggregated health of the subsystems Ripple Main depends on
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub endpoints: HashMap<String, bool>,
+    /// Endpoints which lost their connection and could not reconnect
+    pub fatal_endpoints: Vec<String>,
+    pub metrics_enabled: bool,
+    pub default_app_ready: bool,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.default_app_ready
+            && self.fatal_endpoints.is_empty()
+            && self.endpoints.values().all(|connected| *connected)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PlatformState {
     extn_manifest: ExtnManifest,
@@ -125,15 +146,19 @@ impl PlatformState {
         let extn_sdks = extn_manifest.extn_sdks.clone();
         let provider_registations = extn_manifest.provider_registrations.clone();
         let metrics_state = MetricsState::default();
+        // registered before the open rpc state resolves the attributes of its provider methods
+        for (module, attributes) in manifest.get_provider_attributes() {
+            ProviderAttributes::register(&module, attributes);
+        }
         Self {
             extn_manifest,
-            cap_state: CapState::new(manifest.clone()),
+            cap_state: CapState::new(manifest.clone(), metrics_state.clone()),
             session_state: SessionState::default(),
             device_manifest: manifest.clone(),
             ripple_client: client.clone(),
             app_library_state: AppLibraryState::new(app_library),
             app_events_state: AppEventsState::default(),
-            provider_broker_state: ProviderBrokerState::default(),
+            provider_broker_state: ProviderBrokerState::new(manifest.get_challenge_timeouts()),
             app_manager_state: AppManagerState::new(&manifest.configuration.saved_dir),
             open_rpc_state: OpenRpcState::new(Some(exclusory), extn_sdks, provider_registations),
             router_state: RouterState::new(),
@@ -147,7 +172,11 @@ impl PlatformState {
                 broker_sender,
                 rule_engine,
                 client,
-            ),
+            )
+            .with_provider_timeouts(manifest.get_provider_timeouts())
+            .with_request_trace(manifest.get_features().broker_request_trace)
+            .with_telemetry_policy(&manifest.get_features().telemetry_methods)
+            .with_legacy_messsage_key(manifest.get_features().legacy_messsage_key),
         }
     }
 
@@ -217,6 +246,19 @@ impl PlatformState {
         let contract = RippleContract::RemoteFeatureControl.as_clear_string();
         self.extn_manifest.required_contracts.contains(&contract)
     }
+    pub fn health(&self) -> HealthReport {
+        let default_app_ready = self
+            .app_library_state
+            .get_default_app()
+            .map(|app| self.app_manager_state.exists(&app.app_id))
+            .unwrap_or(false);
+        HealthReport {
+            endpoints: self.endpoint_state.get_endpoint_health(),
+            fatal_endpoints: self.endpoint_state.get_fatal_endpoints(),
+            metrics_enabled: self.metrics.get_context().enabled,
+            default_app_ready,
+        }
+    }
     ///
     /// War on dots
     pub async fn internal_rpc_request(
@@ -243,7 +285,7 @@ impl PlatformState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ripple_sdk::api::manifest::extn_manifest::default_providers;
+    use ripple_sdk::{api::manifest::extn_manifest::default_providers, tokio};
     use ripple_tdk::utils::test_utils::Mockable;
 
     impl Mockable for PlatformState {
@@ -270,4 +312,56 @@ mod tests {
             )
         }
     }
+
+    #[tokio::test]
+    async fn test_provider_attributes_from_manifest() {
+        use ripple_sdk::api::firebolt::provider::ProviderResponsePayloadType;
+
+        let (_, mut manifest) = DeviceManifest::load_from_content(
+            include_str!("../../../../examples/manifest/device-manifest-example.json").to_string(),
+        )
+        .unwrap();
+        manifest.capabilities.provider_attributes.insert(
+            "ManifestProvider".to_owned(),
+            ProviderAttributes {
+                response_payload_type: ProviderResponsePayloadType::GenericResponse,
+                error_payload_type: ProviderResponsePayloadType::GenericError,
+            },
+        );
+        let (_, extn_manifest) = ExtnManifest::load_from_content(
+            include_str!("../../../../examples/manifest/extn-manifest-example.json").to_string(),
+        )
+        .unwrap();
+        let _ = PlatformState::new(
+            extn_manifest,
+            manifest,
+            RippleClient::new(crate::state::bootstrap_state::ChannelsState::new()),
+            vec![],
+            None,
+        );
+        assert!(matches!(
+            ProviderAttributes::get("ManifestProvider")
+                .unwrap()
+                .response_payload_type,
+            ProviderResponsePayloadType::GenericResponse
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        use crate::broker::endpoint_broker::BrokerSender;
+        use tokio::sync::mpsc;
+
+        let mut state = PlatformState::mock();
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        state
+            .endpoint_state
+            .add_endpoint("http".to_owned(), BrokerSender { sender: tx });
+
+        let report = state.health();
+        assert_eq!(report.endpoints.get("http"), Some(&false));
+        assert!(!report.default_app_ready);
+        assert!(!report.is_healthy());
+    }
 }
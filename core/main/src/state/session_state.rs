@@ -62,7 +62,7 @@ impl Session {
         Err(RippleError::SendFailure)
     }
 
-    fn get_app_id(&self) -> String {
+    pub fn get_app_id(&self) -> String {
         self.data.app_id.clone()
     }
 }
@@ -125,6 +125,15 @@ impl SessionState {
         None
     }
 
+    /// Returns true if any connected session belongs to the given app.
+    pub fn has_app_session(&self, app_id: &str) -> bool {
+        self.session_map
+            .read()
+            .unwrap()
+            .values()
+            .any(|session| session.data.app_id == app_id)
+    }
+
     pub fn has_session(&self, ctx: &CallContext) -> bool {
         self.session_map.read().unwrap().contains_key(&ctx.get_id())
     }
@@ -239,10 +239,25 @@ pub struct AppEvent {
     pub app_id: Option<String>,
 }
 
+/// Event decorator served by an extension, see [AppEventRequest::RegisterDecorator]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EventDecorator {
+    /// Name broker rules refer to in their `event_decorator_method`
+    pub name: String,
+    /// Method of the extension called with the event result, it returns the result
+    /// delivered to the app
+    pub method: String,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub enum AppEventRequest {
     Emit(AppEvent),
     Register(CallContext, String, ListenRequest),
+    /// Decorates the events of broker rules naming the decorator, replacing any decorator
+    /// registered under the same name
+    RegisterDecorator(EventDecorator),
+    /// Removes the decorator with the given name, its events are delivered undecorated
+    UnregisterDecorator(String),
 }
 
 impl ExtnPayloadProvider for AppEventRequest {
@@ -378,4 +393,13 @@ mod tests {
         let contract_type: RippleContract = RippleContract::AppEvents;
         test_extn_payload_provider(app_event_request, contract_type);
     }
+
+    #[test]
+    fn test_extn_payload_provider_for_register_decorator() {
+        let app_event_request = AppEventRequest::RegisterDecorator(EventDecorator {
+            name: String::from("PolicyDecorator"),
+            method: String::from("extn.decoratePolicy"),
+        });
+        test_extn_payload_provider(app_event_request, RippleContract::AppEvents);
+    }
 }
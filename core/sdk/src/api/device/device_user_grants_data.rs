@@ -226,6 +226,33 @@ pub struct GrantExclusionFilter {
     pub catalog: Option<String>,
 }
 
+/// Grant seeded at boot so the capability skips the challenge flow.
+/// Applies to the device when `app_id` is not set.
+#[derive(Deserialize, Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "camelCase")]
+pub struct PreGrant {
+    pub capability: String,
+    #[serde(default = "pre_grant_role_default")]
+    pub role: CapabilityRole,
+    pub status: GrantStatus,
+    #[serde(default)]
+    pub app_id: Option<String>,
+}
+
+fn pre_grant_role_default() -> CapabilityRole {
+    CapabilityRole::Use
+}
+
+impl PreGrant {
+    pub fn as_grant_entry(&self) -> GrantEntry {
+        let mut entry = GrantEntry::get(self.role, self.capability.clone());
+        entry.status = Some(self.status.clone());
+        entry.lifespan = Some(GrantLifespan::Forever);
+        entry
+    }
+}
+
 #[derive(Eq, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum GrantStatus {
     Allowed,
@@ -274,6 +301,9 @@ pub struct GrantEntry {
     pub lifespan: Option<GrantLifespan>,
     pub last_modified_time: Duration,
     pub lifespan_ttl_in_secs: Option<u64>,
+    /// Overrides the persistence of the matching grant policy when set
+    #[serde(default)]
+    pub persistence: Option<PolicyPersistenceType>,
 }
 
 impl PartialEq for GrantEntry {
@@ -300,6 +330,7 @@ impl GrantEntry {
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap(),
             lifespan_ttl_in_secs: None,
+            persistence: None,
         }
     }
 
@@ -550,6 +581,7 @@ mod tests {
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap_or_default(),
             lifespan_ttl_in_secs,
+            persistence: None,
         };
 
         assert_eq!(entry.has_expired(), expected_result);
@@ -67,6 +67,15 @@ pub struct AdConfigResponse {
     pub ifa_value: String,
 }
 
+/// Payload of `advertising.onPolicyChanged`, sent whenever the skip restriction or
+/// limit ad tracking setting changes
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvertisingPolicyEvent {
+    pub skip_restriction: String,
+    pub limit_ad_tracking: bool,
+}
+
 impl ExtnPayloadProvider for AdvertisingRequest {
     fn get_extn_payload(&self) -> ExtnPayload {
         ExtnPayload::Request(ExtnRequest::Advertising(self.clone()))
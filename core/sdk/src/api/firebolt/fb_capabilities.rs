@@ -22,7 +22,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::json;
 
 use super::fb_openrpc::CapabilitySet;
-use crate::api::gateway::rpc_error::RpcError;
+use crate::{api::gateway::rpc_error::RpcError, utils::error::RippleError};
 
 /// There are many types of Firebolt Cap enums
 /// 1. Short: `device:model` becomes = `xrn:firebolt:capability:account:session` its just a handy cap which helps us write less code
@@ -142,6 +142,12 @@ impl CapabilityRole {
             CapabilityRole::Provide => "provide",
         }
     }
+
+    /// Returns true if a grant for this role also covers the given role, a Manage grant
+    /// implies Use.
+    pub fn implies(&self, other: &CapabilityRole) -> bool {
+        self == other || matches!((self, other), (CapabilityRole::Manage, CapabilityRole::Use))
+    }
 }
 
 impl Hash for CapabilityRole {
@@ -161,6 +167,28 @@ pub struct FireboltPermission {
 }
 
 impl FireboltPermission {
+    /// Parses a full capability URI with an optional role suffix, e.g.
+    /// `xrn:firebolt:capability:device:model[manage]`. No suffix means the Use role.
+    pub fn parse(permission: &str) -> Result<FireboltPermission, RippleError> {
+        let (cap, role) = match permission
+            .strip_suffix(']')
+            .and_then(|p| p.rsplit_once('['))
+        {
+            Some((cap, "use")) => (cap, CapabilityRole::Use),
+            Some((cap, "manage")) => (cap, CapabilityRole::Manage),
+            Some((cap, "provide")) => (cap, CapabilityRole::Provide),
+            Some(_) => return Err(RippleError::ParseError),
+            None => (permission, CapabilityRole::Use),
+        };
+        if FireboltCap::parse_long(cap.to_owned()).is_none() {
+            return Err(RippleError::ParseError);
+        }
+        Ok(FireboltPermission {
+            cap: FireboltCap::Full(cap.to_owned()),
+            role,
+        })
+    }
+
     pub fn from_vec_string(
         perm_strings: Vec<String>,
         role_based_support: bool,
@@ -593,6 +621,42 @@ impl CapEvent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("xrn:firebolt:capability:device:model", CapabilityRole::Use)]
+    #[case("xrn:firebolt:capability:device:model[use]", CapabilityRole::Use)]
+    #[case("xrn:firebolt:capability:device:model[manage]", CapabilityRole::Manage)]
+    #[case(
+        "xrn:firebolt:capability:device:model[provide]",
+        CapabilityRole::Provide
+    )]
+    fn test_firebolt_permission_parse(#[case] permission: &str, #[case] role: CapabilityRole) {
+        let perm = FireboltPermission::parse(permission).unwrap();
+        assert_eq!(perm.cap.as_str(), "xrn:firebolt:capability:device:model");
+        assert_eq!(perm.role, role);
+    }
+
+    #[rstest]
+    #[case("device:model")]
+    #[case("xrn:firebolt:capability:device:model[admin]")]
+    #[case("xrn:firebolt:capability:Device:Model")]
+    #[case("")]
+    fn test_firebolt_permission_parse_invalid(#[case] permission: &str) {
+        assert_eq!(
+            FireboltPermission::parse(permission),
+            Err(RippleError::ParseError)
+        );
+    }
+
+    #[test]
+    fn test_capability_role_implies() {
+        assert!(CapabilityRole::Use.implies(&CapabilityRole::Use));
+        assert!(CapabilityRole::Manage.implies(&CapabilityRole::Use));
+        assert!(!CapabilityRole::Use.implies(&CapabilityRole::Manage));
+        assert!(!CapabilityRole::Provide.implies(&CapabilityRole::Use));
+        assert!(!CapabilityRole::Manage.implies(&CapabilityRole::Provide));
+    }
 
     #[test]
     fn test_firebolt_cap_short() {
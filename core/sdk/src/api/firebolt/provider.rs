@@ -15,10 +15,19 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use log::trace;
 use serde::{Deserialize, Serialize};
 
-use crate::api::device::entertainment_data::{
-    EntityInfoParameters, EntityInfoResult, PurchasedContentParameters, PurchasedContentResult,
+use crate::{
+    api::device::entertainment_data::{
+        EntityInfoParameters, EntityInfoResult, PurchasedContentParameters, PurchasedContentResult,
+    },
+    utils::error::RippleError,
 };
 
 use super::{
@@ -40,7 +49,20 @@ pub enum ProviderRequestPayload {
     Generic(serde_json::Value),
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl ProviderRequestPayload {
+    /// Returns the attributes describing the expected response for the request, if declared
+    pub fn get_attributes(&self) -> Option<Arc<ProviderAttributes>> {
+        match self {
+            ProviderRequestPayload::AckChallenge(_) => {
+                ProviderAttributes::get("AcknowledgeChallenge")
+            }
+            ProviderRequestPayload::PinChallenge(_) => ProviderAttributes::get("PinChallenge"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProviderResponsePayloadType {
     ChallengeResponse,
     PinChallengeResponse,
@@ -81,6 +103,30 @@ pub enum ProviderResponsePayload {
 }
 
 impl ProviderResponsePayload {
+    pub fn payload_type(&self) -> ProviderResponsePayloadType {
+        match self {
+            ProviderResponsePayload::ChallengeResponse(_) => {
+                ProviderResponsePayloadType::ChallengeResponse
+            }
+            ProviderResponsePayload::GenericError(_) => ProviderResponsePayloadType::GenericError,
+            ProviderResponsePayload::PinChallengeResponse(_) => {
+                ProviderResponsePayloadType::PinChallengeResponse
+            }
+            ProviderResponsePayload::KeyboardResult(_) => {
+                ProviderResponsePayloadType::KeyboardResult
+            }
+            ProviderResponsePayload::EntityInfoResponse(_) => {
+                ProviderResponsePayloadType::EntityInfoResponse
+            }
+            ProviderResponsePayload::PurchasedContentResponse(_) => {
+                ProviderResponsePayloadType::PurchasedContentResponse
+            }
+            ProviderResponsePayload::GenericResponse(_) => {
+                ProviderResponsePayloadType::GenericResponse
+            }
+        }
+    }
+
     pub fn as_keyboard_result(&self) -> Option<KeyboardSessionResponse> {
         match self {
             ProviderResponsePayload::KeyboardResult(res) => Some(res.clone()),
@@ -121,6 +167,13 @@ impl ProviderResponsePayload {
         }
     }
 
+    pub fn as_generic(&self) -> Option<serde_json::Value> {
+        match self {
+            ProviderResponsePayload::GenericResponse(res) => Some(res.clone()),
+            _ => None,
+        }
+    }
+
     pub fn as_value(&self) -> serde_json::Value {
         match self {
             ProviderResponsePayload::ChallengeResponse(res) => serde_json::to_value(res).unwrap(),
@@ -173,20 +226,54 @@ pub struct ExternalProviderError {
     pub error: GenericProviderError,
 }
 
-#[derive(Debug, Clone, Serialize)]
-
+/// Payload types a provider module answers with, see [ProviderAttributes::get]. Modules
+/// other than the defaults are registered from the device manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProviderAttributes {
     pub response_payload_type: ProviderResponsePayloadType,
     pub error_payload_type: ProviderResponsePayloadType,
 }
 
+lazy_static::lazy_static! {
+    static ref PROVIDER_ATTRIBUTES: RwLock<HashMap<String, Arc<ProviderAttributes>>> = {
+        let mut attributes: HashMap<String, Arc<ProviderAttributes>> = HashMap::new();
+        attributes.insert("AcknowledgeChallenge".into(), Arc::new(ACKNOWLEDGE_CHALLENGE_ATTRIBS));
+        attributes.insert("PinChallenge".into(), Arc::new(PIN_CHALLENGE_ATTRIBS));
+        RwLock::new(attributes)
+    };
+}
+
 impl ProviderAttributes {
-    pub fn get(module: &str) -> Option<&'static ProviderAttributes> {
-        match module {
-            "AcknowledgeChallenge" => Some(&ACKNOWLEDGE_CHALLENGE_ATTRIBS),
-            "PinChallenge" => Some(&PIN_CHALLENGE_ATTRIBS),
-            _ => None,
+    pub fn get(module: &str) -> Option<Arc<ProviderAttributes>> {
+        let attributes = PROVIDER_ATTRIBUTES.read().unwrap().get(module).cloned();
+        trace!(
+            "ProviderAttributes::get: module={}, attributes={:?}",
+            module,
+            attributes
+        );
+        attributes
+    }
+
+    /// Registers the attributes for a provider module so it is resolved by [ProviderAttributes::get],
+    /// replacing earlier attributes of the module. Ripple registers the modules of the device
+    /// manifest at startup.
+    pub fn register(module: &str, attributes: ProviderAttributes) {
+        PROVIDER_ATTRIBUTES
+            .write()
+            .unwrap()
+            .insert(module.to_owned(), Arc::new(attributes));
+    }
+
+    /// Checks the payload is either the declared response or error type.
+    pub fn validate_response(&self, payload: &ProviderResponsePayload) -> Result<(), RippleError> {
+        let actual = payload.payload_type().to_string();
+        if actual == self.response_payload_type.to_string()
+            || actual == self.error_payload_type.to_string()
+        {
+            return Ok(());
         }
+        Err(RippleError::InvalidOutput)
     }
 }
 
@@ -253,6 +340,104 @@ mod tests {
     use super::*;
     use rstest::rstest;
 
+    #[test]
+    fn test_provider_attributes_defaults() {
+        assert!(matches!(
+            ProviderAttributes::get("AcknowledgeChallenge")
+                .unwrap()
+                .response_payload_type,
+            ProviderResponsePayloadType::ChallengeResponse
+        ));
+        assert!(matches!(
+            ProviderAttributes::get("PinChallenge")
+                .unwrap()
+                .response_payload_type,
+            ProviderResponsePayloadType::PinChallengeResponse
+        ));
+        assert!(ProviderAttributes::get("UnknownModule").is_none());
+    }
+
+    #[test]
+    fn test_provider_attributes_register() {
+        ProviderAttributes::register(
+            "CustomProvider",
+            ProviderAttributes {
+                response_payload_type: ProviderResponsePayloadType::GenericResponse,
+                error_payload_type: ProviderResponsePayloadType::GenericError,
+            },
+        );
+        let attributes = ProviderAttributes::get("CustomProvider").unwrap();
+        assert!(matches!(
+            attributes.response_payload_type,
+            ProviderResponsePayloadType::GenericResponse
+        ));
+        assert!(matches!(
+            attributes.error_payload_type,
+            ProviderResponsePayloadType::GenericError
+        ));
+    }
+
+    #[test]
+    fn test_validate_response() {
+        let attributes = ProviderAttributes::get("AcknowledgeChallenge").unwrap();
+        assert!(attributes
+            .validate_response(&ProviderResponsePayload::ChallengeResponse(
+                ChallengeResponse {
+                    granted: Some(true)
+                }
+            ))
+            .is_ok());
+        assert!(attributes
+            .validate_response(&ProviderResponsePayload::GenericError(
+                GenericProviderError {
+                    code: 1,
+                    message: "error".to_owned(),
+                    data: None
+                }
+            ))
+            .is_ok());
+        assert_eq!(
+            attributes.validate_response(&ProviderResponsePayload::KeyboardResult(
+                KeyboardSessionResponse {
+                    text: "text".to_owned(),
+                    canceled: false
+                }
+            )),
+            Err(RippleError::InvalidOutput)
+        );
+    }
+
+    #[test]
+    fn test_as_generic() {
+        let value = serde_json::json!({"custom": {"shape": [1, 2, 3]}});
+        let response = ProviderResponsePayload::GenericResponse(value.clone());
+        assert_eq!(response.as_generic(), Some(value.clone()));
+        assert_eq!(response.as_value(), value);
+
+        let response = ProviderResponsePayload::ChallengeResponse(ChallengeResponse {
+            granted: Some(true),
+        });
+        assert_eq!(response.as_generic(), None);
+    }
+
+    #[test]
+    fn test_generic_response_serialization_round_trip() {
+        // untagged, so only shapes not matching a typed payload come back as generic
+        let value = serde_json::json!("custom-result");
+        let response = ProviderResponse {
+            correlation_id: "correlation_id".to_string(),
+            result: ProviderResponsePayload::GenericResponse(value.clone()),
+        };
+        let serialized = serde_json::to_string(&response).unwrap();
+        let deserialized: ProviderResponse = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.correlation_id, "correlation_id");
+        assert_eq!(deserialized.result.as_generic(), Some(value));
+        assert_eq!(
+            ProviderResponsePayloadType::GenericResponse.to_string(),
+            "GenericResponse"
+        );
+    }
+
     #[test]
     fn test_as_keyboard_result() {
         let response = ProviderResponsePayload::KeyboardResult(KeyboardSessionResponse {
@@ -26,9 +26,9 @@ use std::{
 
 use crate::{
     api::{
-        device::device_user_grants_data::{GrantExclusionFilter, GrantPolicies},
+        device::device_user_grants_data::{GrantExclusionFilter, GrantPolicies, PreGrant},
         distributor::distributor_privacy::DataEventType,
-        firebolt::fb_capabilities::FireboltPermission,
+        firebolt::{fb_capabilities::FireboltPermission, provider::ProviderAttributes},
         storage_property::StorageProperty,
     },
     utils::error::RippleError,
@@ -37,6 +37,7 @@ use crate::{
 use super::{apps::AppManifest, exclusory::ExclusoryImpl, remote_feature::FeatureFlag};
 pub const PARTNER_EXCLUSION_REFRESH_TIMEOUT: u32 = 12 * 60 * 60; // 12 hours
 pub const METRICS_LOGGING_PERCENTAGE_DEFAULT: u32 = 10;
+pub const ADVERTISING_APP_BUNDLE_ID_SUFFIX_DEFAULT: &str = "Comcast";
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct RippleConfiguration {
@@ -70,12 +71,18 @@ pub struct RippleConfiguration {
     pub metrics_logging_percentage: u32,
     #[serde(default)]
     pub internet_monitoring_configuration: InternetMonitoringConfiguration,
+    #[serde(default = "advertising_app_bundle_id_suffix_default")]
+    pub advertising_app_bundle_id_suffix: String,
 }
 
 fn partner_exclusion_refresh_timeout_default() -> u32 {
     PARTNER_EXCLUSION_REFRESH_TIMEOUT
 }
 
+fn advertising_app_bundle_id_suffix_default() -> String {
+    ADVERTISING_APP_BUNDLE_ID_SUFFIX_DEFAULT.to_string()
+}
+
 fn metrics_logging_percentage_default() -> u32 {
     METRICS_LOGGING_PERCENTAGE_DEFAULT
 }
@@ -92,6 +99,18 @@ pub struct CapabilityConfiguration {
     pub grant_exclusion_filters: Vec<GrantExclusionFilter>,
     #[serde(default)]
     pub dependencies: HashMap<FireboltPermission, Vec<FireboltPermission>>,
+    /// Time in milliseconds to wait for a provider to answer a challenge for a capability
+    #[serde(default)]
+    pub challenge_timeouts: HashMap<String, u64>,
+    /// Time in milliseconds to wait for a provider app to respond to a brokered request
+    #[serde(default)]
+    pub provider_timeouts: HashMap<String, u64>,
+    #[serde(default)]
+    pub pre_grants: Vec<PreGrant>,
+    /// Payload types of provider modules, keyed by module name, in addition to the
+    /// built in AcknowledgeChallenge and PinChallenge
+    #[serde(default)]
+    pub provider_attributes: HashMap<String, ProviderAttributes>,
 }
 
 #[derive(Deserialize, Debug, Clone, Default)]
@@ -548,6 +567,26 @@ pub struct RippleFeatures {
     pub cloud_permissions: bool,
     #[serde(default)]
     pub catalog_uninstalls_enabled: FeatureFlag,
+    /// Records the request pipeline stages of every brokered request for debugging
+    #[serde(default)]
+    pub broker_request_trace: bool,
+    /// Methods whose brokered responses are reported to telemetry
+    #[serde(default)]
+    pub telemetry_methods: TelemetryMethodPolicy,
+    /// Also emits the misspelled `messsage` key next to `message` in the capability not
+    /// available errors of provided methods. Only kept for one release to migrate consumers.
+    #[serde(default)]
+    pub legacy_messsage_key: bool,
+}
+
+/// Allow and deny lists of the methods reported to telemetry. Every method is reported
+/// when `allow` is unset and `deny` takes precedence over `allow`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TelemetryMethodPolicy {
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+    #[serde(default)]
+    pub deny: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -582,6 +621,9 @@ impl Default for RippleFeatures {
             intent_validation: default_intent_validation(),
             cloud_permissions: default_cloud_permissions(),
             catalog_uninstalls_enabled: Default::default(),
+            broker_request_trace: false,
+            telemetry_methods: TelemetryMethodPolicy::default(),
+            legacy_messsage_key: false,
         }
     }
 }
@@ -680,6 +722,7 @@ impl Default for RippleConfiguration {
             metrics_logging_percentage: metrics_logging_percentage_default(),
             internet_monitoring_configuration: Default::default(),
             log_signal_log_level: log_signal_default_level(),
+            advertising_app_bundle_id_suffix: advertising_app_bundle_id_suffix_default(),
         }
     }
 }
@@ -767,6 +810,22 @@ impl DeviceManifest {
         self.clone().capabilities.grant_policies
     }
 
+    pub fn get_challenge_timeouts(&self) -> HashMap<String, u64> {
+        self.capabilities.challenge_timeouts.clone()
+    }
+
+    pub fn get_provider_timeouts(&self) -> HashMap<String, u64> {
+        self.capabilities.provider_timeouts.clone()
+    }
+
+    pub fn get_pre_grants(&self) -> Vec<PreGrant> {
+        self.capabilities.pre_grants.clone()
+    }
+
+    pub fn get_provider_attributes(&self) -> HashMap<String, ProviderAttributes> {
+        self.capabilities.provider_attributes.clone()
+    }
+
     pub fn get_grant_exclusion_filters(&self) -> Vec<GrantExclusionFilter> {
         self.clone().capabilities.grant_exclusion_filters
     }
@@ -796,6 +855,10 @@ impl DeviceManifest {
             .internet_monitoring_configuration
             .default_monitoring_interval_seconds
     }
+
+    pub fn get_advertising_app_bundle_id_suffix(&self) -> String {
+        self.configuration.advertising_app_bundle_id_suffix.clone()
+    }
 }
 
 #[cfg(test)]
@@ -895,6 +958,9 @@ pub(crate) mod tests {
                             default: false,
                             remote_key: None,
                         },
+                        broker_request_trace: false,
+                        telemetry_methods: TelemetryMethodPolicy::default(),
+                        legacy_messsage_key: false,
                     },
                     internal_app_id: Some("test".to_string()),
                     saved_dir: "/opt/persistent/ripple".to_string(),
@@ -906,6 +972,7 @@ pub(crate) mod tests {
                     internet_monitoring_configuration: InternetMonitoringConfiguration {
                         default_monitoring_interval_seconds: 180,
                     },
+                    advertising_app_bundle_id_suffix: "Comcast".to_string(),
                 },
                 capabilities: CapabilityConfiguration {
                     supported: vec!["main[manage]".to_string(), "test".to_string()],
@@ -916,6 +983,10 @@ pub(crate) mod tests {
                         catalog: Some("test-catalog".to_string()),
                     }],
                     dependencies: HashMap::new(),
+                    challenge_timeouts: HashMap::new(),
+                    provider_timeouts: HashMap::new(),
+                    pre_grants: Vec::new(),
+                    provider_attributes: HashMap::new(),
                 },
                 lifecycle: LifecycleConfiguration {
                     app_ready_timeout_ms: 30000,
@@ -955,6 +1026,15 @@ pub(crate) mod tests {
         assert_eq!(form_factor, "ipstb".to_string());
     }
 
+    #[test]
+    fn test_get_advertising_app_bundle_id_suffix() {
+        let manifest = DeviceManifest::mock();
+        assert_eq!(
+            manifest.get_advertising_app_bundle_id_suffix(),
+            ADVERTISING_APP_BUNDLE_ID_SUFFIX_DEFAULT.to_string()
+        );
+    }
+
     #[test]
     fn test_get_app_library_path() {
         let manifest = DeviceManifest::mock();
@@ -1037,6 +1117,29 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_get_provider_attributes() {
+        let capabilities: CapabilityConfiguration = serde_json::from_str(
+            r#"{
+            "supported": [],
+            "providerAttributes": {
+                "CustomProvider": {
+                    "responsePayloadType": "GenericResponse",
+                    "errorPayloadType": "GenericError"
+                }
+            }
+        }"#,
+        )
+        .unwrap();
+        let mut manifest = DeviceManifest::mock();
+        manifest.capabilities = capabilities;
+        let attributes = manifest.get_provider_attributes();
+        let custom = attributes.get("CustomProvider").unwrap();
+        assert_eq!(custom.response_payload_type.to_string(), "GenericResponse");
+        assert_eq!(custom.error_payload_type.to_string(), "GenericError");
+        assert!(DeviceManifest::mock().get_provider_attributes().is_empty());
+    }
+
     #[test]
     fn test_get_distributor_experience_id() {
         let manifest = DeviceManifest::mock();
@@ -1059,6 +1162,9 @@ pub(crate) mod tests {
                     default: false,
                     remote_key: None,
                 },
+                broker_request_trace: false,
+                telemetry_methods: TelemetryMethodPolicy::default(),
+                legacy_messsage_key: false,
             }
         );
     }
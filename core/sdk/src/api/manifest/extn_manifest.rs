@@ -41,6 +41,9 @@ pub struct ExtnManifest {
     pub extn_sdks: Vec<String>,
     #[serde(default = "default_providers")]
     pub provider_registrations: Vec<String>,
+    /// Refuse to start when the broker rules fail validation
+    #[serde(default)]
+    pub strict_rules: bool,
 }
 
 /// Some unit tests which use defaults are failing because we need default providers for unit testing
@@ -57,6 +60,7 @@ impl Default for ExtnManifest {
             rules_path: Vec::new(),
             extn_sdks: Vec::new(),
             provider_registrations: default_providers(),
+            strict_rules: false,
         }
     }
 }
@@ -235,6 +239,7 @@ mod tests {
                 rules_path: Vec::new(),
                 extn_sdks: Vec::new(),
                 provider_registrations: Vec::new(),
+                strict_rules: false,
             }
         }
     }
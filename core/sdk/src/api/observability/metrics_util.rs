@@ -14,12 +14,15 @@ use chrono::Utc;
 #[cfg(not(test))]
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RpcStats {
     pub start_time: i64,
     pub last_stage: i64,
     stage_durations: String,
+    #[serde(default)]
+    stages: Vec<(String, i64)>,
 }
 
 impl Default for RpcStats {
@@ -28,6 +31,7 @@ impl Default for RpcStats {
             start_time: Utc::now().timestamp_millis(),
             last_stage: 0,
             stage_durations: String::new(),
+            stages: Vec::new(),
         }
     }
 }
@@ -46,6 +50,7 @@ impl RpcStats {
         } else {
             self.stage_durations = format!("{},{}={}", self.stage_durations, stage, duration);
         }
+        self.stages.push((stage.to_owned(), duration));
         duration
     }
 
@@ -57,6 +62,19 @@ impl RpcStats {
     pub fn get_stage_durations(&self) -> String {
         self.stage_durations.clone()
     }
+
+    /// Captured stages in order along with the time spent since the previous stage
+    pub fn get_stages(&self) -> Vec<(String, Duration)> {
+        self.stages
+            .iter()
+            .map(|(stage, duration)| {
+                (
+                    stage.clone(),
+                    Duration::from_millis((*duration).max(0) as u64),
+                )
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
@@ -71,6 +71,7 @@ pub const KEY_ALLOW_WATCH_HISTORY: &str = "allowWatchHistory";
 pub const KEY_VOICE_GUIDANCE_SPEED: &str = "speed";
 pub const KEY_PARTNER_EXCLUSIONS: &str = "partnerExclusions";
 pub const KEY_SKIP_RESTRICTION: &str = "skipRestriction";
+pub const KEY_APP_ADVERTISING_POLICY: &str = "appPolicy";
 pub const KEY_AUDIO_DESCRIPTION_ENABLED: &str = "audioDescriptionEnabled";
 pub const KEY_PREFERRED_AUDIO_LANGUAGES: &str = "preferredAudioLanguages";
 
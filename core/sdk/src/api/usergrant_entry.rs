@@ -21,19 +21,24 @@ use crate::api::firebolt::fb_capabilities::CapabilityRole;
 use crate::{
     extn::extn_client_message::{ExtnPayload, ExtnPayloadProvider, ExtnRequest},
     framework::ripple_contract::RippleContract,
+    utils::error::RippleError,
 };
 use serde::{Deserialize, Serialize};
 
-use super::device::device_user_grants_data::{GrantLifespan, GrantStatus, PolicyPersistenceType};
+use super::device::device_user_grants_data::{
+    GrantEntry, GrantLifespan, GrantStatus, PolicyPersistenceType,
+};
 use super::firebolt::fb_capabilities::FireboltPermission;
 use super::storage_property::StorageAdjective;
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub enum UserGrantsStoreRequest {
     GetUserGrants(String, FireboltPermission),
+    GetUserGrantsByApp(String),
     SetUserGrants(UserGrantInfo),
     SyncGrantMapPerPolicy(),
     ClearUserGrants(PolicyPersistenceType),
+    ClearExpiredUserGrants(),
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -78,6 +83,8 @@ pub struct UserGrantInfo {
     pub expiry_time: Option<Duration>,
     pub app_name: Option<String>,
     pub lifespan: GrantLifespan,
+    #[serde(default)]
+    pub persistence: Option<PolicyPersistenceType>,
 }
 
 impl Default for UserGrantInfo {
@@ -90,10 +97,55 @@ impl Default for UserGrantInfo {
             expiry_time: None,
             app_name: None,
             lifespan: GrantLifespan::Once,
+            persistence: None,
         }
     }
 }
 
+impl UserGrantInfo {
+    pub fn from_grant_entry(grant_entry: &GrantEntry, app_name: Option<String>) -> Self {
+        UserGrantInfo {
+            role: grant_entry.role,
+            capability: grant_entry.capability.to_owned(),
+            status: grant_entry.status.to_owned(),
+            last_modified_time: grant_entry.last_modified_time,
+            expiry_time: grant_entry
+                .lifespan_ttl_in_secs
+                .map(|ttl| grant_entry.last_modified_time + Duration::from_secs(ttl)),
+            app_name,
+            lifespan: grant_entry
+                .lifespan
+                .to_owned()
+                .unwrap_or(GrantLifespan::Forever),
+            persistence: grant_entry.persistence.to_owned(),
+        }
+    }
+
+    /// Rejects grants whose expiry_time is not after last_modified_time, as those
+    /// would be stored already expired.
+    pub fn validate(&self) -> Result<(), RippleError> {
+        match self.expiry_time {
+            Some(expiry_time) if expiry_time <= self.last_modified_time => {
+                Err(RippleError::InvalidInput)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if the grant has an expiry_time at or before `now` (duration since Unix epoch).
+    pub fn is_expired(&self, now: Duration) -> bool {
+        self.expiry_time
+            .map_or(false, |expiry_time| expiry_time <= now)
+    }
+
+    /// Returns the time left until expiry_time at `now`, zero once the grant has expired and
+    /// None if the grant has no expiry.
+    pub fn remaining_ttl(&self, now: Duration) -> Option<Duration> {
+        self.expiry_time
+            .map(|expiry_time| expiry_time.saturating_sub(now))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +167,39 @@ mod tests {
 
         test_extn_payload_provider(user_grants_request, contract_type);
     }
+    #[test]
+    fn test_extn_request_user_grants_by_app() {
+        let user_grants_request = UserGrantsStoreRequest::GetUserGrantsByApp("test_app".into());
+        let contract_type: RippleContract =
+            RippleContract::Storage(StorageAdjective::UsergrantLocal);
+
+        test_extn_payload_provider(user_grants_request, contract_type);
+    }
+
+    #[test]
+    fn test_user_grant_info_from_grant_entry() {
+        let grant_entry = GrantEntry {
+            role: CapabilityRole::Use,
+            capability: "test_capability".to_string(),
+            status: Some(GrantStatus::Allowed),
+            lifespan: Some(GrantLifespan::Seconds),
+            last_modified_time: Duration::new(1000, 0),
+            lifespan_ttl_in_secs: Some(500),
+            persistence: Some(PolicyPersistenceType::Device),
+        };
+        let user_grant_info =
+            UserGrantInfo::from_grant_entry(&grant_entry, Some("test_app".to_string()));
+        assert_eq!(user_grant_info.capability, "test_capability");
+        assert_eq!(user_grant_info.status, Some(GrantStatus::Allowed));
+        assert_eq!(user_grant_info.expiry_time, Some(Duration::new(1500, 0)));
+        assert_eq!(user_grant_info.app_name, Some("test_app".to_string()));
+        assert_eq!(user_grant_info.lifespan, GrantLifespan::Seconds);
+        assert_eq!(
+            user_grant_info.persistence,
+            Some(PolicyPersistenceType::Device)
+        );
+    }
+
     #[test]
     fn test_user_grants_persistence_type_as_string() {
         assert_eq!(UserGrantsPersistenceType::Account.as_string(), "account");
@@ -143,6 +228,7 @@ mod tests {
             expiry_time: Some(Duration::new(2000, 0)),
             app_name: Some("test_app".to_string()),
             lifespan: GrantLifespan::Forever,
+            persistence: None,
         };
 
         let user_grants_request = UserGrantsStoreRequest::SetUserGrants(user_grant_info.clone());
@@ -170,4 +256,71 @@ mod tests {
 
         test_extn_payload_provider(user_grants_request, contract_type);
     }
+
+    #[test]
+    fn test_user_grant_info_validate() {
+        let mut user_grant_info = UserGrantInfo {
+            last_modified_time: Duration::new(1000, 0),
+            expiry_time: Some(Duration::new(2000, 0)),
+            ..Default::default()
+        };
+        assert!(user_grant_info.validate().is_ok());
+
+        user_grant_info.expiry_time = None;
+        assert!(user_grant_info.validate().is_ok());
+
+        user_grant_info.expiry_time = Some(Duration::new(500, 0));
+        assert_eq!(user_grant_info.validate(), Err(RippleError::InvalidInput));
+
+        user_grant_info.expiry_time = Some(Duration::new(1000, 0));
+        assert_eq!(user_grant_info.validate(), Err(RippleError::InvalidInput));
+    }
+
+    #[test]
+    fn test_clear_expired_user_grants_request() {
+        let user_grants_request = UserGrantsStoreRequest::ClearExpiredUserGrants();
+        let contract_type: RippleContract =
+            RippleContract::Storage(StorageAdjective::UsergrantLocal);
+
+        test_extn_payload_provider(user_grants_request, contract_type);
+    }
+
+    #[test]
+    fn test_user_grant_info_is_expired() {
+        let mut user_grant_info = UserGrantInfo {
+            last_modified_time: Duration::new(1000, 0),
+            expiry_time: Some(Duration::new(2000, 0)),
+            ..Default::default()
+        };
+        assert!(!user_grant_info.is_expired(Duration::new(1500, 0)));
+        assert!(user_grant_info.is_expired(Duration::new(2000, 0)));
+        assert!(user_grant_info.is_expired(Duration::new(2500, 0)));
+
+        user_grant_info.expiry_time = None;
+        assert!(!user_grant_info.is_expired(Duration::new(2500, 0)));
+    }
+
+    #[test]
+    fn test_user_grant_info_remaining_ttl() {
+        let mut user_grant_info = UserGrantInfo {
+            last_modified_time: Duration::new(1000, 0),
+            expiry_time: Some(Duration::new(2000, 0)),
+            ..Default::default()
+        };
+        assert_eq!(
+            user_grant_info.remaining_ttl(Duration::new(1500, 0)),
+            Some(Duration::new(500, 0))
+        );
+        assert_eq!(
+            user_grant_info.remaining_ttl(Duration::new(2000, 0)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            user_grant_info.remaining_ttl(Duration::new(2500, 0)),
+            Some(Duration::ZERO)
+        );
+
+        user_grant_info.expiry_time = None;
+        assert_eq!(user_grant_info.remaining_ttl(Duration::new(2500, 0)), None);
+    }
 }
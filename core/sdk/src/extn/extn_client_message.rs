@@ -67,7 +67,7 @@ use crate::{
         settings::{SettingValue, SettingsRequest},
         status_update::ExtnStatus,
         storage_property::StorageManagerRequest,
-        usergrant_entry::UserGrantsStoreRequest,
+        usergrant_entry::{UserGrantInfo, UserGrantsStoreRequest},
     },
     framework::ripple_contract::RippleContract,
     utils::error::RippleError,
@@ -370,6 +370,7 @@ pub enum ExtnResponse {
     SecureStorage(SecureStorageResponse),
     AppCatalog(Vec<AppMetadata>),
     InstalledApps(Vec<InstalledApp>),
+    UserGrants(Vec<UserGrantInfo>),
 }
 
 impl ExtnPayloadProvider for ExtnResponse {
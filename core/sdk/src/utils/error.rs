@@ -16,8 +16,21 @@
 //
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::api::firebolt::fb_capabilities::DenyReason;
+use crate::api::firebolt::fb_capabilities::{
+    DenyReason, CAPABILITY_GRANT_DENIED, CAPABILITY_NOT_AVAILABLE, CAPABILITY_NOT_PERMITTED,
+    CAPABILITY_NOT_SUPPORTED, CAPABILITY_UNGRANTED, JSON_RPC_STANDARD_ERROR_INVALID_PARAMS,
+    JSON_RPC_STANDARD_ERROR_METHOD_NOT_FOUND,
+};
+
+pub const JSON_RPC_PARSE_ERROR: i32 = -32700;
+pub const JSON_RPC_INVALID_REQUEST: i32 = -32600;
+pub const JSON_RPC_INTERNAL_ERROR: i32 = -32603;
+/// Implementation defined server error for services which are unavailable or not ready
+pub const JSON_RPC_SERVICE_UNAVAILABLE: i32 = -32002;
+/// Implementation defined server error for requests which timed out
+pub const JSON_RPC_TIMEOUT: i32 = -32003;
 
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub enum RippleError {
@@ -73,6 +86,74 @@ impl std::fmt::Display for RippleError {
     }
 }
 
+impl RippleError {
+    /// Extracts the numeric code from a JSON-RPC error object
+    pub fn jsonrpc_error_code(error: &Value) -> Option<i64> {
+        error.get("code").and_then(Value::as_i64)
+    }
+
+    /// Maps a JSON-RPC error object to the closest RippleError, falling back to
+    /// BrokerError with the error message for codes without a dedicated variant
+    pub fn from_jsonrpc_error(error: &Value) -> RippleError {
+        match Self::jsonrpc_error_code(error).and_then(|code| i32::try_from(code).ok()) {
+            Some(JSON_RPC_PARSE_ERROR) => RippleError::ParseError,
+            Some(JSON_RPC_INVALID_REQUEST) | Some(JSON_RPC_STANDARD_ERROR_INVALID_PARAMS) => {
+                RippleError::InvalidInput
+            }
+            Some(JSON_RPC_STANDARD_ERROR_METHOD_NOT_FOUND) => RippleError::NotAvailable,
+            Some(JSON_RPC_INTERNAL_ERROR) => RippleError::ServiceError,
+            Some(JSON_RPC_SERVICE_UNAVAILABLE) => RippleError::ServiceNotReady,
+            Some(JSON_RPC_TIMEOUT) => RippleError::TimeoutError,
+            Some(CAPABILITY_NOT_SUPPORTED) => RippleError::Permission(DenyReason::Unsupported),
+            Some(CAPABILITY_NOT_AVAILABLE) => RippleError::Permission(DenyReason::Unavailable),
+            Some(CAPABILITY_NOT_PERMITTED) => RippleError::Permission(DenyReason::Unpermitted),
+            Some(CAPABILITY_GRANT_DENIED) => RippleError::Permission(DenyReason::GrantDenied),
+            Some(CAPABILITY_UNGRANTED) => RippleError::Permission(DenyReason::Ungranted),
+            _ => RippleError::BrokerError(
+                error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| error.to_string()),
+            ),
+        }
+    }
+
+    /// JSON-RPC error code reported to clients for this error
+    pub fn to_jsonrpc_code(&self) -> i32 {
+        match self {
+            RippleError::MissingInput | RippleError::InvalidInput => {
+                JSON_RPC_STANDARD_ERROR_INVALID_PARAMS
+            }
+            RippleError::ParseError => JSON_RPC_PARSE_ERROR,
+            RippleError::NotAvailable
+            | RippleError::ServiceNotReady
+            | RippleError::NoResponse
+            | RippleError::SenderMissing
+            | RippleError::ClientMissing => JSON_RPC_SERVICE_UNAVAILABLE,
+            RippleError::TimeoutError => JSON_RPC_TIMEOUT,
+            RippleError::Permission(reason) => match reason {
+                DenyReason::Unsupported => CAPABILITY_NOT_SUPPORTED,
+                DenyReason::Unavailable => CAPABILITY_NOT_AVAILABLE,
+                DenyReason::GrantDenied => CAPABILITY_GRANT_DENIED,
+                DenyReason::Ungranted => CAPABILITY_UNGRANTED,
+                _ => CAPABILITY_NOT_PERMITTED,
+            },
+            RippleError::ApiAuthenticationFailed | RippleError::InvalidAccess => {
+                CAPABILITY_NOT_PERMITTED
+            }
+            RippleError::InvalidOutput
+            | RippleError::SendFailure
+            | RippleError::ExtnError
+            | RippleError::BootstrapError
+            | RippleError::ProcessorError
+            | RippleError::ServiceError
+            | RippleError::RuleError
+            | RippleError::BrokerError(_) => JSON_RPC_INTERNAL_ERROR,
+        }
+    }
+}
+
 #[cfg(feature = "rpc")]
 impl From<RippleError> for jsonrpsee::core::Error {
     fn from(value: RippleError) -> Self {
@@ -145,4 +226,59 @@ mod tests {
             RippleError::Permission(DenyReason::Unsupported).into(),
         );
     }
+
+    #[test]
+    pub fn test_to_jsonrpc_code() {
+        assert_eq!(
+            RippleError::InvalidInput.to_jsonrpc_code(),
+            JSON_RPC_STANDARD_ERROR_INVALID_PARAMS
+        );
+        assert_eq!(
+            RippleError::SendFailure.to_jsonrpc_code(),
+            JSON_RPC_INTERNAL_ERROR
+        );
+        assert_eq!(
+            RippleError::ServiceNotReady.to_jsonrpc_code(),
+            JSON_RPC_SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            RippleError::TimeoutError.to_jsonrpc_code(),
+            JSON_RPC_TIMEOUT
+        );
+        for error in [
+            RippleError::ParseError,
+            RippleError::ServiceNotReady,
+            RippleError::TimeoutError,
+            RippleError::Permission(DenyReason::GrantDenied),
+        ] {
+            let value = serde_json::json!({"code": error.to_jsonrpc_code(), "message": "error"});
+            assert_eq!(RippleError::from_jsonrpc_error(&value), error);
+        }
+    }
+
+    #[test]
+    pub fn test_from_jsonrpc_error() {
+        let cases = [
+            (-32700, RippleError::ParseError),
+            (-32600, RippleError::InvalidInput),
+            (-32601, RippleError::NotAvailable),
+            (-32602, RippleError::InvalidInput),
+            (-32603, RippleError::ServiceError),
+            (-40300, RippleError::Permission(DenyReason::Unpermitted)),
+            (-40400, RippleError::Permission(DenyReason::GrantDenied)),
+            (-50300, RippleError::Permission(DenyReason::Unavailable)),
+        ];
+        for (code, expected) in cases {
+            let error = serde_json::json!({"code": code, "message": "error"});
+            assert_eq!(RippleError::from_jsonrpc_error(&error), expected);
+        }
+        assert_eq!(
+            RippleError::from_jsonrpc_error(&serde_json::json!({"code": 22, "message": "custom"})),
+            RippleError::BrokerError("custom".to_owned())
+        );
+        assert_eq!(
+            RippleError::jsonrpc_error_code(&serde_json::json!({"message": "no code"})),
+            None
+        );
+    }
 }
@@ -15,7 +15,9 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::RwLock;
 use std::{str::FromStr, sync::atomic::AtomicU32};
 
@@ -25,6 +27,98 @@ lazy_static::lazy_static! {
     pub static ref MODULE_LOG_LEVELS: RwLock<HashMap<String, log::LevelFilter>> = RwLock::new(HashMap::new());
 }
 
+/// Bumped on every change of [MODULE_LOG_LEVELS], so that the logger only copies the
+/// levels again after they changed instead of locking them for every record
+static MODULE_LOG_LEVELS_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Level of targets without a module override, set when the logger is initialized
+static DEFAULT_LOG_LEVEL: RwLock<log::LevelFilter> = RwLock::new(log::LevelFilter::Off);
+
+type ModuleLevels = Vec<(String, log::LevelFilter)>;
+
+thread_local! {
+    static CACHED_MODULE_LOG_LEVELS: RefCell<(u64, ModuleLevels)> = const { RefCell::new((0, Vec::new())) };
+}
+
+/// Crates whose logs are off unless raised with [set_module_log_level]
+const SILENCED_MODULES: [&str; 11] = [
+    "h2",
+    "hyper",
+    "rustls",
+    "tower",
+    "tower_http",
+    "jsonrpsee_client_transport",
+    "jsonrpsee_core",
+    "tokio_tungstenite",
+    "tungstenite",
+    "soketto",
+    "tracing",
+];
+
+fn update_module_log_levels(update: impl FnOnce(&mut HashMap<String, log::LevelFilter>)) {
+    let mut levels = MODULE_LOG_LEVELS.write().unwrap();
+    update(&mut levels);
+    MODULE_LOG_LEVELS_VERSION.fetch_add(1, Ordering::Release);
+    update_max_log_level(&levels);
+}
+
+/// Recomputes `log::max_level()` from the default level and the module overrides, so
+/// that lowering or clearing an override lowers it again
+fn update_max_log_level(levels: &HashMap<String, log::LevelFilter>) {
+    let default = *DEFAULT_LOG_LEVEL.read().unwrap();
+    log::set_max_level(levels.values().copied().fold(default, Ord::max));
+}
+
+/// Overrides the log level for a module target (e.g. `ripple::broker`) and all of its
+/// submodules at runtime, without reinitializing the logger.
+pub fn set_module_log_level(module: &str, level: log::LevelFilter) {
+    update_module_log_levels(|levels| {
+        levels.insert(module.to_owned(), level);
+    });
+}
+
+pub fn clear_module_log_level(module: &str) {
+    update_module_log_levels(|levels| {
+        levels.remove(module);
+    });
+}
+
+/// Level configured for the most specific module containing the given target
+pub fn get_module_log_level(target: &str) -> Option<log::LevelFilter> {
+    module_level(MODULE_LOG_LEVELS.read().unwrap().iter(), target)
+}
+
+fn module_level<'a>(
+    levels: impl Iterator<Item = (&'a String, &'a log::LevelFilter)>,
+    target: &str,
+) -> Option<log::LevelFilter> {
+    levels
+        .filter(|(module, _)| {
+            target == module.as_str()
+                || target
+                    .strip_prefix(module.as_str())
+                    .map_or(false, |rest| rest.starts_with("::"))
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map(|(_, level)| *level)
+}
+
+fn module_log_enabled(metadata: &log::Metadata, default: log::LevelFilter) -> bool {
+    let version = MODULE_LOG_LEVELS_VERSION.load(Ordering::Acquire);
+    CACHED_MODULE_LOG_LEVELS.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        if cached.0 != version {
+            let levels = MODULE_LOG_LEVELS.read().unwrap();
+            *cached = (
+                version,
+                levels.iter().map(|(m, l)| (m.clone(), *l)).collect(),
+            );
+        }
+        let level = module_level(cached.1.iter().map(|(m, l)| (m, l)), metadata.target());
+        metadata.level() <= level.unwrap_or(default)
+    })
+}
+
 pub fn init_logger(name: String) -> Result<(), fern::InitError> {
     let log_string: String = std::env::var("RUST_LOG").unwrap_or_else(|_| "debug".into());
     println!("log level {}", log_string);
@@ -60,6 +154,7 @@ pub fn init_logger(name: String) -> Result<(), fern::InitError> {
         .level_for("jsonrpsee_core", log::LevelFilter::Off)
         .chain(std::io::stdout())
         .apply()?;
+    *DEFAULT_LOG_LEVEL.write().unwrap() = filter;
     Ok(())
 }
 
@@ -75,10 +170,17 @@ pub fn init_and_configure_logger(
     let (extracted_module_name, extracted_level_filter) = additional_modules
         .and_then(|modules| modules.into_iter().last())
         .unwrap_or(("no_module".to_string(), log::LevelFilter::Off));
-    MODULE_LOG_LEVELS
-        .write()
-        .unwrap()
-        .insert(extracted_module_name.clone(), extracted_level_filter);
+    *DEFAULT_LOG_LEVEL.write().unwrap() = filter;
+    // module levels are applied by the filter rather than fern's level_for, so that
+    // set_module_log_level can raise them at runtime
+    update_module_log_levels(|levels| {
+        for module in SILENCED_MODULES {
+            levels
+                .entry(module.to_owned())
+                .or_insert(log::LevelFilter::Off);
+        }
+        levels.insert(extracted_module_name.clone(), extracted_level_filter);
+    });
     println!(
         "additional module: {}, Level filter : {}",
         extracted_module_name, extracted_level_filter
@@ -129,22 +231,74 @@ pub fn init_and_configure_logger(
                 ));
             }
         })
-        .level(filter)
-        .level_for(extracted_module_name, extracted_level_filter)
-        //log filter applied here, making the log level to OFF for the below mentioned crates
-        .level_for("h2", log::LevelFilter::Off)
-        .level_for("hyper", log::LevelFilter::Off)
-        .level_for("rustls", log::LevelFilter::Off)
-        .level_for("tower", log::LevelFilter::Off)
-        .level_for("tower_http", log::LevelFilter::Off)
-        .level_for("jsonrpsee_client_transport", log::LevelFilter::Off)
-        .level_for("jsonrpsee_core", log::LevelFilter::Off)
-        .level_for("tokio_tungstenite", log::LevelFilter::Off)
-        .level_for("tungstenite", log::LevelFilter::Off)
-        .level_for("soketto", log::LevelFilter::Off)
-        .level_for("tracing", log::LevelFilter::Off)
+        // module overrides can be raised at runtime, so the final filtering is done per record
+        .level(log::LevelFilter::Trace)
+        .filter(move |metadata| module_log_enabled(metadata, filter))
         .chain(std::io::stdout())
         .apply()?;
+    // fern sets the max level to the dispatch level, lower it to the levels in use
+    update_max_log_level(&MODULE_LOG_LEVELS.read().unwrap());
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_level() {
+        let levels: HashMap<String, log::LevelFilter> = HashMap::from([
+            ("ripple::broker".to_owned(), log::LevelFilter::Trace),
+            ("hyper".to_owned(), log::LevelFilter::Off),
+            ("hyper::client".to_owned(), log::LevelFilter::Debug),
+        ]);
+        let level = |target| module_level(levels.iter(), target);
+        assert_eq!(
+            level("ripple::broker::endpoint_broker"),
+            Some(log::LevelFilter::Trace)
+        );
+        assert_eq!(level("ripple::state::metrics_state"), None);
+        // a prefix only matches whole module path segments
+        assert_eq!(level("ripple::broker_other"), None);
+        // a silenced crate can be raised for one of its modules
+        assert_eq!(level("hyper::proto"), Some(log::LevelFilter::Off));
+        assert_eq!(level("hyper::client::pool"), Some(log::LevelFilter::Debug));
+    }
+
+    #[test]
+    fn test_set_module_log_level() {
+        let record = |target| {
+            log::Metadata::builder()
+                .level(log::Level::Trace)
+                .target(target)
+                .build()
+        };
+        let default = log::LevelFilter::Info;
+        *DEFAULT_LOG_LEVEL.write().unwrap() = default;
+        set_module_log_level("ripple::broker", log::LevelFilter::Trace);
+        assert!(module_log_enabled(
+            &record("ripple::broker::endpoint_broker"),
+            default
+        ));
+        assert!(!module_log_enabled(
+            &record("ripple::state::metrics_state"),
+            default
+        ));
+        assert!(module_log_enabled(
+            &log::Metadata::builder()
+                .level(log::Level::Info)
+                .target("ripple::state::metrics_state")
+                .build(),
+            default
+        ));
+        assert_eq!(log::max_level(), log::LevelFilter::Trace);
+
+        clear_module_log_level("ripple::broker");
+        assert!(!module_log_enabled(
+            &record("ripple::broker::endpoint_broker"),
+            default
+        ));
+        assert_eq!(log::max_level(), default);
+    }
+}
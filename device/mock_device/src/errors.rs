@@ -98,8 +98,21 @@ pub enum LoadMockDataError {
     PathDoesNotExist(PathBuf),
     FileOpenFailed(PathBuf),
     GetSavedDirFailed,
-    MockDataNotValidJson,
-    MockDataNotArray,
+    MockDataNotValidJson {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+    },
+    /// The root of the mock data must be an object keyed by method
+    MockDataNotObject(PathBuf),
+    /// The responses of the method are not an array
+    MockDataNotArray(String),
+    /// The entry at the index of the method's responses could not be parsed
+    MockDataBadEntry {
+        method: String,
+        index: usize,
+        reason: String,
+    },
     MockDataError(MockDataError),
 }
 
@@ -111,8 +124,22 @@ impl Display for LoadMockDataError {
             }
             Self::FileOpenFailed(path) => format!("Failed to open file. File: {}", path.display()),
             Self::GetSavedDirFailed => "Failed to get SavedDir from config.".to_owned(),
-            Self::MockDataNotValidJson => "The mock data is not valid JSON.".to_owned(),
-            Self::MockDataNotArray => "The mock data file root object must be an array.".to_owned(),
+            Self::MockDataNotValidJson { path, line, column } => format!(
+                "The mock data is not valid JSON. File: {} line: {line} column: {column}",
+                path.display()
+            ),
+            Self::MockDataNotObject(path) => format!(
+                "The mock data file root must be an object. File: {}",
+                path.display()
+            ),
+            Self::MockDataNotArray(method) => {
+                format!("The responses of {method} must be an array.")
+            }
+            Self::MockDataBadEntry {
+                method,
+                index,
+                reason,
+            } => format!("Invalid response {index} of {method}. Error: {reason}"),
             Self::MockDataError(err) => {
                 format!("Failed to parse message in mock data. Error: {err:?}")
             }
@@ -34,6 +34,31 @@ pub struct ParamResponse {
     pub result: Option<Value>,
     pub error: Option<Value>,
     pub events: Option<Vec<EventValue>>,
+    #[serde(default)]
+    pub matcher: ParamMatcher,
+}
+
+/// How the params of a mock entry are matched against the params of a request. Wildcard
+/// entries only answer requests no exact entry of the method matches.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamMatcher {
+    /// The request params equal the entry params, any params match an entry without them
+    #[default]
+    Exact,
+    /// Wildcard matching any params of the method
+    Any,
+    /// Wildcard matching params which contain the entry params
+    Subset,
+}
+
+fn is_subset(subset: &Value, value: &Value) -> bool {
+    match (subset, value) {
+        (Value::Object(subset), Value::Object(value)) => subset
+            .iter()
+            .all(|(k, v)| value.get(k).map_or(false, |value| is_subset(v, value))),
+        _ => subset == value,
+    }
 }
 
 #[derive(Debug)]
@@ -55,6 +80,18 @@ impl ParamResponse {
             None => Some(self.clone()),
         }
     }
+    /// Whether the entry is a wildcard entry matching the params, see [ParamMatcher]
+    pub fn matches_wildcard(&self, params: &Value) -> bool {
+        match self.matcher {
+            ParamMatcher::Exact => false,
+            ParamMatcher::Any => true,
+            ParamMatcher::Subset => self
+                .params
+                .as_ref()
+                .map_or(true, |subset| is_subset(subset, params)),
+        }
+    }
+
     pub fn get_notification_id(&self) -> Option<String> {
         if let Some(params) = &self.params {
             if let Some(event) = params.get("event") {
@@ -214,6 +251,7 @@ mod tests {
             error: None,
             events: None,
             params: None,
+            matcher: ParamMatcher::Exact,
         };
         assert!(response.get_key(&Value::Null).is_some());
         let response = ParamResponse {
@@ -221,6 +259,7 @@ mod tests {
             error: None,
             events: None,
             params: Some(Value::String("Some".to_owned())),
+            matcher: ParamMatcher::Exact,
         };
         assert!(response.get_key(&Value::Null).is_none());
         assert!(response
@@ -235,6 +274,7 @@ mod tests {
             error: None,
             events: None,
             params: None,
+            matcher: ParamMatcher::Exact,
         };
         assert!(response.get_notification_id().is_none());
         let response = ParamResponse {
@@ -242,6 +282,7 @@ mod tests {
             error: None,
             events: None,
             params: Some(Value::String("Some".to_owned())),
+            matcher: ParamMatcher::Exact,
         };
         assert!(response.get_notification_id().is_none());
 
@@ -253,6 +294,7 @@ mod tests {
                 "event": "SomeEvent",
                 "id": "SomeId"
             })),
+            matcher: ParamMatcher::Exact,
         };
 
         assert!(response
@@ -268,6 +310,7 @@ mod tests {
             error: Some(json!({"code": -32010, "message": "Error Message"})),
             events: None,
             params: None,
+            matcher: ParamMatcher::Exact,
         };
         let response = pr.get_all(Some(0), None)[0]
             .data
@@ -287,6 +330,7 @@ mod tests {
                 data: json!({"event": 0}),
             }]),
             params: None,
+            matcher: ParamMatcher::Exact,
         };
 
         let response = pr.get_all(Some(0), None)[0]
@@ -44,7 +44,7 @@ use tokio_tungstenite::{
 use crate::{
     errors::MockServerWebSocketError,
     mock_config::MockConfig,
-    mock_data::{MockData, MockDataError, ParamResponse, ResponseSink},
+    mock_data::{MockData, MockDataError, ParamMatcher, ParamResponse, ResponseSink},
     utils::is_value_jsonrpc,
 };
 
@@ -440,11 +440,20 @@ impl MockWebSocketServer {
                         new_params = json!({"event": v})
                     }
                 }
-                for response in v {
-                    if response.get_key(&new_params).is_some() {
-                        return Some(response);
+                for response in &v {
+                    if response.matcher == ParamMatcher::Exact
+                        && response.get_key(&new_params).is_some()
+                    {
+                        return Some(response.clone());
                     }
                 }
+                return v
+                    .into_iter()
+                    .find(|response| response.matches_wildcard(&new_params));
+            } else {
+                return v
+                    .into_iter()
+                    .find(|response| response.matches_wildcard(&Value::Null));
             }
         }
         None
@@ -688,4 +697,40 @@ mod tests {
         );
         assert_eq!(&response, &expected);
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_wildcard_mock_data() {
+        let method = "org.rdk.DisplaySettings.1.getCurrentResolution";
+        let mock_data = get_mock_data(json!({
+            method: [
+                {"params": {"videoDisplay": "HDMI0"}, "result": "exact"},
+                {"params": {"videoDisplay": "HDMI1"}, "result": "subset", "matcher": "subset"},
+                {"result": "any", "matcher": "any"}
+            ]
+        }));
+        let server = start_server(mock_data).await;
+
+        for (params, expected) in [
+            (json!({"videoDisplay": "HDMI0"}), "exact"),
+            (json!({"videoDisplay": "HDMI1", "verbose": true}), "subset"),
+            (json!({"videoDisplay": "HDMI2"}), "any"),
+        ] {
+            let response = request_response_with_timeout(
+                server.clone(),
+                Message::Text(
+                    json!({"jsonrpc": "2.0", "id": 1, "params": params, "method": method})
+                        .to_string(),
+                ),
+            )
+            .await
+            .expect("no response from server within timeout")
+            .expect("connection to server was closed")
+            .expect("error in server response");
+
+            assert_eq!(
+                response,
+                Message::Text(json!({"id": 1, "jsonrpc": "2.0", "result": expected}).to_string())
+            );
+        }
+    }
 }
@@ -15,7 +15,12 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::{fs::File, io::BufReader, path::PathBuf, sync::Arc};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use ripple_sdk::{
     api::config::Config,
@@ -30,7 +35,7 @@ use url::{Host, Url};
 use crate::{
     errors::{BootFailedError, LoadMockDataError, MockDeviceError},
     mock_config::MockConfig,
-    mock_data::MockData,
+    mock_data::{MockData, ParamResponse},
     mock_web_socket_server::{MockWebSocketServer, WsServerParameters},
 };
 
@@ -148,22 +153,57 @@ pub fn load_config(client: &ExtnClient) -> MockConfig {
 pub async fn load_mock_data_v2(client: ExtnClient) -> Result<MockData, MockDeviceError> {
     let path = find_mock_device_data_file(client).await?;
     debug!("path={:?}", path);
+    Ok(load_mock_data_file(&path)?)
+}
+
+/// Reads the mock data file, which maps each method to an array of responses
+pub fn load_mock_data_file(path: &Path) -> Result<MockData, LoadMockDataError> {
     if !path.is_file() {
-        return Err(LoadMockDataError::PathDoesNotExist(path))?;
+        return Err(LoadMockDataError::PathDoesNotExist(path.to_path_buf()));
     }
 
-    let file = File::open(path.clone()).map_err(|e| {
+    let file = File::open(path).map_err(|e| {
         error!("Failed to open mock data file {e:?}");
-        LoadMockDataError::FileOpenFailed(path)
+        LoadMockDataError::FileOpenFailed(path.to_path_buf())
     })?;
     let reader = BufReader::new(file);
 
-    if let Ok(v) = serde_json::from_reader(reader) {
-        return Ok(v);
+    let value: Value =
+        serde_json::from_reader(reader).map_err(|e| LoadMockDataError::MockDataNotValidJson {
+            path: path.to_path_buf(),
+            line: e.line(),
+            column: e.column(),
+        })?;
+    let Value::Object(methods) = value else {
+        return Err(LoadMockDataError::MockDataNotObject(path.to_path_buf()));
+    };
+
+    let mut mock_data = MockData::new();
+    for (method, responses) in methods {
+        let responses = parse_request_responses(&method, responses)?;
+        mock_data.insert(method, responses);
     }
-    Err(MockDeviceError::LoadMockDataFailed(
-        LoadMockDataError::MockDataNotValidJson,
-    ))
+    Ok(mock_data)
+}
+
+fn parse_request_responses(
+    method: &str,
+    responses: Value,
+) -> Result<Vec<ParamResponse>, LoadMockDataError> {
+    let Value::Array(entries) = responses else {
+        return Err(LoadMockDataError::MockDataNotArray(method.to_owned()));
+    };
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            serde_json::from_value(entry).map_err(|e| LoadMockDataError::MockDataBadEntry {
+                method: method.to_owned(),
+                index,
+                reason: e.to_string(),
+            })
+        })
+        .collect()
 }
 
 pub fn is_value_jsonrpc(value: &Value) -> bool {
@@ -189,4 +229,57 @@ mod tests {
     fn test_is_value_jsonrpc_false() {
         assert!(!is_value_jsonrpc(&json!({"key": "value"})));
     }
+
+    fn mock_data_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_mock_data_file() {
+        let path = mock_data_file(
+            "mock-data-valid",
+            &json!({"org.rdk.System.1.getSerialNumber": [{"result": {"serialNumber": "123"}}]})
+                .to_string(),
+        );
+        let mock_data = load_mock_data_file(&path).unwrap();
+        assert_eq!(mock_data["org.rdk.System.1.getSerialNumber"].len(), 1);
+    }
+
+    #[test]
+    fn test_load_mock_data_file_errors() {
+        let missing = std::env::temp_dir().join("mock-data-missing.json");
+        assert!(matches!(
+            load_mock_data_file(&missing),
+            Err(LoadMockDataError::PathDoesNotExist(path)) if path == missing
+        ));
+
+        let path = mock_data_file("mock-data-invalid", "{\n  \"a\": [\n}");
+        assert!(matches!(
+            load_mock_data_file(&path),
+            Err(LoadMockDataError::MockDataNotValidJson { line: 3, .. })
+        ));
+
+        let path = mock_data_file("mock-data-array", "[]");
+        assert!(matches!(
+            load_mock_data_file(&path),
+            Err(LoadMockDataError::MockDataNotObject(_))
+        ));
+
+        let path = mock_data_file("mock-data-not-array", &json!({"a.b": {}}).to_string());
+        assert!(matches!(
+            load_mock_data_file(&path),
+            Err(LoadMockDataError::MockDataNotArray(method)) if method == "a.b"
+        ));
+
+        let path = mock_data_file(
+            "mock-data-bad-entry",
+            &json!({"a.b": [{"result": 1}, {"events": "none"}]}).to_string(),
+        );
+        assert!(matches!(
+            load_mock_data_file(&path),
+            Err(LoadMockDataError::MockDataBadEntry { method, index: 1, .. }) if method == "a.b"
+        ));
+    }
 }
@@ -30,6 +30,9 @@ pub const DEFAULT_DEVICE_OPERATION_TIMEOUT_SECS: u64 = 5;
 pub trait DeviceOperator: Clone {
     async fn call(&self, request: DeviceCallRequest) -> DeviceResponseMessage;
 
+    /// Sends independent calls together, the responses are in the order of the calls
+    async fn bulk_call(&self, requests: Vec<DeviceCallRequest>) -> Vec<DeviceResponseMessage>;
+
     async fn subscribe(
         &self,
         request: DeviceSubscribeRequest,
@@ -50,11 +53,18 @@ pub enum DeviceChannelRequest {
     Call(DeviceCallRequest),
     Subscribe(DeviceSubscribeRequest),
     Unsubscribe(DeviceUnsubscribeRequest),
+    /// Independent calls written to Thunder back to back, each answered with its own response
+    BulkCall(Vec<DeviceCallRequest>),
 }
 
 impl DeviceChannelRequest {
+    /// Callsign and method of the request, those of the first call for a [DeviceChannelRequest::BulkCall]
     pub fn get_callsign_method(&self) -> (String, String) {
         match self {
+            DeviceChannelRequest::BulkCall(calls) => match calls.first() {
+                Some(c) => DeviceChannelRequest::Call(c.clone()).get_callsign_method(),
+                None => (String::new(), String::new()),
+            },
             DeviceChannelRequest::Call(c) => {
                 let mut collection: Vec<&str> = c.method.split('.').collect();
                 let method = collection.pop().unwrap_or_default();
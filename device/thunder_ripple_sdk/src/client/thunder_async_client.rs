@@ -35,18 +35,24 @@ use serde_json::{json, Value};
 use std::{collections::HashMap, time::Duration};
 use tokio_tungstenite::{client_async, tungstenite::Message, WebSocketStream};
 
+/// Time spent retrying to connect to Thunder before giving up
+const DEFAULT_MAX_CONNECT_TIME: Duration = Duration::from_secs(60 * 60);
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone, Debug)]
 pub struct ThunderAsyncClient {
     status_manager: StatusManager,
     sender: AsyncSender,
     callback: AsyncCallback,
     subscriptions: HashMap<String, JsonRpcApiRequest>,
+    max_connect_time: Duration,
 }
 
 #[derive(Clone, Debug)]
 pub struct ThunderAsyncRequest {
     pub id: u64,
     request: DeviceChannelRequest,
+    bulk_ids: Vec<u64>,
 }
 
 impl std::fmt::Display for ThunderAsyncRequest {
@@ -61,9 +67,36 @@ impl std::fmt::Display for ThunderAsyncRequest {
 
 impl ThunderAsyncRequest {
     pub fn new(request: DeviceChannelRequest) -> Self {
+        let bulk_ids = match &request {
+            DeviceChannelRequest::BulkCall(calls) => calls.iter().map(|_| get_next_id()).collect(),
+            _ => Vec::new(),
+        };
         Self {
             id: get_next_id(),
             request,
+            bulk_ids,
+        }
+    }
+
+    /// Ids of the responses to the calls of a [DeviceChannelRequest::BulkCall], in the
+    /// order of the calls. Empty for other requests.
+    pub fn get_bulk_ids(&self) -> &[u64] {
+        &self.bulk_ids
+    }
+
+    /// Splits a bulk call into one call request per call, other requests are returned as is
+    fn unbundle(self) -> Vec<ThunderAsyncRequest> {
+        match self.request {
+            DeviceChannelRequest::BulkCall(calls) => calls
+                .into_iter()
+                .zip(self.bulk_ids)
+                .map(|(call, id)| ThunderAsyncRequest {
+                    id,
+                    request: DeviceChannelRequest::Call(call),
+                    bulk_ids: Vec::new(),
+                })
+                .collect(),
+            _ => vec![self],
         }
     }
 }
@@ -130,16 +163,28 @@ impl ThunderAsyncClient {
     pub fn get_callback(&self) -> AsyncCallback {
         self.callback.clone()
     }
+    /// Bounds the time spent retrying to connect to Thunder, after which
+    /// [ThunderAsyncClient::start] fails with [RippleError::TimeoutError]
+    pub fn with_max_connect_time(mut self, max_connect_time: Duration) -> Self {
+        self.max_connect_time = max_connect_time;
+        self
+    }
+
     async fn create_ws(
         endpoint: &str,
-    ) -> (
-        SplitSink<WebSocketStream<TcpStream>, Message>,
-        SplitStream<WebSocketStream<TcpStream>>,
-    ) {
+        max_connect_time: Duration,
+    ) -> Result<
+        (
+            SplitSink<WebSocketStream<TcpStream>, Message>,
+            SplitStream<WebSocketStream<TcpStream>>,
+        ),
+        RippleError,
+    > {
         debug!("create_ws: {}", endpoint);
         let port = extract_tcp_port(endpoint);
         let tcp_port = port.unwrap();
         let mut index = 0;
+        let started = tokio::time::Instant::now();
 
         loop {
             // Try connecting to the tcp port first
@@ -148,9 +193,17 @@ impl ThunderAsyncClient {
                 // Setup handshake for websocket with the tcp port
                 // Some WS servers lock on to the Port but not setup handshake till they are fully setup
                 if let Ok((stream, _)) = client_async(endpoint, v).await {
-                    break stream.split();
+                    break Ok(stream.split());
                 }
             }
+            if started.elapsed() + CONNECT_RETRY_INTERVAL > max_connect_time {
+                error!(
+                    "create_ws: giving up on endpoint {} after {:?}",
+                    endpoint,
+                    started.elapsed()
+                );
+                break Err(RippleError::TimeoutError);
+            }
             if (index % 10).eq(&0) {
                 error!(
                     "create_ws: endpoint {} failed with retry for last {} secs in {}",
@@ -158,7 +211,7 @@ impl ThunderAsyncClient {
                 );
             }
             index += 1;
-            tokio::time::sleep(Duration::from_secs(1)).await;
+            tokio::time::sleep(CONNECT_RETRY_INTERVAL).await;
         }
     }
 
@@ -247,6 +300,8 @@ impl ThunderAsyncClient {
                 })),
             )
             .with_id(id),
+            // bulk calls are unbundled before they are prepared
+            DeviceChannelRequest::BulkCall(_) => return Err(RippleError::InvalidInput),
         };
 
         serde_json::to_string(&json_rpc_api_request).map_err(|_e| RippleError::ParseError)
@@ -258,6 +313,7 @@ impl ThunderAsyncClient {
             sender,
             callback,
             subscriptions: HashMap::new(),
+            max_connect_time: DEFAULT_MAX_CONNECT_TIME,
         }
     }
 
@@ -303,15 +359,19 @@ impl ThunderAsyncClient {
         }
     }
 
+    /// Connects to Thunder and processes requests, reconnecting whenever the connection is
+    /// lost. Only returns when a connection could not be established in time, start can be
+    /// called again with the same receiver to keep retrying.
     pub async fn start(
         &mut self,
         url: &str,
-        mut thunder_async_request_rx: Receiver<ThunderAsyncRequest>,
-    ) {
+        thunder_async_request_rx: &mut Receiver<ThunderAsyncRequest>,
+    ) -> Result<(), RippleError> {
         loop {
             info!("start: (re)establishing websocket connection: url={}", url);
 
-            let (mut thunder_tx, mut thunder_rx) = Self::create_ws(url).await;
+            let (mut thunder_tx, mut thunder_rx) =
+                Self::create_ws(url, self.max_connect_time).await?;
 
             // send the controller statechange subscription request
             let status_request = self
@@ -336,6 +396,19 @@ impl ThunderAsyncClient {
                 tokio::select! {
                     Some(value) = &mut subscriptions_socket => {
                         match value {
+                            Ok(Message::Close(frame)) => {
+                                match frame {
+                                    Some(frame) => info!(
+                                        "Thunder_async_client Websocket closed by server code={} reason={}",
+                                        frame.code, frame.reason
+                                    ),
+                                    None => info!("Thunder_async_client Websocket closed by server"),
+                                }
+                                break;
+                            },
+                            Ok(Message::Ping(payload)) => {
+                                let _ = thunder_tx.send(Message::Pong(payload)).await;
+                            },
                             Ok(message) => {
                                 self.handle_response(message).await;
                             },
@@ -346,49 +419,51 @@ impl ThunderAsyncClient {
                         }
                     },
                     Some(request) = thunder_async_request_rx.recv() => {
-                        match self.check_plugin_status_n_prepare_request(&request) {
-                            Ok(updated_request) => {
-                                if let Ok(jsonrpc_request) = serde_json::from_str::<JsonRpcApiRequest>(&updated_request) {
-                                    if jsonrpc_request.method.ends_with(".register") {
-                                        if let Some(Value::Object(ref params)) = jsonrpc_request.params {
-                                            if let Some(Value::String(event)) = params.get("event") {
-                                                debug!("thunder_async_request_rx: Rerouting subscription request for {}", event);
-
-                                                // Store the subscription request in the subscriptions list in case we need to
-                                                // resubscribe later due to a socket disconnect.
-                                                self.subscriptions.insert(event.to_string(), jsonrpc_request.clone());
-                                                debug!("thunder_async_request_rx: subscription request={}", updated_request);
-                                                // Reroute subsubscription requests through the persistent websocket so all notifications
-                                                // are sent to the same websocket connection.
-                                                let _feed = thunder_tx.feed(tokio_tungstenite::tungstenite::Message::Text(updated_request)).await;
-                                                let _flush = thunder_tx.flush().await;
+                        // the calls of a bulk call are fed one after the other and flushed together
+                        for request in request.unbundle() {
+                            match self.check_plugin_status_n_prepare_request(&request) {
+                                Ok(updated_request) => {
+                                    if let Ok(jsonrpc_request) = serde_json::from_str::<JsonRpcApiRequest>(&updated_request) {
+                                        if jsonrpc_request.method.ends_with(".register") {
+                                            if let Some(Value::Object(ref params)) = jsonrpc_request.params {
+                                                if let Some(Value::String(event)) = params.get("event") {
+                                                    debug!("thunder_async_request_rx: Rerouting subscription request for {}", event);
+
+                                                    // Store the subscription request in the subscriptions list in case we need to
+                                                    // resubscribe later due to a socket disconnect.
+                                                    self.subscriptions.insert(event.to_string(), jsonrpc_request.clone());
+                                                    debug!("thunder_async_request_rx: subscription request={}", updated_request);
+                                                    // Reroute subsubscription requests through the persistent websocket so all notifications
+                                                    // are sent to the same websocket connection.
+                                                    let _feed = thunder_tx.feed(tokio_tungstenite::tungstenite::Message::Text(updated_request)).await;
+                                                } else {
+                                                    error!("thunder_async_request_rx: Missing 'event' parameter");
+                                                }
                                             } else {
-                                                error!("thunder_async_request_rx: Missing 'event' parameter");
+                                                error!("thunder_async_request_rx: Missing 'params' object");
                                             }
-                                        } else {
-                                            error!("thunder_async_request_rx: Missing 'params' object");
                                         }
-                                    }
-                                    else {
-                                        debug!("thunder_async_request_rx: call request={}", updated_request);
-                                        let _feed = thunder_tx.feed(tokio_tungstenite::tungstenite::Message::Text(updated_request)).await;
-                                        let _flush = thunder_tx.flush().await;
+                                        else {
+                                            debug!("thunder_async_request_rx: call request={}", updated_request);
+                                            let _feed = thunder_tx.feed(tokio_tungstenite::tungstenite::Message::Text(updated_request)).await;
+                                        }
                                     }
                                 }
-                            }
-                            Err(e) => {
-                                match e {
-                                    RippleError::ServiceNotReady => {
-                                        info!("Thunder Service not ready, request is now in pending list {:?}", request);
-                                    },
-                                    _ => {
-                                        error!("error preparing request {:?}", e);
-                                        let response = ThunderAsyncResponse::new_error(request.id,e.clone());
-                                        self.callback.send(response).await;
+                                Err(e) => {
+                                    match e {
+                                        RippleError::ServiceNotReady => {
+                                            info!("Thunder Service not ready, request is now in pending list {:?}", request);
+                                        },
+                                        _ => {
+                                            error!("error preparing request {:?}", e);
+                                            let response = ThunderAsyncResponse::new_error(request.id,e.clone());
+                                            self.callback.send(response).await;
+                                        }
                                     }
                                 }
                             }
                         }
+                        let _flush = thunder_tx.flush().await;
                     }
                 }
             }
@@ -593,4 +668,159 @@ mod tests {
             Some(json!({"key": "value"}))
         );
     }
+
+    #[tokio::test]
+    async fn test_thunder_async_client_reconnects_on_close() {
+        use tokio::net::TcpListener;
+        use tokio_tungstenite::{
+            accept_async,
+            tungstenite::protocol::{frame::coding::CloseCode, CloseFrame},
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let (resp_tx, _resp_rx) = mpsc::channel(10);
+        let callback = AsyncCallback { sender: resp_tx };
+        let (async_tx, async_rx) = mpsc::channel(10);
+        let async_sender = AsyncSender { sender: async_tx };
+        let mut client = ThunderAsyncClient::new(callback, async_sender);
+        tokio::spawn(async move {
+            let mut async_rx = async_rx;
+            client
+                .start(&format!("ws://127.0.0.1:{}/jsonrpc", port), &mut async_rx)
+                .await
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = accept_async(stream).await.unwrap();
+        ws.send(Message::Ping(b"ping".to_vec())).await.unwrap();
+        loop {
+            if let Some(Ok(Message::Pong(payload))) = ws.next().await {
+                assert_eq!(payload, b"ping".to_vec());
+                break;
+            }
+        }
+        ws.send(Message::Close(Some(CloseFrame {
+            code: CloseCode::Away,
+            reason: "restarting".into(),
+        })))
+        .await
+        .unwrap();
+
+        // the client drops the closed connection and connects again
+        let reconnect = tokio::time::timeout(Duration::from_secs(5), listener.accept()).await;
+        assert!(reconnect.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_thunder_async_client_bulk_call() {
+        use crate::client::thunder_async_client_plugins_status_mgr::State;
+        use tokio::net::TcpListener;
+        use tokio_tungstenite::accept_async;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let (resp_tx, mut resp_rx) = mpsc::channel(10);
+        let callback = AsyncCallback { sender: resp_tx };
+        let (async_tx, async_rx) = mpsc::channel(10);
+        let async_sender = AsyncSender { sender: async_tx };
+        let mut client = ThunderAsyncClient::new(callback, async_sender.clone());
+        client
+            .status_manager
+            .update_status("org.rdk.System".to_string(), State::Activated);
+        tokio::spawn(async move {
+            let mut async_rx = async_rx;
+            client
+                .start(&format!("ws://127.0.0.1:{}/jsonrpc", port), &mut async_rx)
+                .await
+        });
+
+        let methods = ["getSerialNumber", "getDeviceInfo", "getFriendlyName"];
+        let request = ThunderAsyncRequest::new(DeviceChannelRequest::BulkCall(
+            methods
+                .iter()
+                .map(|method| DeviceCallRequest {
+                    method: format!("org.rdk.System.1.{}", method),
+                    params: None,
+                })
+                .collect(),
+        ));
+        let bulk_ids = request.get_bulk_ids().to_vec();
+        assert_eq!(bulk_ids.len(), 3);
+        async_sender.send(request).await.unwrap();
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = accept_async(stream).await.unwrap();
+        let mut answered = 0;
+        while answered < methods.len() {
+            if let Some(Ok(Message::Text(text))) = ws.next().await {
+                let request: JsonRpcApiRequest = serde_json::from_str(&text).unwrap();
+                if !request.method.starts_with("org.rdk.System") {
+                    continue;
+                }
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": request.id,
+                    "result": request.method,
+                });
+                ws.send(Message::Text(response.to_string())).await.unwrap();
+                answered += 1;
+            }
+        }
+
+        for (id, method) in bulk_ids.into_iter().zip(methods) {
+            let response = tokio::time::timeout(Duration::from_secs(5), resp_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.id, Some(id));
+            assert_eq!(
+                response.result.unwrap().result,
+                Some(json!(format!("org.rdk.System.1.{}", method)))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thunder_async_client_connect_timeout() {
+        // nothing listens on the port once the listener is dropped
+        let port = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let (resp_tx, _resp_rx) = mpsc::channel(10);
+        let callback = AsyncCallback { sender: resp_tx };
+        let (async_tx, mut async_rx) = mpsc::channel(10);
+        let async_sender = AsyncSender { sender: async_tx };
+        let mut client = ThunderAsyncClient::new(callback, async_sender)
+            .with_max_connect_time(Duration::from_secs(2));
+
+        let started = tokio::time::Instant::now();
+        let result = tokio::time::timeout(
+            Duration::from_secs(10),
+            client.start(&format!("ws://127.0.0.1:{}/jsonrpc", port), &mut async_rx),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result, Err(RippleError::TimeoutError));
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert!(started.elapsed() <= Duration::from_secs(3));
+
+        // the same receiver can be used to retry once thunder comes up
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            client
+                .start(&format!("ws://127.0.0.1:{}/jsonrpc", port), &mut async_rx)
+                .await
+        });
+        let reconnect = tokio::time::timeout(Duration::from_secs(5), listener.accept()).await;
+        assert!(reconnect.is_ok());
+    }
 }
@@ -64,14 +64,17 @@ pub struct ThunderClientManager;
 impl ThunderClientManager {
     fn start(
         client: ThunderClient,
-        request_tr: Receiver<ThunderAsyncRequest>,
+        mut request_tr: Receiver<ThunderAsyncRequest>,
         mut response_tr: Receiver<ThunderAsyncResponse>,
         thndr_endpoint_url: String,
     ) {
         if let Some(ref thunder_async_client) = client.thunder_async_client {
             let mut tac = thunder_async_client.clone();
             tokio::spawn(async move {
-                tac.start(&thndr_endpoint_url, request_tr).await;
+                // start gives up once its max connect time is spent, keep retrying at that pace
+                while let Err(e) = tac.start(&thndr_endpoint_url, &mut request_tr).await {
+                    error!("Thunder async client could not connect, retrying: {:?}", e);
+                }
             });
         }
 
@@ -256,7 +259,7 @@ impl DeviceOperator for ThunderClient {
         } else {
             let (tx, rx) = oneshot::channel::<DeviceResponseMessage>();
             let async_request = ThunderAsyncRequest::new(DeviceChannelRequest::Call(request));
-            self.add_callback(&async_request, tx);
+            self.add_callback(async_request.id, tx);
             if let Some(async_client) = &self.thunder_async_client {
                 async_client.send(async_request).await;
             }
@@ -264,6 +267,34 @@ impl DeviceOperator for ThunderClient {
         }
     }
 
+    async fn bulk_call(&self, requests: Vec<DeviceCallRequest>) -> Vec<DeviceResponseMessage> {
+        let mut responses = Vec::with_capacity(requests.len());
+        if !self.use_thunder_async {
+            for request in requests {
+                responses.push(self.call(request).await);
+            }
+        } else {
+            let async_request = ThunderAsyncRequest::new(DeviceChannelRequest::BulkCall(requests));
+            // every call is answered under its own id
+            let receivers: Vec<_> = async_request
+                .get_bulk_ids()
+                .iter()
+                .map(|id| {
+                    let (tx, rx) = oneshot::channel::<DeviceResponseMessage>();
+                    self.add_callback(*id, tx);
+                    rx
+                })
+                .collect();
+            if let Some(async_client) = &self.thunder_async_client {
+                async_client.send(async_request).await;
+            }
+            for rx in receivers {
+                responses.push(rx.await.unwrap());
+            }
+        }
+        responses
+    }
+
     async fn subscribe(
         &self,
         request: DeviceSubscribeRequest,
@@ -290,7 +321,7 @@ impl DeviceOperator for ThunderClient {
             self.add_subscription_handler(&request, handler.clone())
         {
             let (tx, rx) = oneshot::channel::<DeviceResponseMessage>();
-            self.add_callback(&subscribe_request, tx);
+            self.add_callback(subscribe_request.id, tx);
             if let Some(async_client) = &self.thunder_async_client {
                 async_client.send(subscribe_request).await;
             }
@@ -561,18 +592,14 @@ impl ThunderClient {
         None
     }
 
-    fn add_callback(
-        &self,
-        request: &ThunderAsyncRequest,
-        dev_resp_callback: Sender<DeviceResponseMessage>,
-    ) {
+    fn add_callback(&self, id: u64, dev_resp_callback: Sender<DeviceResponseMessage>) {
         let mut callbacks = self
             .thunder_async_callbacks
             .as_ref()
             .unwrap()
             .write()
             .unwrap();
-        callbacks.insert(request.id, Some(dev_resp_callback));
+        callbacks.insert(id, Some(dev_resp_callback));
     }
 
     // if already subscribed updated handlers
@@ -1007,6 +1034,63 @@ mod tests {
         assert_eq!(thunder_call_message.method_name(), "createDisplay");
     }
 
+    #[tokio::test]
+    async fn test_bulk_call() {
+        let (async_tx, mut async_rx) = mpsc::channel(4);
+        let callbacks = Arc::new(RwLock::new(HashMap::new()));
+        let client = ThunderClient {
+            sender: None,
+            pooled_sender: None,
+            id: Uuid::new_v4(),
+            plugin_manager_tx: None,
+            subscriptions: None,
+            thunder_async_client: Some(ThunderAsyncClient::new(
+                AsyncCallback {
+                    sender: mpsc::channel(4).0,
+                },
+                AsyncSender { sender: async_tx },
+            )),
+            thunder_async_subscriptions: None,
+            thunder_async_callbacks: Some(callbacks.clone()),
+            use_thunder_async: true,
+        };
+        let methods = ["getSerialNumber", "getDeviceInfo"];
+        let responses = tokio::spawn(async move {
+            client
+                .bulk_call(
+                    methods
+                        .iter()
+                        .map(|method| DeviceCallRequest {
+                            method: format!("org.rdk.System.1.{}", method),
+                            params: None,
+                        })
+                        .collect(),
+                )
+                .await
+        });
+
+        // both calls go out as one request and are answered under their own ids
+        let request = async_rx.recv().await.unwrap();
+        let bulk_ids = request.get_bulk_ids().to_vec();
+        assert_eq!(bulk_ids.len(), 2);
+        for (id, method) in bulk_ids.into_iter().zip(methods).rev() {
+            let callback = callbacks.write().unwrap().remove(&id).unwrap().unwrap();
+            callback
+                .send(DeviceResponseMessage {
+                    message: json!(method),
+                    sub_id: None,
+                })
+                .unwrap();
+        }
+        let messages: Vec<Value> = responses
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|response| response.message)
+            .collect();
+        assert_eq!(messages, vec![json!(methods[0]), json!(methods[1])]);
+    }
+
     #[test]
     fn test_extract_callsign_from_register_method() {
         let method = "org.rdk.RDKShell.1.register";
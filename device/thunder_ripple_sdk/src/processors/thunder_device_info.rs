@@ -759,50 +759,31 @@ impl ThunderDeviceInfoRequestProcessor {
         get_dimension_from_resolution(resol)
     }
 
-    async fn get_current_resolution(state: &CachedState) -> Result<Vec<i32>, ()> {
-        let response = state
-            .get_thunder_client()
-            .call(DeviceCallRequest {
-                method: ThunderPlugin::DisplaySettings.method("getCurrentResolution"),
-                params: None,
-            })
-            .await;
-        if !check_thunder_response_success(&response) {
-            error!("{}", response.message);
-            return Err(());
-        }
-        info!("{}", response.message);
-        let resol = response.message["resolution"].as_str().unwrap_or_default();
-        Ok(get_dimension_from_resolution(resol))
-    }
-
-    async fn get_default_resolution(state: &CachedState) -> Result<Vec<i32>, ()> {
-        let response = state
+    async fn get_video_resolution(state: &CachedState) -> Vec<i32> {
+        // the default resolution is only used when the current one is unknown, both are
+        // asked for at once to save a round trip
+        let responses = state
             .get_thunder_client()
-            .call(DeviceCallRequest {
-                method: ThunderPlugin::DisplaySettings.method("getDefaultResolution"),
-                params: None,
-            })
+            .bulk_call(vec![
+                DeviceCallRequest {
+                    method: ThunderPlugin::DisplaySettings.method("getCurrentResolution"),
+                    params: None,
+                },
+                DeviceCallRequest {
+                    method: ThunderPlugin::DisplaySettings.method("getDefaultResolution"),
+                    params: None,
+                },
+            ])
             .await;
-        if !check_thunder_response_success(&response) {
-            error!("{}", response.message);
-            return Err(());
-        }
-        info!("{}", response.message);
-        if let Some(resol) = response.message.get("defaultResolution") {
-            if let Some(r) = resol.as_str() {
-                return Ok(get_dimension_from_resolution(r));
+        for (response, key) in responses.iter().zip(["resolution", "defaultResolution"]) {
+            if !check_thunder_response_success(response) {
+                error!("{}", response.message);
+                continue;
+            }
+            info!("{}", response.message);
+            if let Some(resol) = response.message.get(key).and_then(Value::as_str) {
+                return get_dimension_from_resolution(resol);
             }
-        }
-        Err(())
-    }
-
-    async fn get_video_resolution(state: &CachedState) -> Vec<i32> {
-        if let Ok(resolution) = Self::get_current_resolution(state).await {
-            return resolution;
-        }
-        if let Ok(resolution) = Self::get_default_resolution(state).await {
-            return resolution;
         }
         if let Ok(response) = state.get_client().request(Config::DefaultValues).await {
             if let Some(ExtnResponse::Value(value)) = response.payload.extract() {